@@ -0,0 +1,406 @@
+//! Batch conversion of many files in parallel.
+//!
+//! Inputs are expanded once, then converted on a thread pool, each file
+//! parsed a single time. Every file gets a result whatever happens to the
+//! others, and the run ends with a summary in text or JSON.
+
+use anyhow::{Context, Result};
+use rayon::prelude::*;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use tracing::{error, info_span};
+
+use otd_core::{Diagnostic, Severity};
+
+use crate::commands::{manifest_path, numbered_base, Conversion, Converted, ValidationFailed};
+use crate::report::{Exit, Outcome};
+use crate::{BatchArgs, OutputFormat};
+
+/// Outcome of one file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Status {
+    /// Programs written.
+    Converted,
+    /// Parsing, validation, generation or writing failed.
+    Failed,
+    /// Not attempted after a failure with `--fail-fast`.
+    Skipped,
+}
+
+/// Result of one file.
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct FileResult {
+    pub input: PathBuf,
+    pub status: Status,
+    /// Files written, programs first.
+    pub outputs: Vec<PathBuf>,
+    /// Validation warnings.
+    pub warnings: usize,
+    /// Validation findings.
+    pub diagnostics: Vec<Diagnostic>,
+    /// Why the file failed.
+    pub error: Option<String>,
+}
+
+/// Summary of a batch run.
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct BatchSummary {
+    pub converted: usize,
+    pub failed: usize,
+    pub skipped: usize,
+    pub warnings: usize,
+    /// One entry per input, in input order.
+    pub files: Vec<FileResult>,
+}
+
+impl FileResult {
+    fn new(input: &Path, status: Status) -> Self {
+        Self {
+            input: input.to_path_buf(),
+            status,
+            outputs: Vec::new(),
+            warnings: 0,
+            diagnostics: Vec::new(),
+            error: None,
+        }
+    }
+
+    pub(crate) fn converted(input: &Path, converted: Converted) -> Self {
+        Self {
+            outputs: converted.outputs,
+            warnings: converted
+                .diagnostics
+                .iter()
+                .filter(|d| d.severity == Severity::Warning)
+                .count(),
+            diagnostics: converted.diagnostics,
+            ..Self::new(input, Status::Converted)
+        }
+    }
+
+    pub(crate) fn failed(input: &Path, error: &anyhow::Error) -> Self {
+        let validation = error
+            .downcast_ref::<ValidationFailed>()
+            .map(|failed| &failed.0);
+        Self {
+            warnings: validation.map_or(0, |v| v.with_severity(Severity::Warning).count()),
+            diagnostics: validation
+                .map(|v| v.diagnostics.clone())
+                .unwrap_or_default(),
+            error: Some(format!("{:#}", error)),
+            ..Self::new(input, Status::Failed)
+        }
+    }
+
+    /// Result as one line of a report.
+    pub(crate) fn report_line(&self) -> String {
+        let input = self.input.display();
+        match self.status {
+            Status::Converted => {
+                let outputs: Vec<String> = self
+                    .outputs
+                    .iter()
+                    .map(|p| p.display().to_string())
+                    .collect();
+                let warnings = match self.warnings {
+                    0 => String::new(),
+                    n => format!(" ({} warning(s))", n),
+                };
+                format!("converted  {} -> {}{}", input, outputs.join(", "), warnings)
+            }
+            Status::Failed => format!(
+                "failed     {}: {}",
+                input,
+                self.error.as_deref().unwrap_or_default()
+            ),
+            Status::Skipped => format!("skipped    {}", input),
+        }
+    }
+}
+
+impl BatchSummary {
+    fn new(files: Vec<FileResult>) -> Self {
+        let count = |status| files.iter().filter(|f| f.status == status).count();
+        Self {
+            converted: count(Status::Converted),
+            failed: count(Status::Failed),
+            skipped: count(Status::Skipped),
+            warnings: files.iter().map(|f| f.warnings).sum(),
+            files,
+        }
+    }
+
+    /// One line per file and a closing count.
+    pub(crate) fn report(&self) -> String {
+        let mut report = String::new();
+        for file in &self.files {
+            report += &file.report_line();
+            report.push('\n');
+        }
+        report += &format!(
+            "{} file(s): {} converted, {} failed, {} skipped, {} warning(s)\n",
+            self.files.len(),
+            self.converted,
+            self.failed,
+            self.skipped,
+            self.warnings
+        );
+        report
+    }
+}
+
+/// Run the batch command.
+pub(crate) fn run(args: &BatchArgs, format: OutputFormat) -> Result<Outcome> {
+    let inputs = expand_inputs(&args.inputs)?;
+    if inputs.is_empty() {
+        anyhow::bail!("No OTD/OTX files found");
+    }
+
+    let conversion = Conversion::new(&args.machine, args.split, args.post, &args.rules)?;
+    if let Some(dir) = &args.output {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+    let outputs: Vec<PathBuf> = inputs
+        .iter()
+        .map(|input| conversion.output_path(input, args.output.as_deref()))
+        .collect();
+    check_distinct_outputs(&inputs, &outputs)?;
+
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(args.jobs.unwrap_or(0))
+        .build()?;
+    let failed = AtomicBool::new(false);
+    let files = pool.install(|| {
+        inputs
+            .par_iter()
+            .zip(&outputs)
+            .map(|(input, output)| {
+                if args.fail_fast && failed.load(Ordering::Relaxed) {
+                    return FileResult::new(input, Status::Skipped);
+                }
+                // Log lines of the file are prefixed with its name
+                let name = input.file_name().unwrap_or_default().to_string_lossy();
+                let _span = info_span!("batch", file = %name).entered();
+                match conversion.run(input, output) {
+                    Ok(converted) => FileResult::converted(input, converted),
+                    Err(e) => {
+                        failed.store(true, Ordering::Relaxed);
+                        error!("{:#}", e);
+                        FileResult::failed(input, &e)
+                    }
+                }
+            })
+            .collect()
+    });
+
+    let summary = BatchSummary::new(files);
+    if format == OutputFormat::Text {
+        print!("{}", summary.report());
+    }
+    let mut outcome = Outcome {
+        outputs: summary
+            .files
+            .iter()
+            .flat_map(|f| f.outputs.iter().cloned())
+            .collect(),
+        ..Outcome::default()
+    };
+    if summary.failed > 0 {
+        error!(
+            "{} of {} file(s) failed",
+            summary.failed,
+            summary.files.len()
+        );
+        outcome.exit = Exit::FilesFailed;
+    }
+    outcome.data = Some(serde_json::to_value(summary)?);
+    Ok(outcome)
+}
+
+/// Files named by the arguments, sorted and without duplicates: files as
+/// given, the OTD/OTX files of directories and the matches of glob patterns.
+///
+/// Missing files are kept so that they are reported as failed.
+pub(crate) fn expand_inputs(args: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    let mut inputs = Vec::new();
+    for arg in args {
+        if arg.is_dir() {
+            let entries = std::fs::read_dir(arg)
+                .with_context(|| format!("Failed to read {}", arg.display()))?;
+            for entry in entries {
+                let path = entry?.path();
+                if path.is_file() && is_layout(&path) {
+                    inputs.push(path);
+                }
+            }
+        } else if is_pattern(arg) {
+            let pattern = arg.to_string_lossy();
+            let matches =
+                glob::glob(&pattern).with_context(|| format!("Invalid pattern {}", pattern))?;
+            for path in matches {
+                let path = path?;
+                if path.is_file() {
+                    inputs.push(path);
+                }
+            }
+        } else {
+            inputs.push(arg.clone());
+        }
+    }
+    inputs.sort();
+    inputs.dedup();
+    Ok(inputs)
+}
+
+/// Whether a file is an OTD or OTX layout, by extension.
+pub(crate) fn is_layout(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("otd") || e.eq_ignore_ascii_case("otx"))
+}
+
+fn is_pattern(arg: &Path) -> bool {
+    arg.to_string_lossy().contains(['*', '?', '['])
+}
+
+/// Refuse runs where two inputs may write the same file.
+///
+/// A layout written as several programs, with `--split` or because its
+/// patterns use different sheets, writes `<stem>_NN` programs and a
+/// manifest instead of its output path, so these are checked as well.
+fn check_distinct_outputs(inputs: &[PathBuf], outputs: &[PathBuf]) -> Result<()> {
+    let collision = |first: &Path, second: &Path, path: &Path| {
+        anyhow::anyhow!(
+            "{} and {} would both write {}",
+            first.display(),
+            second.display(),
+            path.display()
+        )
+    };
+
+    let mut seen: HashMap<PathBuf, &Path> = HashMap::new();
+    let mut paths = Vec::new();
+    for (input, output) in inputs.iter().zip(outputs) {
+        for path in [output.clone(), manifest_path(output)] {
+            if let Some(other) = seen.insert(path.clone(), input) {
+                return Err(collision(other, input, &path));
+            }
+            paths.push((path, input));
+        }
+    }
+
+    let owners: HashMap<&Path, &Path> = outputs
+        .iter()
+        .map(PathBuf::as_path)
+        .zip(inputs.iter().map(PathBuf::as_path))
+        .collect();
+    for (path, input) in &paths {
+        let owner = numbered_base(path).and_then(|base| owners.get(base.as_path()).copied());
+        if let Some(owner) = owner.filter(|owner| owner != input) {
+            return Err(collision(owner, input, path));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Empty scratch directory under the system temp dir.
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("otd_cli_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_expand_inputs() {
+        let dir = scratch_dir("expand");
+        for name in ["a.otd", "b.OTX", "notes.txt", "c.otd"] {
+            std::fs::write(dir.join(name), "").unwrap();
+        }
+        std::fs::create_dir(dir.join("sub.otd")).unwrap();
+
+        let inputs = expand_inputs(std::slice::from_ref(&dir)).unwrap();
+        let names: Vec<_> = inputs
+            .iter()
+            .map(|p| p.file_name().unwrap().to_str().unwrap())
+            .collect();
+        assert_eq!(names, ["a.otd", "b.OTX", "c.otd"]);
+
+        // Glob, plain file given twice, missing file kept
+        let inputs = expand_inputs(&[
+            dir.join("[ab].*"),
+            dir.join("a.otd"),
+            dir.join("missing.otd"),
+        ])
+        .unwrap();
+        assert_eq!(
+            inputs,
+            [
+                dir.join("a.otd"),
+                dir.join("b.OTX"),
+                dir.join("missing.otd")
+            ]
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_check_distinct_outputs() {
+        let check = |names: &[&str]| {
+            let inputs: Vec<PathBuf> = names.iter().map(PathBuf::from).collect();
+            let outputs: Vec<PathBuf> = inputs.iter().map(|i| i.with_extension("cni")).collect();
+            check_distinct_outputs(&inputs, &outputs).map_err(|e| e.to_string())
+        };
+
+        assert!(check(&["a.otd", "b.otd", "a_1.otd", "a_x2.otd"]).is_ok());
+        assert_eq!(
+            check(&["a.otd", "a.otx"]).unwrap_err(),
+            "a.otd and a.otx would both write a.cni"
+        );
+        // The second program of a split layout
+        assert_eq!(
+            check(&["b_02.otd", "b.otd"]).unwrap_err(),
+            "b.otd and b_02.otd would both write b_02.cni"
+        );
+    }
+
+    #[test]
+    fn test_summary_counts() {
+        let converted = FileResult {
+            outputs: vec![PathBuf::from("a.cni")],
+            warnings: 2,
+            ..FileResult::new(Path::new("a.otd"), Status::Converted)
+        };
+        let failed = FileResult::failed(Path::new("b.otd"), &anyhow::anyhow!("Validation failed"));
+        let skipped = FileResult::new(Path::new("c.otd"), Status::Skipped);
+        let summary = BatchSummary::new(vec![converted, failed, skipped]);
+
+        assert_eq!(
+            (
+                summary.converted,
+                summary.failed,
+                summary.skipped,
+                summary.warnings
+            ),
+            (1, 1, 1, 2)
+        );
+        assert_eq!(
+            summary.report(),
+            "converted  a.otd -> a.cni (2 warning(s))\n\
+             failed     b.otd: Validation failed\n\
+             skipped    c.otd\n\
+             3 file(s): 1 converted, 1 failed, 1 skipped, 2 warning(s)\n"
+        );
+        let json = serde_json::to_value(&summary).unwrap();
+        assert_eq!(json["files"][1]["status"], "failed");
+    }
+}
@@ -0,0 +1,720 @@
+//! Implementation of the commands.
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+use otd_core::analysis::{simulate, CycleTime, PatternCycleTime};
+use otd_core::export::{labels_pdf, labels_zpl, piece_labels, LabelTemplate};
+use otd_core::generator::generate_split_with_post;
+use otd_core::{
+    decrypt_otx, diff_cni_files, encrypt_otx, estimate_schemas, export_dxf, export_pdf, export_svg,
+    format_duration, layout_stats, parse_cni_file, parse_otd_file, render_toolpath, transform,
+    CniProgram, ConvertError, Diagnostic, DiffOptions, LayoutStats, MachineConfig,
+    MachineKinematics, PdfOptions, Schema, Severity, SplitManifest, SplitMode, SvgOptions, Unit,
+    ValidationConfig, ValidationResult, Validator,
+};
+
+use crate::report::{Exit, Outcome, Stats};
+use crate::{
+    batch, watch, Command, ExportFormat, LabelFormatArg, MachineArgs, OutputFormat, PostArg,
+    RulesArgs, SplitArg,
+};
+
+/// Run a command; reports go to standard output in the requested format.
+pub fn run(command: Command, format: OutputFormat) -> Result<Outcome> {
+    let text = format == OutputFormat::Text;
+    match command {
+        Command::Convert {
+            input,
+            output,
+            machine,
+            split,
+            post,
+            rules,
+        } => {
+            let conversion = Conversion::new(&machine, split, post, &rules)?;
+            let output = output.unwrap_or_else(|| conversion.output_path(&input.input, None));
+            Ok(conversion.run(&input.input, &output)?.into())
+        }
+        Command::Batch(args) => batch::run(&args, format),
+        Command::Watch {
+            input,
+            output,
+            machine,
+            split,
+            post,
+            rules,
+            interval,
+        } => {
+            let interval = Duration::try_from_secs_f64(interval)
+                .ok()
+                .filter(|d| !d.is_zero())
+                .with_context(|| format!("Interval must be positive, got {}", interval))?;
+            let conversion = Conversion::new(&machine, split, post, &rules)?;
+            let mut watcher = watch::Watcher::new(&input, &output, conversion)?;
+            watcher.run(interval, |result| match format {
+                OutputFormat::Text => println!("{}", result.report_line()),
+                // One document per line, as files are processed
+                OutputFormat::Json => match serde_json::to_string(result) {
+                    Ok(json) => println!("{}", json),
+                    Err(e) => error!("{}", e),
+                },
+            })
+        }
+        Command::Validate { input, rules } => {
+            let schemas = load(&input.input)?;
+            let validation = check(&schemas, &validator(&rules)?)?;
+            info!("Validation passed");
+            Ok(Outcome {
+                diagnostics: validation.diagnostics,
+                ..Outcome::default()
+            })
+        }
+        Command::Info { input } => {
+            let schemas = parse_otd_file(&input.input)
+                .with_context(|| format!("Failed to parse {}", input.input.display()))?;
+            let info = LayoutInfo::new(&input.input, &schemas);
+            if text {
+                print!("{}", info.report());
+            }
+            Ok(Outcome {
+                data: Some(serde_json::to_value(info)?),
+                ..Outcome::default()
+            })
+        }
+        Command::Stats { input, machine } => {
+            let schemas = load(&input.input)?;
+            let config = machine_config(&machine)?;
+            let stats = Stats {
+                layout: layout_stats(&schemas),
+                cycle_time: estimate_schemas(&schemas, &config)?,
+            };
+            if text {
+                print!("{}", stats_report(&stats.layout, &stats.cycle_time));
+            }
+            Ok(Outcome {
+                stats: Some(stats),
+                ..Outcome::default()
+            })
+        }
+        Command::Export {
+            export_format,
+            input,
+            output,
+            paper,
+            width,
+        } => {
+            let schemas = load(&input.input)?;
+            let output =
+                output.unwrap_or_else(|| input.input.with_extension(export_format.extension()));
+            let outputs = match export_format {
+                ExportFormat::Json => {
+                    write_file(&output, serde_json::to_string_pretty(&schemas)?)?;
+                    vec![output]
+                }
+                ExportFormat::Svg => {
+                    let mut options = SvgOptions::default();
+                    options.width = width;
+                    write_per_pattern(&output, &schemas, |schema| {
+                        export_svg(schema, &options).into_bytes()
+                    })?
+                }
+                ExportFormat::Dxf => {
+                    write_per_pattern(&output, &schemas, |schema| export_dxf(schema).into_bytes())?
+                }
+                ExportFormat::Pdf => {
+                    let options = PdfOptions {
+                        title: file_stem(&input.input, "Cut list").to_string(),
+                        paper: paper.into(),
+                    };
+                    write_file(&output, export_pdf(&schemas, &options))?;
+                    vec![output]
+                }
+            };
+            Ok(Outcome {
+                outputs,
+                ..Outcome::default()
+            })
+        }
+        Command::Decrypt { input, output } => {
+            let encrypted = read_input(&input)?;
+            let content = decrypt_otx(&encrypted)
+                .with_context(|| format!("Failed to decrypt {}", input.display()))?;
+            let output = output.unwrap_or_else(|| input.with_extension("otd"));
+            write_file(&output, content)?;
+            Ok(Outcome {
+                outputs: vec![output],
+                ..Outcome::default()
+            })
+        }
+        Command::Encrypt { input, output } => {
+            let content = String::from_utf8(read_input(&input)?)
+                .with_context(|| format!("{} is not OTD text", input.display()))?;
+            let output = output.unwrap_or_else(|| input.with_extension("otx"));
+            write_file(&output, encrypt_otx(&content))?;
+            Ok(Outcome {
+                outputs: vec![output],
+                ..Outcome::default()
+            })
+        }
+        Command::Diff {
+            left,
+            right,
+            tolerance,
+            json: _,
+        } => {
+            let diff =
+                diff_cni_files(&left, &right, &DiffOptions { tolerance }).with_context(|| {
+                    format!(
+                        "Failed to compare {} and {}",
+                        left.display(),
+                        right.display()
+                    )
+                })?;
+            if text {
+                print!("{}", diff.report());
+            }
+
+            // Like diff(1): exit status 1 when the programs differ
+            Ok(Outcome {
+                exit: if diff.is_identical() {
+                    Exit::Success
+                } else {
+                    Exit::Differences
+                },
+                data: Some(serde_json::to_value(diff)?),
+                ..Outcome::default()
+            })
+        }
+        Command::Render {
+            input,
+            output,
+            width,
+        } => {
+            let cni = parse_cni_file(&input)
+                .with_context(|| format!("Failed to parse {}", input.display()))?;
+            let (sheet_width, sheet_height) = cni
+                .parameters
+                .as_ref()
+                .map(|p| (p.width, p.height))
+                .context("The program has no [PARAMETRI01] sheet size")?;
+            let toolpath = simulate(&cni.program)
+                .with_context(|| format!("Failed to simulate {}", input.display()))?;
+            let output = output.unwrap_or_else(|| input.with_extension("svg"));
+            let mut options = SvgOptions::default();
+            options.width = width;
+
+            let mut outputs = Vec::new();
+            for pattern in &toolpath.patterns {
+                let path = if toolpath.patterns.len() == 1 {
+                    output.clone()
+                } else {
+                    numbered_path(&output, pattern.number)
+                };
+                write_file(
+                    &path,
+                    render_toolpath(pattern, sheet_width, sheet_height, &options),
+                )?;
+                outputs.push(path);
+            }
+            Ok(Outcome {
+                outputs,
+                ..Outcome::default()
+            })
+        }
+        Command::Labels {
+            input,
+            output,
+            label_format,
+            template,
+            paper,
+        } => {
+            let schemas = load(&input.input)?;
+            let template = match template {
+                Some(path) => {
+                    let json = std::fs::read_to_string(&path)
+                        .with_context(|| format!("Failed to read {}", path.display()))?;
+                    LabelTemplate::from_json(&json).with_context(|| {
+                        InvalidConfig(format!("Invalid label template {}", path.display()))
+                    })?
+                }
+                None => LabelTemplate::default(),
+            };
+            let labels = piece_labels(&schemas);
+
+            let (content, extension) = match label_format {
+                LabelFormatArg::Zpl => (labels_zpl(&labels, &template).into_bytes(), "zpl"),
+                LabelFormatArg::Pdf => (labels_pdf(&labels, &template, paper.into())?, "pdf"),
+            };
+            let output = output.unwrap_or_else(|| input.input.with_extension(extension));
+            write_file(&output, content)?;
+            info!("{} labels", labels.len());
+            Ok(Outcome {
+                outputs: vec![output],
+                data: Some(serde_json::json!({ "labels": labels.len() })),
+                ..Outcome::default()
+            })
+        }
+    }
+}
+
+/// Conversion settings, shared by every file of a run.
+pub(crate) struct Conversion {
+    machine: MachineConfig,
+    validator: Validator,
+    split: Option<SplitMode>,
+    post: PostArg,
+}
+
+/// Files written for one input.
+pub(crate) struct Converted {
+    /// Programs, then the split manifest if any.
+    pub outputs: Vec<PathBuf>,
+    /// Validation findings, all warnings.
+    pub diagnostics: Vec<Diagnostic>,
+    /// Layout figures and cycle time of the programs.
+    pub stats: Stats,
+}
+
+impl From<Converted> for Outcome {
+    fn from(converted: Converted) -> Self {
+        Self {
+            diagnostics: converted.diagnostics,
+            stats: Some(converted.stats),
+            outputs: converted.outputs,
+            ..Outcome::default()
+        }
+    }
+}
+
+impl Conversion {
+    pub(crate) fn new(
+        machine: &MachineArgs,
+        split: Option<SplitArg>,
+        post: PostArg,
+        rules: &RulesArgs,
+    ) -> Result<Self> {
+        let machine_config = machine_config(machine)?;
+        if !machine_config.is_cutting_table() {
+            warn!(
+                "Machine number {} is outside the cutting table range (100-199)",
+                machine.machine
+            );
+        }
+
+        Ok(Self {
+            machine: machine_config,
+            validator: validator(rules)?,
+            split: split.map(SplitMode::from),
+            post,
+        })
+    }
+
+    /// Output path of an input in `dir`, next to the input without one.
+    pub(crate) fn output_path(&self, input: &Path, dir: Option<&Path>) -> PathBuf {
+        let path = input.with_extension(self.post.post_processor().extension());
+        match (dir, path.file_name()) {
+            (Some(dir), Some(name)) => dir.join(name),
+            _ => path,
+        }
+    }
+
+    /// Convert a file: validate, generate and write the programs.
+    pub(crate) fn run(&self, input: &Path, output_path: &Path) -> Result<Converted> {
+        let schemas = load(input)?;
+        let validation = check(&schemas, &self.validator)?;
+
+        // Generate output (at least one program per sheet format)
+        let mode = self.split.unwrap_or(SplitMode::PerSheet);
+        let post = self.post.post_processor();
+        let mut programs = generate_split_with_post(
+            &schemas,
+            file_name(input),
+            &self.machine,
+            mode,
+            post.as_ref(),
+        )?;
+        report_cycle_time(&programs);
+
+        // Write output
+        let mut converted = Converted {
+            outputs: Vec::new(),
+            diagnostics: validation.diagnostics,
+            stats: Stats {
+                layout: layout_stats(&schemas),
+                cycle_time: CycleTime {
+                    patterns: programs
+                        .iter()
+                        .flat_map(|p| p.cycle_time.patterns.iter().cloned())
+                        .collect(),
+                },
+            },
+        };
+
+        if self.split.is_none() && programs.len() > 1 {
+            warn!(
+                "Patterns use {} sheet formats, writing one CNI program per sheet",
+                programs.len()
+            );
+        }
+
+        if self.split.is_none() && programs.len() == 1 {
+            write_file(output_path, &programs[0].content)?;
+            converted.outputs.push(output_path.to_path_buf());
+            return Ok(converted);
+        }
+
+        for (idx, program) in programs.iter_mut().enumerate() {
+            let path = numbered_path(output_path, idx + 1);
+            program.file_name = file_name(&path).to_string();
+            write_file(&path, &program.content)?;
+            converted.outputs.push(path);
+        }
+
+        // Manifest listing which patterns went into which program
+        let manifest = SplitManifest::new(file_name(input), mode, &programs);
+        let manifest_path = manifest_path(output_path);
+        write_file(&manifest_path, manifest.to_json())?;
+        converted.outputs.push(manifest_path);
+
+        Ok(converted)
+    }
+}
+
+/// Parse an input file and process its cuts, as the converter generates
+/// programs from them.
+fn load(input: &Path) -> Result<Vec<Schema>> {
+    info!("Processing: {}", input.display());
+
+    let mut schemas =
+        parse_otd_file(input).with_context(|| format!("Failed to parse {}", input.display()))?;
+    for schema in &mut schemas {
+        transform::process_linear_cuts(schema);
+        transform::process_shapes(schema);
+    }
+
+    info!("Parsed {} pattern(s)", schemas.len());
+    Ok(schemas)
+}
+
+/// Validator with the policy given on the command line.
+fn validator(rules: &RulesArgs) -> Result<Validator> {
+    let validator = Validator::new();
+    let Some(path) = &rules.rules else {
+        return Ok(validator);
+    };
+    let json = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    ValidationConfig::from_json(&json)
+        .and_then(|config| validator.with_config(config))
+        .with_context(|| InvalidConfig(format!("Invalid validation rules {}", path.display())))
+}
+
+/// Validate and log every finding; fails on errors.
+fn check(schemas: &[Schema], validator: &Validator) -> Result<ValidationResult> {
+    let validation = validator.validate(schemas)?;
+
+    for diagnostic in validation.with_severity(Severity::Warning) {
+        warn!("{}", diagnostic);
+    }
+
+    for diagnostic in validation.with_severity(Severity::Error) {
+        error!("{}", diagnostic);
+    }
+
+    if !validation.passed {
+        return Err(ValidationFailed(validation).into());
+    }
+    Ok(validation)
+}
+
+/// Validation found errors; holds every finding for reports.
+#[derive(Debug)]
+pub(crate) struct ValidationFailed(pub ValidationResult);
+
+impl fmt::Display for ValidationFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Validation failed with {} error(s)",
+            self.0.with_severity(Severity::Error).count()
+        )
+    }
+}
+
+impl std::error::Error for ValidationFailed {}
+
+/// Settings file the converter rejected: validation rules, machine profile
+/// or label template. Not an input error, whatever the converter reports.
+#[derive(Debug)]
+pub(crate) struct InvalidConfig(pub String);
+
+impl fmt::Display for InvalidConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Machine configuration from the command line.
+fn machine_config(args: &MachineArgs) -> Result<MachineConfig> {
+    let mut config = MachineConfig::new(args.machine);
+    if let Some(order) = args.cutting_order {
+        config = config.with_cutting_order(order.into());
+    }
+    if let Some(path) = &args.machine_profile {
+        let json = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let kinematics = MachineKinematics::from_json(&json).with_context(|| {
+            InvalidConfig(format!("Invalid machine profile {}", path.display()))
+        })?;
+        config = config.with_kinematics(kinematics);
+    }
+    Ok(config)
+}
+
+/// Log the estimated cycle time of every pattern.
+fn report_cycle_time(programs: &[CniProgram]) {
+    let patterns: Vec<&PatternCycleTime> = programs
+        .iter()
+        .flat_map(|p| &p.cycle_time.patterns)
+        .collect();
+    for pattern in &patterns {
+        info!(
+            "Pattern {}: estimated cycle time {}",
+            pattern.number,
+            pattern.summary()
+        );
+    }
+    if patterns.len() > 1 {
+        let total: f64 = patterns.iter().map(|p| p.total()).sum();
+        info!("Estimated total cycle time {}", format_duration(total));
+    }
+}
+
+/// Header, sheet and contents of every pattern.
+#[derive(Debug, Serialize)]
+struct LayoutInfo {
+    file: String,
+    /// `OTD` or `OTX`.
+    format: &'static str,
+    version: String,
+    unit: Unit,
+    creator: String,
+    date: String,
+    patterns: Vec<PatternInfo>,
+}
+
+/// Sheet and contents of one pattern.
+#[derive(Debug, Serialize)]
+struct PatternInfo {
+    number: usize,
+    width: f64,
+    height: f64,
+    thickness: f64,
+    material: String,
+    sheets: u32,
+    machine: u16,
+    trim_left: f64,
+    trim_bottom: f64,
+    pieces: usize,
+    shaped_pieces: usize,
+    shapes: usize,
+    linear_cuts: usize,
+}
+
+impl LayoutInfo {
+    fn new(input: &Path, schemas: &[Schema]) -> Self {
+        let first = &schemas[0];
+        let encrypted = input
+            .extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("otx"));
+        Self {
+            file: file_name(input).to_string(),
+            format: if encrypted { "OTX" } else { "OTD" },
+            version: first.otd_version.clone(),
+            unit: first.unit,
+            creator: first.creator.clone(),
+            date: first.date.clone(),
+            patterns: schemas
+                .iter()
+                .enumerate()
+                .map(|(idx, schema)| PatternInfo {
+                    number: idx + 1,
+                    width: schema.width,
+                    height: schema.height,
+                    thickness: schema.thickness,
+                    material: schema.glass_id.clone(),
+                    sheets: schema.quantity,
+                    machine: schema.machine_number,
+                    trim_left: schema.trim_left,
+                    trim_bottom: schema.trim_bottom,
+                    pieces: schema.pieces.len(),
+                    shaped_pieces: schema
+                        .pieces
+                        .iter()
+                        .filter(|p| p.shape_index.is_some())
+                        .count(),
+                    shapes: schema.shapes.len(),
+                    linear_cuts: schema.linear_cuts.len(),
+                })
+                .collect(),
+        }
+    }
+
+    fn report(&self) -> String {
+        let mut report = format!(
+            "{}: {} {}, {} pattern(s), {}\n",
+            self.file,
+            self.format,
+            self.version,
+            self.patterns.len(),
+            self.unit
+        );
+        let created: Vec<&str> = [self.creator.as_str(), self.date.as_str()]
+            .into_iter()
+            .filter(|s| !s.is_empty())
+            .collect();
+        if !created.is_empty() {
+            report += &format!("Created by {}\n", created.join(", "));
+        }
+        for pattern in &self.patterns {
+            report += &format!(
+                "Pattern {}: {} x {} x {} {}, {} sheet(s), machine {}\n",
+                pattern.number,
+                pattern.width,
+                pattern.height,
+                pattern.thickness,
+                pattern.material,
+                pattern.sheets,
+                pattern.machine
+            );
+            report += &format!(
+                "  trims {} / {}, {} pieces ({} shaped), {} shapes, {} linear cuts\n",
+                pattern.trim_left,
+                pattern.trim_bottom,
+                pattern.pieces,
+                pattern.shaped_pieces,
+                pattern.shapes,
+                pattern.linear_cuts
+            );
+        }
+        report
+    }
+}
+
+/// Glass used, yield, cut lengths and cycle time, per pattern and in total.
+fn stats_report(stats: &LayoutStats, cycle_time: &CycleTime) -> String {
+    // Areas in m² and lengths in m, whatever the unit of the layout
+    let mm = stats.unit.to_mm_factor();
+    let area = |a: f64| a * mm * mm / 1e6;
+    let length = |l: f64| l * mm / 1e3;
+
+    let mut report = String::new();
+    for pattern in &stats.patterns {
+        report += &format!(
+            "Pattern {} ({}): {} sheet(s), {} pieces ({} shaped), yield {:.1}%\n",
+            pattern.number,
+            pattern.material,
+            pattern.sheets,
+            pattern.pieces,
+            pattern.shaped_pieces,
+            pattern.yield_ratio() * 100.0
+        );
+        report += &format!(
+            "  glass {:.2} m², waste {:.2} m², cuts {:.2} m ({} linear, {:.2} m of shapes)\n",
+            area(pattern.sheet_area),
+            area(pattern.waste_area()),
+            length(pattern.cut_length()),
+            pattern.linear_cuts,
+            length(pattern.shape_length)
+        );
+        if let Some(time) = cycle_time.pattern(pattern.number) {
+            report += &format!("  cycle time {}\n", time.summary());
+        }
+    }
+    report += &format!(
+        "Total: {} sheet(s), {} pieces, glass {:.2} m², yield {:.1}%, cuts {:.2} m, cycle time {}\n",
+        stats.sheets(),
+        stats.pieces(),
+        area(stats.sheet_area()),
+        stats.yield_ratio() * 100.0,
+        length(stats.cut_length()),
+        format_duration(cycle_time.total())
+    );
+    report
+}
+
+/// Write one file per pattern, numbered when there are several.
+fn write_per_pattern(
+    output: &Path,
+    schemas: &[Schema],
+    render: impl Fn(&Schema) -> Vec<u8>,
+) -> Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    for (idx, schema) in schemas.iter().enumerate() {
+        let path = if schemas.len() == 1 {
+            output.to_path_buf()
+        } else {
+            numbered_path(output, idx + 1)
+        };
+        write_file(&path, render(schema))?;
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+/// Read an input file; a missing input is reported as such, unlike the
+/// files written or the settings read next to it.
+pub(crate) fn read_input(path: &Path) -> std::result::Result<Vec<u8>, ConvertError> {
+    std::fs::read(path).map_err(|e| match e.kind() {
+        std::io::ErrorKind::NotFound => ConvertError::FileNotFound {
+            path: path.to_path_buf(),
+        },
+        _ => ConvertError::Io(e),
+    })
+}
+
+/// Write a generated file to disk.
+pub(crate) fn write_file(path: &Path, content: impl AsRef<[u8]>) -> Result<()> {
+    std::fs::write(path, content).with_context(|| format!("Failed to write {}", path.display()))?;
+
+    info!("Generated: {}", path.display());
+    Ok(())
+}
+
+/// File name used in the generated [COMMENTO] and [*LDIST] sections.
+fn file_name(path: &Path) -> &str {
+    path.file_name()
+        .and_then(|s| s.to_str())
+        .unwrap_or_default()
+}
+
+fn file_stem<'a>(path: &'a Path, default: &'a str) -> &'a str {
+    path.file_stem().and_then(|s| s.to_str()).unwrap_or(default)
+}
+
+/// Build `<stem>_NN.<ext>` next to the requested output path.
+fn numbered_path(path: &Path, number: usize) -> PathBuf {
+    let stem = file_stem(path, "output");
+    let ext = path.extension().and_then(|s| s.to_str()).unwrap_or("cni");
+    path.with_file_name(format!("{}_{:02}.{}", stem, number, ext))
+}
+
+/// Requested output path whose numbered programs include `path`, if any.
+pub(crate) fn numbered_base(path: &Path) -> Option<PathBuf> {
+    let (stem, number) = path.file_stem()?.to_str()?.rsplit_once('_')?;
+    if number.len() < 2 || !number.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let ext = path.extension().and_then(|s| s.to_str()).unwrap_or("cni");
+    Some(path.with_file_name(format!("{}.{}", stem, ext)))
+}
+
+/// Build `<stem>_manifest.json` next to the requested output path.
+pub(crate) fn manifest_path(path: &Path) -> PathBuf {
+    path.with_file_name(format!("{}_manifest.json", file_stem(path, "output")))
+}
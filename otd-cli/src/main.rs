@@ -1,116 +1,530 @@
 //! otd-convert - CLI tool to convert OTD files to CNI format.
 
-use anyhow::{Context, Result};
-use clap::Parser;
+use clap::{Args, Parser, Subcommand, ValueEnum};
 use std::path::PathBuf;
-use tracing::{error, info, warn};
+use std::process::ExitCode;
 use tracing_subscriber::EnvFilter;
 
-use otd_core::{convert_otd_to_cni, parse_otd_file, validate_schemas};
+use otd_core::export::Paper;
+use report::Report;
+
+use otd_core::{CuttingOrder, IntermacCni, IsoPost, PostProcessor, SplitMode};
+
+mod batch;
+mod commands;
+mod report;
+mod watch;
 
 /// Convert OTD files to CNI format for Intermac glass cutting machines.
 #[derive(Parser, Debug)]
 #[command(name = "otd-convert")]
 #[command(author, version, about, long_about = None)]
-struct Args {
-    /// Input OTD/OTX file path
-    #[arg(short, long)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+
+    /// Verbose output
+    #[arg(short, long, global = true)]
+    verbose: bool,
+
+    /// Report format on standard output: text, or one JSON document with
+    /// diagnostics, stats and output paths
+    #[arg(long, value_enum, global = true, default_value = "text")]
+    format: OutputFormat,
+}
+
+impl Cli {
+    /// Format of the report, `diff --json` included.
+    fn format(&self) -> OutputFormat {
+        match self.command {
+            Command::Diff { json: true, .. } => OutputFormat::Json,
+            _ => self.format,
+        }
+    }
+}
+
+/// Input layout shared by the commands reading an OTD/OTX file.
+#[derive(Args, Debug)]
+struct InputArgs {
+    /// Input OTD/OTX file
     input: PathBuf,
+}
 
-    /// Output CNI file path
-    #[arg(short, long)]
-    output: Option<PathBuf>,
+/// Validation policy shared by the commands that validate.
+#[derive(Args, Debug)]
+struct RulesArgs {
+    /// JSON validation policy setting the level (off, warn, error) and
+    /// parameters of each rule
+    #[arg(long)]
+    rules: Option<PathBuf>,
+}
 
+/// Target machine shared by the commands that generate or time programs.
+#[derive(Args, Debug)]
+struct MachineArgs {
     /// Machine number (100-199 for cutting tables)
     #[arg(short, long, default_value = "130")]
     machine: u16,
 
-    /// Validate only, don't generate output
+    /// JSON machine profile overriding feed rates, accelerations and dwells
+    /// used for cycle time estimation
     #[arg(long)]
-    validate: bool,
+    machine_profile: Option<PathBuf>,
 
-    /// Output debug information as JSON
-    #[arg(long)]
-    debug: bool,
+    /// Override the OTD CuttingOrder for all patterns
+    #[arg(long, value_enum)]
+    cutting_order: Option<CuttingOrderArg>,
+}
 
-    /// Verbose output
+/// Commands of the tool.
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Convert an OTD/OTX file to a CNI program
+    Convert {
+        #[command(flatten)]
+        input: InputArgs,
+
+        /// Output CNI file path (default: input with the dialect extension)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        #[command(flatten)]
+        machine: MachineArgs,
+
+        /// Split the file into one numbered CNI program per pattern or per material
+        #[arg(long, value_enum)]
+        split: Option<SplitArg>,
+
+        /// Output dialect
+        #[arg(long, value_enum, default_value = "intermac")]
+        post: PostArg,
+
+        #[command(flatten)]
+        rules: RulesArgs,
+    },
+
+    /// Convert many files in parallel and summarize the results
+    Batch(BatchArgs),
+
+    /// Convert the OTD/OTX files dropped into a directory until stopped
+    Watch {
+        /// Directory to watch; sources move to its done/ and failed/ subdirectories
+        input: PathBuf,
+
+        /// Output directory of the programs
+        output: PathBuf,
+
+        #[command(flatten)]
+        machine: MachineArgs,
+
+        /// Split each file into one numbered CNI program per pattern or per material
+        #[arg(long, value_enum)]
+        split: Option<SplitArg>,
+
+        /// Output dialect
+        #[arg(long, value_enum, default_value = "intermac")]
+        post: PostArg,
+
+        #[command(flatten)]
+        rules: RulesArgs,
+
+        /// Seconds between two polls; a file is converted once unchanged for
+        /// a whole interval
+        #[arg(long, default_value = "2")]
+        interval: f64,
+    },
+
+    /// Validate an OTD/OTX file without generating output
+    Validate {
+        #[command(flatten)]
+        input: InputArgs,
+
+        #[command(flatten)]
+        rules: RulesArgs,
+    },
+
+    /// Print the header, sheets and contents of each pattern
+    Info {
+        #[command(flatten)]
+        input: InputArgs,
+    },
+
+    /// Print glass used, yield, cut lengths and estimated cycle time
+    Stats {
+        #[command(flatten)]
+        input: InputArgs,
+
+        #[command(flatten)]
+        machine: MachineArgs,
+    },
+
+    /// Write the layout as JSON, SVG, DXF or a PDF cut list
+    Export {
+        /// Output format
+        #[arg(value_enum, value_name = "FORMAT")]
+        export_format: ExportFormat,
+
+        #[command(flatten)]
+        input: InputArgs,
+
+        /// Output file path (default: input with the format extension); SVG
+        /// and DXF get one numbered file per pattern when there are several
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Paper size of the PDF cut list
+        #[arg(long, value_enum, default_value = "a4")]
+        paper: PaperArg,
+
+        /// SVG image width in pixels
+        #[arg(long, default_value = "1200")]
+        width: f64,
+    },
+
+    /// Decrypt an OTX file to plain OTD text
+    Decrypt {
+        /// Input OTX file
+        input: PathBuf,
+
+        /// Output OTD file path (default: input with .otd extension)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Encrypt an OTD file as OTX, as written by the optimizer
+    Encrypt {
+        /// Input OTD file
+        input: PathBuf,
+
+        /// Output OTX file path (default: input with .otx extension)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Compare two CNI programs by scored geometry and tool behaviour
+    Diff {
+        /// Reference CNI file
+        left: PathBuf,
+
+        /// CNI file to compare
+        right: PathBuf,
+
+        /// Maximum coordinate difference treated as equal, in file units
+        #[arg(long, default_value = "0.001")]
+        tolerance: f64,
+
+        /// Same as `--format json`
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Draw what a CNI program scores, as one SVG per pattern
+    Render {
+        /// Input CNI file
+        input: PathBuf,
+
+        /// Output SVG file path (default: input with .svg extension); programs
+        /// with several patterns get one numbered drawing per pattern
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Image width in pixels
+        #[arg(long, default_value = "1200")]
+        width: f64,
+    },
+
+    /// Write one label per physical piece, as ZPL for label printers or a PDF sheet
+    Labels {
+        #[command(flatten)]
+        input: InputArgs,
+
+        /// Output file path (default: input with .zpl or .pdf extension)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Label format
+        #[arg(long, value_enum, default_value = "zpl")]
+        label_format: LabelFormatArg,
+
+        /// JSON label template (size, text lines, barcode)
+        #[arg(long)]
+        template: Option<PathBuf>,
+
+        /// Paper size of the PDF label sheet
+        #[arg(long, value_enum, default_value = "a4")]
+        paper: PaperArg,
+    },
+}
+
+impl Command {
+    /// Name of the command, as typed.
+    fn name(&self) -> &'static str {
+        match self {
+            Command::Convert { .. } => "convert",
+            Command::Batch(_) => "batch",
+            Command::Watch { .. } => "watch",
+            Command::Validate { .. } => "validate",
+            Command::Info { .. } => "info",
+            Command::Stats { .. } => "stats",
+            Command::Export { .. } => "export",
+            Command::Decrypt { .. } => "decrypt",
+            Command::Encrypt { .. } => "encrypt",
+            Command::Diff { .. } => "diff",
+            Command::Render { .. } => "render",
+            Command::Labels { .. } => "labels",
+        }
+    }
+}
+
+/// Arguments of the batch command.
+#[derive(Args, Debug)]
+struct BatchArgs {
+    /// Input files, directories (every OTD/OTX file in them) or glob
+    /// patterns such as `orders/*.otx`
+    #[arg(required = true)]
+    inputs: Vec<PathBuf>,
+
+    /// Output directory (default: next to each input)
     #[arg(short, long)]
-    verbose: bool,
+    output: Option<PathBuf>,
+
+    #[command(flatten)]
+    machine: MachineArgs,
+
+    /// Split each file into one numbered CNI program per pattern or per material
+    #[arg(long, value_enum)]
+    split: Option<SplitArg>,
+
+    /// Output dialect
+    #[arg(long, value_enum, default_value = "intermac")]
+    post: PostArg,
+
+    #[command(flatten)]
+    rules: RulesArgs,
+
+    /// Files converted at once (default: one per core)
+    #[arg(short, long)]
+    jobs: Option<usize>,
+
+    /// Start no new file after the first failure
+    #[arg(long, conflicts_with = "keep_going")]
+    fail_fast: bool,
+
+    /// Convert every file whatever the failures (default)
+    #[arg(long)]
+    keep_going: bool,
+}
+
+/// Format of reports printed on standard output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    /// Human-readable text
+    Text,
+    /// JSON document
+    Json,
+}
+
+/// Layout export format accepted on the command line.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum ExportFormat {
+    /// Parsed and processed patterns as JSON
+    Json,
+    /// SVG preview per pattern
+    Svg,
+    /// AutoCAD R12 drawing per pattern
+    Dxf,
+    /// Printable cut list
+    Pdf,
 }
 
-fn main() -> Result<()> {
-    let args = Args::parse();
+impl ExportFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Json => "json",
+            ExportFormat::Svg => "svg",
+            ExportFormat::Dxf => "dxf",
+            ExportFormat::Pdf => "pdf",
+        }
+    }
+}
+
+/// Label output format accepted on the command line.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum LabelFormatArg {
+    /// ZPL II text for Zebra-compatible label printers
+    Zpl,
+    /// PDF sheet with a grid of labels
+    Pdf,
+}
+
+/// Paper size accepted on the command line.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum PaperArg {
+    /// ISO A4
+    A4,
+    /// US Letter
+    Letter,
+}
+
+impl From<PaperArg> for Paper {
+    fn from(arg: PaperArg) -> Self {
+        match arg {
+            PaperArg::A4 => Paper::A4,
+            PaperArg::Letter => Paper::Letter,
+        }
+    }
+}
+
+/// Cutting order accepted on the command line.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum CuttingOrderArg {
+    /// Linear cuts before shapes
+    LinearFirst,
+    /// Shapes before linear cuts
+    ShapesFirst,
+}
+
+impl From<CuttingOrderArg> for CuttingOrder {
+    fn from(arg: CuttingOrderArg) -> Self {
+        match arg {
+            CuttingOrderArg::LinearFirst => CuttingOrder::LinearFirst,
+            CuttingOrderArg::ShapesFirst => CuttingOrder::ShapesFirst,
+        }
+    }
+}
+
+/// Split mode accepted on the command line.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum SplitArg {
+    /// One program per pattern
+    PerPattern,
+    /// One program per material (glass ID)
+    PerMaterial,
+}
+
+impl From<SplitArg> for SplitMode {
+    fn from(arg: SplitArg) -> Self {
+        match arg {
+            SplitArg::PerPattern => SplitMode::PerPattern,
+            SplitArg::PerMaterial => SplitMode::PerMaterial,
+        }
+    }
+}
+
+/// Output dialect accepted on the command line.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum PostArg {
+    /// Intermac CNI
+    Intermac,
+    /// Generic ISO/RS-274 G-code with tangential axis
+    Iso,
+}
+
+impl PostArg {
+    fn post_processor(self) -> Box<dyn PostProcessor> {
+        match self {
+            PostArg::Intermac => Box::new(IntermacCni),
+            PostArg::Iso => Box::new(IsoPost::new()),
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
 
     // Initialize logging
-    let filter = if args.verbose {
+    let filter = if cli.verbose {
         EnvFilter::new("debug")
     } else {
         EnvFilter::new("info")
     };
 
+    // Logs go to stderr, reports and summaries to stdout
     tracing_subscriber::fmt()
         .with_env_filter(filter)
         .with_target(false)
+        .with_writer(std::io::stderr)
         .init();
 
-    // Validate machine number
-    if args.machine < 100 || args.machine >= 200 {
-        warn!(
-            "Machine number {} is outside the cutting table range (100-199)",
-            args.machine
-        );
-    }
-
-    info!("Processing: {}", args.input.display());
-
-    // Parse the input file
-    let schemas = parse_otd_file(&args.input)
-        .with_context(|| format!("Failed to parse {}", args.input.display()))?;
+    let format = cli.format();
+    let name = cli.command.name();
+    let result = commands::run(cli.command, format);
 
-    info!("Parsed {} pattern(s)", schemas.len());
-
-    // Validate
-    let validation = validate_schemas(&schemas)?;
-
-    for warning in &validation.warnings {
-        warn!("{}", warning);
+    if let (OutputFormat::Text, Err(e)) = (format, &result) {
+        eprintln!("Error: {:?}", e);
     }
-
-    for err in &validation.errors {
-        error!("{}", err);
+    let report = Report::new(name, result);
+    if format == OutputFormat::Json {
+        println!("{}", report.to_json());
     }
+    ExitCode::from(report.exit_code)
+}
 
-    if !validation.passed {
-        anyhow::bail!("Validation failed");
-    }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+    use report::Exit;
 
-    // Debug output
-    if args.debug {
-        let json = serde_json::to_string_pretty(&schemas)?;
-        println!("{}", json);
-        return Ok(());
+    const FIXTURE: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/../otd-core/tests/fixtures/integration/simple_linear.otd"
+    );
+
+    /// Exit code of a command line run the way `main` runs it.
+    fn exit_of(args: &[&str]) -> Exit {
+        let cli = Cli::try_parse_from(std::iter::once("otd-convert").chain(args.iter().copied()))
+            .unwrap();
+        let format = cli.format();
+        let result = commands::run(cli.command, format);
+        Report::new("test", result).status
     }
 
-    // Validate-only mode
-    if args.validate {
-        info!("Validation passed");
-        return Ok(());
+    #[test]
+    fn test_cli_definition() {
+        // Catches clashes between global and command arguments
+        Cli::command().debug_assert();
     }
 
-    // Generate output
-    let cni = convert_otd_to_cni(&args.input, args.machine)?;
+    #[test]
+    fn test_exit_codes_of_settings_and_inputs() {
+        let dir = std::env::temp_dir().join(format!("otd_cli_exit_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
 
-    // Write output
-    let output_path = args.output.unwrap_or_else(|| {
-        let mut path = args.input.clone();
-        path.set_extension("cni");
-        path
-    });
+        // Settings the converter rejects are not malformed inputs
+        let rules = dir.join("rules.json");
+        std::fs::write(&rules, r#"{"rules": {"no-such-rule": "error"}}"#).unwrap();
+        let rules = rules.to_str().unwrap();
+        assert_eq!(
+            exit_of(&["validate", FIXTURE, "--rules", rules]),
+            Exit::Failure
+        );
 
-    std::fs::write(&output_path, &cni)
-        .with_context(|| format!("Failed to write {}", output_path.display()))?;
+        let profile = dir.join("profile.json");
+        std::fs::write(&profile, "{ not json").unwrap();
+        let output = dir.join("layout.cni");
+        let (profile, output) = (profile.to_str().unwrap(), output.to_str().unwrap());
+        assert_eq!(
+            exit_of(&[
+                "convert",
+                FIXTURE,
+                "-o",
+                output,
+                "--machine-profile",
+                profile
+            ]),
+            Exit::Failure
+        );
+        assert!(!dir.join("layout.cni").exists());
 
-    info!("Generated: {}", output_path.display());
+        let missing = dir.join("missing.otd");
+        assert_eq!(
+            exit_of(&["validate", missing.to_str().unwrap()]),
+            Exit::FileNotFound
+        );
+        assert_eq!(exit_of(&["validate", FIXTURE]), Exit::Success);
 
-    Ok(())
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
 }
@@ -0,0 +1,304 @@
+//! Exit codes and the JSON document printed with `--format json`.
+//!
+//! Every command returns an [`Outcome`]: the findings, figures and files it
+//! produced. The process exits with the [`Exit`] code of the outcome or of
+//! the error, and `--format json` prints both as one [`Report`] on standard
+//! output. The codes and the document fields are documented in the README
+//! and only ever get additions.
+
+use otd_core::analysis::CycleTime;
+use otd_core::error::ErrorCode;
+use otd_core::{ConvertError, Diagnostic, LayoutStats, Severity};
+use serde::Serialize;
+use std::path::PathBuf;
+
+use crate::commands::{InvalidConfig, ValidationFailed};
+
+/// Version of the JSON document, raised when a field changes meaning.
+pub(crate) const FORMAT_VERSION: u32 = 1;
+
+/// Process exit code.
+///
+/// Code 2 is left to command line errors, reported by the argument parser
+/// before any command runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Exit {
+    /// The command succeeded.
+    #[default]
+    Success = 0,
+    /// `diff`: the programs differ.
+    Differences = 1,
+    /// Failure without a more specific code: reading or writing files,
+    /// invalid rules, profiles or templates.
+    Failure = 3,
+    /// `batch`: at least one file failed.
+    FilesFailed = 4,
+    /// Validation found errors without a code of their own; coded errors
+    /// exit with the code of the first one.
+    ValidationFailed = 10,
+    /// Input file missing (-1).
+    FileNotFound = 20,
+    /// Empty input file (-2).
+    EmptyFile = 21,
+    /// Malformed input (-3).
+    ParseError = 22,
+    /// No `[Pattern]` section (-11).
+    NoPatternSection = 23,
+    /// OTX decryption failed (E300).
+    DecryptionFailed = 24,
+    /// Strip hierarchy nested too deep or skipping a level (-12).
+    InvalidHierarchy = 25,
+    /// Invalid arc geometry (E100).
+    InvalidArc = 30,
+    /// Coordinates out of bounds (E101).
+    OutOfBounds = 31,
+    /// Shape contour crosses itself (E102).
+    SelfIntersection = 32,
+    /// Zero or negative dimension (E103).
+    InvalidDimension = 33,
+    /// Shape contour does not close (E104).
+    OpenContour = 34,
+    /// Shape used on different-sized pieces (E200).
+    ShapeSizeMismatch = 40,
+    /// No cuts in the layout (E201).
+    NoCutsFound = 41,
+    /// No tool for the thickness (E202).
+    ToolNotFound = 42,
+    /// Patterns use different sheet formats (E203).
+    SheetFormatMismatch = 43,
+    /// Feature too small or sharp to cut (E204).
+    Manufacturability = 44,
+    /// Shape or Info reference not found (E205).
+    UnresolvedReference = 45,
+    /// Toolpath simulation failed or differs from the layout (E400).
+    ToolpathMismatch = 50,
+    /// Macro the output dialect cannot translate (E401).
+    UnsupportedMacro = 51,
+}
+
+impl Exit {
+    /// Exit code of a failed command, from the first typed error of its chain.
+    pub(crate) fn of(error: &anyhow::Error) -> Self {
+        // Settings are parsed by the converter too, but are not the input
+        if error.downcast_ref::<InvalidConfig>().is_some() {
+            return Exit::Failure;
+        }
+        for cause in error.chain() {
+            if let Some(failed) = cause.downcast_ref::<ValidationFailed>() {
+                return Exit::of_validation(failed);
+            }
+            // Inputs are read through the converter, so that other I/O
+            // errors, writing outputs above all, stay general failures
+            if let Some(error) = cause.downcast_ref::<ConvertError>() {
+                return error.code().into();
+            }
+        }
+        Exit::Failure
+    }
+
+    /// Exit code of a failed validation, from its first error finding.
+    fn of_validation(failed: &ValidationFailed) -> Self {
+        match failed.0.with_severity(Severity::Error).next() {
+            // Findings reported by message only have no category
+            Some(diagnostic) if diagnostic.code != ErrorCode::ParseError => diagnostic.code.into(),
+            _ => Exit::ValidationFailed,
+        }
+    }
+
+    /// Code passed to the operating system.
+    pub(crate) fn code(self) -> u8 {
+        self as u8
+    }
+}
+
+impl From<ErrorCode> for Exit {
+    fn from(code: ErrorCode) -> Self {
+        match code {
+            ErrorCode::FileNotFound => Exit::FileNotFound,
+            ErrorCode::EmptyFile => Exit::EmptyFile,
+            ErrorCode::ParseError => Exit::ParseError,
+            ErrorCode::Io => Exit::Failure,
+            ErrorCode::NoPatternSection => Exit::NoPatternSection,
+            ErrorCode::InvalidHierarchy => Exit::InvalidHierarchy,
+            ErrorCode::InvalidArc => Exit::InvalidArc,
+            ErrorCode::OutOfBounds => Exit::OutOfBounds,
+            ErrorCode::SelfIntersection => Exit::SelfIntersection,
+            ErrorCode::InvalidDimension => Exit::InvalidDimension,
+            ErrorCode::OpenContour => Exit::OpenContour,
+            ErrorCode::ShapeSizeMismatch => Exit::ShapeSizeMismatch,
+            ErrorCode::NoCutsFound => Exit::NoCutsFound,
+            ErrorCode::ToolNotFound => Exit::ToolNotFound,
+            ErrorCode::SheetFormatMismatch => Exit::SheetFormatMismatch,
+            ErrorCode::Manufacturability => Exit::Manufacturability,
+            ErrorCode::UnresolvedReference => Exit::UnresolvedReference,
+            ErrorCode::DecryptionFailed => Exit::DecryptionFailed,
+            ErrorCode::ToolpathMismatch => Exit::ToolpathMismatch,
+            ErrorCode::UnsupportedMacro => Exit::UnsupportedMacro,
+        }
+    }
+}
+
+/// Figures of a layout and its estimated cycle time.
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct Stats {
+    pub layout: LayoutStats,
+    pub cycle_time: CycleTime,
+}
+
+/// What a command produced.
+#[derive(Debug, Default)]
+pub(crate) struct Outcome {
+    /// Exit code; commands that ran to the end may still report a failure.
+    pub exit: Exit,
+    /// Validation findings.
+    pub diagnostics: Vec<Diagnostic>,
+    /// Layout figures, for the commands that compute them.
+    pub stats: Option<Stats>,
+    /// Files written.
+    pub outputs: Vec<PathBuf>,
+    /// Result specific to the command.
+    pub data: Option<serde_json::Value>,
+}
+
+/// Why a command failed.
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct ErrorReport {
+    /// Converter error code, when the failure has one.
+    pub code: Option<ErrorCode>,
+    /// Error and its causes.
+    pub message: String,
+}
+
+/// JSON document of a command run.
+#[derive(Debug, Serialize)]
+pub(crate) struct Report {
+    pub format_version: u32,
+    pub command: &'static str,
+    pub success: bool,
+    pub exit_code: u8,
+    pub status: Exit,
+    pub error: Option<ErrorReport>,
+    pub diagnostics: Vec<Diagnostic>,
+    pub stats: Option<Stats>,
+    pub outputs: Vec<PathBuf>,
+    pub data: Option<serde_json::Value>,
+}
+
+impl Report {
+    /// Document of a command result; failures keep their validation findings.
+    pub(crate) fn new(command: &'static str, result: anyhow::Result<Outcome>) -> Self {
+        let (outcome, error) = match result {
+            Ok(outcome) => (outcome, None),
+            Err(error) => {
+                let outcome = Outcome {
+                    exit: Exit::of(&error),
+                    diagnostics: error
+                        .chain()
+                        .find_map(|cause| cause.downcast_ref::<ValidationFailed>())
+                        .map(|failed| failed.0.diagnostics.clone())
+                        .unwrap_or_default(),
+                    ..Outcome::default()
+                };
+                let report = ErrorReport {
+                    code: error
+                        .chain()
+                        .find_map(|cause| cause.downcast_ref::<ConvertError>())
+                        .filter(|_| error.downcast_ref::<InvalidConfig>().is_none())
+                        .map(ConvertError::code),
+                    message: format!("{:#}", error),
+                };
+                (outcome, Some(report))
+            }
+        };
+        Self {
+            format_version: FORMAT_VERSION,
+            command,
+            success: outcome.exit == Exit::Success,
+            exit_code: outcome.exit.code(),
+            status: outcome.exit,
+            error,
+            diagnostics: outcome.diagnostics,
+            stats: outcome.stats,
+            outputs: outcome.outputs,
+            data: outcome.data,
+        }
+    }
+
+    /// Pretty-printed JSON.
+    pub(crate) fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("report serializes to JSON")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::{read_input, write_file};
+    use otd_core::ValidationResult;
+    use std::path::Path;
+
+    #[test]
+    fn test_exit_codes() {
+        let parse = anyhow::Error::from(ConvertError::NoPatternSection).context("Failed to parse");
+        assert_eq!(Exit::of(&parse), Exit::NoPatternSection);
+        assert_eq!(Exit::of(&parse).code(), 23);
+
+        let missing =
+            anyhow::Error::from(read_input(Path::new("/nonexistent/layout.otd")).unwrap_err());
+        assert_eq!(Exit::of(&missing), Exit::FileNotFound);
+
+        // Writing into a missing directory is not a missing input
+        let write = write_file(Path::new("/nonexistent/layout.cni"), "").unwrap_err();
+        assert_eq!(Exit::of(&write), Exit::Failure);
+
+        let mut validation = ValidationResult::ok();
+        validation.push(Diagnostic::error(ErrorCode::OutOfBounds, "outside"));
+        validation.push(Diagnostic::error(ErrorCode::NoCutsFound, "no cuts"));
+        let failed = anyhow::Error::from(ValidationFailed(validation));
+        assert_eq!(Exit::of(&failed), Exit::OutOfBounds);
+        assert_eq!(Exit::of(&failed).code(), 31);
+
+        let mut validation = ValidationResult::ok();
+        validation.add_error("uncoded");
+        let failed = anyhow::Error::from(ValidationFailed(validation));
+        assert_eq!(Exit::of(&failed).code(), 10);
+
+        // Converter errors met parsing settings do not make them inputs
+        let config = anyhow::Error::from(ConvertError::NoCutsFound)
+            .context(InvalidConfig("Invalid validation rules".into()))
+            .context("layout.otd");
+        assert_eq!(Exit::of(&config), Exit::Failure);
+
+        assert_eq!(Exit::of(&anyhow::anyhow!("Invalid template")).code(), 3);
+        assert_eq!(Exit::from(ErrorCode::ToolpathMismatch).code(), 50);
+    }
+
+    #[test]
+    fn test_report_json() {
+        let outcome = Outcome {
+            outputs: vec![PathBuf::from("layout.cni")],
+            ..Outcome::default()
+        };
+        let json = serde_json::to_value(Report::new("convert", Ok(outcome))).unwrap();
+        assert_eq!(json["format_version"], 1);
+        assert_eq!(json["success"], true);
+        assert_eq!(json["status"], "success");
+        assert_eq!(json["outputs"][0], "layout.cni");
+        assert!(json["error"].is_null());
+
+        let mut validation = ValidationResult::ok();
+        validation.push(Diagnostic::error(ErrorCode::OutOfBounds, "outside"));
+        let error = anyhow::Error::from(ValidationFailed(validation)).context("layout.otd");
+        let json = serde_json::to_value(Report::new("validate", Err(error))).unwrap();
+        assert_eq!(json["success"], false);
+        assert_eq!(json["exit_code"], 31);
+        assert_eq!(json["status"], "out_of_bounds");
+        assert_eq!(json["diagnostics"][0]["code"], "E101");
+        assert!(json["error"]["code"].is_null());
+        assert_eq!(
+            json["error"]["message"],
+            "layout.otd: Validation failed with 1 error(s)"
+        );
+    }
+}
@@ -0,0 +1,261 @@
+//! Hot folder: convert the layouts dropped into a directory.
+//!
+//! The directory is polled, which also works on network shares where change
+//! notifications are unreliable. A file is converted once its size and
+//! modification time are the same on two polls in a row, so files still being
+//! written by the optimizer are left alone. The source then moves to `done/`,
+//! or to `failed/` next to a `<name>.error.txt` report, and a file dropped
+//! again under the same name is converted again.
+
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tracing::{error, info, info_span, warn};
+
+use crate::batch::{is_layout, FileResult, Status};
+use crate::commands::{Conversion, ValidationFailed};
+
+/// Subdirectory of the watched directory receiving converted sources.
+pub(crate) const DONE_DIR: &str = "done";
+/// Subdirectory of the watched directory receiving failed sources.
+pub(crate) const FAILED_DIR: &str = "failed";
+
+/// Size and modification time of a file when last polled.
+type Snapshot = (u64, Option<SystemTime>);
+
+/// Watched directory and the files seen on the last poll.
+pub(crate) struct Watcher {
+    input: PathBuf,
+    output: PathBuf,
+    conversion: Conversion,
+    pending: HashMap<PathBuf, Snapshot>,
+}
+
+impl Watcher {
+    /// Watch `input`, writing programs to `output`; creates the directories.
+    pub(crate) fn new(input: &Path, output: &Path, conversion: Conversion) -> Result<Self> {
+        for dir in [
+            output.to_path_buf(),
+            input.join(DONE_DIR),
+            input.join(FAILED_DIR),
+        ] {
+            std::fs::create_dir_all(&dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        Ok(Self {
+            input: input.to_path_buf(),
+            output: output.to_path_buf(),
+            conversion,
+            pending: HashMap::new(),
+        })
+    }
+
+    /// Poll every `interval` until the process is stopped.
+    pub(crate) fn run(&mut self, interval: Duration, mut report: impl FnMut(&FileResult)) -> ! {
+        info!(
+            "Watching {}, programs to {}",
+            self.input.display(),
+            self.output.display()
+        );
+        loop {
+            for result in self.poll() {
+                report(&result);
+            }
+            std::thread::sleep(interval);
+        }
+    }
+
+    /// Convert the files that stopped changing since the last poll.
+    ///
+    /// Errors are logged and the poll carries on, so the watcher survives
+    /// unreadable files and directories.
+    pub(crate) fn poll(&mut self) -> Vec<FileResult> {
+        let mut current = HashMap::new();
+        match self.scan() {
+            Ok(files) => current = files,
+            Err(e) => error!("{:#}", e),
+        }
+
+        let mut ready: Vec<PathBuf> = current
+            .iter()
+            .filter(|(path, snapshot)| self.pending.get(*path) == Some(snapshot))
+            .map(|(path, _)| path.clone())
+            .collect();
+        ready.sort();
+        for path in &ready {
+            current.remove(path);
+        }
+        self.pending = current;
+
+        ready.iter().map(|path| self.process(path)).collect()
+    }
+
+    /// Layout files directly in the watched directory.
+    fn scan(&self) -> Result<HashMap<PathBuf, Snapshot>> {
+        let entries = std::fs::read_dir(&self.input)
+            .with_context(|| format!("Failed to read {}", self.input.display()))?;
+        let mut files = HashMap::new();
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            if metadata.is_file() && is_layout(&path) {
+                files.insert(path, (metadata.len(), metadata.modified().ok()));
+            }
+        }
+        Ok(files)
+    }
+
+    /// Convert one file and move it out of the watched directory.
+    fn process(&self, input: &Path) -> FileResult {
+        let name = input.file_name().unwrap_or_default();
+        let _span = info_span!("watch", file = %name.to_string_lossy()).entered();
+        let done = self.input.join(DONE_DIR).join(name);
+        let failed = self.input.join(FAILED_DIR).join(name);
+        let report_path = error_report_path(&failed);
+
+        let output = self.conversion.output_path(input, Some(&self.output));
+        let mut result = match self.conversion.run(input, &output) {
+            Ok(converted) => {
+                // A previous failure of the same file is resolved
+                for stale in [&failed, &report_path] {
+                    let _ = std::fs::remove_file(stale);
+                }
+                FileResult::converted(input, converted)
+            }
+            Err(e) => {
+                error!("{:#}", e);
+                if let Err(e) = std::fs::write(&report_path, error_report(input, &e)) {
+                    warn!("Failed to write {}: {}", report_path.display(), e);
+                }
+                FileResult::failed(input, &e)
+            }
+        };
+
+        let target = if result.status == Status::Converted {
+            done
+        } else {
+            failed
+        };
+        if let Err(e) = move_file(input, &target) {
+            // Left in place, the file would be converted on every poll
+            error!("Failed to move to {}: {:#}", target.display(), e);
+            result.status = Status::Failed;
+            result.error = Some(format!("{:#}", e));
+        }
+        result
+    }
+}
+
+/// `<name>.error.txt` next to a failed source.
+fn error_report_path(failed: &Path) -> PathBuf {
+    let mut name = failed.file_name().unwrap_or_default().to_os_string();
+    name.push(".error.txt");
+    failed.with_file_name(name)
+}
+
+/// Error chain and, for validation failures, every finding.
+fn error_report(input: &Path, error: &anyhow::Error) -> String {
+    let mut report = format!("{}: {:#}\n", input.display(), error);
+    if let Some(ValidationFailed(validation)) = error.downcast_ref() {
+        report.push('\n');
+        for diagnostic in &validation.diagnostics {
+            writeln!(report, "{}: {}", diagnostic.severity, diagnostic).unwrap();
+        }
+    }
+    report
+}
+
+/// Move a file, replacing an older one of the same name.
+fn move_file(from: &Path, to: &Path) -> Result<()> {
+    // Renaming onto an existing file fails on Windows
+    if to.exists() {
+        std::fs::remove_file(to)?;
+    }
+    std::fs::rename(from, to)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MachineArgs, PostArg, RulesArgs};
+
+    const FIXTURE: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/../otd-core/tests/fixtures/integration/simple_linear.otd"
+    );
+
+    fn watcher(name: &str) -> (PathBuf, Watcher) {
+        let dir = std::env::temp_dir().join(format!("otd_watch_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let machine = MachineArgs {
+            machine: 130,
+            machine_profile: None,
+            cutting_order: None,
+        };
+        let conversion = Conversion::new(
+            &machine,
+            None,
+            PostArg::Intermac,
+            &RulesArgs { rules: None },
+        )
+        .unwrap();
+        let watcher = Watcher::new(&dir.join("in"), &dir.join("out"), conversion).unwrap();
+        (dir, watcher)
+    }
+
+    #[test]
+    fn test_watch_converts_settled_files() {
+        let (dir, mut watcher) = watcher("settled");
+        let input = dir.join("in");
+        std::fs::copy(FIXTURE, input.join("layout.otd")).unwrap();
+        std::fs::write(input.join("notes.txt"), "not a layout").unwrap();
+
+        // First seen: wait for the next poll
+        assert!(watcher.poll().is_empty());
+        let results = watcher.poll();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].status, Status::Converted);
+        assert!(dir.join("out/layout.cni").is_file());
+        assert!(input.join("done/layout.otd").is_file());
+        assert!(!input.join("layout.otd").exists());
+        assert!(input.join("notes.txt").exists());
+        assert!(watcher.poll().is_empty());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_watch_waits_for_writes_and_reports_failures() {
+        let (dir, mut watcher) = watcher("failed");
+        let input = dir.join("in");
+        let path = input.join("broken.otd");
+        std::fs::write(&path, "[Header]\n").unwrap();
+        assert!(watcher.poll().is_empty());
+
+        // Still being written
+        std::fs::write(&path, "[Header]\nVersion=2\n").unwrap();
+        assert!(watcher.poll().is_empty());
+
+        let results = watcher.poll();
+        assert_eq!(results[0].status, Status::Failed);
+        assert!(input.join("failed/broken.otd").is_file());
+        let report = std::fs::read_to_string(input.join("failed/broken.otd.error.txt")).unwrap();
+        assert!(report.contains("broken.otd"));
+        assert!(report.contains("[Pattern]"));
+
+        // Fixed and dropped again: converted, stale failure removed
+        std::fs::copy(FIXTURE, &path).unwrap();
+        watcher.poll();
+        assert_eq!(watcher.poll()[0].status, Status::Converted);
+        assert!(!input.join("failed/broken.otd").exists());
+        assert!(!input.join("failed/broken.otd.error.txt").exists());
+        assert!(dir.join("out/broken.cni").is_file());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
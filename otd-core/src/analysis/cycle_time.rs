@@ -0,0 +1,449 @@
+//! Cycle time estimation from the simulated toolpath.
+//!
+//! Each pattern's [`Motion`] log is replayed on the kinematic model of the
+//! table ([`MachineKinematics`]). A move accelerates to its feed rate and
+//! decelerates to a stop (trapezoidal profile, triangular when the move is too
+//! short to reach the feed rate). Scored segments joined without a change of
+//! direction form a single move, so a smooth contour only pays for
+//! acceleration once; a corner stops the wheel and rotates the C axis.
+
+use super::toolpath::{simulate, Motion, Segment, Toolpath};
+use crate::config::{MachineConfig, MachineKinematics, Unit};
+use crate::error::Result;
+use crate::generator::build_contour_program;
+use crate::model::Schema;
+use serde::{Deserialize, Serialize};
+
+/// Heading change, in degrees, below which consecutive segments are tangent.
+const TANGENT_TOLERANCE: f64 = 1.0;
+
+/// Estimated cycle time of a program.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CycleTime {
+    /// One entry per pattern, in program order.
+    pub patterns: Vec<PatternCycleTime>,
+}
+
+/// Estimated cycle time of one pattern, in seconds.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PatternCycleTime {
+    /// 1-based pattern number.
+    pub number: usize,
+    /// Moves at feed rate.
+    pub cutting: f64,
+    /// Rapid traverses.
+    pub rapid: f64,
+    /// Wheel down/up dwells and tool changes.
+    pub tool: f64,
+    /// C axis rotations.
+    pub rotation: f64,
+    /// Scored length (mm).
+    pub cut_length: f64,
+    /// Number of sheets cut with this pattern.
+    pub sheets: u32,
+}
+
+impl PatternCycleTime {
+    /// Time to cut one sheet.
+    pub fn per_sheet(&self) -> f64 {
+        self.cutting + self.rapid + self.tool + self.rotation
+    }
+
+    /// Time to cut every sheet of the pattern.
+    pub fn total(&self) -> f64 {
+        self.per_sheet() * f64::from(self.sheets)
+    }
+
+    /// One-line summary: `02:35 per sheet x 3 sheets = 07:45`.
+    pub fn summary(&self) -> String {
+        if self.sheets == 1 {
+            format_duration(self.per_sheet())
+        } else {
+            format!(
+                "{} per sheet x {} sheets = {}",
+                format_duration(self.per_sheet()),
+                self.sheets,
+                format_duration(self.total())
+            )
+        }
+    }
+}
+
+impl CycleTime {
+    /// Time to cut every sheet of every pattern.
+    pub fn total(&self) -> f64 {
+        self.patterns.iter().map(PatternCycleTime::total).sum()
+    }
+
+    /// Cycle time of a pattern by number.
+    pub fn pattern(&self, number: usize) -> Option<&PatternCycleTime> {
+        self.patterns.iter().find(|p| p.number == number)
+    }
+}
+
+/// Format seconds as `mm:ss`, or `h:mm:ss` from one hour.
+pub fn format_duration(seconds: f64) -> String {
+    let seconds = seconds.max(0.0).round() as u64;
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, seconds)
+    } else {
+        format!("{:02}:{:02}", minutes, seconds)
+    }
+}
+
+/// Estimate the cycle time of the patterns of a simulated program.
+///
+/// Toolpath coordinates are in `unit`; every pattern counts one sheet.
+pub fn estimate_cycle_time(
+    toolpath: &Toolpath,
+    unit: Unit,
+    kinematics: &MachineKinematics,
+) -> CycleTime {
+    let patterns = toolpath
+        .patterns
+        .iter()
+        .map(|pattern| {
+            let mut estimator = Estimator::new(kinematics, unit.to_mm_factor());
+            for motion in &pattern.motions {
+                estimator.apply(motion);
+            }
+            estimator.flush();
+            PatternCycleTime {
+                number: pattern.number,
+                sheets: 1,
+                ..estimator.time
+            }
+        })
+        .collect();
+
+    CycleTime { patterns }
+}
+
+/// Estimate the cycle time of the program generated for a set of schemas.
+///
+/// Pattern `n` counts the sheet quantity of `schemas[n - 1]`.
+pub fn estimate_schemas(schemas: &[Schema], config: &MachineConfig) -> Result<CycleTime> {
+    let program = build_contour_program(schemas, config);
+    let toolpath = simulate(&program)?;
+    Ok(estimate_for_schemas(schemas, &toolpath, config))
+}
+
+/// Estimate the cycle time of an already simulated program of `schemas`.
+pub(crate) fn estimate_for_schemas(
+    schemas: &[Schema],
+    toolpath: &Toolpath,
+    config: &MachineConfig,
+) -> CycleTime {
+    let unit = schemas.first().map(|s| s.unit).unwrap_or_default();
+    let mut cycle_time = estimate_cycle_time(toolpath, unit, &config.kinematics);
+    for pattern in &mut cycle_time.patterns {
+        if let Some(schema) = schemas.get(pattern.number - 1) {
+            pattern.sheets = schema.quantity.max(1);
+        }
+    }
+    cycle_time
+}
+
+/// Time of a move of `distance` mm starting and ending at rest.
+fn move_time(distance: f64, feed: f64, acceleration: f64) -> f64 {
+    if distance <= 0.0 || feed <= 0.0 {
+        return 0.0;
+    }
+    let speed = feed / 60.0;
+    if acceleration <= 0.0 {
+        return distance / speed;
+    }
+    if distance >= speed * speed / acceleration {
+        distance / speed + speed / acceleration
+    } else {
+        2.0 * (distance / acceleration).sqrt()
+    }
+}
+
+/// Smallest rotation between two headings, in degrees.
+fn rotation_between(from: f64, to: f64) -> f64 {
+    let delta = (to - from).rem_euclid(360.0);
+    delta.min(360.0 - delta)
+}
+
+/// Feed moves being joined into one continuous move.
+#[derive(Default)]
+struct Stroke {
+    /// Length (mm).
+    length: f64,
+    /// Time at cruise speed of each segment.
+    cruise: f64,
+    /// Highest speed reached (mm/s).
+    top_speed: f64,
+}
+
+/// Accumulates the time of one pattern.
+struct Estimator<'a> {
+    kinematics: &'a MachineKinematics,
+    mm_per_unit: f64,
+    /// C axis heading in degrees.
+    heading: f64,
+    stroke: Option<Stroke>,
+    time: PatternCycleTime,
+}
+
+impl<'a> Estimator<'a> {
+    fn new(kinematics: &'a MachineKinematics, mm_per_unit: f64) -> Self {
+        Self {
+            kinematics,
+            mm_per_unit,
+            heading: 0.0,
+            stroke: None,
+            time: PatternCycleTime::default(),
+        }
+    }
+
+    fn apply(&mut self, motion: &Motion) {
+        let k = self.kinematics;
+        match motion {
+            Motion::Rapid { from, to } => {
+                self.flush();
+                let distance = (to.0 - from.0).hypot(to.1 - from.1) * self.mm_per_unit;
+                self.time.rapid += move_time(distance, k.rapid_feed, k.acceleration);
+            }
+            Motion::Feed {
+                segment,
+                scored,
+                shaped,
+            } => {
+                let feed = if *shaped { k.shape_feed } else { k.linear_feed };
+                if *scored {
+                    self.score(segment, feed, *shaped);
+                } else {
+                    self.flush();
+                    let distance = segment.length() * self.mm_per_unit;
+                    self.time.cutting += move_time(distance, feed, k.acceleration);
+                }
+            }
+            Motion::ToolDown => {
+                self.flush();
+                self.time.tool += k.tool_down_dwell;
+            }
+            Motion::ToolUp => {
+                self.flush();
+                self.time.tool += k.tool_up_dwell;
+            }
+            Motion::Rotate { angle } => {
+                self.flush();
+                self.rotate(*angle);
+            }
+            Motion::ToolChange { .. } => {
+                self.flush();
+                self.time.tool += k.tool_change_time;
+            }
+        }
+    }
+
+    /// Add a scored segment to the current stroke.
+    ///
+    /// The wheel of a shape contour follows the path (tangent mode), so a
+    /// corner ends the move and turns the C axis. Linear cuts keep the
+    /// orientation set by `PROT_B`.
+    fn score(&mut self, segment: &Segment, feed: f64, shaped: bool) {
+        if shaped && rotation_between(self.heading, segment.start_heading()) > TANGENT_TOLERANCE {
+            self.flush();
+            self.rotate(segment.start_heading());
+        }
+
+        let length = segment.length() * self.mm_per_unit;
+        let mut speed = feed / 60.0;
+        let radius = segment.radius() * self.mm_per_unit;
+        if radius > 0.0 && self.kinematics.acceleration > 0.0 {
+            // Centripetal acceleration limits the speed on tight arcs
+            speed = speed.min((self.kinematics.acceleration * radius).sqrt());
+        }
+
+        let stroke = self.stroke.get_or_insert_with(Stroke::default);
+        stroke.length += length;
+        if speed > 0.0 {
+            stroke.cruise += length / speed;
+        }
+        stroke.top_speed = stroke.top_speed.max(speed);
+        self.time.cut_length += length;
+
+        if shaped {
+            self.heading = segment.end_heading();
+        }
+    }
+
+    /// Close the current stroke, adding its time.
+    fn flush(&mut self) {
+        let Some(stroke) = self.stroke.take() else {
+            return;
+        };
+        let acceleration = self.kinematics.acceleration;
+        let speed = stroke.top_speed;
+        self.time.cutting += if acceleration <= 0.0 || speed <= 0.0 {
+            stroke.cruise
+        } else if stroke.length >= speed * speed / acceleration {
+            stroke.cruise + speed / acceleration
+        } else {
+            2.0 * (stroke.length / acceleration).sqrt()
+        };
+    }
+
+    fn rotate(&mut self, angle: f64) {
+        if self.kinematics.rotation_speed > 0.0 {
+            self.time.rotation +=
+                rotation_between(self.heading, angle) / self.kinematics.rotation_speed;
+        }
+        self.heading = angle;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::PatternToolpath;
+    use crate::config::TOOL_TYPE_SHAPED;
+    use crate::model::{Cut, Piece, Shape};
+
+    fn kinematics() -> MachineKinematics {
+        MachineKinematics {
+            linear_feed: 6000.0,
+            shape_feed: 3000.0,
+            rapid_feed: 12000.0,
+            acceleration: 1000.0,
+            tool_down_dwell: 0.5,
+            tool_up_dwell: 0.25,
+            rotation_speed: 90.0,
+            tool_change_time: 2.0,
+        }
+    }
+
+    fn toolpath(motions: Vec<Motion>) -> Toolpath {
+        Toolpath {
+            patterns: vec![PatternToolpath {
+                label: "0001".to_string(),
+                number: 1,
+                cuts: Vec::new(),
+                motions,
+            }],
+        }
+    }
+
+    fn line(start: (f64, f64), end: (f64, f64), shaped: bool) -> Motion {
+        Motion::Feed {
+            segment: Segment::Line { start, end },
+            scored: true,
+            shaped,
+        }
+    }
+
+    #[test]
+    fn test_move_time_profiles() {
+        // 100 mm/s with 1000 mm/s²: 10 mm to reach full speed and stop
+        assert!((move_time(1000.0, 6000.0, 1000.0) - 10.1).abs() < 1e-9);
+        // Too short to reach full speed: triangular profile
+        assert!((move_time(2.5, 6000.0, 1000.0) - 0.1).abs() < 1e-9);
+        assert_eq!(move_time(0.0, 6000.0, 1000.0), 0.0);
+    }
+
+    #[test]
+    fn test_linear_cut_time() {
+        let k = kinematics();
+        let path = toolpath(vec![
+            Motion::ToolChange { tool: 3 },
+            Motion::Rotate { angle: 90.0 },
+            Motion::Rapid {
+                from: (0.0, 0.0),
+                to: (0.0, 0.0),
+            },
+            Motion::ToolDown,
+            line((0.0, 0.0), (0.0, 1000.0), false),
+            Motion::ToolUp,
+        ]);
+
+        let time = &estimate_cycle_time(&path, Unit::Millimeters, &k).patterns[0];
+        assert!((time.cutting - 10.1).abs() < 1e-9);
+        assert!((time.tool - 2.75).abs() < 1e-9);
+        assert!((time.rotation - 1.0).abs() < 1e-9);
+        assert_eq!(time.rapid, 0.0);
+        assert!((time.cut_length - 1000.0).abs() < 1e-9);
+        assert!((time.per_sheet() - 13.85).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_contour_corners_rotate_and_stop() {
+        let k = kinematics();
+        let square = |shaped| {
+            toolpath(vec![
+                Motion::ToolDown,
+                line((0.0, 0.0), (100.0, 0.0), shaped),
+                line((100.0, 0.0), (100.0, 100.0), shaped),
+                Motion::ToolUp,
+            ])
+        };
+
+        // Two moves of 100 mm at 50 mm/s and one 90 degree corner
+        let shape = &estimate_cycle_time(&square(true), Unit::Millimeters, &k).patterns[0];
+        assert!((shape.cutting - 2.0 * 2.05).abs() < 1e-9);
+        assert!((shape.rotation - 1.0).abs() < 1e-9);
+
+        // Tangent segments are one move
+        let smooth = toolpath(vec![
+            Motion::ToolDown,
+            line((0.0, 0.0), (100.0, 0.0), true),
+            line((100.0, 0.0), (200.0, 0.0), true),
+            Motion::ToolUp,
+        ]);
+        let smooth = &estimate_cycle_time(&smooth, Unit::Millimeters, &k).patterns[0];
+        assert!((smooth.cutting - 4.05).abs() < 1e-9);
+        assert_eq!(smooth.rotation, 0.0);
+    }
+
+    #[test]
+    fn test_inch_layout_is_converted() {
+        let k = kinematics();
+        let path = toolpath(vec![Motion::Rapid {
+            from: (0.0, 0.0),
+            to: (100.0, 0.0),
+        }]);
+        let mm = estimate_cycle_time(&path, Unit::Millimeters, &k).patterns[0].rapid;
+        let inch = estimate_cycle_time(&path, Unit::Inches, &k).patterns[0].rapid;
+        assert!(inch > mm * 5.0);
+    }
+
+    #[test]
+    fn test_estimate_schemas_counts_sheets() {
+        let mut schema = Schema::new();
+        schema.width = 1000.0;
+        schema.height = 500.0;
+        schema.quantity = 3;
+        schema.add_linear_cut(Cut::new_line(300.0, 0.0, 300.0, 500.0));
+
+        let mut shape = Shape::new(1);
+        let mut edge = Cut::new_line(0.0, 0.0, 100.0, 0.0);
+        edge.tool_code = TOOL_TYPE_SHAPED as i32;
+        shape.add_cut(edge);
+        shape.detect_tool_types();
+        schema.add_shape(shape);
+        schema.add_piece(Piece {
+            x_origin: 400.0,
+            y_origin: 100.0,
+            shape_index: Some(0),
+            ..Default::default()
+        });
+
+        let config = MachineConfig::new(130);
+        let cycle_time = estimate_schemas(&[schema], &config).unwrap();
+        let pattern = cycle_time.pattern(1).unwrap();
+        assert_eq!(pattern.sheets, 3);
+        assert!((pattern.cut_length - 600.0).abs() < 1e-9);
+        assert!(pattern.cutting > 0.0 && pattern.rapid > 0.0);
+        assert!((cycle_time.total() - 3.0 * pattern.per_sheet()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(0.4), "00:00");
+        assert_eq!(format_duration(155.0), "02:35");
+        assert_eq!(format_duration(3725.0), "1:02:05");
+    }
+}
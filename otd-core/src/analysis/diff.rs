@@ -0,0 +1,489 @@
+//! Semantic comparison of two CNI programs.
+//!
+//! Both programs are run through the toolpath [`simulate`] and compared by
+//! what the machine would score: cut geometry within a tolerance, tool and
+//! wheel-down macro. Line numbers, label numbering, cut order and number
+//! formatting are ignored.
+
+use super::toolpath::{simulate, ScoredCut, Segment, Toolpath};
+use crate::config::EPS_COARSE;
+use crate::error::Result;
+use crate::parser::cni::CniDistribution;
+use crate::parser::{parse_cni_file, CniFile};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+use std::path::Path;
+
+/// Options for [`diff_cni`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DiffOptions {
+    /// Maximum coordinate difference for two points to match, in file units.
+    pub tolerance: f64,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        Self {
+            tolerance: EPS_COARSE,
+        }
+    }
+}
+
+/// Kind of difference for a cut.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "kind")]
+pub enum ChangeKind {
+    /// Cut only in the left program.
+    Missing,
+    /// Cut only in the right program.
+    Extra,
+    /// Same cut, translated by `(dx, dy)` in the right program.
+    Moved { dx: f64, dy: f64 },
+    /// Same cut, scored in the opposite direction.
+    Reversed,
+    /// Same geometry, different tool or wheel-down macro in the right program.
+    ToolChanged { to_tool: u16, to_scoring: String },
+}
+
+/// A cut that differs between the two programs.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CutChange {
+    /// What changed.
+    #[serde(flatten)]
+    pub kind: ChangeKind,
+    /// Tool of the cut (left program, right program for extra cuts).
+    pub tool: u16,
+    /// Wheel-down macro of the cut.
+    pub scoring: String,
+    /// Shape macro label, `None` for linear cuts.
+    pub shape_macro: Option<String>,
+    /// Start point.
+    pub start: (f64, f64),
+    /// End point.
+    pub end: (f64, f64),
+    /// Number of scored segments.
+    pub segments: usize,
+}
+
+/// Differences within one pattern.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PatternDiff {
+    /// 1-based pattern number.
+    pub number: usize,
+    /// Changed cuts, linear cuts first, then by shape macro.
+    pub changes: Vec<CutChange>,
+}
+
+/// Difference in the program header or piece distribution.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HeaderChange {
+    /// Field name.
+    pub field: String,
+    /// Value in the left program.
+    pub left: String,
+    /// Value in the right program.
+    pub right: String,
+}
+
+/// Result of comparing two CNI programs.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CniDiff {
+    /// Header and distribution differences.
+    pub header: Vec<HeaderChange>,
+    /// Patterns with cut differences.
+    pub patterns: Vec<PatternDiff>,
+}
+
+impl CniDiff {
+    /// Whether the programs behave the same on the machine.
+    pub fn is_identical(&self) -> bool {
+        self.header.is_empty() && self.patterns.is_empty()
+    }
+
+    /// Total number of differences.
+    pub fn change_count(&self) -> usize {
+        self.header.len() + self.patterns.iter().map(|p| p.changes.len()).sum::<usize>()
+    }
+
+    /// Serialize the diff as pretty-printed JSON.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_default()
+    }
+
+    /// Human-readable report grouped by pattern and shape macro.
+    pub fn report(&self) -> String {
+        let mut output = String::new();
+
+        if !self.header.is_empty() {
+            writeln!(output, "Header:").unwrap();
+            for change in &self.header {
+                writeln!(
+                    output,
+                    "  {}: {} -> {}",
+                    change.field, change.left, change.right
+                )
+                .unwrap();
+            }
+        }
+
+        for pattern in &self.patterns {
+            writeln!(output, "Pattern {}:", pattern.number).unwrap();
+            let mut group: Option<Option<&str>> = None;
+            for change in &pattern.changes {
+                let shape = change.shape_macro.as_deref();
+                if group != Some(shape) {
+                    match shape {
+                        Some(label) => writeln!(output, "  shape macro {}:", label).unwrap(),
+                        None => writeln!(output, "  linear:").unwrap(),
+                    }
+                    group = Some(shape);
+                }
+                writeln!(output, "    {}", describe(change)).unwrap();
+            }
+        }
+
+        if self.is_identical() {
+            writeln!(output, "No differences").unwrap();
+        } else {
+            writeln!(output, "{} difference(s)", self.change_count()).unwrap();
+        }
+        output
+    }
+}
+
+/// One-line description of a changed cut.
+fn describe(change: &CutChange) -> String {
+    let tool = format!("tool {:04} {}", change.tool, change.scoring);
+    let what = match &change.kind {
+        ChangeKind::Missing => format!("missing  {}", tool),
+        ChangeKind::Extra => format!("extra    {}", tool),
+        ChangeKind::Moved { dx, dy } => format!("moved    {} by ({:.3}, {:.3})", tool, dx, dy),
+        ChangeKind::Reversed => format!("reversed {}", tool),
+        ChangeKind::ToolChanged {
+            to_tool,
+            to_scoring,
+        } => format!("changed  {} -> {:04} {}", tool, to_tool, to_scoring),
+    };
+    format!(
+        "{} ({:.3}, {:.3}) -> ({:.3}, {:.3}), {} segment(s)",
+        what, change.start.0, change.start.1, change.end.0, change.end.1, change.segments
+    )
+}
+
+/// Compare two CNI files on disk.
+pub fn diff_cni_files(left: &Path, right: &Path, options: &DiffOptions) -> Result<CniDiff> {
+    diff_cni(&parse_cni_file(left)?, &parse_cni_file(right)?, options)
+}
+
+/// Compare two parsed CNI files.
+pub fn diff_cni(left: &CniFile, right: &CniFile, options: &DiffOptions) -> Result<CniDiff> {
+    let left_path = simulate(&left.program)?;
+    let right_path = simulate(&right.program)?;
+
+    let mut numbers: Vec<usize> = left_path
+        .patterns
+        .iter()
+        .chain(&right_path.patterns)
+        .map(|p| p.number)
+        .collect();
+    numbers.sort_unstable();
+    numbers.dedup();
+
+    let patterns = numbers
+        .into_iter()
+        .filter_map(|number| {
+            let cuts = |path: &Toolpath| {
+                path.pattern(number)
+                    .map_or_else(Vec::new, |p| p.cuts.clone())
+            };
+            let changes = diff_cuts(&cuts(&left_path), &cuts(&right_path), options.tolerance);
+            (!changes.is_empty()).then_some(PatternDiff { number, changes })
+        })
+        .collect();
+
+    Ok(CniDiff {
+        header: diff_header(left, right, options.tolerance),
+        patterns,
+    })
+}
+
+/// Compare the header, tools and piece distribution.
+fn diff_header(left: &CniFile, right: &CniFile, tolerance: f64) -> Vec<HeaderChange> {
+    let mut changes = Vec::new();
+    let mut text = |field: &str, a: String, b: String| {
+        if a != b {
+            changes.push(HeaderChange {
+                field: field.to_string(),
+                left: a,
+                right: b,
+            });
+        }
+    };
+    let number = |value: f64| format!("{}", value);
+    let same = |a: f64, b: f64| (a - b).abs() <= tolerance;
+
+    match (&left.parameters, &right.parameters) {
+        (Some(a), Some(b)) => {
+            text("unit", format!("{:?}", a.unit), format!("{:?}", b.unit));
+            for (field, x, y) in [
+                ("LX", a.width, b.width),
+                ("LY", a.height, b.height),
+                ("LZ", a.thickness, b.thickness),
+            ] {
+                if !same(x, y) {
+                    text(field, number(x), number(y));
+                }
+            }
+            text(
+                "P103",
+                a.machine_number.to_string(),
+                b.machine_number.to_string(),
+            );
+        }
+        (a, b) => text(
+            "parameters",
+            a.is_some().to_string(),
+            b.is_some().to_string(),
+        ),
+    }
+
+    let tools = |file: &CniFile| {
+        let mut tools = file.tools.clone();
+        tools.sort_unstable();
+        format!("{:?}", tools)
+    };
+    text("tools", tools(left), tools(right));
+    text(
+        "patterns",
+        left.schema_count().to_string(),
+        right.schema_count().to_string(),
+    );
+
+    for (a, b) in left.distributions.iter().zip(&right.distributions) {
+        let prefix = format!("pattern {}", a.schema_num);
+        text(
+            &format!("{} sheets", prefix),
+            a.quantity.to_string(),
+            b.quantity.to_string(),
+        );
+        let pieces = |dist: &CniDistribution| {
+            let mut pieces: Vec<String> = dist
+                .pieces
+                .iter()
+                .map(|p| format!("{}x{}x{}", p.width, p.height, p.quantity))
+                .collect();
+            pieces.sort();
+            pieces.join(" ")
+        };
+        text(&format!("{} pieces", prefix), pieces(a), pieces(b));
+    }
+
+    changes
+}
+
+/// Match the cuts of one pattern and report the unmatched ones.
+fn diff_cuts(left: &[ScoredCut], right: &[ScoredCut], tolerance: f64) -> Vec<CutChange> {
+    let mut changes = Vec::new();
+    let mut right_used = vec![false; right.len()];
+    let mut left_open = Vec::new();
+
+    // Same geometry, in either direction
+    for cut in left {
+        let found = find_unused(right, &right_used, |other| same_path(cut, other, tolerance))
+            .map(|idx| (idx, false))
+            .or_else(|| {
+                let reversed = cut.reversed();
+                find_unused(right, &right_used, |other| {
+                    same_path(&reversed, other, tolerance)
+                })
+                .map(|idx| (idx, true))
+            });
+
+        let Some((idx, reversed)) = found else {
+            left_open.push(cut);
+            continue;
+        };
+        right_used[idx] = true;
+
+        let other = &right[idx];
+        if other.tool != cut.tool || other.scoring != cut.scoring {
+            changes.push(change(
+                cut,
+                ChangeKind::ToolChanged {
+                    to_tool: other.tool,
+                    to_scoring: other.scoring.clone(),
+                },
+            ));
+        } else if reversed {
+            changes.push(change(cut, ChangeKind::Reversed));
+        }
+    }
+
+    // Same shape and tool at another position, nearest first
+    for cut in left_open {
+        let nearest = right
+            .iter()
+            .enumerate()
+            .filter(|(idx, _)| !right_used[*idx])
+            .filter_map(|(idx, other)| {
+                let (dx, dy) = translation(cut, other, tolerance)?;
+                Some((idx, dx, dy))
+            })
+            .min_by(|a, b| (a.1.hypot(a.2)).total_cmp(&b.1.hypot(b.2)));
+
+        match nearest {
+            Some((idx, dx, dy)) => {
+                right_used[idx] = true;
+                changes.push(change(cut, ChangeKind::Moved { dx, dy }));
+            }
+            None => changes.push(change(cut, ChangeKind::Missing)),
+        }
+    }
+
+    for (cut, used) in right.iter().zip(&right_used) {
+        if !used {
+            changes.push(change(cut, ChangeKind::Extra));
+        }
+    }
+
+    changes.sort_by(|a, b| {
+        a.shape_macro
+            .cmp(&b.shape_macro)
+            .then(a.start.0.total_cmp(&b.start.0))
+            .then(a.start.1.total_cmp(&b.start.1))
+    });
+    changes
+}
+
+fn find_unused(
+    cuts: &[ScoredCut],
+    used: &[bool],
+    matches: impl Fn(&ScoredCut) -> bool,
+) -> Option<usize> {
+    cuts.iter()
+        .enumerate()
+        .find(|(idx, cut)| !used[*idx] && matches(cut))
+        .map(|(idx, _)| idx)
+}
+
+/// Whether two strokes score the same path (tool not compared).
+fn same_path(a: &ScoredCut, b: &ScoredCut, tolerance: f64) -> bool {
+    a.segments.len() == b.segments.len()
+        && a.segments
+            .iter()
+            .zip(&b.segments)
+            .all(|(x, y)| x.approx_eq(y, tolerance))
+}
+
+/// Translation taking `a` onto `b`, if they are the same stroke with the same tool.
+fn translation(a: &ScoredCut, b: &ScoredCut, tolerance: f64) -> Option<(f64, f64)> {
+    if a.tool != b.tool || a.scoring != b.scoring || a.segments.len() != b.segments.len() {
+        return None;
+    }
+    let dx = b.start().0 - a.start().0;
+    let dy = b.start().1 - a.start().1;
+    let shifted: Vec<Segment> = a.segments.iter().map(|s| s.translated(dx, dy)).collect();
+    shifted
+        .iter()
+        .zip(&b.segments)
+        .all(|(x, y)| x.approx_eq(y, tolerance))
+        .then_some((dx, dy))
+}
+
+fn change(cut: &ScoredCut, kind: ChangeKind) -> CutChange {
+    CutChange {
+        kind,
+        tool: cut.tool,
+        scoring: cut.scoring.clone(),
+        shape_macro: cut.shape_macro.clone(),
+        start: cut.start(),
+        end: cut.end(),
+        segments: cut.segments.len(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(x1: f64, y1: f64, x2: f64, y2: f64) -> ScoredCut {
+        ScoredCut {
+            tool: 3,
+            scoring: "PT_GIU".to_string(),
+            shape_macro: None,
+            segments: vec![Segment::Line {
+                start: (x1, y1),
+                end: (x2, y2),
+            }],
+        }
+    }
+
+    fn kinds(changes: &[CutChange]) -> Vec<&ChangeKind> {
+        changes.iter().map(|c| &c.kind).collect()
+    }
+
+    #[test]
+    fn test_diff_cuts_ignores_order_and_rounding() {
+        let left = vec![line(0.0, 0.0, 0.0, 10.0), line(5.0, 0.0, 5.0, 10.0)];
+        let right = vec![line(5.0004, 0.0, 5.0, 10.0), line(0.0, 0.0, 0.0, 10.0)];
+        assert!(diff_cuts(&left, &right, 0.001).is_empty());
+    }
+
+    #[test]
+    fn test_diff_cuts_reversed_and_tool() {
+        let left = vec![line(0.0, 0.0, 0.0, 10.0), line(5.0, 0.0, 5.0, 10.0)];
+        let mut other_tool = line(5.0, 0.0, 5.0, 10.0);
+        other_tool.tool = 4;
+        let right = vec![line(0.0, 10.0, 0.0, 0.0), other_tool];
+
+        let changes = diff_cuts(&left, &right, 0.001);
+        assert_eq!(
+            kinds(&changes),
+            vec![
+                &ChangeKind::Reversed,
+                &ChangeKind::ToolChanged {
+                    to_tool: 4,
+                    to_scoring: "PT_GIU".to_string()
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff_cuts_moved_missing_extra() {
+        let left = vec![line(0.0, 0.0, 0.0, 10.0), line(20.0, 0.0, 30.0, 0.0)];
+        let right = vec![line(2.0, 0.0, 2.0, 10.0), line(0.0, 50.0, 0.0, 60.0)];
+
+        let changes = diff_cuts(&left, &right, 0.001);
+        assert_eq!(
+            kinds(&changes),
+            vec![
+                &ChangeKind::Moved { dx: 2.0, dy: 0.0 },
+                &ChangeKind::Extra,
+                &ChangeKind::Missing,
+            ]
+        );
+        assert!(changes[2].start == (20.0, 0.0));
+    }
+
+    #[test]
+    fn test_report_groups_by_shape_macro() {
+        let mut shape = line(1.0, 1.0, 2.0, 2.0);
+        shape.shape_macro = Some("1310001001".to_string());
+        let diff = CniDiff {
+            header: vec![],
+            patterns: vec![PatternDiff {
+                number: 1,
+                changes: vec![
+                    change(&line(0.0, 0.0, 0.0, 1.0), ChangeKind::Missing),
+                    change(&shape, ChangeKind::Extra),
+                ],
+            }],
+        };
+
+        let report = diff.report();
+        assert!(report.contains("Pattern 1:\n  linear:\n    missing"));
+        assert!(report.contains("  shape macro 1310001001:\n    extra"));
+        assert!(report.ends_with("2 difference(s)\n"));
+    }
+}
@@ -0,0 +1,20 @@
+//! Analysis of generated CNC programs.
+
+mod cycle_time;
+mod diff;
+mod stats;
+mod toolpath;
+mod verify;
+
+pub(crate) use cycle_time::estimate_for_schemas;
+pub use cycle_time::{
+    estimate_cycle_time, estimate_schemas, format_duration, CycleTime, PatternCycleTime,
+};
+pub use diff::{
+    diff_cni, diff_cni_files, ChangeKind, CniDiff, CutChange, DiffOptions, HeaderChange,
+    PatternDiff,
+};
+pub use stats::{layout_stats, LayoutStats, PatternStats};
+pub use toolpath::{simulate, Motion, PatternToolpath, ScoredCut, Segment, Toolpath};
+pub(crate) use verify::verify_toolpath;
+pub use verify::{check_toolpath, verify_program, Divergence, DivergenceKind};
@@ -0,0 +1,190 @@
+//! Production figures of a layout: glass used, yield and cut lengths.
+//!
+//! Figures are per sheet in layout units (areas in square units) and are
+//! computed on the schemas the converter generates programs from, so cut
+//! counts and lengths match the program.
+
+use crate::config::Unit;
+use crate::model::Schema;
+use serde::{Deserialize, Serialize};
+
+/// Figures of every pattern of a file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LayoutStats {
+    /// Unit of lengths and areas.
+    pub unit: Unit,
+    /// One entry per pattern, in file order.
+    pub patterns: Vec<PatternStats>,
+}
+
+/// Figures of one pattern, for one sheet.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PatternStats {
+    /// 1-based pattern number.
+    pub number: usize,
+    /// Material (glass ID).
+    pub material: String,
+    /// Number of sheets cut with this pattern.
+    pub sheets: u32,
+    /// Sheet area.
+    pub sheet_area: f64,
+    /// Area of the pieces that are not waste.
+    pub piece_area: f64,
+    /// Pieces that are not waste.
+    pub pieces: usize,
+    /// Pieces with a shape contour.
+    pub shaped_pieces: usize,
+    /// Active linear cuts.
+    pub linear_cuts: usize,
+    /// Length of the active linear cuts.
+    pub linear_length: f64,
+    /// Length of the shape contours.
+    pub shape_length: f64,
+}
+
+impl PatternStats {
+    /// Share of the sheet covered by pieces, from 0 to 1.
+    pub fn yield_ratio(&self) -> f64 {
+        if self.sheet_area > 0.0 {
+            self.piece_area / self.sheet_area
+        } else {
+            0.0
+        }
+    }
+
+    /// Sheet area not covered by pieces.
+    pub fn waste_area(&self) -> f64 {
+        (self.sheet_area - self.piece_area).max(0.0)
+    }
+
+    /// Scored length of one sheet.
+    pub fn cut_length(&self) -> f64 {
+        self.linear_length + self.shape_length
+    }
+}
+
+impl LayoutStats {
+    /// Sheets of all patterns.
+    pub fn sheets(&self) -> u32 {
+        self.patterns.iter().map(|p| p.sheets).sum()
+    }
+
+    /// Pieces of all sheets.
+    pub fn pieces(&self) -> usize {
+        self.patterns
+            .iter()
+            .map(|p| p.pieces * p.sheets as usize)
+            .sum()
+    }
+
+    /// Glass area of all sheets.
+    pub fn sheet_area(&self) -> f64 {
+        self.total(|p| p.sheet_area)
+    }
+
+    /// Piece area of all sheets.
+    pub fn piece_area(&self) -> f64 {
+        self.total(|p| p.piece_area)
+    }
+
+    /// Yield over all sheets, from 0 to 1.
+    pub fn yield_ratio(&self) -> f64 {
+        let sheet_area = self.sheet_area();
+        if sheet_area > 0.0 {
+            self.piece_area() / sheet_area
+        } else {
+            0.0
+        }
+    }
+
+    /// Scored length of all sheets.
+    pub fn cut_length(&self) -> f64 {
+        self.total(PatternStats::cut_length)
+    }
+
+    fn total(&self, value: impl Fn(&PatternStats) -> f64) -> f64 {
+        self.patterns
+            .iter()
+            .map(|p| value(p) * f64::from(p.sheets))
+            .sum()
+    }
+}
+
+/// Compute the figures of every pattern.
+pub fn layout_stats(schemas: &[Schema]) -> LayoutStats {
+    LayoutStats {
+        unit: schemas.first().map(|s| s.unit).unwrap_or_default(),
+        patterns: schemas
+            .iter()
+            .enumerate()
+            .map(|(idx, schema)| pattern_stats(schema, idx + 1))
+            .collect(),
+    }
+}
+
+fn pattern_stats(schema: &Schema, number: usize) -> PatternStats {
+    let pieces: Vec<_> = schema
+        .pieces
+        .iter()
+        .filter(|piece| !schema.is_waste_piece(piece))
+        .collect();
+    let linear: Vec<_> = schema.linear_cuts.iter().filter(|c| c.active).collect();
+    let shape_length = schema
+        .pieces
+        .iter()
+        .filter_map(|piece| piece.shape_index.and_then(|idx| schema.shapes.get(idx)))
+        .flat_map(|shape| shape.cuts.iter().filter(|c| c.active))
+        .map(|cut| cut.calculate_length())
+        .sum();
+
+    PatternStats {
+        number,
+        material: schema.glass_id.clone(),
+        sheets: schema.quantity.max(1),
+        sheet_area: schema.width * schema.height,
+        piece_area: pieces.iter().map(|p| p.width * p.height).sum(),
+        pieces: pieces.len(),
+        shaped_pieces: pieces.iter().filter(|p| p.shape_index.is_some()).count(),
+        linear_cuts: linear.len(),
+        linear_length: linear.iter().map(|cut| cut.calculate_length()).sum(),
+        shape_length,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{Cut, Piece, Shape};
+
+    #[test]
+    fn test_layout_stats() {
+        let mut schema = Schema::new();
+        schema.width = 1000.0;
+        schema.height = 500.0;
+        schema.quantity = 2;
+        schema.add_linear_cut(Cut::new_line(400.0, 0.0, 400.0, 500.0));
+        let mut shape = Shape::new(1);
+        shape.add_cut(Cut::new_line(0.0, 0.0, 100.0, 0.0));
+        shape.add_cut(Cut::new_line(100.0, 0.0, 0.0, 100.0));
+        shape.add_cut(Cut::new_line(0.0, 100.0, 0.0, 0.0));
+        schema.add_shape(shape);
+        schema.add_piece(Piece::new(0.0, 0.0, 400.0, 500.0));
+        let mut shaped = Piece::new(400.0, 0.0, 100.0, 100.0);
+        shaped.shape_index = Some(0);
+        schema.add_piece(shaped);
+
+        let stats = layout_stats(&[schema]);
+        let pattern = &stats.patterns[0];
+        assert_eq!(pattern.pieces, 2);
+        assert_eq!(pattern.shaped_pieces, 1);
+        assert_eq!(pattern.linear_cuts, 1);
+        assert!((pattern.yield_ratio() - 0.42).abs() < 1e-9);
+        assert!((pattern.waste_area() - 290_000.0).abs() < 1e-6);
+        assert!((pattern.cut_length() - (700.0 + 100.0 * 2f64.sqrt())).abs() < 1e-9);
+
+        assert_eq!(stats.sheets(), 2);
+        assert_eq!(stats.pieces(), 4);
+        assert!((stats.sheet_area() - 1_000_000.0).abs() < 1e-6);
+        assert!((stats.yield_ratio() - 0.42).abs() < 1e-9);
+    }
+}
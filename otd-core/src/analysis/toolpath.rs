@@ -0,0 +1,720 @@
+//! Toolpath reconstruction from a CNI program.
+//!
+//! Runs a [`Program`] the way the machine does for each pattern: the pattern
+//! is selected through `P262`, labels and jumps are followed, subroutines are
+//! called with their `XO`/`YO` origin, and every move made with the wheel down
+//! is recorded as a scored segment in sheet coordinates. Every motion of the
+//! machine (rapids, feeds, wheel and tool changes, rotations) is logged too,
+//! for cycle time estimation.
+
+use crate::error::{ConvertError, Result};
+use crate::generator::{Instruction, OffsetAxis, Param, ParamValue, Program};
+use crate::model::{Cut, CutType};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Upper bound on executed instructions per pattern, guards against jump loops.
+const MAX_STEPS: usize = 1_000_000;
+
+/// Pattern selection parameter used by the indirect jump at program start.
+const PATTERN_PARAM: u16 = 262;
+
+/// Scored geometry of a whole program.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Toolpath {
+    /// One entry per pattern label, in program order.
+    pub patterns: Vec<PatternToolpath>,
+}
+
+/// Scored geometry of one pattern.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PatternToolpath {
+    /// Pattern label (`0001`).
+    pub label: String,
+    /// 1-based pattern number.
+    pub number: usize,
+    /// Cuts in execution order.
+    pub cuts: Vec<ScoredCut>,
+    /// Every machine motion in execution order.
+    #[serde(default)]
+    pub motions: Vec<Motion>,
+}
+
+/// A continuous stroke between lowering and lifting the wheel.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScoredCut {
+    /// Active tool (P007).
+    pub tool: u16,
+    /// Macro that lowered the wheel (`PT_GIU`).
+    pub scoring: String,
+    /// Shape macro label when the cut is made inside a shape subroutine.
+    pub shape_macro: Option<String>,
+    /// Scored segments.
+    pub segments: Vec<Segment>,
+}
+
+/// Scored segment in sheet coordinates.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Segment {
+    /// Straight scored line.
+    Line { start: (f64, f64), end: (f64, f64) },
+    /// Scored arc with absolute centre.
+    Arc {
+        start: (f64, f64),
+        end: (f64, f64),
+        center: (f64, f64),
+        clockwise: bool,
+    },
+}
+
+/// A machine motion, in sheet coordinates.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Motion {
+    /// Rapid traverse with the wheel up.
+    Rapid { from: (f64, f64), to: (f64, f64) },
+    /// Move at feed rate, scoring when the wheel is down.
+    Feed {
+        segment: Segment,
+        scored: bool,
+        /// Made inside a shape subroutine.
+        shaped: bool,
+    },
+    /// Wheel lowered by a scoring macro.
+    ToolDown,
+    /// Wheel lifted.
+    ToolUp,
+    /// C axis rotated to `angle` degrees (`PROT_B`).
+    Rotate { angle: f64 },
+    /// Tool loaded (`PTOOL`).
+    ToolChange { tool: u16 },
+}
+
+impl Segment {
+    /// Length of the segment.
+    pub fn length(&self) -> f64 {
+        match self {
+            Segment::Line { start, end } => (end.0 - start.0).hypot(end.1 - start.1),
+            Segment::Arc { .. } => self.radius() * self.sweep(),
+        }
+    }
+
+    /// Distance from the centre, zero for lines.
+    pub fn radius(&self) -> f64 {
+        match self {
+            Segment::Line { .. } => 0.0,
+            Segment::Arc { start, center, .. } => (start.0 - center.0).hypot(start.1 - center.1),
+        }
+    }
+
+    /// Swept angle of an arc in radians, a full turn when start and end
+    /// coincide; zero for lines.
+    pub(crate) fn sweep(&self) -> f64 {
+        let Segment::Arc {
+            start,
+            end,
+            center,
+            clockwise,
+        } = self
+        else {
+            return 0.0;
+        };
+        let a0 = (start.1 - center.1).atan2(start.0 - center.0);
+        let a1 = (end.1 - center.1).atan2(end.0 - center.0);
+        let sweep = if *clockwise { a0 - a1 } else { a1 - a0 };
+        let sweep = sweep.rem_euclid(std::f64::consts::TAU);
+        if sweep < 1e-9 {
+            std::f64::consts::TAU
+        } else {
+            sweep
+        }
+    }
+
+    /// Direction of travel at the start, in degrees.
+    pub fn start_heading(&self) -> f64 {
+        self.heading_at(self.start())
+    }
+
+    /// Direction of travel at the end, in degrees.
+    pub fn end_heading(&self) -> f64 {
+        self.heading_at(self.end())
+    }
+
+    fn heading_at(&self, point: (f64, f64)) -> f64 {
+        let (dx, dy) = match self {
+            Segment::Line { start, end } => (end.0 - start.0, end.1 - start.1),
+            Segment::Arc {
+                center, clockwise, ..
+            } => {
+                // Tangent is the radius turned a quarter in the direction of travel
+                let (rx, ry) = (point.0 - center.0, point.1 - center.1);
+                if *clockwise {
+                    (ry, -rx)
+                } else {
+                    (-ry, rx)
+                }
+            }
+        };
+        dy.atan2(dx).to_degrees()
+    }
+
+    /// Start point.
+    pub fn start(&self) -> (f64, f64) {
+        match self {
+            Segment::Line { start, .. } | Segment::Arc { start, .. } => *start,
+        }
+    }
+
+    /// End point.
+    pub fn end(&self) -> (f64, f64) {
+        match self {
+            Segment::Line { end, .. } | Segment::Arc { end, .. } => *end,
+        }
+    }
+
+    /// The same segment scored in the opposite direction.
+    pub fn reversed(&self) -> Segment {
+        match self {
+            Segment::Line { start, end } => Segment::Line {
+                start: *end,
+                end: *start,
+            },
+            Segment::Arc {
+                start,
+                end,
+                center,
+                clockwise,
+            } => Segment::Arc {
+                start: *end,
+                end: *start,
+                center: *center,
+                clockwise: !clockwise,
+            },
+        }
+    }
+
+    /// Translate by `(dx, dy)`.
+    pub fn translated(&self, dx: f64, dy: f64) -> Segment {
+        let shift = |(x, y): (f64, f64)| (x + dx, y + dy);
+        match self {
+            Segment::Line { start, end } => Segment::Line {
+                start: shift(*start),
+                end: shift(*end),
+            },
+            Segment::Arc {
+                start,
+                end,
+                center,
+                clockwise,
+            } => Segment::Arc {
+                start: shift(*start),
+                end: shift(*end),
+                center: shift(*center),
+                clockwise: *clockwise,
+            },
+        }
+    }
+
+    /// Whether two segments match within `tolerance`.
+    pub fn approx_eq(&self, other: &Segment, tolerance: f64) -> bool {
+        let near = |a: (f64, f64), b: (f64, f64)| {
+            (a.0 - b.0).abs() <= tolerance && (a.1 - b.1).abs() <= tolerance
+        };
+        match (self, other) {
+            (Segment::Line { start, end }, Segment::Line { start: s, end: e }) => {
+                near(*start, *s) && near(*end, *e)
+            }
+            (
+                Segment::Arc {
+                    start,
+                    end,
+                    center,
+                    clockwise,
+                },
+                Segment::Arc {
+                    start: s,
+                    end: e,
+                    center: c,
+                    clockwise: cw,
+                },
+            ) => clockwise == cw && near(*start, *s) && near(*end, *e) && near(*center, *c),
+            _ => false,
+        }
+    }
+}
+
+impl From<&Cut> for Segment {
+    /// Segment of a layout cut, in the cut's own coordinates.
+    fn from(cut: &Cut) -> Self {
+        let start = (cut.xi, cut.yi);
+        let end = (cut.xf, cut.yf);
+        match cut.cut_type {
+            CutType::Line => Segment::Line { start, end },
+            CutType::ArcCW | CutType::ArcCCW => Segment::Arc {
+                start,
+                end,
+                center: (cut.xc, cut.yc),
+                clockwise: cut.cut_type == CutType::ArcCW,
+            },
+        }
+    }
+}
+
+impl ScoredCut {
+    /// Start point of the stroke.
+    pub fn start(&self) -> (f64, f64) {
+        self.segments.first().map_or((0.0, 0.0), Segment::start)
+    }
+
+    /// End point of the stroke.
+    pub fn end(&self) -> (f64, f64) {
+        self.segments.last().map_or((0.0, 0.0), Segment::end)
+    }
+
+    /// The same stroke scored in the opposite direction.
+    pub fn reversed(&self) -> ScoredCut {
+        ScoredCut {
+            segments: self.segments.iter().rev().map(Segment::reversed).collect(),
+            ..self.clone()
+        }
+    }
+}
+
+impl Toolpath {
+    /// Toolpath of a pattern by 1-based number.
+    pub fn pattern(&self, number: usize) -> Option<&PatternToolpath> {
+        self.patterns.iter().find(|p| p.number == number)
+    }
+}
+
+/// Reconstruct the scored geometry of every pattern in the program.
+pub fn simulate(program: &Program) -> Result<Toolpath> {
+    let labels = label_index(program);
+
+    let mut pattern_labels: Vec<(&str, usize)> = labels
+        .keys()
+        .filter(|label| label.len() == 4 && label.bytes().all(|b| b.is_ascii_digit()))
+        .filter_map(|label| Some((*label, label.parse().ok()?)))
+        .collect();
+    pattern_labels.sort_by_key(|(_, number)| *number);
+
+    let patterns = pattern_labels
+        .into_iter()
+        .map(|(label, number)| {
+            let (cuts, motions) = Machine::new(program, &labels).run(number)?;
+            Ok(PatternToolpath {
+                label: label.to_string(),
+                number,
+                cuts,
+                motions,
+            })
+        })
+        .collect::<Result<_>>()?;
+
+    Ok(Toolpath { patterns })
+}
+
+/// Instruction index of every label.
+fn label_index(program: &Program) -> HashMap<&str, usize> {
+    program
+        .instructions
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, instruction)| match instruction {
+            Instruction::Label(label) => Some((label.as_str(), idx)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Subroutine call frame.
+struct Frame {
+    return_to: usize,
+    label: String,
+    origin: (f64, f64),
+}
+
+/// Machine state while running one pattern.
+struct Machine<'a> {
+    program: &'a Program,
+    labels: &'a HashMap<&'a str, usize>,
+    params: HashMap<String, f64>,
+    stack: Vec<Frame>,
+    /// Pending `XO`/`YO` for the next subroutine call.
+    pending_origin: (f64, f64),
+    position: (f64, f64),
+    tool: u16,
+    cut: Option<ScoredCut>,
+    cuts: Vec<ScoredCut>,
+    motions: Vec<Motion>,
+}
+
+impl<'a> Machine<'a> {
+    fn new(program: &'a Program, labels: &'a HashMap<&'a str, usize>) -> Self {
+        Self {
+            program,
+            labels,
+            params: HashMap::new(),
+            stack: Vec::new(),
+            pending_origin: (0.0, 0.0),
+            position: (0.0, 0.0),
+            tool: 0,
+            cut: None,
+            cuts: Vec::new(),
+            motions: Vec::new(),
+        }
+    }
+
+    /// Run the program for one pattern and return its cuts and motions.
+    fn run(mut self, pattern: usize) -> Result<(Vec<ScoredCut>, Vec<Motion>)> {
+        self.params
+            .insert(param_key(&Param::Numbered(PATTERN_PARAM)), pattern as f64);
+
+        let mut pc = 0;
+        for _ in 0..MAX_STEPS {
+            let Some(instruction) = self.program.instructions.get(pc) else {
+                break;
+            };
+            match self.step(instruction, pc)? {
+                Some(next) => pc = next,
+                None => {
+                    self.lift();
+                    return Ok((self.cuts, self.motions));
+                }
+            }
+        }
+
+        if pc < self.program.instructions.len() {
+            return Err(failure(format!(
+                "pattern {} did not terminate after {} steps",
+                pattern, MAX_STEPS
+            )));
+        }
+        self.lift();
+        Ok((self.cuts, self.motions))
+    }
+
+    /// Execute one instruction, returning the next instruction index or
+    /// `None` when the program ends.
+    fn step(&mut self, instruction: &Instruction, pc: usize) -> Result<Option<usize>> {
+        match instruction {
+            Instruction::End => match self.stack.pop() {
+                Some(frame) => return Ok(Some(frame.return_to)),
+                None => return Ok(None),
+            },
+            Instruction::SetParam { param, value } => {
+                let value = match value {
+                    ParamValue::Int(v) => *v as f64,
+                    ParamValue::Float(v) => *v,
+                };
+                self.params.insert(param_key(param), value);
+            }
+            Instruction::SetTool(tool) => {
+                self.tool = *tool;
+                self.params.insert("P007".to_string(), f64::from(*tool));
+            }
+            Instruction::SetRotation(angle) => {
+                self.params.insert("P539".to_string(), *angle);
+            }
+            Instruction::MacroCall(name) => self.call_macro(name),
+            Instruction::CallLabel(label) => {
+                let target = self.resolve(label)?;
+                let origin = self.pending_origin;
+                self.stack.push(Frame {
+                    return_to: pc + 1,
+                    label: label.clone(),
+                    origin,
+                });
+                return Ok(Some(target));
+            }
+            Instruction::Jump { condition, label } => {
+                let taken = match condition {
+                    Some(condition) => self.evaluate(condition)?,
+                    None => true,
+                };
+                if taken {
+                    return Ok(Some(self.resolve(label)?));
+                }
+            }
+            Instruction::WorkOffset => self.pending_origin = (0.0, 0.0),
+            Instruction::Offset { axis, value } => match axis {
+                OffsetAxis::X => self.pending_origin.0 = *value,
+                OffsetAxis::Y => self.pending_origin.1 = *value,
+            },
+            Instruction::Rapid(target) => {
+                if self.cut.is_some() {
+                    return Err(failure(format!(
+                        "rapid move to ({}, {}) with the wheel down",
+                        target.x, target.y
+                    )));
+                }
+                let to = self.to_sheet(target.x, target.y);
+                self.motions.push(Motion::Rapid {
+                    from: self.position,
+                    to,
+                });
+                self.position = to;
+            }
+            Instruction::Linear(target) => {
+                let end = self.to_sheet(target.x, target.y);
+                self.feed(Segment::Line {
+                    start: self.position,
+                    end,
+                });
+                self.position = end;
+            }
+            Instruction::Arc {
+                x,
+                y,
+                i,
+                j,
+                clockwise,
+            } => {
+                let end = self.to_sheet(*x, *y);
+                self.feed(Segment::Arc {
+                    start: self.position,
+                    end,
+                    center: self.to_sheet(*i, *j),
+                    clockwise: *clockwise,
+                });
+                self.position = end;
+            }
+            Instruction::Raw(_)
+            | Instruction::Comment(_)
+            | Instruction::Label(_)
+            | Instruction::Line(_)
+            | Instruction::Direction { .. }
+            | Instruction::TangentOn
+            | Instruction::TangentOff => {}
+        }
+
+        Ok(Some(pc + 1))
+    }
+
+    fn call_macro(&mut self, name: &str) {
+        match name {
+            "PT_GIU" => {
+                self.lift();
+                self.cut = Some(ScoredCut {
+                    tool: self.tool,
+                    scoring: name.to_string(),
+                    shape_macro: self.stack.last().map(|f| f.label.clone()),
+                    segments: Vec::new(),
+                });
+                self.motions.push(Motion::ToolDown);
+            }
+            "PT_SU" => self.lift(),
+            "PTOOL" => self.motions.push(Motion::ToolChange { tool: self.tool }),
+            "PROT_B" => self.motions.push(Motion::Rotate {
+                angle: self.param("P539").unwrap_or(0.0),
+            }),
+            _ => {}
+        }
+    }
+
+    /// Record a move at feed rate; only moves with the wheel down are scored.
+    fn feed(&mut self, segment: Segment) {
+        self.motions.push(Motion::Feed {
+            segment: segment.clone(),
+            scored: self.cut.is_some(),
+            shaped: !self.stack.is_empty(),
+        });
+        if let Some(cut) = &mut self.cut {
+            cut.segments.push(segment);
+        }
+    }
+
+    /// Lift the wheel, closing the current stroke.
+    fn lift(&mut self) {
+        if let Some(cut) = self.cut.take() {
+            self.motions.push(Motion::ToolUp);
+            if !cut.segments.is_empty() {
+                self.cuts.push(cut);
+            }
+        }
+    }
+
+    /// Program coordinates are relative to the origin of the current subroutine.
+    fn to_sheet(&self, x: f64, y: f64) -> (f64, f64) {
+        let origin = self.stack.last().map_or((0.0, 0.0), |f| f.origin);
+        (x + origin.0, y + origin.1)
+    }
+
+    /// Resolve a jump target, following indirect `(Pnnn)` targets.
+    fn resolve(&self, label: &str) -> Result<usize> {
+        if let Some(&idx) = self.labels.get(label) {
+            return Ok(idx + 1);
+        }
+
+        if let Some(param) = label.strip_prefix('(').and_then(|l| l.strip_suffix(')')) {
+            let value = self.param(param)?;
+            let found = self
+                .labels
+                .iter()
+                .filter(|(name, _)| name.parse::<f64>().ok() == Some(value))
+                .map(|(_, idx)| *idx)
+                .min();
+            if let Some(idx) = found {
+                return Ok(idx + 1);
+            }
+        }
+
+        Err(failure(format!("jump to unknown label '{}'", label)))
+    }
+
+    /// Evaluate a jump condition: `(Pnnn=value)` terms joined by `~` (and).
+    fn evaluate(&self, condition: &str) -> Result<bool> {
+        for term in condition.split('~') {
+            let term = term.trim().trim_start_matches('(').trim_end_matches(')');
+            let (name, value) = term
+                .split_once('=')
+                .ok_or_else(|| failure(format!("unsupported condition '{}'", condition)))?;
+            let expected: f64 = value
+                .parse()
+                .map_err(|_| failure(format!("unsupported condition '{}'", condition)))?;
+            if (self.param(name).unwrap_or(0.0) - expected).abs() > f64::EPSILON {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    fn param(&self, name: &str) -> Result<f64> {
+        self.params
+            .get(&normalize_param(name))
+            .copied()
+            .ok_or_else(|| failure(format!("parameter {} is not set", name)))
+    }
+}
+
+fn param_key(param: &Param) -> String {
+    match param {
+        Param::Numbered(number) => format!("P{:03}", number),
+        Param::Named(name) => name.clone(),
+    }
+}
+
+/// `P7` and `P007` refer to the same parameter.
+fn normalize_param(name: &str) -> String {
+    match name.strip_prefix('P').and_then(|n| n.parse::<u16>().ok()) {
+        Some(number) => format!("P{:03}", number),
+        None => name.to_string(),
+    }
+}
+
+fn failure(message: String) -> ConvertError {
+    ConvertError::SimulationFailed { message }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::GcodeWriter;
+
+    /// Two patterns: a linear pass on the first, a shape subroutine on the second.
+    fn create_program() -> Program {
+        let mut writer = GcodeWriter::with_start(20);
+        writer.call_macro("PRGINIT");
+        writer.jump("(P262)");
+
+        writer.write_label("0001");
+        writer.jump_if("(P260=2)~(P007=0003)", "010001");
+        writer.write_label("010001");
+        writer.set_tool(3);
+        writer.rapid_move_aligned(10.0, 0.0, "P540");
+        writer.tool_down();
+        writer.linear_move(10.0, 100.0, Some("P540"));
+        writer.tool_up();
+        writer.jump("999999999");
+
+        writer.write_label("0002");
+        writer.set_tool(31);
+        writer.set_work_offset();
+        writer.set_xo(50.0);
+        writer.set_yo(20.0);
+        writer.call_label("1310002001");
+        writer.jump("999999999");
+
+        writer.write_label("1310002001");
+        writer.rapid_move(0.0, 0.0, None);
+        writer.tool_down();
+        writer.linear_move(10.0, 0.0, None);
+        writer.arc_cw(0.0, 0.0, 5.0, 0.0);
+        writer.tool_up();
+        writer.write_terminator();
+
+        writer.write_label("999999999");
+        writer.call_macro("PFOXOUT");
+        writer.write_terminator();
+        writer.into_program()
+    }
+
+    #[test]
+    fn test_simulate_linear_pass() {
+        let toolpath = simulate(&create_program()).unwrap();
+        assert_eq!(toolpath.patterns.len(), 2);
+
+        let first = toolpath.pattern(1).unwrap();
+        assert_eq!(first.cuts.len(), 1);
+        assert_eq!(first.cuts[0].tool, 3);
+        assert_eq!(first.cuts[0].scoring, "PT_GIU");
+        assert_eq!(
+            first.cuts[0].segments,
+            vec![Segment::Line {
+                start: (10.0, 0.0),
+                end: (10.0, 100.0)
+            }]
+        );
+    }
+
+    #[test]
+    fn test_simulate_shape_subroutine_offset() {
+        let toolpath = simulate(&create_program()).unwrap();
+        let second = toolpath.pattern(2).unwrap();
+        assert_eq!(second.cuts.len(), 1);
+
+        let cut = &second.cuts[0];
+        assert_eq!(cut.shape_macro.as_deref(), Some("1310002001"));
+        assert_eq!(cut.start(), (50.0, 20.0));
+        assert_eq!(
+            cut.segments[1],
+            Segment::Arc {
+                start: (60.0, 20.0),
+                end: (50.0, 20.0),
+                center: (55.0, 20.0),
+                clockwise: true,
+            }
+        );
+    }
+
+    #[test]
+    fn test_segment_reversed_and_translated() {
+        let cut = ScoredCut {
+            tool: 3,
+            scoring: "PT_GIU".to_string(),
+            shape_macro: None,
+            segments: vec![Segment::Line {
+                start: (0.0, 0.0),
+                end: (1.0, 0.0),
+            }],
+        };
+        let reversed = cut.reversed();
+        assert_eq!(reversed.start(), (1.0, 0.0));
+        assert!(reversed.segments[0]
+            .translated(1.0, 1.0)
+            .approx_eq(&cut.segments[0].reversed().translated(1.0, 1.0), 1e-9));
+    }
+
+    #[test]
+    fn test_simulate_unknown_label() {
+        let mut writer = GcodeWriter::new();
+        writer.write_label("0001");
+        writer.jump("424242");
+        match simulate(&writer.into_program()) {
+            Err(ConvertError::SimulationFailed { message }) => {
+                assert!(message.contains("424242"))
+            }
+            other => panic!("Expected SimulationFailed, got {:?}", other),
+        }
+    }
+}
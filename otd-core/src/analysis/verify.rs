@@ -0,0 +1,301 @@
+//! Verification of a generated program against its source layout.
+//!
+//! The program is run through the toolpath [`simulate`] and every scored
+//! segment is matched against the active linear cuts and the shape contours
+//! placed on the pieces of the corresponding [`Schema`]. A segment that is
+//! scored but not in the layout, or in the layout but never scored, is a
+//! divergence.
+
+use super::toolpath::{simulate, ScoredCut, Segment, Toolpath};
+use crate::config::{EPS, TOOL_TYPE_SHAPED};
+use crate::error::{ConvertError, Result};
+use crate::generator::Program;
+use crate::model::{Cut, Schema};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// How a scored path diverges from the layout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DivergenceKind {
+    /// In the layout but never scored.
+    Missing,
+    /// Scored but not in the layout.
+    Unexpected,
+}
+
+/// A segment where the program and the layout disagree.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Divergence {
+    /// 1-based pattern number within the program.
+    pub pattern: usize,
+    /// Missing or unexpected.
+    pub kind: DivergenceKind,
+    /// Pass of the segment: wheel-down macro for linear cuts, `shape` for contours.
+    pub pass: String,
+    /// The segment, in sheet coordinates.
+    pub segment: Segment,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let what = match self.kind {
+            DivergenceKind::Missing => "not scored",
+            DivergenceKind::Unexpected => "scored but not in the layout",
+        };
+        let (start, end) = (self.segment.start(), self.segment.end());
+        let shape = match self.segment {
+            Segment::Line { .. } => "line",
+            Segment::Arc { .. } => "arc",
+        };
+        write!(
+            f,
+            "{} {} ({:.3}, {:.3}) -> ({:.3}, {:.3}) {}",
+            self.pass, shape, start.0, start.1, end.0, end.1, what
+        )
+    }
+}
+
+/// Check that a program scores exactly the cuts and shapes of its schemas.
+///
+/// Pattern `n` of the program is checked against `schemas[n - 1]`.
+pub fn verify_program(schemas: &[Schema], program: &Program) -> Result<()> {
+    verify_toolpath(schemas, &simulate(program)?)
+}
+
+/// Check an already simulated program against its schemas.
+pub(crate) fn verify_toolpath(schemas: &[Schema], toolpath: &Toolpath) -> Result<()> {
+    let divergences = check_toolpath(schemas, toolpath, EPS);
+
+    match divergences.first() {
+        None => Ok(()),
+        Some(first) => Err(ConvertError::ToolpathMismatch {
+            pattern: first.pattern,
+            message: format!(
+                "{} divergent segment(s), first: {}",
+                divergences
+                    .iter()
+                    .filter(|d| d.pattern == first.pattern)
+                    .count(),
+                first
+            ),
+        }),
+    }
+}
+
+/// List every divergence between a toolpath and the schemas it was built from.
+pub fn check_toolpath(schemas: &[Schema], toolpath: &Toolpath, tolerance: f64) -> Vec<Divergence> {
+    let linear_passes: &[&str] = &["PT_GIU"];
+
+    let mut divergences = Vec::new();
+    for (idx, schema) in schemas.iter().enumerate() {
+        let pattern = idx + 1;
+        let cuts = toolpath.pattern(pattern).map_or(&[][..], |p| &p.cuts[..]);
+        let (shape_cuts, linear_cuts): (Vec<&ScoredCut>, Vec<&ScoredCut>) =
+            cuts.iter().partition(|c| c.shape_macro.is_some());
+
+        let expected_linear: Vec<Segment> = schema
+            .linear_cuts
+            .iter()
+            .filter(|c| c.active)
+            .map(|c| segment(c, (0.0, 0.0)))
+            .collect();
+        for pass in linear_passes {
+            let scored = scored_segments(linear_cuts.iter().filter(|c| c.scoring == *pass));
+            compare(
+                pattern,
+                pass,
+                &expected_linear,
+                scored,
+                tolerance,
+                &mut divergences,
+            );
+        }
+
+        compare(
+            pattern,
+            "shape",
+            &shape_segments(schema),
+            scored_segments(shape_cuts.iter()),
+            tolerance,
+            &mut divergences,
+        );
+
+        // Linear strokes lowered by another macro never match the layout
+        let other_passes = linear_cuts
+            .iter()
+            .filter(|c| !linear_passes.contains(&c.scoring.as_str()));
+        for cut in other_passes {
+            for segment in &cut.segments {
+                divergences.push(Divergence {
+                    pattern,
+                    kind: DivergenceKind::Unexpected,
+                    pass: cut.scoring.clone(),
+                    segment: segment.clone(),
+                });
+            }
+        }
+    }
+
+    divergences
+}
+
+/// Contours of the shaped pieces, placed at the piece origin.
+fn shape_segments(schema: &Schema) -> Vec<Segment> {
+    schema
+        .pieces
+        .iter()
+        .filter_map(|piece| {
+            let shape = &schema.shapes[piece.shape_index?];
+            shape
+                .uses_tool(TOOL_TYPE_SHAPED as usize)
+                .then_some((shape, (piece.x_origin, piece.y_origin)))
+        })
+        .flat_map(|(shape, origin)| {
+            shape
+                .cuts
+                .iter()
+                .filter(|c| c.active)
+                .map(move |c| segment(c, origin))
+        })
+        .collect()
+}
+
+fn segment(cut: &Cut, origin: (f64, f64)) -> Segment {
+    Segment::from(cut).translated(origin.0, origin.1)
+}
+
+fn scored_segments<'a>(cuts: impl Iterator<Item = &'a &'a ScoredCut>) -> Vec<&'a Segment> {
+    cuts.flat_map(|c| c.segments.iter()).collect()
+}
+
+/// Match scored segments to expected ones, in either direction.
+fn compare(
+    pattern: usize,
+    pass: &str,
+    expected: &[Segment],
+    scored: Vec<&Segment>,
+    tolerance: f64,
+    divergences: &mut Vec<Divergence>,
+) {
+    let mut remaining = scored;
+
+    for segment in expected {
+        let reversed = segment.reversed();
+        let found = remaining
+            .iter()
+            .position(|s| s.approx_eq(segment, tolerance) || s.approx_eq(&reversed, tolerance));
+        match found {
+            Some(idx) => {
+                remaining.swap_remove(idx);
+            }
+            None => divergences.push(Divergence {
+                pattern,
+                kind: DivergenceKind::Missing,
+                pass: pass.to_string(),
+                segment: segment.clone(),
+            }),
+        }
+    }
+
+    for segment in remaining {
+        divergences.push(Divergence {
+            pattern,
+            kind: DivergenceKind::Unexpected,
+            pass: pass.to_string(),
+            segment: segment.clone(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::MachineConfig;
+    use crate::generator::{build_contour_program, Instruction};
+    use crate::model::{Piece, Shape};
+
+    fn create_schema() -> Schema {
+        let mut schema = Schema::new();
+        schema.width = 1000.0;
+        schema.height = 500.0;
+        schema.add_linear_cut(Cut::new_line(300.0, 0.0, 300.0, 500.0));
+        schema.add_linear_cut(Cut::new_line(0.0, 250.0, 300.0, 250.0));
+
+        let mut shape = Shape::new(1);
+        let mut edge = Cut::new_line(0.0, 0.0, 100.0, 0.0);
+        edge.tool_code = TOOL_TYPE_SHAPED as i32;
+        shape.add_cut(edge);
+        let mut arc = Cut::new_arc_ccw(100.0, 0.0, 0.0, 0.0, 50.0);
+        arc.tool_code = TOOL_TYPE_SHAPED as i32;
+        shape.add_cut(arc);
+        shape.detect_tool_types();
+        schema.add_shape(shape);
+
+        schema.add_piece(Piece {
+            x_origin: 400.0,
+            y_origin: 100.0,
+            shape_index: Some(0),
+            ..Default::default()
+        });
+        schema
+    }
+
+    #[test]
+    fn test_verify_generated_program() {
+        let schemas = vec![create_schema()];
+        let program = build_contour_program(&schemas, &MachineConfig::new(130));
+        verify_program(&schemas, &program).unwrap();
+    }
+
+    #[test]
+    fn test_verify_detects_moved_cut() {
+        let schemas = vec![create_schema()];
+        let config = MachineConfig::new(130);
+        let mut program = build_contour_program(&schemas, &config);
+
+        // Score the first linear cut 1 unit off
+        for instruction in &mut program.instructions {
+            if let Instruction::Linear(target) = instruction {
+                if target.c.is_some() {
+                    target.x += 1.0;
+                    break;
+                }
+            }
+        }
+
+        let toolpath = simulate(&program).unwrap();
+        let divergences = check_toolpath(&schemas, &toolpath, EPS);
+        let kinds: Vec<DivergenceKind> = divergences.iter().map(|d| d.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![DivergenceKind::Missing, DivergenceKind::Unexpected]
+        );
+        assert_eq!(divergences[0].pass, "PT_GIU");
+
+        match verify_program(&schemas, &program) {
+            Err(ConvertError::ToolpathMismatch { pattern, message }) => {
+                assert_eq!(pattern, 1);
+                assert!(message.starts_with("2 divergent segment(s)"));
+            }
+            other => panic!("Expected ToolpathMismatch, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_verify_detects_missing_shape() {
+        let schemas = vec![create_schema()];
+        let config = MachineConfig::new(130);
+        let mut program = build_contour_program(&schemas, &config);
+        program
+            .instructions
+            .retain(|i| !matches!(i, Instruction::CallLabel(_)));
+
+        let toolpath = simulate(&program).unwrap();
+        let divergences = check_toolpath(&schemas, &toolpath, EPS);
+        assert_eq!(divergences.len(), 2);
+        assert!(divergences
+            .iter()
+            .all(|d| d.kind == DivergenceKind::Missing && d.pass == "shape"));
+    }
+}
@@ -45,6 +45,7 @@ pub const TOOL_TYPE_SHAPED: u32 = 1;
 /// Coarse epsilon for rest dimension calculations.
 pub const EPS_COARSE: f64 = 0.001;
 
+use crate::error::{ConvertError, Result};
 use serde::{Deserialize, Serialize};
 
 /// Unit of measurement.
@@ -95,6 +96,34 @@ impl std::fmt::Display for Unit {
     }
 }
 
+/// Order in which linear cuts and shapes are processed on a sheet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum CuttingOrder {
+    /// Linear cuts first, then shapes (OTD `CuttingOrder=0`).
+    #[default]
+    LinearFirst,
+    /// Shapes first, then linear cuts (OTD `CuttingOrder=1`).
+    ShapesFirst,
+}
+
+impl CuttingOrder {
+    /// Parse cutting order from OTD CuttingOrder= value.
+    pub fn from_code(code: u8) -> Self {
+        match code {
+            1 => CuttingOrder::ShapesFirst,
+            _ => CuttingOrder::LinearFirst,
+        }
+    }
+
+    /// Get the OTD CuttingOrder= value.
+    pub fn code(&self) -> u8 {
+        match self {
+            CuttingOrder::LinearFirst => 0,
+            CuttingOrder::ShapesFirst => 1,
+        }
+    }
+}
+
 /// Machine configuration.
 #[derive(Debug, Clone)]
 pub struct MachineConfig {
@@ -104,6 +133,10 @@ pub struct MachineConfig {
     pub linear_tool: u16,
     /// Shaped tool code.
     pub shaped_tool: u16,
+    /// Cutting order override (None = use the OTD CuttingOrder of each pattern).
+    pub cutting_order: Option<CuttingOrder>,
+    /// Kinematic model used for cycle time estimation.
+    pub kinematics: MachineKinematics,
 }
 
 impl Default for MachineConfig {
@@ -112,6 +145,8 @@ impl Default for MachineConfig {
             machine_number: 130,
             linear_tool: DEFAULT_LINEAR_TOOL,
             shaped_tool: DEFAULT_SHAPED_TOOL,
+            cutting_order: None,
+            kinematics: MachineKinematics::default(),
         }
     }
 }
@@ -134,6 +169,90 @@ impl MachineConfig {
     pub fn is_laminated(&self) -> bool {
         self.machine_number >= 200
     }
+
+    /// Set the cutting order override.
+    pub fn with_cutting_order(mut self, order: CuttingOrder) -> Self {
+        self.cutting_order = Some(order);
+        self
+    }
+
+    /// Set the kinematic model used for cycle time estimation.
+    pub fn with_kinematics(mut self, kinematics: MachineKinematics) -> Self {
+        self.kinematics = kinematics;
+        self
+    }
+
+    /// Resolve the cutting order for a pattern (override first, then OTD value).
+    pub fn cutting_order_for(&self, schema_cutting_order: u8) -> CuttingOrder {
+        self.cutting_order
+            .unwrap_or_else(|| CuttingOrder::from_code(schema_cutting_order))
+    }
+}
+
+/// Kinematic model of a table, used to estimate cycle times.
+///
+/// Lengths are in millimeters and times in seconds whatever the unit of the
+/// layout.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MachineKinematics {
+    /// Scoring feed rate of linear cuts (mm/min).
+    pub linear_feed: f64,
+    /// Scoring feed rate of shape contours (mm/min).
+    pub shape_feed: f64,
+    /// Rapid traverse rate (mm/min).
+    pub rapid_feed: f64,
+    /// X/Y acceleration (mm/s²).
+    pub acceleration: f64,
+    /// Dwell to lower the wheel onto the glass (s).
+    pub tool_down_dwell: f64,
+    /// Dwell to lift the wheel (s).
+    pub tool_up_dwell: f64,
+    /// C axis rotation speed (degrees/s).
+    pub rotation_speed: f64,
+    /// Time to load a tool (s).
+    pub tool_change_time: f64,
+}
+
+impl Default for MachineKinematics {
+    fn default() -> Self {
+        Self::cutting_table()
+    }
+}
+
+impl MachineKinematics {
+    /// Typical monolithic cutting table (100-199).
+    pub fn cutting_table() -> Self {
+        Self {
+            linear_feed: 100_000.0,
+            shape_feed: 60_000.0,
+            rapid_feed: 150_000.0,
+            acceleration: 5_000.0,
+            tool_down_dwell: 0.2,
+            tool_up_dwell: 0.15,
+            rotation_speed: 720.0,
+            tool_change_time: 2.0,
+        }
+    }
+
+    /// Load a machine profile from JSON.
+    ///
+    /// Fields missing from the profile keep the cutting table defaults.
+    pub fn from_json(json: &str) -> Result<Self> {
+        let invalid = |e: serde_json::Error| ConvertError::InvalidValue {
+            field: "machine profile".to_string(),
+            expected: "a JSON object of kinematic settings".to_string(),
+            value: e.to_string(),
+        };
+
+        let mut profile = serde_json::to_value(Self::cutting_table()).map_err(invalid)?;
+        let overrides: serde_json::Map<String, serde_json::Value> =
+            serde_json::from_str(json).map_err(invalid)?;
+        if let Some(fields) = profile.as_object_mut() {
+            fields.extend(overrides);
+        }
+        serde_json::from_value(profile).map_err(invalid)
+    }
 }
 
 /// Utility functions for floating-point comparisons.
@@ -1,5 +1,7 @@
 //! Error types for OTD to CNI conversion.
 
+use serde::{Serialize, Serializer};
+use std::fmt;
 use std::path::PathBuf;
 use thiserror::Error;
 
@@ -12,20 +14,58 @@ pub enum ErrorCode {
     EmptyFile = -2,
     /// General parse error (-3)
     ParseError = -3,
+    /// File could not be read or written (-4)
+    Io = -4,
     /// No [Pattern] section found (-11)
     NoPatternSection = -11,
+    /// Strip hierarchy nested too deep or skipping a level (-12)
+    InvalidHierarchy = -12,
     /// Invalid arc geometry (E100)
     InvalidArc = 100,
     /// Coordinates out of bounds (E101)
     OutOfBounds = 101,
+    /// Shape contour crosses itself (E102)
+    SelfIntersection = 102,
+    /// Zero or negative sheet, piece or trim dimension (E103)
+    InvalidDimension = 103,
+    /// Shape contour does not close (E104)
+    OpenContour = 104,
     /// Shape used on different-sized pieces (E200)
     ShapeSizeMismatch = 200,
     /// No cuts found in layout (E201)
     NoCutsFound = 201,
     /// Tool not found for thickness (E202)
     ToolNotFound = 202,
+    /// Patterns use different sheet formats (E203)
+    SheetFormatMismatch = 203,
+    /// Feature too small or sharp to cut and break out reliably (E204)
+    Manufacturability = 204,
+    /// Shape or Info reference not found (E205)
+    UnresolvedReference = 205,
     /// OTX decryption failed (E300)
     DecryptionFailed = 300,
+    /// Generated toolpath differs from the layout (E400)
+    ToolpathMismatch = 400,
+    /// Program macro the output dialect cannot translate (E401)
+    UnsupportedMacro = 401,
+}
+
+impl fmt::Display for ErrorCode {
+    /// `E100` style for layout and machine codes, the plain number for file errors.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = *self as i32;
+        if value > 0 {
+            write!(f, "E{}", value)
+        } else {
+            write!(f, "{}", value)
+        }
+    }
+}
+
+impl Serialize for ErrorCode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
 }
 
 /// Main error type for the converter.
@@ -93,6 +133,15 @@ pub enum ConvertError {
 
     #[error("Invalid numeric value at line {line}: {value}")]
     InvalidNumber { line: usize, value: String },
+
+    #[error("Toolpath simulation failed: {message}")]
+    SimulationFailed { message: String },
+
+    #[error("Toolpath of pattern {pattern} diverges from the layout: {message}")]
+    ToolpathMismatch { pattern: usize, message: String },
+
+    #[error("The {dialect} post-processor cannot translate macro {name}")]
+    UnsupportedMacro { dialect: String, name: String },
 }
 
 impl ConvertError {
@@ -110,11 +159,14 @@ impl ConvertError {
             ConvertError::OutOfBounds { .. } => ErrorCode::OutOfBounds,
             ConvertError::ShapeSizeMismatch { .. } => ErrorCode::ShapeSizeMismatch,
             ConvertError::NoCutsFound => ErrorCode::NoCutsFound,
-            ConvertError::ShapeNotFound { .. } => ErrorCode::ParseError,
-            ConvertError::InfoNotFound { .. } => ErrorCode::ParseError,
+            ConvertError::ShapeNotFound { .. } => ErrorCode::UnresolvedReference,
+            ConvertError::InfoNotFound { .. } => ErrorCode::UnresolvedReference,
             ConvertError::DecryptionFailed { .. } => ErrorCode::DecryptionFailed,
-            ConvertError::Io(_) => ErrorCode::FileNotFound,
+            ConvertError::Io(_) => ErrorCode::Io,
             ConvertError::InvalidNumber { .. } => ErrorCode::ParseError,
+            ConvertError::SimulationFailed { .. } => ErrorCode::ToolpathMismatch,
+            ConvertError::ToolpathMismatch { .. } => ErrorCode::ToolpathMismatch,
+            ConvertError::UnsupportedMacro { .. } => ErrorCode::UnsupportedMacro,
         }
     }
 
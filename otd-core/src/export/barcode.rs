@@ -0,0 +1,505 @@
+//! Barcode symbols for piece labels.
+//!
+//! ZPL printers encode barcodes themselves, so these encoders are only used to
+//! draw the symbols into PDF label sheets: Code 128 (code set B) as a list of
+//! bar widths and ECC 200 Data Matrix as a module matrix.
+
+use crate::error::{ConvertError, Result};
+
+/// Code 128 symbol patterns as bar/space widths, indexed by symbol value.
+/// Values 103-105 are the start codes A/B/C, 106 is the stop code.
+const CODE128_PATTERNS: [&str; 107] = [
+    "212222", "222122", "222221", "121223", "121322", "131222", "122213", "122312", "132212",
+    "221213", "221312", "231212", "112232", "122132", "122231", "113222", "123122", "123221",
+    "223211", "221132", "221231", "213212", "223112", "312131", "311222", "321122", "321221",
+    "312212", "322112", "322211", "212123", "212321", "232121", "111323", "131123", "131321",
+    "112313", "132113", "132311", "211313", "231113", "231311", "112133", "112331", "132131",
+    "113123", "113321", "133121", "313121", "211331", "231131", "213113", "213311", "213131",
+    "311123", "311321", "331121", "312113", "312311", "332111", "314111", "221411", "431111",
+    "111224", "111422", "121124", "121421", "141122", "141221", "112214", "112412", "122114",
+    "122411", "142112", "142211", "241211", "221114", "413111", "241112", "134111", "111242",
+    "121142", "121241", "114212", "124112", "124211", "411212", "421112", "421211", "212141",
+    "214121", "412121", "111143", "111341", "131141", "114113", "114311", "411113", "411311",
+    "113141", "114131", "311141", "411131", "211412", "211214", "211232", "2331112",
+];
+
+const CODE128_START_B: usize = 104;
+const CODE128_STOP: usize = 106;
+
+/// Symbol values of `data` in code set B, with start, check and stop symbols.
+///
+/// Characters outside printable ASCII are encoded as `?`.
+pub(crate) fn code128_values(data: &str) -> Vec<usize> {
+    let mut values = vec![CODE128_START_B];
+    values.extend(data.chars().map(|c| match c {
+        ' '..='~' => c as usize - 32,
+        _ => '?' as usize - 32,
+    }));
+    let check = values
+        .iter()
+        .enumerate()
+        .map(|(pos, value)| pos.max(1) * value)
+        .sum::<usize>()
+        % 103;
+    values.push(check);
+    values.push(CODE128_STOP);
+    values
+}
+
+/// Alternating bar and space widths in modules, starting with a bar.
+pub(crate) fn code128_widths(data: &str) -> Vec<u8> {
+    code128_values(data)
+        .into_iter()
+        .flat_map(|value| CODE128_PATTERNS[value].bytes().map(|b| b - b'0'))
+        .collect()
+}
+
+/// Square ECC 200 symbol: size, data and error correction codewords, data
+/// region size and data regions per side.
+struct SymbolSize {
+    size: usize,
+    data: usize,
+    ecc: usize,
+    region: usize,
+    regions: usize,
+}
+
+const DATAMATRIX_SIZES: [SymbolSize; 14] = [
+    SymbolSize::new(10, 3, 5, 8, 1),
+    SymbolSize::new(12, 5, 7, 10, 1),
+    SymbolSize::new(14, 8, 10, 12, 1),
+    SymbolSize::new(16, 12, 12, 14, 1),
+    SymbolSize::new(18, 18, 14, 16, 1),
+    SymbolSize::new(20, 22, 18, 18, 1),
+    SymbolSize::new(22, 30, 20, 20, 1),
+    SymbolSize::new(24, 36, 24, 22, 1),
+    SymbolSize::new(26, 44, 28, 24, 1),
+    SymbolSize::new(32, 62, 36, 14, 2),
+    SymbolSize::new(36, 86, 42, 16, 2),
+    SymbolSize::new(40, 114, 48, 18, 2),
+    SymbolSize::new(44, 144, 56, 20, 2),
+    // Largest size without interleaved error correction blocks
+    SymbolSize::new(48, 174, 68, 22, 2),
+];
+
+impl SymbolSize {
+    const fn new(size: usize, data: usize, ecc: usize, region: usize, regions: usize) -> Self {
+        Self {
+            size,
+            data,
+            ecc,
+            region,
+            regions,
+        }
+    }
+}
+
+/// Data codewords of `data` in ASCII encodation (digit pairs packed).
+fn datamatrix_data(data: &str) -> Vec<u8> {
+    let bytes: Vec<u8> = data
+        .chars()
+        .map(|c| if (c as u32) < 256 { c as u8 } else { b'?' })
+        .collect();
+    let mut codewords = Vec::with_capacity(bytes.len());
+    let mut idx = 0;
+    while idx < bytes.len() {
+        let byte = bytes[idx];
+        if byte.is_ascii_digit() && bytes.get(idx + 1).is_some_and(u8::is_ascii_digit) {
+            codewords.push(130 + (byte - b'0') * 10 + (bytes[idx + 1] - b'0'));
+            idx += 2;
+            continue;
+        }
+        if byte >= 128 {
+            // Upper shift to the extended ASCII range
+            codewords.push(235);
+            codewords.push(byte - 127);
+        } else {
+            codewords.push(byte + 1);
+        }
+        idx += 1;
+    }
+    codewords
+}
+
+/// Reed-Solomon error correction codewords over GF(256) with polynomial 0x12D.
+fn reed_solomon(data: &[u8], count: usize) -> Vec<u8> {
+    let mut exp = [0u8; 255];
+    let mut log = [0u8; 256];
+    let mut value: u16 = 1;
+    for (power, slot) in exp.iter_mut().enumerate() {
+        *slot = value as u8;
+        log[value as usize] = power as u8;
+        value <<= 1;
+        if value & 0x100 != 0 {
+            value ^= 0x12D;
+        }
+    }
+    let mul = |a: u8, b: u8| -> u8 {
+        if a == 0 || b == 0 {
+            0
+        } else {
+            exp[(log[a as usize] as usize + log[b as usize] as usize) % 255]
+        }
+    };
+
+    // Generator (x - 2^1)(x - 2^2)...(x - 2^count), highest degree first
+    let mut generator = vec![1u8];
+    for power in 1..=count {
+        let root = exp[power % 255];
+        let mut next = vec![0u8; generator.len() + 1];
+        for (idx, &coef) in generator.iter().enumerate() {
+            next[idx] ^= coef;
+            next[idx + 1] ^= mul(coef, root);
+        }
+        generator = next;
+    }
+
+    let mut remainder = vec![0u8; count];
+    for &byte in data {
+        let factor = byte ^ remainder[0];
+        remainder.remove(0);
+        remainder.push(0);
+        for (slot, &coef) in remainder.iter_mut().zip(&generator[1..]) {
+            *slot ^= mul(factor, coef);
+        }
+    }
+    remainder
+}
+
+/// All codewords of the smallest square symbol holding `data`.
+fn datamatrix_codewords(data: &str) -> Result<(&'static SymbolSize, Vec<u8>)> {
+    let mut codewords = datamatrix_data(data);
+    let symbol = DATAMATRIX_SIZES
+        .iter()
+        .find(|s| s.data >= codewords.len())
+        .ok_or_else(|| ConvertError::InvalidValue {
+            field: "barcode".to_string(),
+            expected: format!(
+                "at most {} Data Matrix codewords",
+                DATAMATRIX_SIZES[DATAMATRIX_SIZES.len() - 1].data
+            ),
+            value: data.to_string(),
+        })?;
+
+    // First pad is 129, further pads are scrambled by their position
+    let mut first_pad = true;
+    while codewords.len() < symbol.data {
+        let pad = if first_pad {
+            129
+        } else {
+            let position = codewords.len() + 1;
+            let pad = 129 + (149 * position) % 253 + 1;
+            if pad > 254 {
+                pad - 254
+            } else {
+                pad
+            }
+        };
+        first_pad = false;
+        codewords.push(pad as u8);
+    }
+
+    let ecc = reed_solomon(&codewords, symbol.ecc);
+    codewords.extend(ecc);
+    Ok((symbol, codewords))
+}
+
+/// Data Matrix (ECC 200) modules of `data`, row by row from the top,
+/// `true` for dark modules.
+pub(crate) fn datamatrix(data: &str) -> Result<Vec<Vec<bool>>> {
+    let (symbol, codewords) = datamatrix_codewords(data)?;
+    let mapping = symbol.region * symbol.regions;
+    let placed = Placement::run(mapping, &codewords);
+
+    let block = symbol.region + 2;
+    let mut modules = vec![vec![false; symbol.size]; symbol.size];
+    for region_row in 0..symbol.regions {
+        for region_col in 0..symbol.regions {
+            let top = region_row * block;
+            let left = region_col * block;
+            for offset in 0..block {
+                // Solid L on the left and bottom, clock track on top and right
+                modules[top + offset][left] = true;
+                modules[top + block - 1][left + offset] = true;
+                modules[top][left + offset] = offset % 2 == 0;
+                modules[top + offset][left + block - 1] = offset % 2 == 1;
+            }
+        }
+    }
+    for (row, cells) in placed.iter().enumerate() {
+        for (col, &dark) in cells.iter().enumerate() {
+            let sym_row = row / symbol.region * block + 1 + row % symbol.region;
+            let sym_col = col / symbol.region * block + 1 + col % symbol.region;
+            modules[sym_row][sym_col] = dark;
+        }
+    }
+    Ok(modules)
+}
+
+/// ECC 200 codeword placement in the mapping matrix (ISO/IEC 16022 Annex F).
+struct Placement<'a> {
+    rows: usize,
+    cols: usize,
+    cells: Vec<Vec<Option<bool>>>,
+    codewords: &'a [u8],
+}
+
+impl<'a> Placement<'a> {
+    fn run(size: usize, codewords: &'a [u8]) -> Vec<Vec<bool>> {
+        let mut placement = Placement {
+            rows: size,
+            cols: size,
+            cells: vec![vec![None; size]; size],
+            codewords,
+        };
+        let placed = placement.place();
+        debug_assert_eq!(placed, codewords.len());
+        placement
+            .cells
+            .into_iter()
+            .map(|row| row.into_iter().map(|cell| cell.unwrap_or(false)).collect())
+            .collect()
+    }
+
+    /// Place all codewords, returning how many were placed.
+    fn place(&mut self) -> usize {
+        let (nrow, ncol) = (self.rows as isize, self.cols as isize);
+        let mut chr = 0;
+        let mut row: isize = 4;
+        let mut col: isize = 0;
+
+        loop {
+            if row == nrow && col == 0 {
+                self.corner(chr, &CORNER_1);
+                chr += 1;
+            }
+            if row == nrow - 2 && col == 0 && ncol % 4 != 0 {
+                self.corner(chr, &CORNER_2);
+                chr += 1;
+            }
+            if row == nrow - 2 && col == 0 && ncol % 8 == 4 {
+                self.corner(chr, &CORNER_3);
+                chr += 1;
+            }
+            if row == nrow + 4 && col == 2 && ncol % 8 == 0 {
+                self.corner(chr, &CORNER_4);
+                chr += 1;
+            }
+
+            // Sweep up and to the right
+            loop {
+                if row < nrow && col >= 0 && self.cells[row as usize][col as usize].is_none() {
+                    self.utah(row, col, chr);
+                    chr += 1;
+                }
+                row -= 2;
+                col += 2;
+                if !(row >= 0 && col < ncol) {
+                    break;
+                }
+            }
+            row += 1;
+            col += 3;
+
+            // Sweep down and to the left
+            loop {
+                if row >= 0 && col < ncol && self.cells[row as usize][col as usize].is_none() {
+                    self.utah(row, col, chr);
+                    chr += 1;
+                }
+                row += 2;
+                col -= 2;
+                if !(row < nrow && col >= 0) {
+                    break;
+                }
+            }
+            row += 3;
+            col += 1;
+
+            if !(row < nrow || col < ncol) {
+                break;
+            }
+        }
+
+        // Unused bottom-right corner gets a fixed pattern
+        let (r, c) = (self.rows - 1, self.cols - 1);
+        if self.cells[r][c].is_none() {
+            self.cells[r][c] = Some(true);
+            self.cells[r - 1][c - 1] = Some(true);
+            self.cells[r][c - 1] = Some(false);
+            self.cells[r - 1][c] = Some(false);
+        }
+        chr
+    }
+
+    /// Place bit `bit` (1 = most significant) of codeword `chr`, wrapping
+    /// negative positions around the matrix.
+    fn module(&mut self, mut row: isize, mut col: isize, chr: usize, bit: u32) {
+        let (nrow, ncol) = (self.rows as isize, self.cols as isize);
+        if row < 0 {
+            row += nrow;
+            col += 4 - (nrow + 4) % 8;
+        }
+        if col < 0 {
+            col += ncol;
+            row += 4 - (ncol + 4) % 8;
+        }
+        let codeword = self.codewords.get(chr).copied().unwrap_or(0);
+        self.cells[row as usize][col as usize] = Some((codeword >> (8 - bit)) & 1 == 1);
+    }
+
+    /// Standard L-shaped placement of one codeword ending at `(row, col)`.
+    fn utah(&mut self, row: isize, col: isize, chr: usize) {
+        let positions = [
+            (row - 2, col - 2),
+            (row - 2, col - 1),
+            (row - 1, col - 2),
+            (row - 1, col - 1),
+            (row - 1, col),
+            (row, col - 2),
+            (row, col - 1),
+            (row, col),
+        ];
+        for (bit, (r, c)) in positions.into_iter().enumerate() {
+            self.module(r, c, chr, bit as u32 + 1);
+        }
+    }
+
+    /// Special corner placement; offsets are relative to the matrix edges.
+    fn corner(&mut self, chr: usize, positions: &[(Edge, Edge); 8]) {
+        for (bit, (r, c)) in positions.iter().enumerate() {
+            let row = r.resolve(self.rows);
+            let col = c.resolve(self.cols);
+            self.module(row, col, chr, bit as u32 + 1);
+        }
+    }
+}
+
+/// Position counted from the start or from the end of an axis.
+#[derive(Clone, Copy)]
+enum Edge {
+    Start(isize),
+    End(isize),
+}
+
+impl Edge {
+    fn resolve(self, len: usize) -> isize {
+        match self {
+            Edge::Start(offset) => offset,
+            Edge::End(offset) => len as isize - offset,
+        }
+    }
+}
+
+use Edge::{End, Start};
+
+const CORNER_1: [(Edge, Edge); 8] = [
+    (End(1), Start(0)),
+    (End(1), Start(1)),
+    (End(1), Start(2)),
+    (Start(0), End(2)),
+    (Start(0), End(1)),
+    (Start(1), End(1)),
+    (Start(2), End(1)),
+    (Start(3), End(1)),
+];
+
+const CORNER_2: [(Edge, Edge); 8] = [
+    (End(3), Start(0)),
+    (End(2), Start(0)),
+    (End(1), Start(0)),
+    (Start(0), End(4)),
+    (Start(0), End(3)),
+    (Start(0), End(2)),
+    (Start(0), End(1)),
+    (Start(1), End(1)),
+];
+
+const CORNER_3: [(Edge, Edge); 8] = [
+    (End(3), Start(0)),
+    (End(2), Start(0)),
+    (End(1), Start(0)),
+    (Start(0), End(2)),
+    (Start(0), End(1)),
+    (Start(1), End(1)),
+    (Start(2), End(1)),
+    (Start(3), End(1)),
+];
+
+const CORNER_4: [(Edge, Edge); 8] = [
+    (End(1), Start(0)),
+    (End(1), End(1)),
+    (Start(0), End(3)),
+    (Start(0), End(2)),
+    (Start(0), End(1)),
+    (Start(1), End(3)),
+    (Start(1), End(2)),
+    (Start(1), End(1)),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_code128_patterns() {
+        for pattern in &CODE128_PATTERNS[..CODE128_STOP] {
+            let modules: u32 = pattern.bytes().map(|b| (b - b'0') as u32).sum();
+            assert_eq!(modules, 11, "{}", pattern);
+        }
+
+        // Start B, "Wikipedia", check symbol 88, stop
+        let values = code128_values("Wikipedia");
+        assert_eq!(values[0], 104);
+        assert_eq!(values[1], 55);
+        assert_eq!(values[values.len() - 2], 88);
+        assert_eq!(values[values.len() - 1], 106);
+
+        let widths = code128_widths("A");
+        let modules: u32 = widths.iter().map(|&w| w as u32).sum();
+        assert_eq!(modules, 11 * 3 + 13);
+    }
+
+    #[test]
+    fn test_datamatrix_codewords() {
+        // Example from ISO/IEC 16022: "123456" in a 10 x 10 symbol
+        let (symbol, codewords) = datamatrix_codewords("123456").unwrap();
+        assert_eq!(symbol.size, 10);
+        assert_eq!(codewords, [142, 164, 186, 114, 25, 5, 88, 102]);
+
+        assert_eq!(datamatrix_data("A1"), [66, 50]);
+        assert_eq!(datamatrix_data("é"), [235, 106]);
+    }
+
+    #[test]
+    fn test_datamatrix_finder_pattern() {
+        for data in ["123456", "ORDER-4711/12", &"X".repeat(70)] {
+            let modules = datamatrix(data).unwrap();
+            let size = modules.len();
+            assert!(modules.iter().all(|row| row.len() == size));
+            for (idx, row) in modules.iter().enumerate() {
+                assert!(row[0], "left edge");
+                assert!(modules[size - 1][idx], "bottom edge");
+                assert_eq!(modules[0][idx], idx % 2 == 0, "top clock track");
+                assert_eq!(row[size - 1], idx % 2 == 1, "right clock track");
+            }
+        }
+        assert!(datamatrix(&"X".repeat(500)).is_err());
+    }
+
+    #[test]
+    fn test_datamatrix_placement_fills_every_size() {
+        for symbol in &DATAMATRIX_SIZES {
+            let size = symbol.region * symbol.regions;
+            let codewords = vec![0xA5; symbol.data + symbol.ecc];
+            let mut placement = Placement {
+                rows: size,
+                cols: size,
+                cells: vec![vec![None; size]; size],
+                codewords: &codewords,
+            };
+            assert_eq!(placement.place(), codewords.len(), "{}", symbol.size);
+            assert!(placement.cells.iter().flatten().all(Option::is_some));
+        }
+    }
+}
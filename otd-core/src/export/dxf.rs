@@ -0,0 +1,457 @@
+//! Standalone DXF drawing of a pattern.
+//!
+//! Unlike the `[*PRWB]`/`[*PRWC]` previews embedded in CNI files, the output
+//! is a complete AutoCAD R12 (AC1009) file: HEADER with the drawing extents,
+//! TABLES with line type, layers and text style, one BLOCK per shape, and
+//! ENTITIES where every shaped piece is an INSERT of its shape block.
+
+use crate::config::angle::normalize_degrees;
+use crate::generator::{piece_texts, DxfColors, ViewMode};
+use crate::model::{Cut, CutType, Piece, Schema};
+use std::fmt::Write;
+
+/// Layers of an exported drawing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DxfLayer {
+    /// Sheet outline.
+    Sheet,
+    /// Trim boundaries.
+    Trims,
+    /// Linear cuts.
+    Linear,
+    /// Shape contours (blocks and their inserts).
+    Shape,
+    /// Low-E coating removal.
+    LowE,
+    /// Piece labels.
+    Labels,
+}
+
+impl DxfLayer {
+    /// All layers, in table order.
+    pub const ALL: [DxfLayer; 6] = [
+        DxfLayer::Sheet,
+        DxfLayer::Trims,
+        DxfLayer::Linear,
+        DxfLayer::Shape,
+        DxfLayer::LowE,
+        DxfLayer::Labels,
+    ];
+
+    /// Layer name.
+    pub fn name(&self) -> &'static str {
+        match self {
+            DxfLayer::Sheet => "SHEET",
+            DxfLayer::Trims => "TRIMS",
+            DxfLayer::Linear => "LINEAR",
+            DxfLayer::Shape => "SHAPE",
+            DxfLayer::LowE => "LOWE",
+            DxfLayer::Labels => "LABELS",
+        }
+    }
+
+    /// AutoCAD color index.
+    pub fn color(&self) -> i32 {
+        match self {
+            DxfLayer::Sheet => 7,
+            DxfLayer::Trims => 8,
+            DxfLayer::Linear => 1,
+            DxfLayer::Shape => 6,
+            DxfLayer::LowE => 3,
+            DxfLayer::Labels => 5,
+        }
+    }
+}
+
+/// Render a pattern as a standalone DXF file.
+pub fn export_dxf(schema: &Schema) -> String {
+    let mut dxf = Dxf::default();
+
+    write_header(&mut dxf, schema);
+    write_tables(&mut dxf);
+    write_blocks(&mut dxf, schema);
+
+    dxf.section("ENTITIES");
+    write_sheet(&mut dxf, schema);
+    for cut in schema.linear_cuts.iter().filter(|c| c.active) {
+        dxf.cut(DxfLayer::Linear, cut, (0.0, 0.0));
+    }
+    for piece in &schema.pieces {
+        if let Some(idx) = piece.shape_index {
+            dxf.insert(&block_name(schema, idx), piece.x_origin, piece.y_origin);
+        }
+    }
+    for cut in &schema.lowe_cuts {
+        dxf.cut(DxfLayer::LowE, cut, (0.0, 0.0));
+    }
+    for piece in &schema.lowe_pieces {
+        dxf.rectangle(
+            DxfLayer::LowE,
+            (piece.x_origin, piece.y_origin),
+            (piece.width, piece.height),
+        );
+    }
+    write_labels(&mut dxf, schema);
+    dxf.end_section();
+
+    dxf.group(0, "EOF");
+    dxf.output
+}
+
+fn write_header(dxf: &mut Dxf, schema: &Schema) {
+    dxf.section("HEADER");
+    dxf.group(9, "$ACADVER");
+    dxf.group(1, "AC1009");
+    dxf.group(9, "$INSBASE");
+    dxf.point(10, 0.0, 0.0);
+    for (name, x, y) in [
+        ("$EXTMIN", 0.0, 0.0),
+        ("$EXTMAX", schema.width, schema.height),
+        ("$LIMMIN", 0.0, 0.0),
+        ("$LIMMAX", schema.width, schema.height),
+    ] {
+        dxf.group(9, name);
+        dxf.point(10, x, y);
+    }
+    dxf.end_section();
+}
+
+fn write_tables(dxf: &mut Dxf) {
+    dxf.section("TABLES");
+
+    dxf.group(0, "TABLE");
+    dxf.group(2, "LTYPE");
+    dxf.group(70, "1");
+    dxf.group(0, "LTYPE");
+    dxf.group(2, "CONTINUOUS");
+    dxf.group(70, "0");
+    dxf.group(3, "Solid line");
+    dxf.group(72, "65");
+    dxf.group(73, "0");
+    dxf.group(40, "0.0");
+    dxf.group(0, "ENDTAB");
+
+    dxf.group(0, "TABLE");
+    dxf.group(2, "LAYER");
+    dxf.group(70, &(DxfLayer::ALL.len() + 1).to_string());
+    dxf.layer("0", 7);
+    for layer in DxfLayer::ALL {
+        dxf.layer(layer.name(), layer.color());
+    }
+    dxf.group(0, "ENDTAB");
+
+    dxf.group(0, "TABLE");
+    dxf.group(2, "STYLE");
+    dxf.group(70, "1");
+    dxf.group(0, "STYLE");
+    dxf.group(2, "STANDARD");
+    dxf.group(70, "0");
+    dxf.group(40, "0.0");
+    dxf.group(41, "1.0");
+    dxf.group(50, "0.0");
+    dxf.group(71, "0");
+    dxf.group(42, "2.5");
+    dxf.group(3, "txt");
+    dxf.group(4, "");
+    dxf.group(0, "ENDTAB");
+
+    dxf.end_section();
+}
+
+/// One block per shape, with the contour relative to the piece origin.
+fn write_blocks(dxf: &mut Dxf, schema: &Schema) {
+    dxf.section("BLOCKS");
+    for (idx, shape) in schema.shapes.iter().enumerate() {
+        let name = block_name(schema, idx);
+        dxf.group(0, "BLOCK");
+        dxf.group(8, "0");
+        dxf.group(2, &name);
+        dxf.group(70, "0");
+        dxf.point(10, 0.0, 0.0);
+        dxf.group(3, &name);
+        for cut in shape.cuts.iter().filter(|c| c.active) {
+            dxf.cut(DxfLayer::Shape, cut, (0.0, 0.0));
+        }
+        dxf.group(0, "ENDBLK");
+        dxf.group(8, "0");
+    }
+    dxf.end_section();
+}
+
+/// Sheet outline and the inner boundary of each trim.
+fn write_sheet(dxf: &mut Dxf, schema: &Schema) {
+    let (w, h) = (schema.width, schema.height);
+    dxf.rectangle(DxfLayer::Sheet, (0.0, 0.0), (w, h));
+    if schema.trim_left > 0.0 {
+        dxf.line(
+            DxfLayer::Trims,
+            (schema.trim_left, 0.0),
+            (schema.trim_left, h),
+        );
+    }
+    if schema.trim_bottom > 0.0 {
+        dxf.line(
+            DxfLayer::Trims,
+            (0.0, schema.trim_bottom),
+            (w, schema.trim_bottom),
+        );
+    }
+}
+
+/// Labels of every piece with piece data, as in the CNI preview.
+fn write_labels(dxf: &mut Dxf, schema: &Schema) {
+    let colors = DxfColors::default();
+    for piece in schema.pieces.iter().filter(|p| p.info_id.is_some()) {
+        let height = label_height(schema, piece);
+        for text in piece_texts(schema, piece, &colors, &ViewMode::Normal) {
+            dxf.text(text.x, text.y, height, &text.text);
+        }
+    }
+}
+
+/// Text height fitting the piece, capped relative to the sheet.
+pub(super) fn label_height(schema: &Schema, piece: &Piece) -> f64 {
+    let cap = schema.width.max(schema.height) / 100.0;
+    (piece.width.min(piece.height) / 10.0).min(cap)
+}
+
+/// Block name of a shape, unique within the drawing.
+fn block_name(schema: &Schema, idx: usize) -> String {
+    let shape = &schema.shapes[idx];
+    let duplicate = schema
+        .shapes
+        .iter()
+        .enumerate()
+        .any(|(other, s)| other != idx && s.id == shape.id);
+    if duplicate {
+        format!("SHAPE_{}_{}", shape.id, idx + 1)
+    } else {
+        format!("SHAPE_{}", shape.id)
+    }
+}
+
+/// DXF group writer.
+#[derive(Default)]
+struct Dxf {
+    output: String,
+}
+
+impl Dxf {
+    fn group(&mut self, code: i32, value: &str) {
+        writeln!(self.output, "{:>3}", code).unwrap();
+        writeln!(self.output, "{}", value).unwrap();
+    }
+
+    fn number(&mut self, code: i32, value: f64) {
+        self.group(code, &format_number(value));
+    }
+
+    /// Point with X at `code`, Y at `code + 10` and Z at `code + 20`.
+    fn point(&mut self, code: i32, x: f64, y: f64) {
+        self.number(code, x);
+        self.number(code + 10, y);
+        self.number(code + 20, 0.0);
+    }
+
+    fn section(&mut self, name: &str) {
+        self.group(0, "SECTION");
+        self.group(2, name);
+    }
+
+    fn end_section(&mut self) {
+        self.group(0, "ENDSEC");
+    }
+
+    fn layer(&mut self, name: &str, color: i32) {
+        self.group(0, "LAYER");
+        self.group(2, name);
+        self.group(70, "0");
+        self.group(62, &color.to_string());
+        self.group(6, "CONTINUOUS");
+    }
+
+    fn line(&mut self, layer: DxfLayer, start: (f64, f64), end: (f64, f64)) {
+        self.group(0, "LINE");
+        self.group(8, layer.name());
+        self.point(10, start.0, start.1);
+        self.point(11, end.0, end.1);
+    }
+
+    fn rectangle(&mut self, layer: DxfLayer, origin: (f64, f64), size: (f64, f64)) {
+        let (x0, y0) = origin;
+        let (x1, y1) = (x0 + size.0, y0 + size.1);
+        self.line(layer, (x0, y0), (x1, y0));
+        self.line(layer, (x1, y0), (x1, y1));
+        self.line(layer, (x1, y1), (x0, y1));
+        self.line(layer, (x0, y1), (x0, y0));
+    }
+
+    /// A cut as LINE or ARC; DXF arcs always run counter-clockwise.
+    fn cut(&mut self, layer: DxfLayer, cut: &Cut, origin: (f64, f64)) {
+        let (ox, oy) = origin;
+        match cut.cut_type {
+            CutType::Line => self.line(
+                layer,
+                (ox + cut.xi, oy + cut.yi),
+                (ox + cut.xf, oy + cut.yf),
+            ),
+            CutType::ArcCW | CutType::ArcCCW => {
+                let start = (cut.yi - cut.yc).atan2(cut.xi - cut.xc).to_degrees();
+                let end = (cut.yf - cut.yc).atan2(cut.xf - cut.xc).to_degrees();
+                let (start, end) = if cut.cut_type == CutType::ArcCW {
+                    (end, start)
+                } else {
+                    (start, end)
+                };
+                self.group(0, "ARC");
+                self.group(8, layer.name());
+                self.point(10, ox + cut.xc, oy + cut.yc);
+                self.number(40, cut.radius);
+                self.number(50, arc_angle(start));
+                self.number(51, arc_angle(end));
+            }
+        }
+    }
+
+    fn insert(&mut self, block: &str, x: f64, y: f64) {
+        self.group(0, "INSERT");
+        self.group(8, DxfLayer::Shape.name());
+        self.group(2, block);
+        self.point(10, x, y);
+    }
+
+    /// Text centred on `(x, y)`.
+    fn text(&mut self, x: f64, y: f64, height: f64, text: &str) {
+        self.group(0, "TEXT");
+        self.group(8, DxfLayer::Labels.name());
+        self.point(10, x, y);
+        self.number(40, height);
+        self.group(1, text);
+        self.group(72, "1");
+        self.point(11, x, y);
+    }
+}
+
+/// Angle in `[0, 360)`, with rounding noise just below 360 folded to 0.
+fn arc_angle(degrees: f64) -> f64 {
+    let angle = normalize_degrees(degrees);
+    if 360.0 - angle < 1e-6 {
+        0.0
+    } else {
+        angle
+    }
+}
+
+/// Shortest decimal representation, rounded to 1e-6.
+fn format_number(value: f64) -> String {
+    let rounded = (value * 1e6).round() / 1e6;
+    if rounded == 0.0 {
+        "0.0".to_string()
+    } else {
+        format!("{:?}", rounded)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{PieceType, Shape};
+
+    fn create_schema() -> Schema {
+        let mut schema = Schema::new();
+        schema.width = 1000.0;
+        schema.height = 500.0;
+        schema.trim_left = 10.0;
+        schema.add_linear_cut(Cut::new_line(300.0, 0.0, 300.0, 500.0));
+
+        let mut shape = Shape::new(7);
+        shape.name = "ROUND".to_string();
+        shape.add_cut(Cut::new_line(0.0, 0.0, 100.0, 0.0));
+        shape.add_cut(Cut::new_arc_cw(100.0, 0.0, 0.0, 0.0, 50.0));
+        schema.add_shape(shape);
+
+        schema.add_piece_type(PieceType {
+            id: 1,
+            customer: "ACME".to_string(),
+            order_no: "A-17".to_string(),
+            ..Default::default()
+        });
+        for x in [400.0, 600.0] {
+            schema.add_piece(Piece {
+                x_origin: x,
+                y_origin: 100.0,
+                width: 100.0,
+                height: 50.0,
+                info_id: Some(1),
+                piece_type_index: Some(0),
+                shape_index: Some(0),
+                ..Default::default()
+            });
+        }
+        schema
+    }
+
+    /// Group code/value pairs of a DXF file.
+    fn groups(dxf: &str) -> Vec<(i32, &str)> {
+        let lines: Vec<&str> = dxf.lines().collect();
+        lines
+            .chunks(2)
+            .map(|pair| (pair[0].trim().parse().unwrap(), pair[1]))
+            .collect()
+    }
+
+    #[test]
+    fn test_export_dxf_sections() {
+        let dxf = export_dxf(&create_schema());
+        let groups = groups(&dxf);
+
+        let sections: Vec<&str> = groups
+            .windows(2)
+            .filter(|w| w[0] == (0, "SECTION"))
+            .map(|w| w[1].1)
+            .collect();
+        assert_eq!(sections, ["HEADER", "TABLES", "BLOCKS", "ENTITIES"]);
+        assert_eq!(groups.last(), Some(&(0, "EOF")));
+
+        for layer in DxfLayer::ALL {
+            assert!(groups.contains(&(2, layer.name())), "{}", layer.name());
+        }
+    }
+
+    #[test]
+    fn test_export_dxf_one_block_per_shape() {
+        let dxf = export_dxf(&create_schema());
+        let groups = groups(&dxf);
+
+        assert_eq!(groups.iter().filter(|g| **g == (0, "BLOCK")).count(), 1);
+        assert_eq!(groups.iter().filter(|g| **g == (0, "INSERT")).count(), 2);
+        assert_eq!(groups.iter().filter(|g| **g == (2, "SHAPE_7")).count(), 3);
+
+        // Clockwise arc from (100, 0) to (0, 0) around (50, 0): 0 to 180 swapped
+        let arc = groups.iter().position(|g| *g == (0, "ARC")).unwrap();
+        assert_eq!(groups[arc + 6], (50, "180.0"));
+        assert_eq!(groups[arc + 7], (51, "0.0"));
+    }
+
+    #[test]
+    fn test_export_dxf_layers_and_labels() {
+        let dxf = export_dxf(&create_schema());
+        let groups = groups(&dxf);
+
+        let on_layer = |layer: DxfLayer| {
+            groups
+                .windows(2)
+                .filter(|w| w[0].0 == 0 && w[1] == (8, layer.name()))
+                .count()
+        };
+        assert_eq!(on_layer(DxfLayer::Sheet), 4);
+        assert_eq!(on_layer(DxfLayer::Trims), 1);
+        assert_eq!(on_layer(DxfLayer::Linear), 1);
+        assert_eq!(on_layer(DxfLayer::LowE), 0);
+
+        assert!(groups.contains(&(1, "100.000 x 50.000")));
+        assert!(groups.contains(&(1, "ROUND")));
+        assert!(groups.contains(&(1, "ACME")));
+        assert!(groups.contains(&(1, "A-17")));
+    }
+}
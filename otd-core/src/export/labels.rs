@@ -0,0 +1,614 @@
+//! Piece labels for label printers (ZPL) and PDF label sheets.
+//!
+//! One label is produced per physical piece: every non-waste piece of a
+//! pattern, once per sheet of `Schema::quantity`. The text lines and the
+//! barcode content come from a [`LabelTemplate`] with `{placeholder}` fields.
+
+use super::barcode::{code128_widths, datamatrix};
+use super::pdf::{fit_text, format_length, write_document, Align, Font, Page, Paper, TEXT};
+use crate::config::Unit;
+use crate::error::{ConvertError, Result};
+use crate::model::Schema;
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+
+/// Placeholders available in template lines and barcode data.
+pub const LABEL_FIELDS: [&str; 14] = [
+    "pattern",
+    "sheet",
+    "piece",
+    "order",
+    "position",
+    "customer",
+    "commission",
+    "rack",
+    "width",
+    "height",
+    "dimensions",
+    "unit",
+    "shape",
+    "material",
+];
+
+/// Label data of one physical piece.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PieceLabel {
+    /// Pattern number, from 1.
+    pub pattern: usize,
+    /// Sheet of the pattern, from 1 to its quantity.
+    pub sheet: u32,
+    /// Piece number within the pattern, as in the PDF cut list.
+    pub piece: usize,
+    /// Order number.
+    pub order_no: String,
+    /// Position within the order.
+    pub position_no: String,
+    /// Customer name.
+    pub customer: String,
+    /// Commission reference.
+    pub commission: String,
+    /// Rack number.
+    pub rack_no: String,
+    /// Piece width.
+    pub width: f64,
+    /// Piece height.
+    pub height: f64,
+    /// Unit of the dimensions.
+    pub unit: Unit,
+    /// Shape name, empty for rectangles.
+    pub shape: String,
+    /// Glass description.
+    pub material: String,
+}
+
+impl PieceLabel {
+    /// Value of a template placeholder.
+    fn field(&self, name: &str) -> String {
+        match name {
+            "pattern" => self.pattern.to_string(),
+            "sheet" => self.sheet.to_string(),
+            "piece" => self.piece.to_string(),
+            "order" => self.order_no.clone(),
+            "position" => self.position_no.clone(),
+            "customer" => self.customer.clone(),
+            "commission" => self.commission.clone(),
+            "rack" => self.rack_no.clone(),
+            "width" => format_length(self.width),
+            "height" => format_length(self.height),
+            "dimensions" => format!(
+                "{} x {}",
+                format_length(self.width),
+                format_length(self.height)
+            ),
+            "unit" => self.unit.to_string(),
+            "shape" => self.shape.clone(),
+            "material" => self.material.clone(),
+            _ => String::new(),
+        }
+    }
+
+    /// Template text with its placeholders filled in.
+    pub fn render(&self, template: &str) -> String {
+        let mut output = String::with_capacity(template.len());
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            output.push_str(&rest[..start]);
+            match rest[start..].find('}') {
+                Some(end) => {
+                    output.push_str(&self.field(&rest[start + 1..start + end]));
+                    rest = &rest[start + end + 1..];
+                }
+                None => {
+                    output.push_str(&rest[start..]);
+                    rest = "";
+                }
+            }
+        }
+        output.push_str(rest);
+        output
+    }
+}
+
+/// Labels of every physical piece of a job, pattern by pattern and sheet by sheet.
+pub fn piece_labels(schemas: &[Schema]) -> Vec<PieceLabel> {
+    let mut labels = Vec::new();
+    for (idx, schema) in schemas.iter().enumerate() {
+        for sheet in 1..=schema.quantity.max(1) {
+            let pieces = schema
+                .pieces
+                .iter()
+                .filter(|piece| !schema.is_waste_piece(piece));
+            for (number, piece) in pieces.enumerate() {
+                let piece_type = piece
+                    .piece_type_index
+                    .and_then(|i| schema.piece_types.get(i));
+                let text = |f: fn(&crate::model::PieceType) -> &String| {
+                    piece_type.map(|pt| f(pt).clone()).unwrap_or_default()
+                };
+                labels.push(PieceLabel {
+                    pattern: idx + 1,
+                    sheet,
+                    piece: number + 1,
+                    order_no: text(|pt| &pt.order_no),
+                    position_no: text(|pt| &pt.position_no),
+                    customer: text(|pt| &pt.customer),
+                    commission: text(|pt| &pt.commission),
+                    rack_no: text(|pt| &pt.rack_no),
+                    width: piece.width,
+                    height: piece.height,
+                    unit: schema.unit,
+                    shape: piece
+                        .shape_index
+                        .and_then(|i| schema.shapes.get(i))
+                        .map(|s| s.name.clone())
+                        .unwrap_or_default(),
+                    material: schema.glass_description.clone(),
+                });
+            }
+        }
+    }
+    labels
+}
+
+/// Barcode symbology of a label.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Barcode {
+    /// Linear Code 128 along the bottom of the label.
+    #[default]
+    Code128,
+    /// Square Data Matrix (ECC 200) at the right of the label.
+    DataMatrix,
+    /// No barcode.
+    None,
+}
+
+/// Layout and content of a label.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LabelTemplate {
+    /// Label width in mm.
+    pub width: f64,
+    /// Label height in mm.
+    pub height: f64,
+    /// Printer resolution in dots per inch (ZPL only).
+    pub dpi: u32,
+    /// Largest text height in mm; lines shrink to fit the label.
+    pub font_size: f64,
+    /// Text lines with `{placeholder}` fields.
+    pub lines: Vec<String>,
+    /// Barcode symbology.
+    pub barcode: Barcode,
+    /// Barcode content with `{placeholder}` fields.
+    pub barcode_data: String,
+}
+
+impl Default for LabelTemplate {
+    fn default() -> Self {
+        Self {
+            width: 100.0,
+            height: 50.0,
+            dpi: 203,
+            font_size: 5.0,
+            lines: vec![
+                "{customer}".to_string(),
+                "Order {order}  Pos. {position}".to_string(),
+                "{dimensions} {unit}  {shape}".to_string(),
+                "Rack {rack}  Pattern {pattern}/{piece}".to_string(),
+            ],
+            barcode: Barcode::Code128,
+            barcode_data: "{order}-{position}".to_string(),
+        }
+    }
+}
+
+impl LabelTemplate {
+    /// Load a template from JSON; missing fields keep their defaults.
+    pub fn from_json(json: &str) -> Result<Self> {
+        let template: LabelTemplate =
+            serde_json::from_str(json).map_err(|e| ConvertError::InvalidValue {
+                field: "label template".to_string(),
+                expected: "a JSON label template".to_string(),
+                value: e.to_string(),
+            })?;
+        template.validate()?;
+        Ok(template)
+    }
+
+    /// Check sizes and placeholder names.
+    pub fn validate(&self) -> Result<()> {
+        let invalid = |expected: &str, value: String| ConvertError::InvalidValue {
+            field: "label template".to_string(),
+            expected: expected.to_string(),
+            value,
+        };
+        if !(self.width > 0.0 && self.height > 0.0 && self.font_size > 0.0) {
+            return Err(invalid(
+                "positive width, height and font size",
+                format!(
+                    "{} x {} mm, font {}",
+                    self.width, self.height, self.font_size
+                ),
+            ));
+        }
+        if self.dpi == 0 {
+            return Err(invalid("a positive printer resolution", "0".to_string()));
+        }
+        for text in self.lines.iter().chain([&self.barcode_data]) {
+            let mut rest = text.as_str();
+            while let Some(start) = rest.find('{') {
+                let Some(end) = rest[start..].find('}') else {
+                    break;
+                };
+                let name = &rest[start + 1..start + end];
+                if !LABEL_FIELDS.contains(&name) {
+                    return Err(invalid(
+                        &format!("one of the fields {}", LABEL_FIELDS.join(", ")),
+                        format!("{{{}}}", name),
+                    ));
+                }
+                rest = &rest[start + end + 1..];
+            }
+        }
+        Ok(())
+    }
+
+    /// Boxes of the label in mm from the top-left corner.
+    fn layout(&self) -> LabelLayout {
+        let margin = (self.width.min(self.height) * 0.05).clamp(1.0, 3.0);
+        let inner_width = self.width - 2.0 * margin;
+        let inner_height = self.height - 2.0 * margin;
+
+        let (text_width, text_height, barcode) = match self.barcode {
+            Barcode::Code128 => {
+                let height = inner_height * 0.3;
+                (
+                    inner_width,
+                    inner_height - height - margin,
+                    Some(Rect {
+                        x: margin,
+                        y: self.height - margin - height,
+                        width: inner_width,
+                        height,
+                    }),
+                )
+            }
+            Barcode::DataMatrix => {
+                let side = inner_height.min(inner_width * 0.4);
+                (
+                    inner_width - side - margin,
+                    inner_height,
+                    Some(Rect {
+                        x: self.width - margin - side,
+                        y: margin,
+                        width: side,
+                        height: side,
+                    }),
+                )
+            }
+            Barcode::None => (inner_width, inner_height, None),
+        };
+
+        let count = self.lines.len().max(1) as f64;
+        let line_height = self.font_size.min(text_height / count).max(0.0);
+        LabelLayout {
+            text: Rect {
+                x: margin,
+                y: margin,
+                width: text_width.max(0.0),
+                height: text_height.max(0.0),
+            },
+            line_height,
+            barcode,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Rect {
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+}
+
+struct LabelLayout {
+    text: Rect,
+    line_height: f64,
+    barcode: Option<Rect>,
+}
+
+/// Labels as ZPL II, one `^XA`...`^XZ` format per label.
+pub fn labels_zpl(labels: &[PieceLabel], template: &LabelTemplate) -> String {
+    let layout = template.layout();
+    let dots_per_mm = template.dpi as f64 / 25.4;
+    let dots = |mm: f64| (mm * dots_per_mm).round() as i64;
+    let font = dots(layout.line_height * 0.8);
+    let mut output = String::new();
+
+    for label in labels {
+        output.push_str("^XA\n^CI28\n");
+        writeln!(
+            output,
+            "^PW{}\n^LL{}\n^LH0,0",
+            dots(template.width),
+            dots(template.height)
+        )
+        .unwrap();
+        for (idx, line) in template.lines.iter().enumerate() {
+            writeln!(
+                output,
+                "^FO{},{}^A0N,{},{}^FB{},1,0,L^FH_^FD{}^FS",
+                dots(layout.text.x),
+                dots(layout.text.y + layout.line_height * idx as f64),
+                font,
+                font,
+                dots(layout.text.width),
+                zpl_field(&label.render(line))
+            )
+            .unwrap();
+        }
+        if let Some(area) = layout.barcode {
+            let data = zpl_field(&label.render(&template.barcode_data));
+            match template.barcode {
+                Barcode::Code128 => {
+                    let modules = code128_widths(&label.render(&template.barcode_data))
+                        .iter()
+                        .map(|&w| w as f64)
+                        .sum::<f64>();
+                    let module = (dots(area.width) as f64 / modules).floor().clamp(1.0, 10.0);
+                    writeln!(
+                        output,
+                        "^FO{},{}^BY{}^BCN,{},N,N,N^FH_^FD{}^FS",
+                        dots(area.x),
+                        dots(area.y),
+                        module,
+                        dots(area.height),
+                        data
+                    )
+                    .unwrap();
+                }
+                Barcode::DataMatrix => {
+                    // Module size for a symbol of up to 24 modules
+                    let module = (dots(area.width) / 24).max(1);
+                    writeln!(
+                        output,
+                        "^FO{},{}^BXN,{},200^FH_^FD{}^FS",
+                        dots(area.x),
+                        dots(area.y),
+                        module,
+                        data
+                    )
+                    .unwrap();
+                }
+                Barcode::None => {}
+            }
+        }
+        output.push_str("^XZ\n");
+    }
+    output
+}
+
+/// Field data with the ZPL control characters hex-escaped for `^FH_`.
+fn zpl_field(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '^' => output.push_str("_5E"),
+            '~' => output.push_str("_7E"),
+            '_' => output.push_str("_5F"),
+            _ => output.push(c),
+        }
+    }
+    output
+}
+
+/// Labels arranged in a grid on portrait pages, with cut marks around each.
+pub fn labels_pdf(
+    labels: &[PieceLabel],
+    template: &LabelTemplate,
+    paper: Paper,
+) -> Result<Vec<u8>> {
+    const POINTS_PER_MM: f64 = 72.0 / 25.4;
+    const SHEET_MARGIN: f64 = 10.0;
+
+    let (page_height, page_width) = paper.size();
+    let layout = template.layout();
+    let label_width = template.width * POINTS_PER_MM;
+    let label_height = template.height * POINTS_PER_MM;
+    let columns = (((page_width - 2.0 * SHEET_MARGIN) / label_width).floor() as usize).max(1);
+    let rows = (((page_height - 2.0 * SHEET_MARGIN) / label_height).floor() as usize).max(1);
+
+    let mut pages: Vec<Page> = Vec::new();
+    for (idx, label) in labels.iter().enumerate() {
+        let slot = idx % (columns * rows);
+        if slot == 0 {
+            pages.push(Page::default());
+        }
+        let page = pages.last_mut().unwrap();
+        let left = SHEET_MARGIN + (slot % columns) as f64 * label_width;
+        let top = page_height - SHEET_MARGIN - (slot / columns) as f64 * label_height;
+        // Label coordinates are mm from the top-left corner
+        let point = |x: f64, y: f64| (left + x * POINTS_PER_MM, top - y * POINTS_PER_MM);
+
+        page.line_width(0.3);
+        page.stroke_color((0.7, 0.7, 0.7));
+        page.rect(left, top - label_height, label_width, label_height, "S");
+
+        page.fill_color(TEXT);
+        let size = layout.line_height * 0.8 * POINTS_PER_MM;
+        for (line_idx, line) in template.lines.iter().enumerate() {
+            let (x, y) = point(
+                layout.text.x,
+                layout.text.y + layout.line_height * (line_idx as f64 + 0.8),
+            );
+            let text = fit_text(
+                &label.render(line),
+                layout.text.width * POINTS_PER_MM,
+                size,
+                Font::Regular,
+            );
+            page.text(x, y, size, Font::Regular, &text, Align::Left);
+        }
+
+        let Some(area) = layout.barcode else {
+            continue;
+        };
+        let data = label.render(&template.barcode_data);
+        match template.barcode {
+            Barcode::Code128 => {
+                let widths = code128_widths(&data);
+                let modules: f64 = widths.iter().map(|&w| w as f64).sum();
+                let module = area.width * POINTS_PER_MM / modules;
+                let (mut x, y) = point(area.x, area.y + area.height);
+                for (bar_idx, &width) in widths.iter().enumerate() {
+                    let width = width as f64 * module;
+                    if bar_idx % 2 == 0 {
+                        page.rect(x, y, width, area.height * POINTS_PER_MM, "f");
+                    }
+                    x += width;
+                }
+            }
+            Barcode::DataMatrix => {
+                let modules = datamatrix(&data)?;
+                let module = area.width * POINTS_PER_MM / modules.len() as f64;
+                let (x0, y0) = point(area.x, area.y);
+                for (row, cells) in modules.iter().enumerate() {
+                    for (col, &dark) in cells.iter().enumerate() {
+                        if dark {
+                            page.rect(
+                                x0 + col as f64 * module,
+                                y0 - (row + 1) as f64 * module,
+                                module,
+                                module,
+                                "f",
+                            );
+                        }
+                    }
+                }
+            }
+            Barcode::None => {}
+        }
+    }
+    if pages.is_empty() {
+        pages.push(Page::default());
+    }
+
+    Ok(write_document(
+        &pages,
+        (page_width, page_height),
+        "Piece labels",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{Piece, PieceType, Shape};
+
+    fn create_schema() -> Schema {
+        let mut schema = Schema::new();
+        schema.width = 1000.0;
+        schema.height = 500.0;
+        schema.quantity = 2;
+        schema.glass_description = "Float 4".to_string();
+
+        let mut shape = Shape::new(3);
+        shape.name = "ARCH".to_string();
+        schema.add_shape(shape);
+        schema.add_piece_type(PieceType {
+            id: 1,
+            order_no: "4711".to_string(),
+            position_no: "12".to_string(),
+            customer: "Glas^Bau_GmbH".to_string(),
+            rack_no: "R2".to_string(),
+            ..Default::default()
+        });
+        schema.add_piece_type(PieceType {
+            id: 2,
+            waste: true,
+            ..Default::default()
+        });
+        for (x, type_idx) in [(0.0, 0), (300.0, 0), (600.0, 1)] {
+            schema.add_piece(Piece {
+                x_origin: x,
+                width: 300.0,
+                height: 250.5,
+                piece_type_index: Some(type_idx),
+                shape_index: (x == 300.0).then_some(0),
+                ..Default::default()
+            });
+        }
+        schema
+    }
+
+    #[test]
+    fn test_piece_labels_per_physical_piece() {
+        let labels = piece_labels(&[create_schema()]);
+        // Two pieces (waste skipped) on two sheets
+        assert_eq!(labels.len(), 4);
+        assert_eq!(
+            labels
+                .iter()
+                .map(|l| (l.sheet, l.piece))
+                .collect::<Vec<_>>(),
+            [(1, 1), (1, 2), (2, 1), (2, 2)]
+        );
+        assert_eq!(labels[1].shape, "ARCH");
+        assert_eq!(
+            labels[0].render("{order}/{position} {dimensions} {unit} {bogus}"),
+            "4711/12 300 x 250.5 mm "
+        );
+    }
+
+    #[test]
+    fn test_labels_zpl() {
+        let labels = piece_labels(&[create_schema()]);
+        let zpl = labels_zpl(&labels, &LabelTemplate::default());
+
+        assert_eq!(zpl.matches("^XA").count(), 4);
+        assert_eq!(zpl.matches("^XZ").count(), 4);
+        assert!(zpl.contains("^PW799\n^LL400"));
+        assert!(zpl.contains("^FDGlas_5EBau_5FGmbH^FS"));
+        assert!(zpl.contains("^BCN,") && zpl.contains("^FD4711-12^FS"));
+
+        let template = LabelTemplate {
+            barcode: Barcode::DataMatrix,
+            ..Default::default()
+        };
+        let zpl = labels_zpl(&labels, &template);
+        assert!(zpl.contains("^BXN,"));
+    }
+
+    #[test]
+    fn test_label_template_from_json() {
+        let template = LabelTemplate::from_json(
+            r#"{"width": 60, "height": 30, "lines": ["{order}"], "barcode": "datamatrix"}"#,
+        )
+        .unwrap();
+        assert_eq!(template.width, 60.0);
+        assert_eq!(template.barcode, Barcode::DataMatrix);
+        assert_eq!(template.barcode_data, "{order}-{position}");
+
+        assert!(LabelTemplate::from_json(r#"{"lines": ["{ordr}"]}"#).is_err());
+        assert!(LabelTemplate::from_json(r#"{"widht": 60}"#).is_err());
+        assert!(LabelTemplate::from_json(r#"{"width": 0}"#).is_err());
+    }
+
+    #[test]
+    fn test_labels_pdf_sheet() {
+        let labels = piece_labels(&[create_schema()]);
+        for barcode in [Barcode::Code128, Barcode::DataMatrix, Barcode::None] {
+            let template = LabelTemplate {
+                barcode,
+                ..Default::default()
+            };
+            let pdf = labels_pdf(&labels, &template, Paper::A4).unwrap();
+            let pdf = String::from_utf8(pdf).unwrap();
+            assert!(pdf.starts_with("%PDF-"));
+            // 2 columns x 5 rows of 100 x 50 mm fit on A4
+            assert_eq!(pdf.matches("/Type /Page ").count(), 1);
+            assert_eq!(pdf.matches("(Glas^Bau_GmbH) Tj").count(), 4);
+        }
+    }
+}
@@ -0,0 +1,14 @@
+//! Standalone drawings of layouts for office review and archiving, and piece labels.
+
+mod barcode;
+mod dxf;
+mod labels;
+mod pdf;
+mod svg;
+
+pub use dxf::{export_dxf, DxfLayer};
+pub use labels::{
+    labels_pdf, labels_zpl, piece_labels, Barcode, LabelTemplate, PieceLabel, LABEL_FIELDS,
+};
+pub use pdf::{export_pdf, Paper, PdfOptions};
+pub use svg::{export_svg, render_toolpath, SvgLayer, SvgOptions, SvgStyle};
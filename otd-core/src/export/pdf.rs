@@ -0,0 +1,901 @@
+//! Printable PDF cut list of a job.
+//!
+//! The report has a cover with material, sheet quantity and yield of every
+//! pattern, then one landscape page per pattern with a scaled drawing whose
+//! pieces are numbered, followed by a table of those pieces built from their
+//! `PieceType` data. Long tables continue on further pages.
+//!
+//! The file is written by hand with the standard Helvetica fonts, so no font
+//! embedding or external PDF library is needed.
+
+use crate::model::{Cut, CutType, Piece, PieceType, Schema};
+use std::f64::consts::FRAC_PI_2;
+use std::fmt::Write;
+
+/// Paper size of the report, always printed landscape.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Paper {
+    /// ISO A4.
+    #[default]
+    A4,
+    /// US Letter.
+    Letter,
+}
+
+impl Paper {
+    /// Landscape page size in points.
+    pub(super) fn size(&self) -> (f64, f64) {
+        match self {
+            Paper::A4 => (841.89, 595.28),
+            Paper::Letter => (792.0, 612.0),
+        }
+    }
+}
+
+/// Options for [`export_pdf`].
+#[derive(Debug, Clone)]
+pub struct PdfOptions {
+    /// Job title on the cover and in the page footers.
+    pub title: String,
+    /// Paper size.
+    pub paper: Paper,
+}
+
+impl Default for PdfOptions {
+    fn default() -> Self {
+        Self {
+            title: "Cut list".to_string(),
+            paper: Paper::default(),
+        }
+    }
+}
+
+/// Render the cut list of a job as a PDF document.
+pub fn export_pdf(schemas: &[Schema], options: &PdfOptions) -> Vec<u8> {
+    let mut report = Report::new(options);
+
+    write_cover(&mut report, schemas);
+    for (idx, schema) in schemas.iter().enumerate() {
+        write_pattern(&mut report, schema, idx + 1);
+    }
+    report.write_footers();
+
+    write_document(&report.pages, options.paper.size(), &options.title)
+}
+
+const MARGIN: f64 = 36.0;
+const ROW_HEIGHT: f64 = 13.0;
+const TABLE_FONT: f64 = 8.0;
+
+/// Piece of a pattern as listed in the table.
+#[derive(Debug, Clone, PartialEq)]
+struct CutListRow {
+    number: usize,
+    order_no: String,
+    position_no: String,
+    customer: String,
+    rack_no: String,
+    dimensions: String,
+    shape: String,
+}
+
+/// Pieces of a pattern that are not waste, numbered from 1 in layout order.
+fn cut_list(schema: &Schema) -> Vec<(CutListRow, &Piece)> {
+    schema
+        .pieces
+        .iter()
+        .filter(|piece| !schema.is_waste_piece(piece))
+        .enumerate()
+        .map(|(idx, piece)| {
+            let piece_type = piece
+                .piece_type_index
+                .and_then(|i| schema.piece_types.get(i));
+            let text = |f: fn(&PieceType) -> &String| {
+                piece_type.map(|pt| f(pt).clone()).unwrap_or_default()
+            };
+            let shape = piece
+                .shape_index
+                .and_then(|i| schema.shapes.get(i))
+                .map(|s| {
+                    if s.name.is_empty() {
+                        format!("#{}", s.id)
+                    } else {
+                        s.name.clone()
+                    }
+                })
+                .unwrap_or_default();
+            let row = CutListRow {
+                number: idx + 1,
+                order_no: text(|pt| &pt.order_no),
+                position_no: text(|pt| &pt.position_no),
+                customer: text(|pt| &pt.customer),
+                rack_no: text(|pt| &pt.rack_no),
+                dimensions: format!(
+                    "{} x {}",
+                    format_length(piece.width),
+                    format_length(piece.height)
+                ),
+                shape,
+            };
+            (row, piece)
+        })
+        .collect()
+}
+
+fn material(schema: &Schema) -> String {
+    match (
+        schema.glass_id.is_empty(),
+        schema.glass_description.is_empty(),
+    ) {
+        (false, false) => format!("{} {}", schema.glass_id, schema.glass_description),
+        (false, true) => schema.glass_id.clone(),
+        (true, false) => schema.glass_description.clone(),
+        (true, true) => "-".to_string(),
+    }
+}
+
+fn sheet_count(schema: &Schema) -> u32 {
+    schema.quantity.max(1)
+}
+
+/// Cover: job totals and one row per pattern.
+fn write_cover(report: &mut Report, schemas: &[Schema]) {
+    let (_, height) = report.size;
+    let title = report.title.clone();
+    let page = report.new_page();
+    let mut y = height - MARGIN - 20.0;
+    page.text(MARGIN, y, 22.0, Font::Bold, &title, Align::Left);
+    y -= 22.0;
+
+    if let Some(first) = schemas.first() {
+        let source = [first.creator.as_str(), first.date.as_str()]
+            .iter()
+            .filter(|s| !s.is_empty())
+            .copied()
+            .collect::<Vec<_>>()
+            .join(", ");
+        if !source.is_empty() {
+            page.text(MARGIN, y, 10.0, Font::Regular, &source, Align::Left);
+        }
+    }
+    y -= 30.0;
+
+    let sheets: u32 = schemas.iter().map(sheet_count).sum();
+    let pieces: usize = schemas
+        .iter()
+        .map(|s| cut_list(s).len() * sheet_count(s) as usize)
+        .sum();
+    let sheet_area: f64 = schemas
+        .iter()
+        .map(|s| s.width * s.height * sheet_count(s) as f64)
+        .sum();
+    let used_area: f64 = schemas
+        .iter()
+        .map(|s| s.yield_ratio() * s.width * s.height * sheet_count(s) as f64)
+        .sum();
+    let overall = if sheet_area > 0.0 {
+        used_area / sheet_area
+    } else {
+        0.0
+    };
+
+    for (label, value) in [
+        ("Patterns", schemas.len().to_string()),
+        ("Sheets", sheets.to_string()),
+        ("Pieces", pieces.to_string()),
+        ("Yield", format_percent(overall)),
+    ] {
+        page.text(MARGIN, y, 12.0, Font::Bold, label, Align::Left);
+        page.text(MARGIN + 90.0, y, 12.0, Font::Regular, &value, Align::Left);
+        y -= 16.0;
+    }
+    y -= 20.0;
+
+    let columns = [
+        Column::new("Pattern", 0.08, Align::Right),
+        Column::new("Material", 0.34, Align::Left),
+        Column::new("Thickness", 0.1, Align::Right),
+        Column::new("Sheet", 0.18, Align::Right),
+        Column::new("Sheets", 0.1, Align::Right),
+        Column::new("Pieces", 0.1, Align::Right),
+        Column::new("Yield", 0.1, Align::Right),
+    ];
+    let rows = schemas
+        .iter()
+        .enumerate()
+        .map(|(idx, schema)| {
+            vec![
+                (idx + 1).to_string(),
+                material(schema),
+                format_length(schema.thickness),
+                format!(
+                    "{} x {} {}",
+                    format_length(schema.width),
+                    format_length(schema.height),
+                    schema.unit
+                ),
+                sheet_count(schema).to_string(),
+                cut_list(schema).len().to_string(),
+                format_percent(schema.yield_ratio()),
+            ]
+        })
+        .collect();
+    report.table(y, &columns, rows, &format!("{} (continued)", title));
+}
+
+/// Pattern page: heading, drawing with numbered pieces and the piece table.
+fn write_pattern(report: &mut Report, schema: &Schema, number: usize) {
+    let (width, height) = report.size;
+    let list = cut_list(schema);
+    let page = report.new_page();
+
+    let heading = format!("Pattern {}", number);
+    let details = format!(
+        "{} - thickness {} - sheet {} x {} {} - {} sheet(s) - yield {}",
+        material(schema),
+        format_length(schema.thickness),
+        format_length(schema.width),
+        format_length(schema.height),
+        schema.unit,
+        sheet_count(schema),
+        format_percent(schema.yield_ratio()),
+    );
+    let mut y = height - MARGIN - 14.0;
+    page.text(MARGIN, y, 16.0, Font::Bold, &heading, Align::Left);
+    y -= 16.0;
+    page.text(MARGIN, y, 10.0, Font::Regular, &details, Align::Left);
+    y -= 12.0;
+
+    // Drawing in the upper part of the page, the table below it
+    let area_top = y;
+    let area_bottom = height * 0.42;
+    let area_width = width - 2.0 * MARGIN;
+    let area_height = area_top - area_bottom;
+    if schema.width > 0.0 && schema.height > 0.0 && area_height > 0.0 {
+        let scale = (area_width / schema.width).min(area_height / schema.height);
+        let origin = (
+            MARGIN + (area_width - schema.width * scale) / 2.0,
+            area_bottom + (area_height - schema.height * scale) / 2.0,
+        );
+        draw_pattern(page, schema, &list, origin, scale);
+    }
+
+    let columns = [
+        Column::new("No.", 0.06, Align::Right),
+        Column::new("Order", 0.14, Align::Left),
+        Column::new("Position", 0.1, Align::Left),
+        Column::new("Customer", 0.26, Align::Left),
+        Column::new("Rack", 0.1, Align::Left),
+        Column::new("Dimensions", 0.16, Align::Right),
+        Column::new("Shape", 0.18, Align::Left),
+    ];
+    let rows = list
+        .iter()
+        .map(|(row, _)| {
+            vec![
+                row.number.to_string(),
+                row.order_no.clone(),
+                row.position_no.clone(),
+                row.customer.clone(),
+                row.rack_no.clone(),
+                row.dimensions.clone(),
+                row.shape.clone(),
+            ]
+        })
+        .collect();
+    report.table(
+        area_bottom - 18.0,
+        &columns,
+        rows,
+        &format!("{} (continued)", heading),
+    );
+}
+
+/// Scaled drawing of the sheet with the cut list numbers on the pieces.
+fn draw_pattern(
+    page: &mut Page,
+    schema: &Schema,
+    list: &[(CutListRow, &Piece)],
+    origin: (f64, f64),
+    scale: f64,
+) {
+    let map = |x: f64, y: f64| (origin.0 + x * scale, origin.1 + y * scale);
+    let (ox, oy) = origin;
+
+    page.line_width(0.8);
+    page.fill_color(GLASS);
+    page.stroke_color(OUTLINE);
+    page.rect(ox, oy, schema.width * scale, schema.height * scale, "B");
+
+    page.fill_color(TRIM);
+    if schema.trim_left > 0.0 {
+        page.rect(ox, oy, schema.trim_left * scale, schema.height * scale, "f");
+    }
+    if schema.trim_bottom > 0.0 {
+        page.rect(
+            ox,
+            oy,
+            schema.width * scale,
+            schema.trim_bottom * scale,
+            "f",
+        );
+    }
+    page.fill_color(WASTE);
+    for (x1, y1, x2, y2) in schema.waste_regions() {
+        let (x, y) = map(x1, y1);
+        page.rect(x, y, (x2 - x1) * scale, (y2 - y1) * scale, "f");
+    }
+    for piece in schema.pieces.iter().filter(|p| schema.is_waste_piece(p)) {
+        let (x, y) = map(piece.x_origin, piece.y_origin);
+        page.rect(x, y, piece.width * scale, piece.height * scale, "f");
+    }
+
+    page.line_width(0.4);
+    page.stroke_color(PIECE);
+    for piece in &schema.pieces {
+        let (x, y) = map(piece.x_origin, piece.y_origin);
+        page.rect(x, y, piece.width * scale, piece.height * scale, "S");
+    }
+
+    page.stroke_color(LINEAR);
+    for cut in schema.linear_cuts.iter().filter(|c| c.active) {
+        page.cut_path(std::slice::from_ref(cut), origin, scale);
+    }
+
+    page.line_width(0.8);
+    page.stroke_color(SHAPE);
+    for piece in &schema.pieces {
+        if let Some(shape) = piece.shape_index.and_then(|idx| schema.shapes.get(idx)) {
+            let cuts: Vec<Cut> = shape.cuts.iter().filter(|c| c.active).cloned().collect();
+            page.cut_path(&cuts, map(piece.x_origin, piece.y_origin), scale);
+        }
+    }
+
+    page.fill_color(TEXT);
+    for (row, piece) in list {
+        let (x, y) = map(
+            piece.x_origin + piece.width / 2.0,
+            piece.y_origin + piece.height / 2.0,
+        );
+        let size = (piece.width.min(piece.height) * scale * 0.4).clamp(4.0, 12.0);
+        page.text(
+            x,
+            y - size * 0.35,
+            size,
+            Font::Bold,
+            &row.number.to_string(),
+            Align::Center,
+        );
+    }
+}
+
+pub(super) type Rgb = (f64, f64, f64);
+
+const GLASS: Rgb = (0.89, 0.93, 0.94);
+const OUTLINE: Rgb = (0.27, 0.35, 0.37);
+const TRIM: Rgb = (0.77, 0.8, 0.82);
+const WASTE: Rgb = (0.95, 0.86, 0.86);
+const PIECE: Rgb = (0.45, 0.5, 0.55);
+const LINEAR: Rgb = (0.63, 0.32, 0.18);
+const SHAPE: Rgb = (0.18, 0.55, 0.45);
+pub(super) const TEXT: Rgb = (0.1, 0.1, 0.1);
+const RULE: Rgb = (0.75, 0.75, 0.75);
+
+/// Table column: title, share of the printable width and alignment.
+struct Column {
+    title: &'static str,
+    width: f64,
+    align: Align,
+}
+
+impl Column {
+    fn new(title: &'static str, width: f64, align: Align) -> Self {
+        Self {
+            title,
+            width,
+            align,
+        }
+    }
+}
+
+/// Pages of the report being laid out.
+struct Report {
+    size: (f64, f64),
+    title: String,
+    pages: Vec<Page>,
+}
+
+impl Report {
+    fn new(options: &PdfOptions) -> Self {
+        Self {
+            size: options.paper.size(),
+            title: options.title.clone(),
+            pages: Vec::new(),
+        }
+    }
+
+    fn new_page(&mut self) -> &mut Page {
+        self.pages.push(Page::default());
+        self.pages.last_mut().unwrap()
+    }
+
+    /// Table from `top` down on the last page, continuing on new pages.
+    fn table(&mut self, top: f64, columns: &[Column], rows: Vec<Vec<String>>, continued: &str) {
+        let (width, height) = self.size;
+        let usable = width - 2.0 * MARGIN;
+        let mut y = top;
+        self.table_header(y, columns);
+
+        for row in rows {
+            y -= ROW_HEIGHT;
+            if y < MARGIN + ROW_HEIGHT {
+                let page = self.new_page();
+                y = height - MARGIN - 14.0;
+                page.fill_color(TEXT);
+                page.text(MARGIN, y, 12.0, Font::Bold, continued, Align::Left);
+                y -= 24.0;
+                self.table_header(y, columns);
+                y -= ROW_HEIGHT;
+            }
+
+            let page = self.pages.last_mut().unwrap();
+            page.fill_color(TEXT);
+            let mut x = MARGIN;
+            for (column, value) in columns.iter().zip(&row) {
+                let cell = usable * column.width;
+                page.cell(x, y, cell, value, Font::Regular, column.align);
+                x += cell;
+            }
+            page.line_width(0.3);
+            page.stroke_color(RULE);
+            page.line((MARGIN, y - 3.0), (MARGIN + usable, y - 3.0));
+        }
+    }
+
+    fn table_header(&mut self, y: f64, columns: &[Column]) {
+        let usable = self.size.0 - 2.0 * MARGIN;
+        let page = self.pages.last_mut().unwrap();
+        page.fill_color(TEXT);
+        let mut x = MARGIN;
+        for column in columns {
+            let cell = usable * column.width;
+            page.cell(x, y, cell, column.title, Font::Bold, column.align);
+            x += cell;
+        }
+        page.line_width(0.8);
+        page.stroke_color(OUTLINE);
+        page.line((MARGIN, y - 4.0), (MARGIN + usable, y - 4.0));
+    }
+
+    /// Title and page number at the bottom of every page.
+    fn write_footers(&mut self) {
+        let (width, _) = self.size;
+        let count = self.pages.len();
+        for (idx, page) in self.pages.iter_mut().enumerate() {
+            page.fill_color(TEXT);
+            let y = MARGIN / 2.0;
+            page.text(MARGIN, y, 8.0, Font::Regular, &self.title, Align::Left);
+            let number = format!("Page {} of {}", idx + 1, count);
+            page.text(width - MARGIN, y, 8.0, Font::Regular, &number, Align::Right);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Font {
+    Regular,
+    Bold,
+}
+
+impl Font {
+    fn resource(&self) -> &'static str {
+        match self {
+            Font::Regular => "F1",
+            Font::Bold => "F2",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+/// Content stream of one page, in points from the bottom-left corner.
+#[derive(Default)]
+pub(super) struct Page {
+    content: String,
+}
+
+impl Page {
+    pub(super) fn line_width(&mut self, width: f64) {
+        writeln!(self.content, "{} w", num(width)).unwrap();
+    }
+
+    pub(super) fn stroke_color(&mut self, (r, g, b): Rgb) {
+        writeln!(self.content, "{} {} {} RG", num(r), num(g), num(b)).unwrap();
+    }
+
+    pub(super) fn fill_color(&mut self, (r, g, b): Rgb) {
+        writeln!(self.content, "{} {} {} rg", num(r), num(g), num(b)).unwrap();
+    }
+
+    pub(super) fn line(&mut self, from: (f64, f64), to: (f64, f64)) {
+        writeln!(
+            self.content,
+            "{} {} m {} {} l S",
+            num(from.0),
+            num(from.1),
+            num(to.0),
+            num(to.1)
+        )
+        .unwrap();
+    }
+
+    /// Rectangle painted with `op`: `S` stroke, `f` fill, `B` both.
+    pub(super) fn rect(&mut self, x: f64, y: f64, width: f64, height: f64, op: &str) {
+        writeln!(
+            self.content,
+            "{} {} {} {} re {}",
+            num(x),
+            num(y),
+            num(width),
+            num(height),
+            op
+        )
+        .unwrap();
+    }
+
+    /// Stroke connected cuts, with arcs as cubic Bézier curves.
+    fn cut_path(&mut self, cuts: &[Cut], origin: (f64, f64), scale: f64) {
+        let map = |x: f64, y: f64| (origin.0 + x * scale, origin.1 + y * scale);
+        let mut pen: Option<(f64, f64)> = None;
+        for cut in cuts {
+            let start = map(cut.xi, cut.yi);
+            let end = map(cut.xf, cut.yf);
+            let connected = pen.is_some_and(|(x, y)| (x - start.0).hypot(y - start.1) < 1e-6);
+            if !connected {
+                writeln!(self.content, "{} {} m", num(start.0), num(start.1)).unwrap();
+            }
+            match cut.cut_type {
+                CutType::Line => writeln!(self.content, "{} {} l", num(end.0), num(end.1)).unwrap(),
+                CutType::ArcCW | CutType::ArcCCW => {
+                    let center = map(cut.xc, cut.yc);
+                    let radius = cut.radius * scale;
+                    let start_angle = (cut.yi - cut.yc).atan2(cut.xi - cut.xc);
+                    self.arc(center, radius, start_angle, cut.arc_angle());
+                }
+            }
+            pen = Some(end);
+        }
+        if pen.is_some() {
+            self.content.push_str("S\n");
+        }
+    }
+
+    /// Arc as Bézier segments of at most 90°.
+    fn arc(&mut self, center: (f64, f64), radius: f64, start: f64, sweep: f64) {
+        let segments = (sweep.abs() / FRAC_PI_2).ceil().max(1.0) as usize;
+        let step = sweep / segments as f64;
+        let k = 4.0 / 3.0 * (step / 4.0).tan() * radius;
+        for idx in 0..segments {
+            let a = start + step * idx as f64;
+            let b = a + step;
+            let p0 = (center.0 + radius * a.cos(), center.1 + radius * a.sin());
+            let p3 = (center.0 + radius * b.cos(), center.1 + radius * b.sin());
+            let p1 = (p0.0 - k * a.sin(), p0.1 + k * a.cos());
+            let p2 = (p3.0 + k * b.sin(), p3.1 - k * b.cos());
+            writeln!(
+                self.content,
+                "{} {} {} {} {} {} c",
+                num(p1.0),
+                num(p1.1),
+                num(p2.0),
+                num(p2.1),
+                num(p3.0),
+                num(p3.1)
+            )
+            .unwrap();
+        }
+    }
+
+    pub(super) fn text(&mut self, x: f64, y: f64, size: f64, font: Font, text: &str, align: Align) {
+        let width = text_width(text, size, font);
+        let x = match align {
+            Align::Left => x,
+            Align::Center => x - width / 2.0,
+            Align::Right => x - width,
+        };
+        writeln!(
+            self.content,
+            "BT /{} {} Tf {} {} Td ({}) Tj ET",
+            font.resource(),
+            num(size),
+            num(x),
+            num(y),
+            pdf_string(text)
+        )
+        .unwrap();
+    }
+
+    /// Table cell text, shortened with an ellipsis to fit its column.
+    fn cell(&mut self, x: f64, y: f64, width: f64, text: &str, font: Font, align: Align) {
+        let padding = 4.0;
+        let available = width - 2.0 * padding;
+        let text = fit_text(text, available, TABLE_FONT, font);
+        let x = match align {
+            Align::Left => x + padding,
+            Align::Center => x + width / 2.0,
+            Align::Right => x + width - padding,
+        };
+        self.text(x, y, TABLE_FONT, font, &text, align);
+    }
+}
+
+/// Assemble catalog, fonts, pages and cross-reference table.
+pub(super) fn write_document(pages: &[Page], size: (f64, f64), title: &str) -> Vec<u8> {
+    let (width, height) = size;
+    let mut objects: Vec<String> = vec![
+        "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+        format!(
+            "<< /Type /Pages /Kids [{}] /Count {} >>",
+            (0..pages.len())
+                .map(|idx| format!("{} 0 R", 5 + 2 * idx))
+                .collect::<Vec<_>>()
+                .join(" "),
+            pages.len()
+        ),
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>"
+            .to_string(),
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica-Bold /Encoding /WinAnsiEncoding >>"
+            .to_string(),
+    ];
+    for (idx, page) in pages.iter().enumerate() {
+        objects.push(format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] \
+             /Resources << /Font << /F1 3 0 R /F2 4 0 R >> >> /Contents {} 0 R >>",
+            num(width),
+            num(height),
+            6 + 2 * idx
+        ));
+        objects.push(format!(
+            "<< /Length {} >>\nstream\n{}endstream",
+            page.content.len(),
+            page.content
+        ));
+    }
+    objects.push(format!(
+        "<< /Title ({}) /Producer (otd-convert) >>",
+        pdf_string(title)
+    ));
+
+    let mut output = String::from("%PDF-1.4\n");
+    let mut offsets = Vec::with_capacity(objects.len());
+    for (idx, object) in objects.iter().enumerate() {
+        offsets.push(output.len());
+        writeln!(output, "{} 0 obj\n{}\nendobj", idx + 1, object).unwrap();
+    }
+
+    let xref = output.len();
+    writeln!(output, "xref\n0 {}", objects.len() + 1).unwrap();
+    output.push_str("0000000000 65535 f \n");
+    for offset in offsets {
+        writeln!(output, "{:010} 00000 n ", offset).unwrap();
+    }
+    writeln!(
+        output,
+        "trailer\n<< /Size {} /Root 1 0 R /Info {} 0 R >>\nstartxref\n{}\n%%EOF",
+        objects.len() + 1,
+        objects.len(),
+        xref
+    )
+    .unwrap();
+
+    output.into_bytes()
+}
+
+/// Text as the body of a PDF literal string in WinAnsi encoding.
+///
+/// Latin-1 characters become octal escapes; anything else is replaced by `?`.
+fn pdf_string(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '(' | ')' | '\\' => {
+                output.push('\\');
+                output.push(c);
+            }
+            ' '..='~' => output.push(c),
+            '\u{a0}'..='\u{ff}' => write!(output, "\\{:03o}", c as u32).unwrap(),
+            _ => output.push('?'),
+        }
+    }
+    output
+}
+
+/// Helvetica advance widths of the printable ASCII characters, per 1000 em.
+const HELVETICA_WIDTHS: [u16; 95] = [
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278, 556, 556, 556,
+    556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556, 1015, 667, 667, 722, 722, 667,
+    611, 778, 722, 278, 500, 667, 556, 833, 722, 778, 667, 778, 722, 667, 611, 722, 667, 944, 667,
+    667, 611, 278, 278, 278, 469, 556, 333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500,
+    222, 833, 556, 556, 556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584,
+];
+
+/// Approximate text width in points; bold text runs about 5% wider.
+fn text_width(text: &str, size: f64, font: Font) -> f64 {
+    let units: f64 = text
+        .chars()
+        .map(|c| match c {
+            ' '..='~' => HELVETICA_WIDTHS[c as usize - 32] as f64,
+            _ => 556.0,
+        })
+        .sum();
+    let factor = if font == Font::Bold { 1.05 } else { 1.0 };
+    units * size / 1000.0 * factor
+}
+
+/// Shorten text with "..." until it fits the width.
+pub(super) fn fit_text(text: &str, width: f64, size: f64, font: Font) -> String {
+    if text_width(text, size, font) <= width {
+        return text.to_string();
+    }
+    let mut chars: Vec<char> = text.chars().collect();
+    while !chars.is_empty() {
+        chars.pop();
+        let candidate: String = chars.iter().collect::<String>() + "...";
+        if text_width(&candidate, size, font) <= width {
+            return candidate;
+        }
+    }
+    String::new()
+}
+
+/// Length with up to three decimals.
+pub(super) fn format_length(value: f64) -> String {
+    let text = format!("{:.3}", value);
+    text.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+fn format_percent(ratio: f64) -> String {
+    format!("{:.1}%", ratio * 100.0)
+}
+
+/// Number for content streams, rounded to 1/1000 of a point.
+fn num(value: f64) -> String {
+    let text = format!("{:.3}", value);
+    let text = text.trim_end_matches('0').trim_end_matches('.');
+    if text == "-0" {
+        "0".to_string()
+    } else {
+        text.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::Shape;
+
+    fn create_schema(pieces: usize) -> Schema {
+        let mut schema = Schema::new();
+        schema.width = 1000.0;
+        schema.height = 500.0;
+        schema.quantity = 3;
+        schema.glass_id = "F4".to_string();
+        schema.glass_description = "Float 4mm".to_string();
+        schema.thickness = 4.0;
+
+        let mut shape = Shape::new(7);
+        shape.name = "ARCH".to_string();
+        shape.add_cut(Cut::new_line(0.0, 0.0, 100.0, 0.0));
+        shape.add_cut(Cut::new_arc_ccw(100.0, 0.0, 0.0, 0.0, 50.0));
+        schema.add_shape(shape);
+
+        schema.add_piece_type(PieceType {
+            id: 1,
+            order_no: "A-17".to_string(),
+            position_no: "3".to_string(),
+            customer: "Müller (Bau)".to_string(),
+            rack_no: "R5".to_string(),
+            ..Default::default()
+        });
+        schema.add_piece_type(PieceType {
+            id: 2,
+            waste: true,
+            ..Default::default()
+        });
+        for idx in 0..pieces {
+            schema.add_piece(Piece {
+                x_origin: (idx % 10) as f64 * 100.0,
+                y_origin: (idx / 10 % 5) as f64 * 100.0,
+                width: 100.0,
+                height: 50.0,
+                info_id: Some(1),
+                piece_type_index: Some(0),
+                shape_index: (idx == 0).then_some(0),
+                ..Default::default()
+            });
+        }
+        schema.add_piece(Piece {
+            x_origin: 900.0,
+            y_origin: 450.0,
+            width: 100.0,
+            height: 50.0,
+            info_id: Some(2),
+            piece_type_index: Some(1),
+            ..Default::default()
+        });
+        schema
+    }
+
+    fn page_count(pdf: &str) -> usize {
+        pdf.matches("/Type /Page ").count()
+    }
+
+    #[test]
+    fn test_export_pdf_structure() {
+        let pdf = export_pdf(&[create_schema(2)], &PdfOptions::default());
+        let pdf = String::from_utf8(pdf).unwrap();
+
+        assert!(pdf.starts_with("%PDF-1.4\n"));
+        assert!(pdf.ends_with("%%EOF\n"));
+        assert_eq!(page_count(&pdf), 2);
+
+        // Every cross-reference entry points at its object
+        let xref = pdf.rfind("\nxref\n").unwrap() + 1;
+        let entries: Vec<usize> = pdf[xref..]
+            .lines()
+            .skip(3)
+            .take_while(|line| line.ends_with(" n "))
+            .map(|line| line[..10].parse().unwrap())
+            .collect();
+        for (idx, offset) in entries.iter().enumerate() {
+            assert!(pdf[*offset..].starts_with(&format!("{} 0 obj", idx + 1)));
+        }
+        let start: usize = pdf.lines().rev().nth(1).unwrap().parse().unwrap();
+        assert_eq!(start, xref);
+    }
+
+    #[test]
+    fn test_export_pdf_cut_list() {
+        let schema = create_schema(2);
+        let list = cut_list(&schema);
+        assert_eq!(list.len(), 2, "waste pieces are not listed");
+        assert_eq!(
+            list[0].0,
+            CutListRow {
+                number: 1,
+                order_no: "A-17".to_string(),
+                position_no: "3".to_string(),
+                customer: "Müller (Bau)".to_string(),
+                rack_no: "R5".to_string(),
+                dimensions: "100 x 50".to_string(),
+                shape: "ARCH".to_string(),
+            }
+        );
+        assert_eq!(list[1].0.shape, "");
+
+        let pdf = String::from_utf8(export_pdf(&[schema], &PdfOptions::default())).unwrap();
+        assert!(pdf.contains("(M\\374ller \\(Bau\\)) Tj"));
+        assert!(pdf.contains("(A-17) Tj"));
+        assert!(pdf.contains("(Pattern 1) Tj"));
+        // Yield of the cover: two 100 x 50 pieces on a 1000 x 500 sheet
+        assert!(pdf.contains("(2.0%) Tj"));
+        // Arcs of the shape are curves
+        assert!(pdf.contains(" c\n"));
+    }
+
+    #[test]
+    fn test_export_pdf_long_table_continues() {
+        let pdf = export_pdf(&[create_schema(50)], &PdfOptions::default());
+        let pdf = String::from_utf8(pdf).unwrap();
+
+        assert!(page_count(&pdf) > 2);
+        assert!(pdf.contains("(Pattern 1 \\(continued\\)) Tj"));
+        let last = page_count(&pdf);
+        assert!(pdf.contains(&format!("(Page {0} of {0}) Tj", last)));
+    }
+}
@@ -0,0 +1,623 @@
+//! SVG drawing of a pattern for web previews.
+//!
+//! Renders without a GUI toolkit, so services can embed layout previews.
+//! Coordinates stay in layout units (the `viewBox` spans the sheet plus a
+//! margin) with the Y axis flipped so the sheet origin is at the bottom left
+//! like on the table. Arcs are written as true SVG arcs, and every layer is a
+//! `<g>` element whose visibility and style come from [`SvgOptions`].
+//!
+//! [`render_toolpath`] draws what a program scores instead, from its
+//! simulated toolpath, for programs without a layout.
+
+use super::dxf::label_height;
+use crate::analysis::{Motion, PatternToolpath, Segment};
+use crate::generator::{piece_texts, DxfColors, ViewMode};
+use crate::model::{Cut, CutType, Piece, Schema};
+use std::f64::consts::PI;
+use std::fmt::Write;
+
+/// Layers of an SVG drawing, in drawing order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SvgLayer {
+    /// Sheet outline and fill.
+    Sheet,
+    /// Trim zones along the left and bottom edges.
+    Trims,
+    /// Hatched waste regions and scrap pieces.
+    Waste,
+    /// Piece rectangles.
+    Pieces,
+    /// Linear cuts.
+    Linear,
+    /// Shape contours.
+    Shapes,
+    /// Low-E coating removal.
+    LowE,
+    /// Piece labels.
+    Labels,
+}
+
+impl SvgLayer {
+    /// All layers, in drawing order.
+    pub const ALL: [SvgLayer; 8] = [
+        SvgLayer::Sheet,
+        SvgLayer::Trims,
+        SvgLayer::Waste,
+        SvgLayer::Pieces,
+        SvgLayer::Linear,
+        SvgLayer::Shapes,
+        SvgLayer::LowE,
+        SvgLayer::Labels,
+    ];
+
+    /// Layer name, used as the `id` of its group.
+    pub fn name(&self) -> &'static str {
+        match self {
+            SvgLayer::Sheet => "sheet",
+            SvgLayer::Trims => "trims",
+            SvgLayer::Waste => "waste",
+            SvgLayer::Pieces => "pieces",
+            SvgLayer::Linear => "linear",
+            SvgLayer::Shapes => "shapes",
+            SvgLayer::LowE => "lowe",
+            SvgLayer::Labels => "labels",
+        }
+    }
+
+    fn default_style(&self) -> SvgStyle {
+        let (stroke, fill, stroke_width) = match self {
+            SvgLayer::Sheet => ("#46595f", "#e3eeef", 1.5),
+            SvgLayer::Trims => ("none", "#c4ccd1", 0.0),
+            SvgLayer::Waste => ("#b45050", "#f3dcdc", 0.5),
+            SvgLayer::Pieces => ("#8c969f", "none", 0.75),
+            SvgLayer::Linear => ("#a0522d", "none", 1.0),
+            SvgLayer::Shapes => ("#2e8b74", "#2e8b7426", 1.25),
+            SvgLayer::LowE => ("#3c8c3c", "none", 1.0),
+            SvgLayer::Labels => ("none", "#202428", 0.0),
+        };
+        SvgStyle {
+            visible: true,
+            stroke: stroke.to_string(),
+            fill: fill.to_string(),
+            stroke_width,
+        }
+    }
+}
+
+/// Style of one layer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SvgStyle {
+    /// Whether the layer is drawn at all.
+    pub visible: bool,
+    /// Stroke color (any SVG paint, `none` for no outline).
+    pub stroke: String,
+    /// Fill color (any SVG paint, `none` for no fill).
+    pub fill: String,
+    /// Stroke width in pixels of the rendered image.
+    pub stroke_width: f64,
+}
+
+/// Options for [`export_svg`].
+#[derive(Debug, Clone)]
+pub struct SvgOptions {
+    /// Image width in pixels; the height follows the sheet aspect ratio.
+    pub width: f64,
+    /// Margin around the sheet, in layout units.
+    pub margin: f64,
+    styles: Vec<SvgStyle>,
+}
+
+impl Default for SvgOptions {
+    fn default() -> Self {
+        Self {
+            width: 1200.0,
+            margin: 0.0,
+            styles: SvgLayer::ALL.iter().map(SvgLayer::default_style).collect(),
+        }
+    }
+}
+
+impl SvgOptions {
+    /// Style of a layer.
+    pub fn style(&self, layer: SvgLayer) -> &SvgStyle {
+        &self.styles[layer as usize]
+    }
+
+    /// Mutable style of a layer.
+    pub fn style_mut(&mut self, layer: SvgLayer) -> &mut SvgStyle {
+        &mut self.styles[layer as usize]
+    }
+
+    /// Show or hide a layer.
+    pub fn with_layer(mut self, layer: SvgLayer, visible: bool) -> Self {
+        self.style_mut(layer).visible = visible;
+        self
+    }
+
+    /// Replace the style of a layer.
+    pub fn with_style(mut self, layer: SvgLayer, style: SvgStyle) -> Self {
+        self.styles[layer as usize] = style;
+        self
+    }
+}
+
+/// Render a pattern as an SVG document.
+pub fn export_svg(schema: &Schema, options: &SvgOptions) -> String {
+    let (mut svg, pixel) = Svg::document(schema.width, schema.height, options);
+    let waste = options.style(SvgLayer::Waste);
+    if waste.visible {
+        write_hatch_pattern(
+            &mut svg,
+            waste,
+            schema.width.max(schema.height) / 100.0,
+            pixel,
+        );
+    }
+
+    for layer in SvgLayer::ALL {
+        let style = options.style(layer);
+        if !style.visible {
+            continue;
+        }
+        svg.group(layer.name(), style, pixel, "");
+        match layer {
+            SvgLayer::Sheet => svg.rectangle((0.0, 0.0), (schema.width, schema.height), None),
+            SvgLayer::Trims => write_trims(&mut svg, schema),
+            SvgLayer::Waste => write_waste(&mut svg, schema),
+            SvgLayer::Pieces => {
+                for piece in &schema.pieces {
+                    svg.piece(piece, None);
+                }
+            }
+            SvgLayer::Linear => {
+                for cut in schema.linear_cuts.iter().filter(|c| c.active) {
+                    svg.path(std::slice::from_ref(cut), (0.0, 0.0), false);
+                }
+            }
+            SvgLayer::Shapes => write_shapes(&mut svg, schema),
+            SvgLayer::LowE => {
+                for cut in &schema.lowe_cuts {
+                    svg.path(std::slice::from_ref(cut), (0.0, 0.0), false);
+                }
+                for piece in &schema.lowe_pieces {
+                    svg.piece(piece, None);
+                }
+            }
+            SvgLayer::Labels => write_labels(&mut svg, schema),
+        }
+        svg.output.push_str("</g>\n");
+    }
+
+    svg.output.push_str("</svg>\n");
+    svg.output
+}
+
+/// Render the simulated toolpath of a pattern on a `width` x `height` sheet.
+///
+/// Strokes use the style of the [`SvgLayer::Linear`] layer, strokes made in
+/// shape subroutines the [`SvgLayer::Shapes`] one, and rapid traverses are
+/// dashed in the [`SvgLayer::Pieces`] style. Other layers have nothing to
+/// draw without a layout, except the sheet outline.
+pub fn render_toolpath(
+    pattern: &PatternToolpath,
+    width: f64,
+    height: f64,
+    options: &SvgOptions,
+) -> String {
+    let (mut svg, pixel) = Svg::document(width, height, options);
+
+    let sheet = options.style(SvgLayer::Sheet);
+    if sheet.visible {
+        svg.group(SvgLayer::Sheet.name(), sheet, pixel, "");
+        svg.rectangle((0.0, 0.0), (width, height), None);
+        svg.output.push_str("</g>\n");
+    }
+
+    let rapids = options.style(SvgLayer::Pieces);
+    if rapids.visible {
+        let dash = format!(
+            r#" stroke-dasharray="{0} {0}""#,
+            fmt(4.0 * rapids.stroke_width * pixel)
+        );
+        svg.group("rapids", rapids, pixel, &dash);
+        for motion in &pattern.motions {
+            if let Motion::Rapid { from, to } = motion {
+                svg.path(
+                    &[Cut::new_line(from.0, from.1, to.0, to.1)],
+                    (0.0, 0.0),
+                    false,
+                );
+            }
+        }
+        svg.output.push_str("</g>\n");
+    }
+
+    for (layer, shaped) in [(SvgLayer::Linear, false), (SvgLayer::Shapes, true)] {
+        let style = options.style(layer);
+        if !style.visible {
+            continue;
+        }
+        svg.group(layer.name(), style, pixel, r#" fill-opacity="0""#);
+        for cut in pattern
+            .cuts
+            .iter()
+            .filter(|c| c.shape_macro.is_some() == shaped)
+        {
+            let cuts: Vec<Cut> = cut.segments.iter().map(segment_cut).collect();
+            svg.path(&cuts, (0.0, 0.0), false);
+        }
+        svg.output.push_str("</g>\n");
+    }
+
+    svg.output.push_str("</svg>\n");
+    svg.output
+}
+
+/// Layout cut drawing a toolpath segment.
+fn segment_cut(segment: &Segment) -> Cut {
+    match *segment {
+        Segment::Line { start, end } => Cut::new_line(start.0, start.1, end.0, end.1),
+        Segment::Arc {
+            start,
+            end,
+            center,
+            clockwise,
+        } => {
+            let new_arc = if clockwise {
+                Cut::new_arc_cw
+            } else {
+                Cut::new_arc_ccw
+            };
+            let mut cut = new_arc(start.0, start.1, end.0, end.1, segment.radius());
+            (cut.xc, cut.yc) = center;
+            cut
+        }
+    }
+}
+
+/// Diagonal hatch on the waste fill, spaced in layout units.
+fn write_hatch_pattern(svg: &mut Svg, style: &SvgStyle, spacing: f64, pixel: f64) {
+    let size = fmt(spacing);
+    writeln!(
+        svg.output,
+        concat!(
+            r#"<defs><pattern id="waste-hatch" patternUnits="userSpaceOnUse" "#,
+            r#"width="{0}" height="{0}" patternTransform="rotate(45)">"#,
+            r#"<rect width="{0}" height="{0}" fill="{1}"/>"#,
+            r#"<line x1="0" y1="0" x2="0" y2="{0}" stroke="{2}" stroke-width="{3}"/>"#,
+            "</pattern></defs>"
+        ),
+        size,
+        escape(&style.fill),
+        escape(&style.stroke),
+        fmt(style.stroke_width * pixel),
+    )
+    .unwrap();
+}
+
+fn write_trims(svg: &mut Svg, schema: &Schema) {
+    if schema.trim_left > 0.0 {
+        svg.rectangle((0.0, 0.0), (schema.trim_left, schema.height), None);
+    }
+    if schema.trim_bottom > 0.0 {
+        svg.rectangle((0.0, 0.0), (schema.width, schema.trim_bottom), None);
+    }
+}
+
+/// Uncovered sheet areas and pieces marked as waste, hatched.
+fn write_waste(svg: &mut Svg, schema: &Schema) {
+    let hatch = Some("url(#waste-hatch)");
+    for (x1, y1, x2, y2) in schema.waste_regions() {
+        svg.rectangle((x1, y1), (x2 - x1, y2 - y1), hatch);
+    }
+    for piece in schema.pieces.iter().filter(|p| schema.is_waste_piece(p)) {
+        svg.piece(piece, hatch);
+    }
+}
+
+/// Contour of every shaped piece, translated to the piece origin.
+fn write_shapes(svg: &mut Svg, schema: &Schema) {
+    for piece in &schema.pieces {
+        let Some(shape) = piece.shape_index.and_then(|idx| schema.shapes.get(idx)) else {
+            continue;
+        };
+        let cuts: Vec<Cut> = shape.cuts.iter().filter(|c| c.active).cloned().collect();
+        svg.path(
+            &cuts,
+            (piece.x_origin, piece.y_origin),
+            shape.is_closed() && cuts.len() == shape.cuts.len(),
+        );
+    }
+}
+
+/// Labels of every piece with piece data, as in the CNI preview.
+fn write_labels(svg: &mut Svg, schema: &Schema) {
+    let colors = DxfColors::default();
+    for piece in schema.pieces.iter().filter(|p| p.info_id.is_some()) {
+        let height = label_height(schema, piece);
+        for text in piece_texts(schema, piece, &colors, &ViewMode::Normal) {
+            svg.text(text.x, text.y, height, &text.text);
+        }
+    }
+}
+
+/// SVG element writer in layout coordinates.
+struct Svg {
+    output: String,
+    /// Sheet height, to flip the Y axis.
+    height: f64,
+}
+
+impl Svg {
+    /// Document spanning a `width` x `height` sheet plus the margin, with the
+    /// size of an image pixel in layout units.
+    fn document(width: f64, height: f64, options: &SvgOptions) -> (Svg, f64) {
+        let margin = options.margin.max(0.0);
+        let view_width = width + 2.0 * margin;
+        let view_height = height + 2.0 * margin;
+        let mut svg = Svg {
+            output: String::new(),
+            height,
+        };
+
+        writeln!(
+            svg.output,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" viewBox="{} {} {} {}">"#,
+            fmt(options.width),
+            fmt(options.width * view_height / view_width.max(f64::EPSILON)),
+            fmt(-margin),
+            fmt(-margin),
+            fmt(view_width),
+            fmt(view_height),
+        )
+        .unwrap();
+
+        // Stroke widths are given in pixels of the rendered image
+        (svg, view_width / options.width.max(1.0))
+    }
+
+    /// Open a layer group; `extra` holds further attributes.
+    fn group(&mut self, id: &str, style: &SvgStyle, pixel: f64, extra: &str) {
+        writeln!(
+            self.output,
+            r#"<g id="{}" stroke="{}" stroke-width="{}" fill="{}"{}>"#,
+            id,
+            escape(&style.stroke),
+            fmt(style.stroke_width * pixel),
+            escape(&style.fill),
+            extra,
+        )
+        .unwrap();
+    }
+
+    fn point(&self, x: f64, y: f64) -> String {
+        format!("{} {}", fmt(x), fmt(self.height - y))
+    }
+
+    /// Rectangle from its bottom-left corner, optionally with its own fill.
+    fn rectangle(&mut self, origin: (f64, f64), size: (f64, f64), fill: Option<&str>) {
+        let fill = fill
+            .map(|f| format!(r#" fill="{}""#, f))
+            .unwrap_or_default();
+        writeln!(
+            self.output,
+            r#"<rect x="{}" y="{}" width="{}" height="{}"{}/>"#,
+            fmt(origin.0),
+            fmt(self.height - origin.1 - size.1),
+            fmt(size.0),
+            fmt(size.1),
+            fill,
+        )
+        .unwrap();
+    }
+
+    fn piece(&mut self, piece: &Piece, fill: Option<&str>) {
+        self.rectangle(
+            (piece.x_origin, piece.y_origin),
+            (piece.width, piece.height),
+            fill,
+        );
+    }
+
+    /// Connected cuts as one path; a new subpath starts at every gap.
+    fn path(&mut self, cuts: &[Cut], origin: (f64, f64), closed: bool) {
+        if cuts.is_empty() {
+            return;
+        }
+        let (ox, oy) = origin;
+        let mut data = String::new();
+        let mut pen: Option<(f64, f64)> = None;
+        for cut in cuts {
+            let start = (ox + cut.xi, oy + cut.yi);
+            let end = (ox + cut.xf, oy + cut.yf);
+            let connected =
+                pen.is_some_and(|(x, y)| (x - start.0).abs() < 1e-6 && (y - start.1).abs() < 1e-6);
+            if !connected {
+                write!(data, "M{} ", self.point(start.0, start.1)).unwrap();
+            }
+            match cut.cut_type {
+                CutType::Line => write!(data, "L{} ", self.point(end.0, end.1)).unwrap(),
+                CutType::ArcCW | CutType::ArcCCW => {
+                    let large = u8::from(cut.arc_angle().abs() > PI);
+                    // Flipping Y turns counter-clockwise into the SVG positive sweep
+                    let sweep = u8::from(cut.cut_type == CutType::ArcCCW);
+                    write!(
+                        data,
+                        "A{0} {0} 0 {1} {2} {3} ",
+                        fmt(cut.radius),
+                        large,
+                        sweep,
+                        self.point(end.0, end.1)
+                    )
+                    .unwrap();
+                }
+            }
+            pen = Some(end);
+        }
+        if closed {
+            data.push('Z');
+        }
+        writeln!(self.output, r#"<path d="{}"/>"#, data.trim_end()).unwrap();
+    }
+
+    /// Text centred on `(x, y)`.
+    fn text(&mut self, x: f64, y: f64, size: f64, text: &str) {
+        writeln!(
+            self.output,
+            r#"<text x="{}" y="{}" font-size="{}" font-family="sans-serif" text-anchor="middle" dominant-baseline="central">{}</text>"#,
+            fmt(x),
+            fmt(self.height - y),
+            fmt(size),
+            escape(text),
+        )
+        .unwrap();
+    }
+}
+
+/// Number rounded to 1e-4 without trailing zeros.
+fn fmt(value: f64) -> String {
+    let text = format!("{:.4}", value);
+    let text = text.trim_end_matches('0').trim_end_matches('.');
+    if text == "-0" {
+        "0".to_string()
+    } else {
+        text.to_string()
+    }
+}
+
+/// Escape text for element content and attribute values.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::ScoredCut;
+    use crate::model::{PieceType, Shape};
+
+    fn create_schema() -> Schema {
+        let mut schema = Schema::new();
+        schema.width = 1000.0;
+        schema.height = 500.0;
+        schema.trim_left = 10.0;
+        schema.add_linear_cut(Cut::new_line(300.0, 0.0, 300.0, 500.0));
+
+        let mut shape = Shape::new(7);
+        shape.name = "ROUND".to_string();
+        shape.add_cut(Cut::new_line(0.0, 0.0, 100.0, 0.0));
+        shape.add_cut(Cut::new_arc_ccw(100.0, 0.0, 0.0, 0.0, 50.0));
+        schema.add_shape(shape);
+
+        schema.add_piece_type(PieceType {
+            id: 1,
+            customer: "Smith & Sons".to_string(),
+            order_no: "A-17".to_string(),
+            ..Default::default()
+        });
+        schema.add_piece(Piece {
+            x_origin: 400.0,
+            y_origin: 100.0,
+            width: 100.0,
+            height: 50.0,
+            info_id: Some(1),
+            piece_type_index: Some(0),
+            shape_index: Some(0),
+            ..Default::default()
+        });
+        schema
+    }
+
+    #[test]
+    fn test_export_svg_layers() {
+        let svg = export_svg(&create_schema(), &SvgOptions::default());
+
+        assert!(svg.starts_with("<svg "));
+        assert!(svg.contains(r#"viewBox="0 0 1000 500""#));
+        assert!(svg.contains(r#"height="600""#));
+        for layer in SvgLayer::ALL {
+            assert!(svg.contains(&format!(r#"<g id="{}""#, layer.name())));
+        }
+        assert!(svg.contains(r#"fill="url(#waste-hatch)""#));
+        // Linear cut with the Y axis flipped
+        assert!(svg.contains(r#"<path d="M300 500 L300 0"/>"#));
+        assert!(svg.contains("Smith &amp; Sons"));
+        assert!(svg.contains(">A-17</text>"));
+        assert!(svg.trim_end().ends_with("</svg>"));
+    }
+
+    #[test]
+    fn test_export_svg_true_arcs() {
+        let svg = export_svg(&create_schema(), &SvgOptions::default());
+        // Semicircle on top of the shape base, counter-clockwise
+        assert!(svg.contains(r#"<path d="M400 400 L500 400 A50 50 0 0 1 400 400 Z"/>"#));
+    }
+
+    #[test]
+    fn test_render_toolpath() {
+        let pattern = PatternToolpath {
+            label: "0001".to_string(),
+            number: 1,
+            cuts: vec![
+                ScoredCut {
+                    tool: 1,
+                    scoring: "PT_GIU".to_string(),
+                    shape_macro: None,
+                    segments: vec![Segment::Line {
+                        start: (300.0, 0.0),
+                        end: (300.0, 500.0),
+                    }],
+                },
+                ScoredCut {
+                    tool: 2,
+                    scoring: "PT_GIU".to_string(),
+                    shape_macro: Some("0101".to_string()),
+                    segments: vec![Segment::Arc {
+                        start: (500.0, 100.0),
+                        end: (400.0, 100.0),
+                        center: (450.0, 100.0),
+                        clockwise: false,
+                    }],
+                },
+            ],
+            motions: vec![Motion::Rapid {
+                from: (0.0, 0.0),
+                to: (300.0, 0.0),
+            }],
+        };
+        let svg = render_toolpath(&pattern, 1000.0, 500.0, &SvgOptions::default());
+
+        assert!(svg.contains(r#"viewBox="0 0 1000 500""#));
+        assert!(svg.contains(r#"<g id="rapids""#));
+        assert!(svg.contains(r#"<path d="M0 500 L300 500"/>"#));
+        assert!(svg.contains(r#"<path d="M300 500 L300 0"/>"#));
+        assert!(svg.contains(r#"<path d="M500 400 A50 50 0 0 1 400 400"/>"#));
+        assert!(!svg.contains(r#"<g id="labels""#));
+        assert!(svg.trim_end().ends_with("</svg>"));
+    }
+
+    #[test]
+    fn test_export_svg_hidden_layers_and_styles() {
+        let options = SvgOptions::default()
+            .with_layer(SvgLayer::Labels, false)
+            .with_layer(SvgLayer::Waste, false)
+            .with_style(
+                SvgLayer::Linear,
+                SvgStyle {
+                    visible: true,
+                    stroke: "red".to_string(),
+                    fill: "none".to_string(),
+                    stroke_width: 2.0,
+                },
+            );
+        let svg = export_svg(&create_schema(), &options);
+
+        assert!(!svg.contains(r#"<g id="labels""#));
+        assert!(!svg.contains("waste-hatch"));
+        assert!(svg.contains(r#"<g id="linear" stroke="red""#));
+        assert!(!svg.contains("<text"));
+    }
+}
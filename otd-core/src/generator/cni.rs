@@ -1,16 +1,25 @@
 //! CNI file generator for cutting table machines (100-199).
+//!
+//! The file layout is written by the [`IntermacCni`] post-processor.
 
+use crate::analysis::{
+    estimate_for_schemas, format_duration, simulate, verify_toolpath, CycleTime,
+};
 use crate::config::{
-    MachineConfig, DEFAULT_LINEAR_TOOL, DEFAULT_SHAPED_TOOL, EPS_COARSE, TOOL_TYPE_SHAPED,
+    CuttingOrder, MachineConfig, DEFAULT_LINEAR_TOOL, DEFAULT_SHAPED_TOOL, EPS_COARSE,
+    TOOL_TYPE_SHAPED,
 };
 use crate::error::Result;
-use crate::model::{CutType, Schema};
+use crate::model::{Cut, CutType, Schema};
 use std::collections::HashSet;
 use std::fmt::Write;
 use std::path::Path;
 
 use super::dxf::generate_dxf_sections;
+use super::emit::CniEmitter;
 use super::gcode::{format_coord, format_tool_code, GcodeWriter};
+use super::post::{PostJob, PostProcessor};
+use super::program::Program;
 
 /// Generate a CNI file from parsed schemas.
 pub fn generate_cni(
@@ -18,49 +27,183 @@ pub fn generate_cni(
     input_filename: &str,
     config: &MachineConfig,
 ) -> Result<String> {
-    let mut output = String::new();
+    if schemas.iter().any(|s| !s.same_sheet(&schemas[0])) {
+        tracing::warn!(
+            "Patterns use different sheet formats, [PARAMETRI01] only describes the first one; \
+             use generate_cni_programs or generate_split_cni to split them"
+        );
+    }
 
-    // Generate [COMMENTO] section
-    generate_comment_section(&mut output, schemas, input_filename);
+    let pattern_numbers: Vec<usize> = (1..=schemas.len()).collect();
+    generate_cni_for_patterns(schemas, input_filename, config, &pattern_numbers)
+}
 
-    // Generate [CENTRO01] section (empty)
-    writeln!(output, "[CENTRO01]").unwrap();
-    writeln!(output).unwrap();
+/// Generate a CNI file for a subset of the patterns of an OTD file.
+///
+/// `pattern_numbers` holds the 1-based position of each schema in the
+/// original file, so `[*LDIST]` codes stay the same whichever program a
+/// pattern ends up in.
+pub(super) fn generate_cni_for_patterns(
+    schemas: &[Schema],
+    input_filename: &str,
+    config: &MachineConfig,
+    pattern_numbers: &[usize],
+) -> Result<String> {
+    generate_for_patterns(
+        schemas,
+        input_filename,
+        config,
+        pattern_numbers,
+        &IntermacCni,
+    )
+    .map(|(content, _)| content)
+}
 
-    // Generate [PARAMETRI01] section
-    generate_parameters_section(&mut output, schemas, config);
+/// Generate an output file for another controller dialect.
+pub fn generate_with_post(
+    schemas: &[Schema],
+    input_filename: &str,
+    config: &MachineConfig,
+    post: &dyn PostProcessor,
+) -> Result<String> {
+    let pattern_numbers: Vec<usize> = (1..=schemas.len()).collect();
+    generate_for_patterns(schemas, input_filename, config, &pattern_numbers, post)
+        .map(|(content, _)| content)
+}
 
-    // Generate [UTENSILI01] section
-    generate_tools_section(&mut output, schemas);
+/// Build the cutting program, check it against the layout, estimate its
+/// cycle time and hand it to the post-processor.
+///
+/// Cycle times are numbered with the source `pattern_numbers`.
+pub(super) fn generate_for_patterns(
+    schemas: &[Schema],
+    input_filename: &str,
+    config: &MachineConfig,
+    pattern_numbers: &[usize],
+    post: &dyn PostProcessor,
+) -> Result<(String, CycleTime)> {
+    let program = build_contour_program(schemas, config);
+    let toolpath = simulate(&program)?;
+    verify_toolpath(schemas, &toolpath)?;
+
+    let mut cycle_time = estimate_for_schemas(schemas, &toolpath, config);
+    for pattern in &mut cycle_time.patterns {
+        if let Some(&number) = pattern_numbers.get(pattern.number - 1) {
+            pattern.number = number;
+        }
+    }
 
-    // Generate [LAVORAZIONI01] section (empty)
-    writeln!(output, "[LAVORAZIONI01]").unwrap();
-    writeln!(output, "%").unwrap();
-    writeln!(output).unwrap();
+    let job = PostJob {
+        schemas,
+        input_filename,
+        config,
+        pattern_numbers,
+        cycle_time: &cycle_time,
+    };
+
+    let content = post.emit_file(&job, &program)?;
+    Ok((content, cycle_time))
+}
+
+/// Intermac CNI post-processor.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct IntermacCni;
 
-    // Generate [CONTORNATURA01] section
-    generate_contour_section(&mut output, schemas, config);
+impl PostProcessor for IntermacCni {
+    fn name(&self) -> &'static str {
+        "intermac"
+    }
+
+    fn extension(&self) -> &'static str {
+        "cni"
+    }
+
+    fn emit_program(&self, program: &Program) -> Result<String> {
+        Ok(CniEmitter.emit(program))
+    }
 
-    // Generate [*LDIST] sections
-    generate_distribution_sections(&mut output, schemas, input_filename);
+    fn emit_file(&self, job: &PostJob<'_>, program: &Program) -> Result<String> {
+        let schemas = job.schemas;
+        let mut output = String::new();
 
-    // Generate [*PRWB] and [*PRWC] DXF visualization sections
-    output.push_str(&generate_dxf_sections(schemas));
+        // Generate [COMMENTO] section
+        generate_comment_section(&mut output, schemas, job.input_filename, job.cycle_time);
 
-    Ok(output)
+        // Generate [CENTRO01] section (empty)
+        writeln!(output, "[CENTRO01]").unwrap();
+        writeln!(output).unwrap();
+
+        // Generate [PARAMETRI01] section
+        generate_parameters_section(&mut output, schemas, job.config);
+
+        // Generate [UTENSILI01] section
+        generate_tools_section(&mut output, schemas);
+
+        // Generate [LAVORAZIONI01] section (empty)
+        writeln!(output, "[LAVORAZIONI01]").unwrap();
+        writeln!(output, "%").unwrap();
+        writeln!(output).unwrap();
+
+        // Generate [CONTORNATURA01] section
+        writeln!(output, "[CONTORNATURA01]").unwrap();
+        output.push_str(&self.emit_program(program)?);
+        writeln!(output).unwrap();
+
+        // Generate [*LDIST] sections
+        generate_distribution_sections(
+            &mut output,
+            schemas,
+            job.input_filename,
+            job.pattern_numbers,
+        );
+
+        // Generate [*PRWB] and [*PRWC] DXF visualization sections
+        output.push_str(&generate_dxf_sections(schemas));
+
+        Ok(output)
+    }
 }
 
 /// Generate the [COMMENTO] section.
-fn generate_comment_section(output: &mut String, schemas: &[Schema], filename: &str) {
+fn generate_comment_section(
+    output: &mut String,
+    schemas: &[Schema],
+    filename: &str,
+    cycle_time: &CycleTime,
+) {
     writeln!(output, "[COMMENTO]").unwrap();
     writeln!(output, "; Project: {}", filename).unwrap();
 
-    if let Some(schema) = schemas.first() {
-        writeln!(output, "; Material : {}", schema.glass_id).unwrap();
+    let mut materials: Vec<&str> = Vec::new();
+    for schema in schemas {
+        if !materials.contains(&schema.glass_id.as_str()) {
+            materials.push(&schema.glass_id);
+        }
+    }
+    for material in materials {
+        writeln!(output, "; Material : {}", material).unwrap();
     }
 
     writeln!(output, "; Creator: otd-convert-rs").unwrap();
     writeln!(output, "; Version: 0.1.0").unwrap();
+
+    for pattern in &cycle_time.patterns {
+        writeln!(
+            output,
+            "; Cycle time {}: {}",
+            pattern.number,
+            pattern.summary()
+        )
+        .unwrap();
+    }
+    if cycle_time.patterns.len() > 1 {
+        writeln!(
+            output,
+            "; Cycle time total: {}",
+            format_duration(cycle_time.total())
+        )
+        .unwrap();
+    }
     writeln!(output).unwrap();
 }
 
@@ -103,6 +246,11 @@ fn generate_tools_section(output: &mut String, schemas: &[Schema]) {
             }
         }
 
+        // Add incision tool if some linear cuts are only scored
+        if let Some(tool) = incision_tool(schema) {
+            tools.insert(tool);
+        }
+
         // Add shaped tool if shapes actually use it (have cuts with that tool type)
         if schema_uses_tool_type(schema, TOOL_TYPE_SHAPED as usize) {
             if schema.shaped_tool > 0 {
@@ -124,16 +272,14 @@ fn generate_tools_section(output: &mut String, schemas: &[Schema]) {
     writeln!(output).unwrap();
 }
 
-/// Generate the [CONTORNATURA01] section.
-fn generate_contour_section(output: &mut String, schemas: &[Schema], config: &MachineConfig) {
-    writeln!(output, "[CONTORNATURA01]").unwrap();
-
+/// Build the [CONTORNATURA01] cutting program for a cutting table.
+pub fn build_contour_program(schemas: &[Schema], config: &MachineConfig) -> Program {
     let mut writer = GcodeWriter::with_start(20);
 
     // Program initialization
     writer.set_param(15, 1);
     writer.call_macro("PRGINIT");
-    writer.write_line("JM:(P262)");
+    writer.jump("(P262)");
     writer.write_raw("");
 
     // Generate each pattern/schema
@@ -153,8 +299,14 @@ fn generate_contour_section(output: &mut String, schemas: &[Schema], config: &Ma
     writer.call_macro("PFOXOUT");
     writer.write_terminator();
 
-    write!(output, "{}", writer.take_output()).unwrap();
-    writeln!(output).unwrap();
+    writer.into_program()
+}
+
+/// Tool of the score-only pass: the pattern incision tool (ToolCode2), when
+/// set and some linear cuts are only scored.
+fn incision_tool(schema: &Schema) -> Option<u16> {
+    (schema.incision_tool > 0 && schema.has_score_only_cuts())
+        .then_some(schema.incision_tool as u16)
 }
 
 /// Check if schema has shapes that use a specific tool type.
@@ -162,12 +314,20 @@ fn schema_uses_tool_type(schema: &Schema, tool_type: usize) -> bool {
     schema.shapes.iter().any(|shape| shape.uses_tool(tool_type))
 }
 
+/// Tool pass within a schema program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ToolPass {
+    Linear,
+    Incision,
+    Shaped,
+}
+
 /// Generate G-code for a single schema/pattern.
 fn generate_schema_code(
     writer: &mut GcodeWriter,
     schema: &Schema,
     schema_num: usize,
-    _config: &MachineConfig,
+    config: &MachineConfig,
 ) {
     let label = format!("{:04}", schema_num);
 
@@ -200,54 +360,83 @@ fn generate_schema_code(
 
     // Calculate rest dimensions (PXRS/PYRS)
     let (pxrs, pyrs) = calculate_rest_dimensions(schema);
-    writer.write_line(&format!("PXRS={}", format_coord(pxrs)));
-    writer.write_line(&format!("PYRS={}", format_coord(pyrs)));
+    writer.set_named_param_float("PXRS", pxrs);
+    writer.set_named_param_float("PYRS", pyrs);
     writer.call_macro("PTMREP_B");
 
     // Tool selection jumps
     writer.write_raw(";parte relativa al Taglio --------");
     let linear_label = format!("01{:04}", schema_num);
     let shaped_label = format!("02{:04}", schema_num);
+    let incision_label = format!("03{:04}", schema_num);
 
     // Check if this schema has shapes using the shaped tool
     let has_shaped_cuts = schema_uses_tool_type(schema, TOOL_TYPE_SHAPED as usize);
 
-    // Linear tool jump (always present if there are linear cuts)
-    if !schema.linear_cuts.is_empty() {
-        writer.write_line(&format!(
-            "JM((P260=2)~(P007={:04})):{}",
-            DEFAULT_LINEAR_TOOL, linear_label
-        ));
-    }
+    // Score-only cuts get their own pass when an incision tool is set,
+    // otherwise they stay in the linear pass
+    let incision = incision_tool(schema);
+    let in_linear_pass = |cut: &Cut| incision.is_none() || !cut.score_only;
 
-    // Shaped tool jump (only if shapes use this tool type)
-    if has_shaped_cuts {
-        writer.write_line(&format!(
-            "JM((P260=2)~(P007={:04})):{}",
-            DEFAULT_SHAPED_TOOL, shaped_label
-        ));
+    // Pass order follows CuttingOrder (shapes are scored before the separating
+    // linear cuts on thick glass)
+    let passes = match config.cutting_order_for(schema.cutting_order) {
+        CuttingOrder::LinearFirst => [ToolPass::Linear, ToolPass::Incision, ToolPass::Shaped],
+        CuttingOrder::ShapesFirst => [ToolPass::Shaped, ToolPass::Linear, ToolPass::Incision],
+    };
+    let passes: Vec<ToolPass> = passes
+        .into_iter()
+        .filter(|pass| match pass {
+            // Linear pass only if there are linear cuts left for it
+            ToolPass::Linear => {
+                !schema.linear_cuts.is_empty()
+                    && (incision.is_none()
+                        || schema
+                            .linear_cuts
+                            .iter()
+                            .any(|c| c.active && in_linear_pass(c)))
+            }
+            // Incision pass only if some cuts are only scored
+            ToolPass::Incision => incision.is_some(),
+            // Shaped pass only if shapes use the shaped tool
+            ToolPass::Shaped => has_shaped_cuts,
+        })
+        .collect();
+
+    for pass in &passes {
+        let (tool, label) = match pass {
+            ToolPass::Linear => (DEFAULT_LINEAR_TOOL, &linear_label),
+            ToolPass::Incision => (incision.unwrap_or_default(), &incision_label),
+            ToolPass::Shaped => (DEFAULT_SHAPED_TOOL, &shaped_label),
+        };
+        writer.jump_if(&format!("(P260=2)~(P007={:04})", tool), label);
     }
 
-    writer.write_line("JM(P260=2):999999999");
+    writer.jump_if("P260=2", "999999999");
     writer.write_raw("");
 
-    // Linear cuts section (only if there are linear cuts)
-    if !schema.linear_cuts.is_empty() {
-        writer.write_comment("parte geometrica lineare ----------");
-        writer.write_label(&linear_label);
-        generate_linear_cuts(writer, schema);
-        writer.write_raw("");
-        writer.write_line("JM(P260=2):999999999");
-        writer.write_raw("");
-    }
-
-    // Shape cuts section (only if shapes use the shaped tool)
-    if has_shaped_cuts {
-        writer.write_comment("parte geometrica sagomata ----------");
-        writer.write_label(&shaped_label);
-        generate_shape_cuts(writer, schema, schema_num);
+    for pass in &passes {
+        match pass {
+            ToolPass::Linear => {
+                writer.write_comment("parte geometrica lineare ----------");
+                writer.write_label(&linear_label);
+                generate_linear_cuts(writer, schema, DEFAULT_LINEAR_TOOL, in_linear_pass);
+            }
+            ToolPass::Incision => {
+                writer.write_comment("parte geometrica solo incisione ----------");
+                writer.write_label(&incision_label);
+                generate_linear_cuts(writer, schema, incision.unwrap_or_default(), |cut| {
+                    cut.score_only
+                });
+            }
+            ToolPass::Shaped => {
+                writer.write_comment("parte geometrica sagomata ----------");
+                writer.write_label(&shaped_label);
+                generate_shape_cuts(writer, schema, schema_num);
+            }
+        }
         writer.write_raw("");
-        writer.write_line("JM(P260=2):999999999");
+        writer.jump_if("P260=2", "999999999");
         writer.write_raw("");
     }
 
@@ -255,14 +444,19 @@ fn generate_schema_code(
     writer.write_raw("");
 }
 
-/// Generate linear cut G-code.
-fn generate_linear_cuts(writer: &mut GcodeWriter, schema: &Schema) {
-    writer.set_tool(DEFAULT_LINEAR_TOOL);
+/// Generate G-code for the active linear cuts selected by `include`.
+fn generate_linear_cuts(
+    writer: &mut GcodeWriter,
+    schema: &Schema,
+    tool: u16,
+    include: impl Fn(&Cut) -> bool,
+) {
+    writer.set_tool(tool);
     writer.load_tool();
     writer.tool_up();
 
     for cut in &schema.linear_cuts {
-        if !cut.active {
+        if !cut.active || !include(cut) {
             continue;
         }
 
@@ -512,11 +706,7 @@ fn generate_shape_macro_content(
             writer.apply_rotation();
 
             // Rapid move to start position
-            writer.write_line(&format!(
-                "G00 X={} Y={} C=P540 AR=P540",
-                format_coord(cut.xi),
-                format_coord(cut.yi)
-            ));
+            writer.rapid_move_aligned(cut.xi, cut.yi, "P540");
 
             // Calculate path length for this segment
             let segment_length =
@@ -595,7 +785,12 @@ fn angle_min_degrees(angle1: f64, angle2: f64) -> f64 {
 }
 
 /// Generate [*LDIST] distribution sections.
-fn generate_distribution_sections(output: &mut String, schemas: &[Schema], input_filename: &str) {
+fn generate_distribution_sections(
+    output: &mut String,
+    schemas: &[Schema],
+    input_filename: &str,
+    pattern_numbers: &[usize],
+) {
     let base_name = Path::new(input_filename)
         .file_stem()
         .and_then(|s| s.to_str())
@@ -605,8 +800,12 @@ fn generate_distribution_sections(output: &mut String, schemas: &[Schema], input
         let section_name = format!("*LDIST{:04}_01", schema_idx + 1);
         writeln!(output, "[{}]", section_name).unwrap();
 
-        // Code identifier
-        writeln!(output, ";Cod={}{}", base_name, schema_idx + 1).unwrap();
+        // Code identifier, numbered after the pattern's position in the source file
+        let pattern_num = pattern_numbers
+            .get(schema_idx)
+            .copied()
+            .unwrap_or(schema_idx + 1);
+        writeln!(output, ";Cod={}{}", base_name, pattern_num).unwrap();
 
         // Sheet dimensions
         writeln!(output, ";DimX={}", format_coord(schema.width)).unwrap();
@@ -367,15 +367,39 @@ fn format_dimension(value: f64) -> String {
     format!("{:.3}", rounded / 1000.0)
 }
 
-/// Generate text labels for a piece.
+/// A text label placed on a piece.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct PieceText {
+    /// Preview layer of the label.
+    pub layer: &'static str,
+    /// Color index.
+    pub color: i32,
+    /// Insertion point.
+    pub x: f64,
+    pub y: f64,
+    /// Label text.
+    pub text: String,
+}
+
+/// Text labels for a piece: dimensions, shape name, piece code, customer and order.
 /// Uses view mode to handle coordinate transformation for normal vs mirrored views.
-fn generate_piece_texts(
-    dxf: &mut DxfWriter,
+pub(crate) fn piece_texts(
     schema: &Schema,
     piece: &Piece,
     colors: &DxfColors,
     mode: &ViewMode,
-) {
+) -> Vec<PieceText> {
+    let mut texts = Vec::new();
+    let mut add = |layer: &'static str, color: i32, x: f64, y: f64, text: String| {
+        texts.push(PieceText {
+            layer,
+            color,
+            x,
+            y,
+            text,
+        })
+    };
+
     // Get piece type info if available
     let piece_type = piece
         .piece_type_index
@@ -383,7 +407,7 @@ fn generate_piece_texts(
 
     let is_scrap = piece_type.map(|pt| pt.waste).unwrap_or(false);
     if is_scrap {
-        return; // Don't add labels to scrap pieces
+        return texts; // Don't add labels to scrap pieces
     }
 
     // Calculate the base X coordinate for text positioning
@@ -401,7 +425,7 @@ fn generate_piece_texts(
         format_dimension(piece.width),
         format_dimension(piece.height)
     );
-    dxf.write_text_entity("Dimensioni", colors.dimensions, dim_x, dim_y, &dim_text);
+    add("Dimensioni", colors.dimensions, dim_x, dim_y, dim_text);
 
     // Shape name text - positioned at center, only if piece has shape
     if piece.shape_index.is_some() {
@@ -427,35 +451,62 @@ fn generate_piece_texts(
                     "?".to_string()
                 }
             });
-        dxf.write_text_entity("NomeSagoma", colors.shape_name, name_x, name_y, &shape_name);
+        add("NomeSagoma", colors.shape_name, name_x, name_y, shape_name);
     }
 
     // Piece type code - positioned at 9/10 x, 9/10 y
     if let Some(pt) = piece_type {
         let type_x = base_x + piece.width * 9.0 / 10.0;
         let type_y = piece.y_origin + piece.height * 9.0 / 10.0;
-        dxf.write_text_entity(
+        add(
             "TipoP",
             colors.piece_type,
             type_x,
             type_y,
-            &pt.piece_code.to_string(),
+            pt.piece_code.to_string(),
         );
 
         // Customer text - positioned at 3/4 x, 1/4 y
         if !pt.customer.is_empty() {
             let cust_x = base_x + piece.width * 3.0 / 4.0;
             let cust_y = piece.y_origin + piece.height * 1.0 / 4.0;
-            dxf.write_text_entity("Cliente", colors.customer, cust_x, cust_y, &pt.customer);
+            add(
+                "Cliente",
+                colors.customer,
+                cust_x,
+                cust_y,
+                pt.customer.clone(),
+            );
         }
 
         // Order number text - positioned at 3/4 x, 1/8 y
         if !pt.order_no.is_empty() {
             let order_x = base_x + piece.width * 3.0 / 4.0;
             let order_y = piece.y_origin + piece.height * 1.0 / 8.0;
-            dxf.write_text_entity("Ordine", colors.order, order_x, order_y, &pt.order_no);
+            add(
+                "Ordine",
+                colors.order,
+                order_x,
+                order_y,
+                pt.order_no.clone(),
+            );
         }
     }
+
+    texts
+}
+
+/// Generate text labels for a piece.
+fn generate_piece_texts(
+    dxf: &mut DxfWriter,
+    schema: &Schema,
+    piece: &Piece,
+    colors: &DxfColors,
+    mode: &ViewMode,
+) {
+    for text in piece_texts(schema, piece, colors, mode) {
+        dxf.write_text_entity(text.layer, text.color, text.x, text.y, &text.text);
+    }
 }
 
 /// Draw shape cuts for a piece.
@@ -0,0 +1,118 @@
+//! Rendering of a [`Program`] to Intermac CNI text.
+
+use std::fmt::Write;
+
+use super::gcode::format_coord;
+use super::program::{Instruction, Move, OffsetAxis, Param, ParamValue, Program};
+
+/// Renders programs in the Intermac CNI dialect.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CniEmitter;
+
+impl CniEmitter {
+    /// Render a full program, numbering blocks from `program.first_line`.
+    pub fn emit(&self, program: &Program) -> String {
+        let mut output = String::new();
+        let mut line_number = program.first_line;
+
+        for instruction in &program.instructions {
+            let text = self.render(instruction);
+            if instruction.is_numbered() {
+                writeln!(output, "N{} {}", line_number, text).unwrap();
+                line_number += program.increment;
+            } else {
+                writeln!(output, "{}", text).unwrap();
+            }
+        }
+
+        output
+    }
+
+    /// Render a single instruction without its line number.
+    pub fn render(&self, instruction: &Instruction) -> String {
+        match instruction {
+            Instruction::Raw(text) | Instruction::Line(text) => text.clone(),
+            Instruction::Comment(text) => format!("; {}", text),
+            Instruction::Label(label) => format!(":{}", label),
+            Instruction::End => "%".to_string(),
+            Instruction::SetParam { param, value } => {
+                format!("{}={}", render_param(param), render_value(value))
+            }
+            Instruction::SetTool(tool) => format!("P007={:04}", tool),
+            Instruction::SetRotation(angle) => format!("P539={}", render_angle(*angle)),
+            Instruction::Rapid(target) => format!("G00 {}", render_move(target)),
+            Instruction::Linear(target) => format!("G01 {}", render_move(target)),
+            Instruction::Arc {
+                x,
+                y,
+                i,
+                j,
+                clockwise,
+            } => format!(
+                "{} X={} Y={} I={} J={}",
+                if *clockwise { "G02" } else { "G03" },
+                format_coord(*x),
+                format_coord(*y),
+                format_coord(*i),
+                format_coord(*j)
+            ),
+            Instruction::MacroCall(name) => format!("L={}", name),
+            Instruction::CallLabel(label) => format!("L:{}", label),
+            Instruction::Jump {
+                condition: Some(condition),
+                label,
+            } => format!("JM({}):{}", condition, label),
+            Instruction::Jump {
+                condition: None,
+                label,
+            } => format!("JM:{}", label),
+            Instruction::Direction { vertical } => {
+                format!("M={}", if *vertical { 533 } else { 532 })
+            }
+            Instruction::WorkOffset => "G58".to_string(),
+            Instruction::Offset { axis, value } => {
+                let name = match axis {
+                    OffsetAxis::X => "XO",
+                    OffsetAxis::Y => "YO",
+                };
+                format!("{}={}", name, format_coord(*value))
+            }
+            Instruction::TangentOn => "G28".to_string(),
+            Instruction::TangentOff => "G01 G46".to_string(),
+        }
+    }
+}
+
+fn render_param(param: &Param) -> String {
+    match param {
+        Param::Numbered(number) => format!("P{:03}", number),
+        Param::Named(name) => name.clone(),
+    }
+}
+
+fn render_value(value: &ParamValue) -> String {
+    match value {
+        ParamValue::Int(value) => value.to_string(),
+        ParamValue::Float(value) => format_coord(*value),
+    }
+}
+
+/// Whole angles are written as integers (0, 90, 180, ...).
+fn render_angle(angle: f64) -> String {
+    if (angle - angle.round()).abs() < 0.0001 {
+        format!("{}", angle.round() as i32)
+    } else {
+        format_coord(angle)
+    }
+}
+
+fn render_move(target: &Move) -> String {
+    let mut text = format!("X={} Y={}", format_coord(target.x), format_coord(target.y));
+    if let Some(c) = &target.c {
+        write!(text, " C={}", c).unwrap();
+    }
+    if let Some(ar) = &target.ar {
+        write!(text, " AR={}", ar).unwrap();
+    }
+    text
+}
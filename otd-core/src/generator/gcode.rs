@@ -1,105 +1,120 @@
 //! G-code generation utilities.
 
-use std::fmt::Write;
+use super::emit::CniEmitter;
+use super::program::{Instruction, Move, OffsetAxis, Param, ParamValue, Program};
 
-/// G-code writer with line numbering.
+/// G-code program builder with line numbering.
+///
+/// Records instructions into a [`Program`]; text is produced by [`CniEmitter`].
 pub struct GcodeWriter {
-    /// Current line number.
-    line_number: u32,
-    /// Line number increment.
-    increment: u32,
-    /// Output buffer.
-    buffer: String,
+    /// Program being built.
+    program: Program,
 }
 
 impl GcodeWriter {
     /// Create a new G-code writer.
     pub fn new() -> Self {
-        Self {
-            line_number: 10,
-            increment: 10,
-            buffer: String::new(),
-        }
+        Self::with_start(10)
     }
 
     /// Create a new G-code writer starting at a specific line number.
     pub fn with_start(start: u32) -> Self {
         Self {
-            line_number: start,
-            increment: 10,
-            buffer: String::new(),
+            program: Program::new(start, 10),
         }
     }
 
     /// Get the current line number.
     pub fn current_line(&self) -> u32 {
-        self.line_number
+        self.program.next_line()
+    }
+
+    /// Get the program built so far.
+    pub fn program(&self) -> &Program {
+        &self.program
+    }
+
+    /// Take the built program.
+    pub fn into_program(self) -> Program {
+        self.program
     }
 
     /// Get the generated G-code.
-    pub fn output(&self) -> &str {
-        &self.buffer
+    pub fn output(&self) -> String {
+        CniEmitter.emit(&self.program)
     }
 
     /// Take the generated G-code.
     pub fn take_output(self) -> String {
-        self.buffer
+        CniEmitter.emit(&self.program)
+    }
+
+    /// Append an instruction.
+    pub fn push(&mut self, instruction: Instruction) {
+        self.program.push(instruction);
     }
 
     /// Write a numbered line.
     pub fn write_line(&mut self, content: &str) {
-        writeln!(self.buffer, "N{} {}", self.line_number, content).unwrap();
-        self.line_number += self.increment;
+        self.push(Instruction::Line(content.to_string()));
     }
 
     /// Write a line without numbering.
     pub fn write_raw(&mut self, content: &str) {
-        writeln!(self.buffer, "{}", content).unwrap();
+        self.push(Instruction::Raw(content.to_string()));
     }
 
     /// Write a comment line.
     pub fn write_comment(&mut self, comment: &str) {
-        writeln!(self.buffer, "; {}", comment).unwrap();
+        self.push(Instruction::Comment(comment.to_string()));
     }
 
     /// Write a label.
     pub fn write_label(&mut self, label: &str) {
-        writeln!(self.buffer, ":{}", label).unwrap();
+        self.push(Instruction::Label(label.to_string()));
     }
 
     /// Write a section terminator.
     pub fn write_terminator(&mut self) {
-        writeln!(self.buffer, "%").unwrap();
+        self.push(Instruction::End);
     }
 
     // === Parameter commands ===
 
     /// Set a parameter value.
-    pub fn set_param(&mut self, param: u16, value: impl std::fmt::Display) {
-        self.write_line(&format!("P{:03}={}", param, value));
+    pub fn set_param(&mut self, param: u16, value: impl Into<i64>) {
+        self.push(Instruction::SetParam {
+            param: Param::Numbered(param),
+            value: ParamValue::Int(value.into()),
+        });
     }
 
     /// Set a parameter with a formatted float.
     pub fn set_param_float(&mut self, param: u16, value: f64) {
-        self.write_line(&format!("P{:03}={}", param, format_coord(value)));
+        self.push(Instruction::SetParam {
+            param: Param::Numbered(param),
+            value: ParamValue::Float(value),
+        });
+    }
+
+    /// Set a named parameter (`PXRS`, `PYRS`, ...) with a formatted float.
+    pub fn set_named_param_float(&mut self, name: &str, value: f64) {
+        self.push(Instruction::SetParam {
+            param: Param::Named(name.to_string()),
+            value: ParamValue::Float(value),
+        });
     }
 
     /// Set the tool parameter (P007).
     pub fn set_tool(&mut self, tool_code: u16) {
-        self.write_line(&format!("P007={:04}", tool_code));
+        self.push(Instruction::SetTool(tool_code));
     }
 
     /// Set the rotation angle (P539).
     ///
     /// Uses integer format for angles like 90, 0 for horizontal.
     pub fn set_rotation(&mut self, angle: f64) {
-        let angle_str = if (angle - angle.round()).abs() < 0.0001 {
-            // Use integer format for whole number angles (0, 90, 180, etc.)
-            format!("{}", angle.round() as i32)
-        } else {
-            format_coord(angle)
-        };
-        self.write_line(&format!("P539={}", angle_str));
+        self.push(Instruction::SetRotation(angle));
     }
 
     /// Set the rotation angle for shape cuts (P539).
@@ -108,89 +123,99 @@ impl GcodeWriter {
     pub fn set_rotation_shape(&mut self, angle: f64) {
         use crate::config::MIN_VAL_C_POSITIVE;
 
-        let angle_str = if angle.abs() < MIN_VAL_C_POSITIVE {
-            // Use minimum positive value for tangent mode
-            format!("{}", MIN_VAL_C_POSITIVE)
-        } else if (angle - angle.round()).abs() < 0.0001 {
-            // Use integer format for whole number angles
-            format!("{}", angle.round() as i32)
+        let angle = if angle.abs() < MIN_VAL_C_POSITIVE {
+            MIN_VAL_C_POSITIVE
         } else {
-            format_coord(angle)
+            angle
         };
-        self.write_line(&format!("P539={}", angle_str));
+        self.push(Instruction::SetRotation(angle));
     }
 
     // === Movement commands ===
 
     /// Rapid move (G00).
     pub fn rapid_move(&mut self, x: f64, y: f64, c: Option<&str>) {
-        let c_str = c.map(|s| format!(" C={}", s)).unwrap_or_default();
-        self.write_line(&format!(
-            "G00 X={} Y={}{}",
-            format_coord(x),
-            format_coord(y),
-            c_str
-        ));
+        self.push(Instruction::Rapid(Move {
+            x,
+            y,
+            c: c.map(str::to_string),
+            ar: None,
+        }));
+    }
+
+    /// Rapid move aligning both the C axis and the tangential reference (AR).
+    pub fn rapid_move_aligned(&mut self, x: f64, y: f64, c: &str) {
+        self.push(Instruction::Rapid(Move {
+            x,
+            y,
+            c: Some(c.to_string()),
+            ar: Some(c.to_string()),
+        }));
     }
 
     /// Linear interpolation (G01).
     pub fn linear_move(&mut self, x: f64, y: f64, c: Option<&str>) {
-        let c_str = c.map(|s| format!(" C={}", s)).unwrap_or_default();
-        self.write_line(&format!(
-            "G01 X={} Y={}{}",
-            format_coord(x),
-            format_coord(y),
-            c_str
-        ));
+        self.push(Instruction::Linear(Move {
+            x,
+            y,
+            c: c.map(str::to_string),
+            ar: None,
+        }));
     }
 
     /// Clockwise arc (G02).
     pub fn arc_cw(&mut self, x: f64, y: f64, i: f64, j: f64) {
-        self.write_line(&format!(
-            "G02 X={} Y={} I={} J={}",
-            format_coord(x),
-            format_coord(y),
-            format_coord(i),
-            format_coord(j)
-        ));
+        self.push(Instruction::Arc {
+            x,
+            y,
+            i,
+            j,
+            clockwise: true,
+        });
     }
 
     /// Counter-clockwise arc (G03).
     pub fn arc_ccw(&mut self, x: f64, y: f64, i: f64, j: f64) {
-        self.write_line(&format!(
-            "G03 X={} Y={} I={} J={}",
-            format_coord(x),
-            format_coord(y),
-            format_coord(i),
-            format_coord(j)
-        ));
+        self.push(Instruction::Arc {
+            x,
+            y,
+            i,
+            j,
+            clockwise: false,
+        });
     }
 
     // === Macro calls ===
 
     /// Call a macro/subroutine.
     pub fn call_macro(&mut self, name: &str) {
-        self.write_line(&format!("L={}", name));
+        self.push(Instruction::MacroCall(name.to_string()));
     }
 
     /// Call a labeled subroutine.
     pub fn call_label(&mut self, label: &str) {
-        self.write_line(&format!("L:{}", label));
+        self.push(Instruction::CallLabel(label.to_string()));
     }
 
     /// Conditional jump.
     pub fn jump_if(&mut self, condition: &str, label: &str) {
-        self.write_line(&format!("JM({}):{}", condition, label));
+        self.push(Instruction::Jump {
+            condition: Some(condition.to_string()),
+            label: label.to_string(),
+        });
     }
 
     /// Unconditional jump.
     pub fn jump(&mut self, label: &str) {
-        self.write_line(&format!("JM:{}", label));
+        self.push(Instruction::Jump {
+            condition: None,
+            label: label.to_string(),
+        });
     }
 
     /// Complex conditional jump.
     pub fn jump_complex(&mut self, conditions: &str, label: &str) {
-        self.write_line(&format!("JM({}):{}", conditions, label));
+        self.jump_if(conditions, label);
     }
 
     // === Tool control ===
@@ -219,33 +244,40 @@ impl GcodeWriter {
 
     /// Direction code for cut (M=532 or M=533).
     pub fn direction_code(&mut self, is_vertical: bool) {
-        let code = if is_vertical { 533 } else { 532 };
-        self.write_line(&format!("M={}", code));
+        self.push(Instruction::Direction {
+            vertical: is_vertical,
+        });
     }
 
     /// Set work offset (G58).
     pub fn set_work_offset(&mut self) {
-        self.write_line("G58");
+        self.push(Instruction::WorkOffset);
     }
 
     /// Set XO offset.
     pub fn set_xo(&mut self, value: f64) {
-        self.write_line(&format!("XO={}", format_coord(value)));
+        self.push(Instruction::Offset {
+            axis: OffsetAxis::X,
+            value,
+        });
     }
 
     /// Set YO offset.
     pub fn set_yo(&mut self, value: f64) {
-        self.write_line(&format!("YO={}", format_coord(value)));
+        self.push(Instruction::Offset {
+            axis: OffsetAxis::Y,
+            value,
+        });
     }
 
     /// Enable tangent mode (G28).
     pub fn tangent_mode_on(&mut self) {
-        self.write_line("G28");
+        self.push(Instruction::TangentOn);
     }
 
     /// Disable tangent mode (G46).
     pub fn tangent_mode_off(&mut self) {
-        self.write_line("G01 G46");
+        self.push(Instruction::TangentOff);
     }
 
     /// Set shape parameters.
@@ -330,6 +362,34 @@ mod tests {
         assert_eq!(format_coord(0.5 / 25.4), "0.0196850393700787");
     }
 
+    #[test]
+    fn test_gcode_writer_records_program() {
+        let mut writer = GcodeWriter::with_start(20);
+        writer.write_label("0001");
+        writer.set_tool(3);
+        writer.set_rotation(90.0);
+        writer.rapid_move(10.0, 20.5, Some("P540"));
+        writer.jump_if("P260=2", "999999999");
+
+        let program = writer.program();
+        assert_eq!(program.numbered_count(), 4);
+        assert_eq!(writer.current_line(), 60);
+        assert_eq!(program.instructions[2], Instruction::SetRotation(90.0));
+
+        let output = writer.output();
+        assert_eq!(
+            output,
+            ":0001\nN20 P007=0003\nN30 P539=90\nN40 G00 X=10 Y=20.5 C=P540\nN50 JM(P260=2):999999999\n"
+        );
+    }
+
+    #[test]
+    fn test_set_rotation_shape_minimum() {
+        let mut writer = GcodeWriter::new();
+        writer.set_rotation_shape(0.0);
+        assert_eq!(writer.output(), "N10 P539=0.001\n");
+    }
+
     #[test]
     fn test_gcode_writer() {
         let mut writer = GcodeWriter::new();
@@ -2,8 +2,20 @@
 
 mod cni;
 mod dxf;
+mod emit;
 mod gcode;
+mod post;
+mod program;
+mod split;
 
-pub use cni::generate_cni;
+pub use cni::{build_contour_program, generate_cni, generate_with_post, IntermacCni};
 pub use dxf::generate_dxf_sections;
+pub(crate) use dxf::{piece_texts, DxfColors, ViewMode};
+pub use emit::CniEmitter;
 pub use gcode::GcodeWriter;
+pub use post::{IsoPost, PostJob, PostProcessor};
+pub use program::{Block, Instruction, Move, OffsetAxis, Param, ParamValue, Program};
+pub use split::{
+    generate_cni_programs, generate_split_cni, generate_split_with_post, group_by_sheet,
+    split_schemas, CniProgram, ManifestEntry, SplitManifest, SplitMode,
+};
@@ -0,0 +1,506 @@
+//! Post-processors rendering cutting programs for a controller dialect.
+//!
+//! The generator builds a [`Program`] and hands it to a [`PostProcessor`],
+//! which writes the complete output file. [`IntermacCni`](super::IntermacCni)
+//! produces CNI files; [`IsoPost`] produces generic ISO/RS-274 G-code with a
+//! tangential axis for tables driven by other controllers.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use super::program::{Block, Instruction, OffsetAxis, Program};
+use crate::analysis::CycleTime;
+use crate::config::{MachineConfig, Unit};
+use crate::error::{ConvertError, Result};
+use crate::model::Schema;
+
+/// Everything a post-processor needs besides the program itself.
+#[derive(Debug, Clone, Copy)]
+pub struct PostJob<'a> {
+    /// Schemas in the program.
+    pub schemas: &'a [Schema],
+    /// Source file name.
+    pub input_filename: &'a str,
+    /// Target machine.
+    pub config: &'a MachineConfig,
+    /// 1-based pattern numbers of the schemas in the source file.
+    pub pattern_numbers: &'a [usize],
+    /// Estimated cycle time, numbered like `pattern_numbers`.
+    pub cycle_time: &'a CycleTime,
+}
+
+/// Renders programs for one controller dialect.
+pub trait PostProcessor {
+    /// Dialect name.
+    fn name(&self) -> &'static str;
+
+    /// Output file extension, without the dot.
+    fn extension(&self) -> &'static str;
+
+    /// Render the cutting program alone.
+    ///
+    /// Fails on macros the dialect has no translation for.
+    fn emit_program(&self, program: &Program) -> Result<String>;
+
+    /// Render a complete output file around the cutting program.
+    fn emit_file(&self, job: &PostJob<'_>, program: &Program) -> Result<String>;
+}
+
+/// Generic ISO/RS-274 G-code post-processor.
+///
+/// Moves become G0/G1/G2/G3 with incremental arc centres, the wheel
+/// orientation is written on the tangential axis, and Intermac control flow
+/// is flattened: shape subroutines are inlined at their offsets and each
+/// pattern ends with an M0 stop for the next sheet. Intermac bookkeeping
+/// macros are kept as comments; any other macro is an error.
+#[derive(Debug, Clone)]
+pub struct IsoPost {
+    /// Tangential (wheel orientation) axis letter.
+    pub tangential_axis: char,
+    /// Code lowering the cutting wheel.
+    pub tool_down: String,
+    /// Code lifting the cutting wheel.
+    pub tool_up: String,
+}
+
+impl Default for IsoPost {
+    fn default() -> Self {
+        Self {
+            tangential_axis: 'C',
+            tool_down: "M3".to_string(),
+            tool_up: "M5".to_string(),
+        }
+    }
+}
+
+impl IsoPost {
+    /// Create an ISO post with the default C tangential axis and M3/M5 tool codes.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use another tangential axis letter.
+    pub fn with_tangential_axis(mut self, axis: char) -> Self {
+        self.tangential_axis = axis;
+        self
+    }
+}
+
+impl PostProcessor for IsoPost {
+    fn name(&self) -> &'static str {
+        "iso"
+    }
+
+    fn extension(&self) -> &'static str {
+        "nc"
+    }
+
+    fn emit_program(&self, program: &Program) -> Result<String> {
+        let blocks = program.blocks();
+        let subroutines: HashMap<&str, Block<'_>> = program
+            .instructions
+            .iter()
+            .filter_map(|i| match i {
+                Instruction::CallLabel(label) => Some(label.as_str()),
+                _ => None,
+            })
+            .filter_map(|label| {
+                blocks
+                    .iter()
+                    .find(|b| b.label == Some(label))
+                    .map(|b| (label, *b))
+            })
+            .collect();
+
+        let mut state = IsoState::new(self, &subroutines);
+        for block in &blocks {
+            if block.label.is_some_and(|l| subroutines.contains_key(l)) {
+                continue;
+            }
+            state.render_all(block.instructions)?;
+        }
+
+        Ok(state.output)
+    }
+
+    fn emit_file(&self, job: &PostJob<'_>, program: &Program) -> Result<String> {
+        let mut output = String::new();
+
+        writeln!(output, "%").unwrap();
+        writeln!(output, "(Project: {})", job.input_filename).unwrap();
+        if let Some(schema) = job.schemas.first() {
+            writeln!(output, "(Material: {})", schema.glass_id).unwrap();
+            writeln!(
+                output,
+                "(Sheet: {} x {} x {})",
+                format_iso(schema.width),
+                format_iso(schema.height),
+                format_iso(schema.thickness)
+            )
+            .unwrap();
+        }
+        writeln!(output, "(Machine: {})", job.config.machine_number).unwrap();
+
+        let unit = job.schemas.first().map(|s| s.unit).unwrap_or_default();
+        let unit_code = if unit == Unit::Millimeters {
+            "G21"
+        } else {
+            "G20"
+        };
+        writeln!(output, "G90 G17 {}", unit_code).unwrap();
+
+        output.push_str(&self.emit_program(program)?);
+
+        writeln!(output, "M30").unwrap();
+        writeln!(output, "%").unwrap();
+        Ok(output)
+    }
+}
+
+/// Machine state while flattening a program to ISO G-code.
+struct IsoState<'a> {
+    post: &'a IsoPost,
+    subroutines: &'a HashMap<&'a str, Block<'a>>,
+    output: String,
+    x: f64,
+    y: f64,
+    rotation: f64,
+    x_offset: f64,
+    y_offset: f64,
+    in_subroutine: bool,
+}
+
+impl<'a> IsoState<'a> {
+    fn new(post: &'a IsoPost, subroutines: &'a HashMap<&'a str, Block<'a>>) -> Self {
+        Self {
+            post,
+            subroutines,
+            output: String::new(),
+            x: 0.0,
+            y: 0.0,
+            rotation: 0.0,
+            x_offset: 0.0,
+            y_offset: 0.0,
+            in_subroutine: false,
+        }
+    }
+
+    fn line(&mut self, text: &str) {
+        writeln!(self.output, "{}", text).unwrap();
+    }
+
+    fn render_all(&mut self, instructions: &[Instruction]) -> Result<()> {
+        for (idx, instruction) in instructions.iter().enumerate() {
+            // Orient the wheel along the next cut, not the nominal P539 angle
+            if matches!(instruction, Instruction::MacroCall(name) if name == "PROT_B") {
+                if let Some(angle) = self.next_cut_direction(&instructions[idx + 1..]) {
+                    self.rotation = angle;
+                }
+            }
+            self.render(instruction)?;
+        }
+        Ok(())
+    }
+
+    /// Start direction of the next cut, following rapid moves up to it.
+    fn next_cut_direction(&self, instructions: &[Instruction]) -> Option<f64> {
+        let (mut x, mut y) = (self.x, self.y);
+
+        for instruction in instructions {
+            match instruction {
+                Instruction::Rapid(target) => (x, y) = self.absolute(target.x, target.y),
+                Instruction::Linear(target) => {
+                    let (tx, ty) = self.absolute(target.x, target.y);
+                    return direction_degrees(tx - x, ty - y);
+                }
+                Instruction::Arc {
+                    i, j, clockwise, ..
+                } => {
+                    let (cx, cy) = self.absolute(*i, *j);
+                    return Some(arc_tangent_degrees(x - cx, y - cy, *clockwise));
+                }
+                Instruction::MacroCall(name) if name == "PROT_B" => return None,
+                _ => {}
+            }
+        }
+
+        None
+    }
+
+    fn render(&mut self, instruction: &Instruction) -> Result<()> {
+        let axis = self.post.tangential_axis;
+
+        match instruction {
+            Instruction::Raw(text) => {
+                let text = text.trim_start_matches(';').trim();
+                if !text.is_empty() {
+                    self.line(&format!("({})", text));
+                }
+            }
+            Instruction::Comment(text) | Instruction::Line(text) => {
+                self.line(&format!("({})", text));
+            }
+            Instruction::Label(label) => self.line(&format!("(label {})", label)),
+            Instruction::SetTool(tool) => self.line(&format!("T{} M6", tool)),
+            Instruction::SetRotation(angle) => self.rotation = *angle,
+            Instruction::MacroCall(name) => self.macro_call(name)?,
+            Instruction::CallLabel(label) => self.inline_subroutine(label)?,
+            Instruction::Jump {
+                condition: None,
+                label,
+            } if label == "999999999" => self.line("M0 (end of pattern)"),
+            Instruction::Offset { axis, value } => match axis {
+                OffsetAxis::X => self.x_offset = *value,
+                OffsetAxis::Y => self.y_offset = *value,
+            },
+            Instruction::Rapid(target) => {
+                let (x, y) = self.absolute(target.x, target.y);
+                let text = if target.ar.is_some() {
+                    format!(
+                        "G0 X{} Y{} {}{}",
+                        format_iso(x),
+                        format_iso(y),
+                        axis,
+                        format_iso(self.rotation)
+                    )
+                } else {
+                    format!("G0 X{} Y{}", format_iso(x), format_iso(y))
+                };
+                self.line(&text);
+                self.x = x;
+                self.y = y;
+            }
+            Instruction::Linear(target) => {
+                let (x, y) = self.absolute(target.x, target.y);
+                let angle = direction_degrees(x - self.x, y - self.y).unwrap_or(self.rotation);
+                self.line(&format!(
+                    "G1 X{} Y{} {}{}",
+                    format_iso(x),
+                    format_iso(y),
+                    axis,
+                    format_iso(angle)
+                ));
+                self.x = x;
+                self.y = y;
+                self.rotation = angle;
+            }
+            Instruction::Arc {
+                x,
+                y,
+                i,
+                j,
+                clockwise,
+            } => {
+                let (x, y) = self.absolute(*x, *y);
+                let (cx, cy) = self.absolute(*i, *j);
+                let angle = arc_tangent_degrees(x - cx, y - cy, *clockwise);
+                self.line(&format!(
+                    "{} X{} Y{} I{} J{} {}{}",
+                    if *clockwise { "G2" } else { "G3" },
+                    format_iso(x),
+                    format_iso(y),
+                    format_iso(cx - self.x),
+                    format_iso(cy - self.y),
+                    axis,
+                    format_iso(angle)
+                ));
+                self.x = x;
+                self.y = y;
+                self.rotation = angle;
+            }
+            // Intermac parameters, control flow and modal codes have no ISO equivalent
+            Instruction::End
+            | Instruction::SetParam { .. }
+            | Instruction::Jump { .. }
+            | Instruction::Direction { .. }
+            | Instruction::WorkOffset
+            | Instruction::TangentOn
+            | Instruction::TangentOff => {}
+        }
+        Ok(())
+    }
+
+    fn macro_call(&mut self, name: &str) -> Result<()> {
+        match name {
+            "PT_GIU" => {
+                let code = self.post.tool_down.clone();
+                self.line(&code);
+            }
+            "PT_SU" => {
+                let code = self.post.tool_up.clone();
+                self.line(&code);
+            }
+            "PROT_B" => {
+                let text = format!(
+                    "G0 {}{}",
+                    self.post.tangential_axis,
+                    format_iso(self.rotation)
+                );
+                self.line(&text);
+            }
+            // Tool change is already written by T.. M6
+            "PTOOL" => {}
+            // Program start and end, remnant report and shape parameters
+            // drive no axis
+            "PRGINIT" | "PFOXOUT" | "PTMREP_B" | "PSETSAG" => self.line(&format!("({})", name)),
+            other => {
+                return Err(ConvertError::UnsupportedMacro {
+                    dialect: self.post.name().to_string(),
+                    name: other.to_string(),
+                })
+            }
+        }
+        Ok(())
+    }
+
+    fn inline_subroutine(&mut self, label: &str) -> Result<()> {
+        let Some(block) = self.subroutines.get(label).copied() else {
+            self.line(&format!("(missing subroutine {})", label));
+            return Ok(());
+        };
+
+        self.in_subroutine = true;
+        self.render_all(&block.instructions[1..])?;
+        self.in_subroutine = false;
+        self.x_offset = 0.0;
+        self.y_offset = 0.0;
+        Ok(())
+    }
+
+    /// Shape coordinates are relative to the XO/YO offset of the calling block.
+    fn absolute(&self, x: f64, y: f64) -> (f64, f64) {
+        if self.in_subroutine {
+            (x + self.x_offset, y + self.y_offset)
+        } else {
+            (x, y)
+        }
+    }
+}
+
+/// Direction of a move in degrees (0-360), `None` for a zero-length move.
+fn direction_degrees(dx: f64, dy: f64) -> Option<f64> {
+    if dx.abs() < crate::config::EPS && dy.abs() < crate::config::EPS {
+        None
+    } else {
+        Some(normalize_degrees(dy.atan2(dx).to_degrees()))
+    }
+}
+
+/// Tangent direction at a point of an arc, given the radius vector from the centre.
+fn arc_tangent_degrees(rx: f64, ry: f64, clockwise: bool) -> f64 {
+    let radius_angle = ry.atan2(rx).to_degrees();
+    normalize_degrees(if clockwise {
+        radius_angle - 90.0
+    } else {
+        radius_angle + 90.0
+    })
+}
+
+fn normalize_degrees(angle: f64) -> f64 {
+    let angle = angle.rem_euclid(360.0);
+    if (angle - 360.0).abs() < 1e-9 {
+        0.0
+    } else {
+        angle
+    }
+}
+
+/// Format a value with at most four decimals, trimming trailing zeros.
+fn format_iso(value: f64) -> String {
+    let formatted = format!("{:.4}", value);
+    let trimmed = formatted.trim_end_matches('0').trim_end_matches('.');
+    match trimmed {
+        "" | "-0" => "0".to_string(),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::GcodeWriter;
+
+    #[test]
+    fn test_format_iso() {
+        assert_eq!(format_iso(0.0), "0");
+        assert_eq!(format_iso(-0.00001), "0");
+        assert_eq!(format_iso(12.5), "12.5");
+        assert_eq!(format_iso(1.0 / 3.0), "0.3333");
+    }
+
+    #[test]
+    fn test_iso_linear_cut() {
+        let mut writer = GcodeWriter::new();
+        writer.set_tool(3);
+        writer.set_rotation(90.0);
+        writer.apply_rotation();
+        writer.rapid_move(100.0, 0.0, Some("P540"));
+        writer.direction_code(true);
+        writer.tool_down();
+        writer.linear_move(100.0, 500.0, Some("P540"));
+        writer.tool_up();
+        // Same nominal P539 angle, but cut downwards
+        writer.set_rotation(90.0);
+        writer.apply_rotation();
+        writer.rapid_move(200.0, 500.0, Some("P540"));
+        writer.tool_down();
+        writer.linear_move(200.0, 0.0, Some("P540"));
+        writer.tool_up();
+
+        let output = IsoPost::new().emit_program(writer.program()).unwrap();
+        assert_eq!(
+            output,
+            "T3 M6\nG0 C90\nG0 X100 Y0\nM3\nG1 X100 Y500 C90\nM5\n\
+             G0 C270\nG0 X200 Y500\nM3\nG1 X200 Y0 C270\nM5\n"
+        );
+    }
+
+    #[test]
+    fn test_iso_inlines_shape_subroutine() {
+        let mut writer = GcodeWriter::new();
+        writer.set_work_offset();
+        writer.set_xo(1000.0);
+        writer.set_yo(200.0);
+        writer.call_label("1010001001");
+        writer.jump("999999999");
+        writer.write_label("1010001001");
+        writer.rapid_move_aligned(0.0, 50.0, "P540");
+        writer.tool_down();
+        // Clockwise quarter circle around (50, 50), from (0, 50) to (50, 100)
+        writer.arc_cw(50.0, 100.0, 50.0, 50.0);
+        writer.tool_up();
+        writer.write_terminator();
+
+        let output = IsoPost::new()
+            .with_tangential_axis('A')
+            .emit_program(writer.program())
+            .unwrap();
+        assert!(output.contains("G0 X1000 Y250 A0"));
+        // Arc centre is incremental from the start point
+        assert!(output.contains("G2 X1050 Y300 I50 J0 A0"));
+        assert!(output.contains("M0 (end of pattern)"));
+        // The subroutine body is not emitted a second time
+        assert_eq!(output.matches("G2 ").count(), 1);
+    }
+
+    #[test]
+    fn test_iso_rejects_unknown_macro() {
+        let mut writer = GcodeWriter::new();
+        writer.call_macro("PRGINIT");
+        writer.rapid_move(100.0, 0.0, None);
+        writer.call_macro("PTAGLIO_X");
+
+        let error = IsoPost::new().emit_program(writer.program()).unwrap_err();
+        assert!(matches!(
+            &error,
+            ConvertError::UnsupportedMacro { dialect, name } if dialect == "iso" && name == "PTAGLIO_X"
+        ));
+        assert_eq!(error.code(), crate::error::ErrorCode::UnsupportedMacro);
+
+        // Bookkeeping macros alone render
+        let mut writer = GcodeWriter::new();
+        writer.call_macro("PRGINIT");
+        let output = IsoPost::new().emit_program(writer.program()).unwrap();
+        assert_eq!(output, "(PRGINIT)\n");
+    }
+}
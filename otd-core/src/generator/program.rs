@@ -0,0 +1,259 @@
+//! Intermediate representation of a CNC cutting program.
+//!
+//! [`GcodeWriter`](super::GcodeWriter) records instructions into a [`Program`]
+//! instead of formatting text directly. The program can be inspected or
+//! rewritten before [`CniEmitter`](super::CniEmitter) renders it to CNI text.
+
+use serde::{Deserialize, Serialize};
+
+/// Machine parameter target of a parameter set.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Param {
+    /// Numbered parameter (`P012`, `P203`, ...).
+    Numbered(u16),
+    /// Named parameter (`PXRS`, `PYRS`, ...).
+    Named(String),
+}
+
+/// Value assigned to a parameter.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ParamValue {
+    /// Integer value.
+    Int(i64),
+    /// Floating point value, rendered like coordinates (15 significant digits).
+    Float(f64),
+}
+
+/// Offset axis for shape placement.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OffsetAxis {
+    /// X origin (`XO`).
+    X,
+    /// Y origin (`YO`).
+    Y,
+}
+
+/// Straight move target.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Move {
+    /// Target X coordinate.
+    pub x: f64,
+    /// Target Y coordinate.
+    pub y: f64,
+    /// C axis (wheel orientation) parameter, e.g. `P540`.
+    pub c: Option<String>,
+    /// Tangential axis reference parameter (`AR`), used when entering a shape.
+    pub ar: Option<String>,
+}
+
+/// Single program instruction.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Instruction {
+    /// Unnumbered verbatim line (blank lines, separators).
+    Raw(String),
+    /// Comment line.
+    Comment(String),
+    /// Jump target.
+    Label(String),
+    /// Section/macro terminator.
+    End,
+    /// Numbered verbatim line.
+    Line(String),
+    /// Parameter assignment.
+    SetParam { param: Param, value: ParamValue },
+    /// Tool selection (P007).
+    SetTool(u16),
+    /// C axis rotation in degrees (P539).
+    SetRotation(f64),
+    /// Rapid move (G00).
+    Rapid(Move),
+    /// Linear interpolation (G01).
+    Linear(Move),
+    /// Circular interpolation (G02/G03) with absolute centre.
+    Arc {
+        /// Target X coordinate.
+        x: f64,
+        /// Target Y coordinate.
+        y: f64,
+        /// Centre X coordinate.
+        i: f64,
+        /// Centre Y coordinate.
+        j: f64,
+        /// Clockwise (G02) or counter-clockwise (G03).
+        clockwise: bool,
+    },
+    /// Macro call (`L=NAME`).
+    MacroCall(String),
+    /// Labeled subroutine call (`L:label`).
+    CallLabel(String),
+    /// Jump to a label, optionally conditional.
+    ///
+    /// The label may be a parameter in parentheses for an indirect jump.
+    Jump {
+        condition: Option<String>,
+        label: String,
+    },
+    /// Cut direction code (M=532 horizontal, M=533 vertical).
+    Direction { vertical: bool },
+    /// Work offset (G58).
+    WorkOffset,
+    /// Shape origin offset (`XO`/`YO`).
+    Offset { axis: OffsetAxis, value: f64 },
+    /// Tangent mode on (G28).
+    TangentOn,
+    /// Tangent mode off (G01 G46).
+    TangentOff,
+}
+
+impl Instruction {
+    /// Whether the instruction is a numbered block (`N..`) in the output.
+    pub fn is_numbered(&self) -> bool {
+        !matches!(
+            self,
+            Instruction::Raw(_)
+                | Instruction::Comment(_)
+                | Instruction::Label(_)
+                | Instruction::End
+        )
+    }
+}
+
+/// Instructions following a label, up to the next label.
+#[derive(Debug, Clone, Copy)]
+pub struct Block<'a> {
+    /// Label opening the block (`None` for the program prologue).
+    pub label: Option<&'a str>,
+    /// Instructions in the block, including the label itself.
+    pub instructions: &'a [Instruction],
+}
+
+/// A complete CNC program.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Program {
+    /// Line number of the first numbered block.
+    pub first_line: u32,
+    /// Line number increment.
+    pub increment: u32,
+    /// Instructions in program order.
+    pub instructions: Vec<Instruction>,
+}
+
+impl Default for Program {
+    /// Empty program numbered from N10 in steps of 10.
+    fn default() -> Self {
+        Self::new(10, 10)
+    }
+}
+
+impl Program {
+    /// Create an empty program.
+    pub fn new(first_line: u32, increment: u32) -> Self {
+        Self {
+            first_line,
+            increment,
+            instructions: Vec::new(),
+        }
+    }
+
+    /// Append an instruction.
+    pub fn push(&mut self, instruction: Instruction) {
+        self.instructions.push(instruction);
+    }
+
+    /// Number of numbered blocks.
+    pub fn numbered_count(&self) -> usize {
+        self.instructions.iter().filter(|i| i.is_numbered()).count()
+    }
+
+    /// Line number the next numbered block would get.
+    pub fn next_line(&self) -> u32 {
+        self.first_line + self.increment * self.numbered_count() as u32
+    }
+
+    /// Split the program into labeled blocks.
+    pub fn blocks(&self) -> Vec<Block<'_>> {
+        let mut blocks = Vec::new();
+        let mut start = 0;
+        let mut label = None;
+
+        for (idx, instruction) in self.instructions.iter().enumerate() {
+            if let Instruction::Label(name) = instruction {
+                if idx > start || label.is_some() {
+                    blocks.push(Block {
+                        label,
+                        instructions: &self.instructions[start..idx],
+                    });
+                }
+                start = idx;
+                label = Some(name.as_str());
+            }
+        }
+
+        if start < self.instructions.len() {
+            blocks.push(Block {
+                label,
+                instructions: &self.instructions[start..],
+            });
+        }
+
+        blocks
+    }
+
+    /// Find the block opened by a label.
+    pub fn block(&self, label: &str) -> Option<Block<'_>> {
+        self.blocks().into_iter().find(|b| b.label == Some(label))
+    }
+
+    /// Iterate over macro calls in program order.
+    pub fn macro_calls(&self) -> impl Iterator<Item = &str> {
+        self.instructions.iter().filter_map(|i| match i {
+            Instruction::MacroCall(name) => Some(name.as_str()),
+            _ => None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_program() -> Program {
+        let mut program = Program::new(20, 10);
+        program.push(Instruction::MacroCall("PRGINIT".to_string()));
+        program.push(Instruction::Label("0001".to_string()));
+        program.push(Instruction::SetTool(3));
+        program.push(Instruction::MacroCall("PT_GIU".to_string()));
+        program.push(Instruction::Label("999999999".to_string()));
+        program.push(Instruction::MacroCall("PFOXOUT".to_string()));
+        program.push(Instruction::End);
+        program
+    }
+
+    #[test]
+    fn test_program_blocks() {
+        let program = create_program();
+        let blocks = program.blocks();
+        assert_eq!(blocks.len(), 3);
+        assert_eq!(blocks[0].label, None);
+        assert_eq!(blocks[1].label, Some("0001"));
+        assert_eq!(blocks[1].instructions.len(), 3);
+        assert_eq!(blocks[2].instructions.last(), Some(&Instruction::End));
+    }
+
+    #[test]
+    fn test_program_numbering() {
+        let program = create_program();
+        // Labels and terminators are not numbered
+        assert_eq!(program.numbered_count(), 4);
+        assert_eq!(program.next_line(), 60);
+    }
+
+    #[test]
+    fn test_program_macro_calls() {
+        let program = create_program();
+        let calls: Vec<&str> = program.macro_calls().collect();
+        assert_eq!(calls, vec!["PRGINIT", "PT_GIU", "PFOXOUT"]);
+        assert!(program.block("0001").is_some());
+        assert!(program.block("0002").is_none());
+    }
+}
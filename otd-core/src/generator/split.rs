@@ -0,0 +1,252 @@
+//! Splitting multi-pattern OTD files into several CNI programs.
+//!
+//! Each program is generated by the regular pipeline on a subset of the
+//! schemas. `[*LDIST]` codes keep the pattern number from the source file,
+//! and a [`SplitManifest`] records which patterns went into which file.
+
+use crate::analysis::CycleTime;
+use crate::config::MachineConfig;
+use crate::error::Result;
+use crate::model::Schema;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use super::cni::{generate_for_patterns, IntermacCni};
+use super::post::PostProcessor;
+
+/// How to split the patterns of an OTD file into CNI programs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SplitMode {
+    /// One program per sheet format (unit, size and thickness).
+    PerSheet,
+    /// One program per pattern.
+    PerPattern,
+    /// One program per material (glass ID), further split by sheet format.
+    PerMaterial,
+}
+
+/// A generated program and the patterns it contains.
+#[derive(Debug, Clone)]
+pub struct CniProgram {
+    /// Output file name (`<stem>_NN.cni`).
+    pub file_name: String,
+    /// Material (glass ID) of the patterns.
+    pub material: String,
+    /// 1-based pattern numbers from the source file.
+    pub patterns: Vec<usize>,
+    /// Output file content.
+    pub content: String,
+    /// Estimated cycle time of each pattern.
+    pub cycle_time: CycleTime,
+}
+
+/// Manifest entry for one generated program.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    /// Output file name.
+    pub file: String,
+    /// Material (glass ID) of the patterns.
+    pub material: String,
+    /// 1-based pattern numbers from the source file.
+    pub patterns: Vec<usize>,
+}
+
+/// Lists which patterns of a source file went into which CNI program.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SplitManifest {
+    /// Source OTD file name.
+    pub source: String,
+    /// Split mode used.
+    pub mode: SplitMode,
+    /// Generated programs, in file order.
+    pub programs: Vec<ManifestEntry>,
+}
+
+impl SplitManifest {
+    /// Build the manifest for a set of generated programs.
+    pub fn new(source: &str, mode: SplitMode, programs: &[CniProgram]) -> Self {
+        Self {
+            source: source.to_string(),
+            mode,
+            programs: programs
+                .iter()
+                .map(|p| ManifestEntry {
+                    file: p.file_name.clone(),
+                    material: p.material.clone(),
+                    patterns: p.patterns.clone(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Serialize the manifest as pretty-printed JSON.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_default()
+    }
+}
+
+/// Group schema indices according to the split mode.
+///
+/// Groups are ordered by first appearance and keep the source pattern order.
+pub fn split_schemas(schemas: &[Schema], mode: SplitMode) -> Vec<Vec<usize>> {
+    match mode {
+        SplitMode::PerPattern => (0..schemas.len()).map(|idx| vec![idx]).collect(),
+        SplitMode::PerSheet => group_indices(schemas, |a, b| a.same_sheet(b)),
+        // A program holds a single sheet format, so materials are also split by sheet
+        SplitMode::PerMaterial => {
+            group_indices(schemas, |a, b| a.glass_id == b.glass_id && a.same_sheet(b))
+        }
+    }
+}
+
+/// Generate one CNI program per group of patterns.
+///
+/// Programs are named `<stem>_NN.cni` after the input file, numbered from 01.
+pub fn generate_split_cni(
+    schemas: &[Schema],
+    input_filename: &str,
+    config: &MachineConfig,
+    mode: SplitMode,
+) -> Result<Vec<CniProgram>> {
+    generate_split_with_post(schemas, input_filename, config, mode, &IntermacCni)
+}
+
+/// Generate one program per group of patterns for any post-processor.
+///
+/// Programs are named `<stem>_NN.<ext>` with the post-processor's extension.
+pub fn generate_split_with_post(
+    schemas: &[Schema],
+    input_filename: &str,
+    config: &MachineConfig,
+    mode: SplitMode,
+    post: &dyn PostProcessor,
+) -> Result<Vec<CniProgram>> {
+    let stem = Path::new(input_filename)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("output");
+
+    split_schemas(schemas, mode)
+        .into_iter()
+        .enumerate()
+        .map(|(program_idx, indices)| {
+            let subset: Vec<Schema> = indices.iter().map(|&i| schemas[i].clone()).collect();
+            let patterns: Vec<usize> = indices.iter().map(|&i| i + 1).collect();
+            let (content, cycle_time) =
+                generate_for_patterns(&subset, input_filename, config, &patterns, post)?;
+
+            Ok(CniProgram {
+                file_name: format!("{}_{:02}.{}", stem, program_idx + 1, post.extension()),
+                material: subset[0].glass_id.clone(),
+                patterns,
+                content,
+                cycle_time,
+            })
+        })
+        .collect()
+}
+
+/// Generate one CNI program per sheet format.
+///
+/// Patterns cut from the same sheet (unit, size and thickness) are grouped
+/// into one program, in order of first appearance. A file with a single
+/// sheet format yields exactly the output of [`generate_cni`](super::generate_cni).
+pub fn generate_cni_programs(
+    schemas: &[Schema],
+    input_filename: &str,
+    config: &MachineConfig,
+) -> Result<Vec<String>> {
+    let programs = generate_split_cni(schemas, input_filename, config, SplitMode::PerSheet)?;
+    Ok(programs.into_iter().map(|p| p.content).collect())
+}
+
+/// Group schemas by sheet format, keeping the original pattern order within each group.
+pub fn group_by_sheet(schemas: &[Schema]) -> Vec<Vec<Schema>> {
+    split_schemas(schemas, SplitMode::PerSheet)
+        .into_iter()
+        .map(|indices| indices.iter().map(|&i| schemas[i].clone()).collect())
+        .collect()
+}
+
+/// Group indices of schemas that compare equal, in order of first appearance.
+fn group_indices(schemas: &[Schema], same: impl Fn(&Schema, &Schema) -> bool) -> Vec<Vec<usize>> {
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+
+    for (idx, schema) in schemas.iter().enumerate() {
+        match groups.iter_mut().find(|g| same(&schemas[g[0]], schema)) {
+            Some(group) => group.push(idx),
+            None => groups.push(vec![idx]),
+        }
+    }
+
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_schema(glass_id: &str, thickness: f64) -> Schema {
+        Schema {
+            glass_id: glass_id.to_string(),
+            width: 3210.0,
+            height: 2250.0,
+            thickness,
+            ..Default::default()
+        }
+    }
+
+    fn create_schemas() -> Vec<Schema> {
+        vec![
+            create_schema("F4", 4.0),
+            create_schema("LOWE4", 4.0),
+            create_schema("F4", 4.0),
+            create_schema("F8", 8.0),
+        ]
+    }
+
+    #[test]
+    fn test_split_per_pattern() {
+        let groups = split_schemas(&create_schemas(), SplitMode::PerPattern);
+        assert_eq!(groups, vec![vec![0], vec![1], vec![2], vec![3]]);
+    }
+
+    #[test]
+    fn test_split_per_sheet() {
+        let groups = split_schemas(&create_schemas(), SplitMode::PerSheet);
+        assert_eq!(groups, vec![vec![0, 1, 2], vec![3]]);
+    }
+
+    #[test]
+    fn test_split_per_material() {
+        let groups = split_schemas(&create_schemas(), SplitMode::PerMaterial);
+        assert_eq!(groups, vec![vec![0, 2], vec![1], vec![3]]);
+    }
+
+    #[test]
+    fn test_generate_split_cni_names_and_codes() {
+        let config = MachineConfig::new(130);
+        let programs = generate_split_cni(
+            &create_schemas(),
+            "job.otd",
+            &config,
+            SplitMode::PerMaterial,
+        )
+        .unwrap();
+
+        let names: Vec<&str> = programs.iter().map(|p| p.file_name.as_str()).collect();
+        assert_eq!(names, vec!["job_01.cni", "job_02.cni", "job_03.cni"]);
+
+        // LDIST codes keep the source pattern numbers
+        assert!(programs[0].content.contains(";Cod=job1\n"));
+        assert!(programs[0].content.contains(";Cod=job3\n"));
+        assert!(programs[2].content.contains(";Cod=job4\n"));
+        assert!(programs[2].content.contains("[*LDIST0001_01]"));
+
+        let manifest = SplitManifest::new("job.otd", SplitMode::PerMaterial, &programs);
+        assert_eq!(manifest.programs[0].patterns, vec![1, 3]);
+        assert_eq!(manifest.programs[1].material, "LOWE4");
+        assert!(manifest.to_json().contains("\"mode\": \"per-material\""));
+    }
+}
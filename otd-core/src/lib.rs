@@ -15,8 +15,10 @@
 //! println!("{}", cni);
 //! ```
 
+pub mod analysis;
 pub mod config;
 pub mod error;
+pub mod export;
 pub mod generator;
 pub mod model;
 pub mod parser;
@@ -24,12 +26,28 @@ pub mod transform;
 pub mod validation;
 
 // Re-exports for convenience
-pub use config::{MachineConfig, Unit};
+pub use analysis::{
+    diff_cni, diff_cni_files, estimate_schemas, format_duration, layout_stats, CniDiff, CycleTime,
+    DiffOptions, LayoutStats,
+};
+pub use config::{CuttingOrder, MachineConfig, MachineKinematics, Unit};
 pub use error::{ConvertError, Result};
-pub use generator::generate_cni;
+pub use export::{
+    export_dxf, export_pdf, export_svg, labels_pdf, labels_zpl, piece_labels, render_toolpath,
+    LabelTemplate, PdfOptions, SvgOptions,
+};
+pub use generator::{
+    generate_cni, generate_cni_programs, generate_split_cni, generate_with_post, CniProgram,
+    IntermacCni, IsoPost, ManifestEntry, PostProcessor, SplitManifest, SplitMode,
+};
 pub use model::{Cut, CutType, LineType, Piece, PieceType, Schema, Shape};
-pub use parser::parse_otd_file;
-pub use validation::{validate_schemas, ValidationResult};
+pub use parser::{
+    decrypt_otx, encrypt_otx, import_dxf_shapes, import_dxf_shapes_file, parse_cni, parse_cni_file,
+    parse_otd_file, read_otd_source, CniFile, DxfImportOptions,
+};
+pub use validation::{
+    validate_schemas, Diagnostic, Severity, ValidationConfig, ValidationResult, Validator,
+};
 
 /// Convert an OTD file to CNI format.
 ///
@@ -48,6 +66,64 @@ pub use validation::{validate_schemas, ValidationResult};
 ///
 /// The generated CNI file content as a string.
 pub fn convert_otd_to_cni(input_path: &std::path::Path, machine_number: u16) -> Result<String> {
+    convert_otd_to_cni_with_config(input_path, &MachineConfig::new(machine_number))
+}
+
+/// Convert an OTD file to CNI format using a full machine configuration.
+///
+/// Same pipeline as [`convert_otd_to_cni`], for callers that need to override
+/// machine settings such as the cutting order.
+pub fn convert_otd_to_cni_with_config(
+    input_path: &std::path::Path,
+    config: &MachineConfig,
+) -> Result<String> {
+    let schemas = load_schemas(input_path)?;
+    generate_cni(&schemas, input_filename(input_path), config)
+}
+
+/// Convert an OTD file to one CNI program per sheet format.
+///
+/// A CNI program describes a single sheet size and thickness, so files whose
+/// patterns use different sheets are split. Files with one sheet format
+/// produce a single program identical to [`convert_otd_to_cni_with_config`].
+pub fn convert_otd_to_cni_programs(
+    input_path: &std::path::Path,
+    config: &MachineConfig,
+) -> Result<Vec<String>> {
+    let schemas = load_schemas(input_path)?;
+    generate_cni_programs(&schemas, input_filename(input_path), config)
+}
+
+/// Convert an OTD file to several CNI programs according to a split mode.
+///
+/// Programs are named after the input file (`<stem>_NN.cni`); use
+/// [`SplitManifest::new`] to record which patterns went into which program.
+pub fn convert_otd_to_split_cni(
+    input_path: &std::path::Path,
+    config: &MachineConfig,
+    mode: SplitMode,
+) -> Result<Vec<CniProgram>> {
+    convert_otd_with_post(input_path, config, mode, &IntermacCni)
+}
+
+/// Convert an OTD file with a post-processor for another controller dialect.
+///
+/// With `mode`, the file is split as in [`convert_otd_to_split_cni`].
+pub fn convert_otd_with_post(
+    input_path: &std::path::Path,
+    config: &MachineConfig,
+    mode: SplitMode,
+    post: &dyn PostProcessor,
+) -> Result<Vec<CniProgram>> {
+    let schemas = load_schemas(input_path)?;
+    generator::generate_split_with_post(&schemas, input_filename(input_path), config, mode, post)
+}
+
+/// Parse, transform and validate an OTD file.
+///
+/// The schemas are the ones the converter generates programs from: linear
+/// cuts are ordered and shapes normalised.
+pub fn load_schemas(input_path: &std::path::Path) -> Result<Vec<Schema>> {
     // Parse the OTD file
     let mut schemas = parse_otd_file(input_path)?;
 
@@ -69,12 +145,13 @@ pub fn convert_otd_to_cni(input_path: &std::path::Path, machine_number: u16) ->
         }
     }
 
-    // Generate CNI
-    let config = MachineConfig::new(machine_number);
-    let filename = input_path
+    Ok(schemas)
+}
+
+/// File name used in the generated [COMMENTO] and [*LDIST] sections.
+fn input_filename(input_path: &std::path::Path) -> &str {
+    input_path
         .file_name()
         .and_then(|s| s.to_str())
-        .unwrap_or("output.otd");
-
-    generate_cni(&schemas, filename, &config)
+        .unwrap_or("output.otd")
 }
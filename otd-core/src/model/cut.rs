@@ -71,6 +71,9 @@ pub struct Cut {
     pub num_pieces: i32,
     /// Whether this cut is a waste/scrap cut.
     pub is_scrap: bool,
+    /// Whether this cut is only scored: the piece or rest it leaves is too
+    /// small to be separated on the table.
+    pub score_only: bool,
     /// Parent shape index (-1 if not part of a shape).
     pub parent_shape: i32,
     /// Whether this cut is active/enabled.
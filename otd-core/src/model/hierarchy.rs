@@ -0,0 +1,29 @@
+//! HierarchyEntry - Nested strip coordinates as written in the OTD file.
+
+use serde::{Deserialize, Serialize};
+
+/// Coordinate variables of the nesting levels, from the outermost.
+pub const LEVEL_VARS: [char; 10] = ['X', 'Y', 'Z', 'W', 'V', 'A', 'B', 'C', 'D', 'E'];
+
+/// One `X=`/`Y=`/`Z=`... line of the [Pattern] hierarchy.
+///
+/// Even levels (X, Z, V, B, D) split along the width, odd levels (Y, W, A,
+/// C, E) along the height.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct HierarchyEntry {
+    /// Coordinate variable.
+    pub var: char,
+    /// Nesting level, 0 for X; levels from `LEVEL_VARS.len()` are not supported.
+    pub level: usize,
+    /// Strip dimension.
+    pub value: f64,
+    /// Line of the source file, from 1.
+    pub line: usize,
+}
+
+impl HierarchyEntry {
+    /// Whether the level is one of the supported coordinate variables.
+    pub fn is_supported(&self) -> bool {
+        self.level < LEVEL_VARS.len()
+    }
+}
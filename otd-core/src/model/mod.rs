@@ -1,12 +1,14 @@
 //! Data model types for OTD to CNI conversion.
 
 mod cut;
+mod hierarchy;
 mod piece;
 mod piece_type;
 mod schema;
 mod shape;
 
 pub use cut::{Cut, CutType, LineType};
+pub use hierarchy::{HierarchyEntry, LEVEL_VARS};
 pub use piece::Piece;
 pub use piece_type::PieceType;
 pub use schema::Schema;
@@ -1,6 +1,6 @@
 //! Schema - Complete cutting layout for one glass sheet.
 
-use super::{Cut, Piece, PieceType, Shape};
+use super::{Cut, HierarchyEntry, LineType, Piece, PieceType, Shape};
 use crate::config::Unit;
 use serde::{Deserialize, Serialize};
 
@@ -48,7 +48,7 @@ pub struct Schema {
     // === Cutting parameters ===
     /// Number of sheets to cut.
     pub quantity: u32,
-    /// Cutting order: 0=linear first, 1=shapes first.
+    /// Cutting order: 0=linear first, 1=shapes first (see `CuttingOrder`).
     pub cutting_order: u8,
     /// Linear advance/offset for cuts.
     pub linear_advance: f64,
@@ -86,6 +86,9 @@ pub struct Schema {
     pub piece_types: Vec<PieceType>,
     /// Shape definitions.
     pub shapes: Vec<Shape>,
+    /// Nested coordinates the linear cuts and pieces were built from.
+    #[serde(default)]
+    pub hierarchy: Vec<HierarchyEntry>,
 
     // === Flags ===
     /// Whether IndPiece was present in OTD.
@@ -181,6 +184,48 @@ impl Schema {
         self.height - self.trim_bottom
     }
 
+    /// Glass thickness in millimetres.
+    pub fn thickness_mm(&self) -> f64 {
+        self.thickness * self.unit.to_mm_factor()
+    }
+
+    /// Check whether any active linear cut is only scored.
+    pub fn has_score_only_cuts(&self) -> bool {
+        self.linear_cuts.iter().any(|c| c.active && c.score_only)
+    }
+
+    /// Check whether a linear cut is the left or bottom trim cut.
+    pub fn is_trim_cut(&self, cut: &Cut) -> bool {
+        use crate::config::float_cmp::approx_eq;
+
+        match cut.line_type {
+            LineType::Vertical => {
+                self.trim_left > 0.0
+                    && approx_eq(cut.xi, self.trim_left)
+                    && approx_eq(cut.xf, self.trim_left)
+            }
+            LineType::Horizontal => {
+                self.trim_bottom > 0.0
+                    && approx_eq(cut.yi, self.trim_bottom)
+                    && approx_eq(cut.yf, self.trim_bottom)
+            }
+            LineType::Oblique => false,
+        }
+    }
+
+    /// Check whether two schemas are cut from the same sheet format.
+    ///
+    /// The CNI `[PARAMETRI01]` header holds a single unit, sheet size and
+    /// thickness, so only schemas that agree on all of them can share a program.
+    pub fn same_sheet(&self, other: &Schema) -> bool {
+        use crate::config::float_cmp::approx_eq;
+
+        self.unit == other.unit
+            && approx_eq(self.width, other.width)
+            && approx_eq(self.height, other.height)
+            && approx_eq(self.thickness, other.thickness)
+    }
+
     /// Resolve piece indices to piece types and shapes.
     pub fn resolve_piece_references(&mut self) {
         for piece in &mut self.pieces {
@@ -208,6 +253,73 @@ impl Schema {
         }
     }
 
+    /// Share of the sheet area covered by pieces that are not waste, from 0 to 1.
+    pub fn yield_ratio(&self) -> f64 {
+        let sheet_area = self.width * self.height;
+        if sheet_area <= 0.0 {
+            return 0.0;
+        }
+        let piece_area: f64 = self
+            .pieces
+            .iter()
+            .filter(|piece| !self.is_waste_piece(piece))
+            .map(|piece| piece.width * piece.height)
+            .sum();
+        piece_area / sheet_area
+    }
+
+    /// Check whether a piece is marked as waste by its piece type.
+    pub fn is_waste_piece(&self, piece: &Piece) -> bool {
+        piece
+            .piece_type_index
+            .and_then(|idx| self.piece_types.get(idx))
+            .is_some_and(|pt| pt.waste)
+    }
+
+    /// Sheet areas not covered by any piece, as `(x1, y1, x2, y2)` rectangles.
+    ///
+    /// The usable sheet is divided into a grid along the active linear cuts;
+    /// every cell whose center lies outside all pieces is waste. Empty layouts
+    /// have no waste regions.
+    pub fn waste_regions(&self) -> Vec<(f64, f64, f64, f64)> {
+        if self.pieces.is_empty() {
+            return Vec::new();
+        }
+
+        let mut x_coords: Vec<f64> = vec![self.trim_left, self.width];
+        let mut y_coords: Vec<f64> = vec![self.trim_bottom, self.height];
+        for cut in self.linear_cuts.iter().filter(|c| c.active) {
+            if (cut.xi - cut.xf).abs() < 0.001 {
+                x_coords.push(cut.xi);
+            }
+            if (cut.yi - cut.yf).abs() < 0.001 {
+                y_coords.push(cut.yi);
+            }
+        }
+        x_coords.sort_by(|a, b| a.total_cmp(b));
+        y_coords.sort_by(|a, b| a.total_cmp(b));
+        x_coords.dedup_by(|a, b| (*a - *b).abs() < 0.01);
+        y_coords.dedup_by(|a, b| (*a - *b).abs() < 0.01);
+
+        let mut regions = Vec::new();
+        for xs in x_coords.windows(2) {
+            for ys in y_coords.windows(2) {
+                let center_x = (xs[0] + xs[1]) / 2.0;
+                let center_y = (ys[0] + ys[1]) / 2.0;
+                let covered = self.pieces.iter().any(|piece| {
+                    center_x >= piece.x_origin
+                        && center_x <= piece.x_origin + piece.width
+                        && center_y >= piece.y_origin
+                        && center_y <= piece.y_origin + piece.height
+                });
+                if !covered {
+                    regions.push((xs[0], ys[0], xs[1], ys[1]));
+                }
+            }
+        }
+        regions
+    }
+
     /// Get distribution of pieces by piece type.
     pub fn piece_distribution(&self) -> Vec<(i32, usize)> {
         let mut counts: std::collections::HashMap<i32, usize> = std::collections::HashMap::new();
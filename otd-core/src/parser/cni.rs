@@ -0,0 +1,859 @@
+//! CNI file reader.
+//!
+//! Reads CNI programs (ours, the legacy converter's or the machine PC's) back
+//! into typed structs. The `[CONTORNATURA01]` section is parsed into the same
+//! [`Program`] representation that [`CniEmitter`](crate::generator::CniEmitter)
+//! renders, so a parsed program re-emits to the original text.
+
+use crate::config::Unit;
+use crate::error::{ConvertError, Result};
+use crate::generator::{Instruction, Move, OffsetAxis, Param, ParamValue, Program};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// A parsed CNI file.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CniFile {
+    /// `[COMMENTO]` header.
+    pub comment: CniComment,
+    /// `[PARAMETRI01]` sheet and machine parameters.
+    pub parameters: Option<CniParameters>,
+    /// `[UTENSILI01]` tool codes.
+    pub tools: Vec<u16>,
+    /// `[CONTORNATURA01]` cutting program.
+    pub program: Program,
+    /// `[*LDIST]` piece distribution, one per pattern.
+    pub distributions: Vec<CniDistribution>,
+    /// `[*PRWB]`/`[*PRWC]` DXF previews.
+    pub previews: Vec<CniPreview>,
+}
+
+/// `[COMMENTO]` header fields.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CniComment {
+    /// Source project file name.
+    pub project: Option<String>,
+    /// Materials (glass IDs).
+    pub materials: Vec<String>,
+    /// Generating software.
+    pub creator: Option<String>,
+    /// Generator version.
+    pub version: Option<String>,
+}
+
+/// `[PARAMETRI01]` fields.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CniParameters {
+    /// Unit system (G71 metric, G70 inch).
+    pub unit: Unit,
+    /// Sheet width (LX).
+    pub width: f64,
+    /// Sheet height (LY).
+    pub height: f64,
+    /// Sheet thickness (LZ).
+    pub thickness: f64,
+    /// Machine type number (P103).
+    pub machine_number: u16,
+}
+
+/// `[*LDIST]` distribution of one pattern.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CniDistribution {
+    /// 1-based pattern number within the file (from the section name).
+    pub schema_num: usize,
+    /// Pattern code (`Cod`).
+    pub code: String,
+    /// Sheet width (`DimX`).
+    pub width: f64,
+    /// Sheet height (`DimY`).
+    pub height: f64,
+    /// Sheet thickness (`Spes`).
+    pub thickness: f64,
+    /// Number of sheets (`Qta`).
+    pub quantity: u32,
+    /// Glass type (`TipoVetro`).
+    pub glass_type: String,
+    /// Pieces by piece code.
+    pub pieces: Vec<CniDistributionPiece>,
+}
+
+/// Piece entry of a `[*LDIST]` section.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CniDistributionPiece {
+    /// Shape name (`NomeSagoma`), empty for rectangles.
+    pub shape_name: String,
+    /// Piece code (`CodPz`).
+    pub code: i32,
+    /// Piece width (`DimXPz`).
+    pub width: f64,
+    /// Piece height (`DimYPz`).
+    pub height: f64,
+    /// Number of pieces (`QtaPz`).
+    pub quantity: u32,
+    /// Customer (`ClientePz`).
+    pub customer: String,
+    /// Order number (`OrdinePz`).
+    pub order: String,
+}
+
+/// `[*PRWB]`/`[*PRWC]` DXF preview of one pattern.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CniPreview {
+    /// 1-based pattern number within the file.
+    pub schema_num: usize,
+    /// Mirrored C-side view (`PRWC`) rather than bottom view (`PRWB`).
+    pub mirrored: bool,
+    /// Entities of the ENTITIES section.
+    pub entities: Vec<DxfEntity>,
+}
+
+/// DXF entity of a preview.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DxfEntity {
+    /// Layer name (`EST`, `Tagli`, `TagliSag`, ...).
+    pub layer: String,
+    /// AutoCAD color index.
+    pub color: i32,
+    /// Entity geometry.
+    pub geometry: DxfGeometry,
+}
+
+/// Geometry of a DXF entity.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum DxfGeometry {
+    /// Straight line.
+    Line { x1: f64, y1: f64, x2: f64, y2: f64 },
+    /// Arc, angles in degrees counter-clockwise.
+    Arc {
+        cx: f64,
+        cy: f64,
+        radius: f64,
+        start_angle: f64,
+        end_angle: f64,
+    },
+    /// Filled quadrilateral.
+    Solid { corners: [(f64, f64); 4] },
+    /// Text label.
+    Text { x: f64, y: f64, text: String },
+}
+
+impl CniFile {
+    /// Number of patterns in the program (from `[*LDIST]` sections).
+    pub fn schema_count(&self) -> usize {
+        self.distributions.len()
+    }
+
+    /// Preview of a pattern.
+    pub fn preview(&self, schema_num: usize, mirrored: bool) -> Option<&CniPreview> {
+        self.previews
+            .iter()
+            .find(|p| p.schema_num == schema_num && p.mirrored == mirrored)
+    }
+}
+
+/// Parse a CNI file from disk.
+pub fn parse_cni_file(path: &Path) -> Result<CniFile> {
+    if !path.exists() {
+        return Err(ConvertError::FileNotFound {
+            path: path.to_path_buf(),
+        });
+    }
+
+    let content = std::fs::read_to_string(path)?;
+    if content.trim().is_empty() {
+        return Err(ConvertError::EmptyFile {
+            path: path.to_path_buf(),
+        });
+    }
+
+    parse_cni(&content)
+}
+
+/// Parse CNI content.
+pub fn parse_cni(content: &str) -> Result<CniFile> {
+    let mut file = CniFile::default();
+
+    for section in split_sections(content) {
+        let name = section.name;
+        if name == "COMMENTO" {
+            file.comment = parse_comment(&section);
+        } else if name.starts_with("PARAMETRI") {
+            file.parameters = parse_parameters(&section)?;
+        } else if name.starts_with("UTENSILI") {
+            file.tools = parse_tools(&section)?;
+        } else if name.starts_with("CONTORNATURA") {
+            file.program = parse_program(&section)?;
+        } else if let Some(num) = name.strip_prefix("*LDIST") {
+            let schema_num = parse_section_number(num, &section)?;
+            file.distributions
+                .push(parse_distribution(&section, schema_num)?);
+        } else if let Some(num) = name.strip_prefix("*PRWB") {
+            let schema_num = parse_section_number(num, &section)?;
+            file.previews
+                .push(parse_preview(&section, schema_num, false)?);
+        } else if let Some(num) = name.strip_prefix("*PRWC") {
+            let schema_num = parse_section_number(num, &section)?;
+            file.previews
+                .push(parse_preview(&section, schema_num, true)?);
+        }
+    }
+
+    Ok(file)
+}
+
+/// A `[NAME]` section and its lines.
+struct Section<'a> {
+    name: &'a str,
+    /// 1-based line number of the header.
+    header_line: usize,
+    lines: Vec<&'a str>,
+}
+
+impl Section<'_> {
+    /// Lines with their 1-based file line numbers.
+    fn numbered(&self) -> impl Iterator<Item = (usize, &str)> {
+        self.lines
+            .iter()
+            .enumerate()
+            .map(move |(idx, line)| (self.header_line + idx + 1, *line))
+    }
+
+    fn error(&self, line: usize, message: impl Into<String>) -> ConvertError {
+        ConvertError::ParseError {
+            line,
+            message: format!("[{}]: {}", self.name, message.into()),
+        }
+    }
+}
+
+fn split_sections(content: &str) -> Vec<Section<'_>> {
+    let mut sections: Vec<Section<'_>> = Vec::new();
+
+    for (idx, line) in content.lines().enumerate() {
+        let trimmed = line.trim_end();
+        if trimmed.starts_with('[') && trimmed.ends_with(']') && trimmed.len() > 2 {
+            sections.push(Section {
+                name: &trimmed[1..trimmed.len() - 1],
+                header_line: idx + 1,
+                lines: Vec::new(),
+            });
+        } else if let Some(section) = sections.last_mut() {
+            section.lines.push(line);
+        }
+    }
+
+    sections
+}
+
+/// Pattern number from a section suffix like `0001_01`.
+fn parse_section_number(suffix: &str, section: &Section<'_>) -> Result<usize> {
+    suffix
+        .split('_')
+        .next()
+        .and_then(|n| n.parse().ok())
+        .ok_or_else(|| section.error(section.header_line, "invalid pattern number"))
+}
+
+fn parse_comment(section: &Section<'_>) -> CniComment {
+    let mut comment = CniComment::default();
+
+    for line in &section.lines {
+        let Some(body) = line.trim().strip_prefix(';') else {
+            continue;
+        };
+        let Some((key, value)) = body.split_once(':') else {
+            continue;
+        };
+        let value = value.trim().to_string();
+        match key.trim() {
+            "Project" => comment.project = Some(value),
+            "Material" => comment.materials.push(value),
+            "Creator" => comment.creator = Some(value),
+            "Version" => comment.version = Some(value),
+            _ => {}
+        }
+    }
+
+    comment
+}
+
+fn parse_parameters(section: &Section<'_>) -> Result<Option<CniParameters>> {
+    for (line_num, line) in section.numbered() {
+        let Some((_, body)) = split_line_number(line.trim()) else {
+            continue;
+        };
+
+        let mut params = CniParameters {
+            unit: Unit::Millimeters,
+            width: 0.0,
+            height: 0.0,
+            thickness: 0.0,
+            machine_number: 0,
+        };
+        for word in body.split_whitespace() {
+            match word.split_once('=') {
+                None if word == "G70" => params.unit = Unit::Inches,
+                None if word == "G71" => params.unit = Unit::Millimeters,
+                Some(("LX", v)) => params.width = parse_number(v, line_num, section)?,
+                Some(("LY", v)) => params.height = parse_number(v, line_num, section)?,
+                Some(("LZ", v)) => params.thickness = parse_number(v, line_num, section)?,
+                Some(("P103", v)) => {
+                    params.machine_number = v
+                        .parse()
+                        .map_err(|_| section.error(line_num, format!("invalid P103 '{}'", v)))?
+                }
+                _ => {}
+            }
+        }
+        return Ok(Some(params));
+    }
+
+    Ok(None)
+}
+
+fn parse_tools(section: &Section<'_>) -> Result<Vec<u16>> {
+    let mut tools = Vec::new();
+
+    for (line_num, line) in section.numbered() {
+        let line = line.trim();
+        if line.is_empty() || line == "%" {
+            continue;
+        }
+        let tool = line
+            .parse()
+            .map_err(|_| section.error(line_num, format!("invalid tool code '{}'", line)))?;
+        tools.push(tool);
+    }
+
+    Ok(tools)
+}
+
+fn parse_program(section: &Section<'_>) -> Result<Program> {
+    let mut program = Program::new(10, 10);
+    let mut line_numbers = Vec::new();
+
+    // Drop the blank separator line(s) before the next section
+    let mut lines: Vec<(usize, &str)> = section.numbered().collect();
+    while lines.last().is_some_and(|(_, l)| l.trim().is_empty()) {
+        lines.pop();
+    }
+
+    for (line_num, line) in lines {
+        let instruction = if let Some((number, body)) = split_line_number(line) {
+            line_numbers.push(number);
+            parse_block(body, line_num, section)?
+        } else if line == "%" {
+            Instruction::End
+        } else if let Some(label) = line.strip_prefix(':') {
+            Instruction::Label(label.to_string())
+        } else if let Some(text) = line.strip_prefix("; ") {
+            Instruction::Comment(text.to_string())
+        } else {
+            Instruction::Raw(line.to_string())
+        };
+        program.push(instruction);
+    }
+
+    if let Some(first) = line_numbers.first() {
+        program.first_line = *first;
+    }
+    if let [first, second, ..] = line_numbers[..] {
+        program.increment = second.saturating_sub(first).max(1);
+    }
+
+    Ok(program)
+}
+
+/// Split `N123 body` into the line number and the body.
+fn split_line_number(line: &str) -> Option<(u32, &str)> {
+    let rest = line.strip_prefix('N')?;
+    let (number, body) = rest.split_once(' ')?;
+    Some((number.parse().ok()?, body))
+}
+
+/// Parse the body of a numbered block into an instruction.
+fn parse_block(body: &str, line_num: usize, section: &Section<'_>) -> Result<Instruction> {
+    let instruction = match body {
+        "G58" => Instruction::WorkOffset,
+        "G28" => Instruction::TangentOn,
+        "G01 G46" => Instruction::TangentOff,
+        "M=532" => Instruction::Direction { vertical: false },
+        "M=533" => Instruction::Direction { vertical: true },
+        _ => {
+            if let Some(name) = body.strip_prefix("L=") {
+                Instruction::MacroCall(name.to_string())
+            } else if let Some(label) = body.strip_prefix("L:") {
+                Instruction::CallLabel(label.to_string())
+            } else if let Some(label) = body.strip_prefix("JM:") {
+                Instruction::Jump {
+                    condition: None,
+                    label: label.to_string(),
+                }
+            } else if let Some(rest) = body.strip_prefix("JM(") {
+                let (condition, label) = rest
+                    .rsplit_once("):")
+                    .ok_or_else(|| section.error(line_num, format!("invalid jump '{}'", body)))?;
+                Instruction::Jump {
+                    condition: Some(condition.to_string()),
+                    label: label.to_string(),
+                }
+            } else if let Some(motion) = parse_motion(body, line_num, section)? {
+                motion
+            } else if let Some(assignment) = parse_assignment(body, line_num, section)? {
+                assignment
+            } else {
+                Instruction::Line(body.to_string())
+            }
+        }
+    };
+
+    Ok(instruction)
+}
+
+/// Parse `G00`/`G01`/`G02`/`G03` moves.
+fn parse_motion(body: &str, line_num: usize, section: &Section<'_>) -> Result<Option<Instruction>> {
+    let mut words = body.split_whitespace();
+    let Some(code) = words.next() else {
+        return Ok(None);
+    };
+    if !matches!(code, "G00" | "G01" | "G02" | "G03") {
+        return Ok(None);
+    }
+
+    let (mut x, mut y, mut i, mut j) = (None, None, None, None);
+    let (mut c, mut ar) = (None, None);
+    for word in words {
+        match word.split_once('=') {
+            Some(("X", v)) => x = Some(parse_number(v, line_num, section)?),
+            Some(("Y", v)) => y = Some(parse_number(v, line_num, section)?),
+            Some(("I", v)) => i = Some(parse_number(v, line_num, section)?),
+            Some(("J", v)) => j = Some(parse_number(v, line_num, section)?),
+            Some(("C", v)) => c = Some(v.to_string()),
+            Some(("AR", v)) => ar = Some(v.to_string()),
+            // Unknown words: keep the block verbatim
+            _ => return Ok(None),
+        }
+    }
+
+    let (Some(x), Some(y)) = (x, y) else {
+        return Ok(None);
+    };
+
+    let instruction = match (code, i, j) {
+        ("G00", None, None) => Instruction::Rapid(Move { x, y, c, ar }),
+        ("G01", None, None) if ar.is_none() => Instruction::Linear(Move { x, y, c, ar }),
+        ("G02" | "G03", Some(i), Some(j)) if c.is_none() && ar.is_none() => Instruction::Arc {
+            x,
+            y,
+            i,
+            j,
+            clockwise: code == "G02",
+        },
+        _ => return Ok(None),
+    };
+
+    Ok(Some(instruction))
+}
+
+/// Parse parameter, tool, rotation and offset assignments.
+fn parse_assignment(
+    body: &str,
+    line_num: usize,
+    section: &Section<'_>,
+) -> Result<Option<Instruction>> {
+    let Some((name, value)) = body.split_once('=') else {
+        return Ok(None);
+    };
+    if name.contains(' ') || value.contains(' ') {
+        return Ok(None);
+    }
+
+    let instruction = match name {
+        "P007" => Instruction::SetTool(
+            value
+                .parse()
+                .map_err(|_| section.error(line_num, format!("invalid tool '{}'", value)))?,
+        ),
+        "P539" => Instruction::SetRotation(parse_number(value, line_num, section)?),
+        "XO" => Instruction::Offset {
+            axis: OffsetAxis::X,
+            value: parse_number(value, line_num, section)?,
+        },
+        "YO" => Instruction::Offset {
+            axis: OffsetAxis::Y,
+            value: parse_number(value, line_num, section)?,
+        },
+        _ if name.starts_with('P') => {
+            let param = match name[1..].parse::<u16>() {
+                Ok(number) if name.len() == 4 => Param::Numbered(number),
+                _ => Param::Named(name.to_string()),
+            };
+            let value = match value.parse::<i64>() {
+                Ok(int) => ParamValue::Int(int),
+                Err(_) => ParamValue::Float(parse_number(value, line_num, section)?),
+            };
+            Instruction::SetParam { param, value }
+        }
+        _ => return Ok(None),
+    };
+
+    Ok(Some(instruction))
+}
+
+fn parse_distribution(section: &Section<'_>, schema_num: usize) -> Result<CniDistribution> {
+    let mut dist = CniDistribution {
+        schema_num,
+        ..Default::default()
+    };
+
+    for (line_num, line) in section.numbered() {
+        let Some((key, value)) = line
+            .trim()
+            .strip_prefix(';')
+            .and_then(|l| l.split_once('='))
+        else {
+            continue;
+        };
+
+        // NomeSagoma opens a new piece entry
+        if key == "NomeSagoma" {
+            dist.pieces.push(CniDistributionPiece {
+                shape_name: value.to_string(),
+                ..Default::default()
+            });
+            continue;
+        }
+
+        match (key, dist.pieces.last_mut()) {
+            ("Cod", _) => dist.code = value.to_string(),
+            ("DimX", _) => dist.width = parse_number(value, line_num, section)?,
+            ("DimY", _) => dist.height = parse_number(value, line_num, section)?,
+            ("Spes", _) => dist.thickness = parse_number(value, line_num, section)?,
+            ("Qta", _) => dist.quantity = parse_count(value, line_num, section)?,
+            ("TipoVetro", _) => dist.glass_type = value.to_string(),
+            ("CodPz", Some(piece)) => {
+                piece.code = value
+                    .parse()
+                    .map_err(|_| section.error(line_num, format!("invalid CodPz '{}'", value)))?
+            }
+            ("DimXPz", Some(piece)) => piece.width = parse_number(value, line_num, section)?,
+            ("DimYPz", Some(piece)) => piece.height = parse_number(value, line_num, section)?,
+            ("QtaPz", Some(piece)) => piece.quantity = parse_count(value, line_num, section)?,
+            ("ClientePz", Some(piece)) => piece.customer = value.to_string(),
+            ("OrdinePz", Some(piece)) => piece.order = value.to_string(),
+            _ => {}
+        }
+    }
+
+    Ok(dist)
+}
+
+fn parse_preview(section: &Section<'_>, schema_num: usize, mirrored: bool) -> Result<CniPreview> {
+    let lines: Vec<(usize, &str)> = section.numbered().collect();
+
+    // Group code / value pairs of the ENTITIES section
+    let mut pairs: Vec<(usize, i32, &str)> = Vec::new();
+    let mut idx = 0;
+    let mut in_entities = false;
+    while idx + 1 < lines.len() {
+        let (line_num, code_line) = lines[idx];
+        let Ok(code) = code_line.trim().parse::<i32>() else {
+            idx += 1;
+            continue;
+        };
+        let value = lines[idx + 1].1.trim();
+        idx += 2;
+
+        if code == 2 && value == "ENTITIES" {
+            in_entities = true;
+        } else if code == 0 && value == "ENDSEC" {
+            in_entities = false;
+        } else if in_entities {
+            pairs.push((line_num, code, value));
+        }
+    }
+
+    let mut entities = Vec::new();
+    let mut start = 0;
+    while start < pairs.len() {
+        let end = pairs[start + 1..]
+            .iter()
+            .position(|(_, code, _)| *code == 0)
+            .map_or(pairs.len(), |p| start + 1 + p);
+        if let Some(entity) = build_entity(&pairs[start..end], section)? {
+            entities.push(entity);
+        }
+        start = end;
+    }
+
+    Ok(CniPreview {
+        schema_num,
+        mirrored,
+        entities,
+    })
+}
+
+/// Build a DXF entity from its group codes (starting with code 0).
+fn build_entity(pairs: &[(usize, i32, &str)], section: &Section<'_>) -> Result<Option<DxfEntity>> {
+    let kind = pairs[0].2;
+    let mut layer = String::new();
+    let mut color = 0;
+    let mut text = String::new();
+    let mut values = [0.0_f64; 100];
+
+    for &(line_num, code, value) in &pairs[1..] {
+        match code {
+            1 => text = value.to_string(),
+            8 => layer = value.to_string(),
+            62 => {
+                color = value
+                    .parse()
+                    .map_err(|_| section.error(line_num, format!("invalid color '{}'", value)))?
+            }
+            10..=59 => values[code as usize] = parse_number(value, line_num, section)?,
+            _ => {}
+        }
+    }
+
+    let geometry = match kind {
+        "LINE" => DxfGeometry::Line {
+            x1: values[10],
+            y1: values[20],
+            x2: values[11],
+            y2: values[21],
+        },
+        "ARC" => DxfGeometry::Arc {
+            cx: values[10],
+            cy: values[20],
+            radius: values[40],
+            start_angle: values[50],
+            end_angle: values[51],
+        },
+        "SOLID" => DxfGeometry::Solid {
+            corners: [
+                (values[10], values[20]),
+                (values[11], values[21]),
+                (values[12], values[22]),
+                (values[13], values[23]),
+            ],
+        },
+        "TEXT" => DxfGeometry::Text {
+            x: values[10],
+            y: values[20],
+            text,
+        },
+        _ => return Ok(None),
+    };
+
+    Ok(Some(DxfEntity {
+        layer,
+        color,
+        geometry,
+    }))
+}
+
+fn parse_number(value: &str, line: usize, section: &Section<'_>) -> Result<f64> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| section.error(line, format!("invalid number '{}'", value)))
+}
+
+fn parse_count(value: &str, line: usize, section: &Section<'_>) -> Result<u32> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| section.error(line, format!("invalid quantity '{}'", value)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::CniEmitter;
+
+    const SAMPLE: &str = "[COMMENTO]
+; Project: job.otd
+; Material : F4
+; Creator: otd-convert-rs
+; Version: 0.1.0
+
+[CENTRO01]
+
+[PARAMETRI01]
+N10 G71 LX=3210 LY=2250 LZ=4 P103=130
+%
+
+[UTENSILI01]
+0003
+%
+
+[CONTORNATURA01]
+N20 P015=1
+N30 L=PRGINIT
+N40 JM:(P262)
+
+; --- inizio Schema=1 Lastre=1
+:0001
+N50 PXRS=10.5
+N60 JM((P260=2)~(P007=0003)):010001
+:010001
+N70 P007=0003
+N80 P539=90
+N90 G00 X=100 Y=0 C=P540
+N100 M=533
+N110 G01 X=100 Y=2250 C=P540
+N120 G03 X=1 Y=2 I=3 J=4
+
+:999999999
+N130 L=PFOXOUT
+%
+
+[*LDIST0001_01]
+;Cod=job1
+;DimX=3210
+;DimY=2250
+;Spes=4
+;Qta=2
+;TipoVetro=F4
+;NomeSagoma=
+;CodPz=1
+;DimXPz=100
+;DimYPz=200
+;QtaPz=3
+;ClientePz=ACME
+;OrdinePz=42
+%
+
+[*PRWB0001_01]
+  0
+SECTION
+  2
+ENTITIES
+0
+LINE
+8
+Tagli
+62
+140
+10
+100.000
+20
+0.000
+30
+0.000
+11
+100.000
+21
+2250.000
+31
+0.000
+  0
+TEXT
+  8
+Cliente
+ 62
+5
+ 10
+12.000
+ 20
+34.000
+ 30
+0.000
+  1
+ACME
+  0
+ENDSEC
+  0
+EOF
+%%
+";
+
+    #[test]
+    fn test_parse_header_sections() {
+        let cni = parse_cni(SAMPLE).unwrap();
+        assert_eq!(cni.comment.project.as_deref(), Some("job.otd"));
+        assert_eq!(cni.comment.materials, vec!["F4"]);
+
+        let params = cni.parameters.unwrap();
+        assert_eq!(params.unit, Unit::Millimeters);
+        assert_eq!(params.width, 3210.0);
+        assert_eq!(params.thickness, 4.0);
+        assert_eq!(params.machine_number, 130);
+        assert_eq!(cni.tools, vec![3]);
+    }
+
+    #[test]
+    fn test_parse_program() {
+        let cni = parse_cni(SAMPLE).unwrap();
+        let program = &cni.program;
+        assert_eq!(program.first_line, 20);
+        assert_eq!(program.increment, 10);
+        assert!(program.instructions.contains(&Instruction::SetTool(3)));
+        assert!(program.instructions.contains(&Instruction::Jump {
+            condition: Some("(P260=2)~(P007=0003)".to_string()),
+            label: "010001".to_string(),
+        }));
+        assert!(program.block("010001").is_some());
+        assert!(program.instructions.contains(&Instruction::Arc {
+            x: 1.0,
+            y: 2.0,
+            i: 3.0,
+            j: 4.0,
+            clockwise: false,
+        }));
+    }
+
+    #[test]
+    fn test_program_round_trip() {
+        let cni = parse_cni(SAMPLE).unwrap();
+        let start = SAMPLE.find("N20 ").unwrap();
+        let end = SAMPLE.find("[*LDIST").unwrap();
+        assert_eq!(
+            CniEmitter.emit(&cni.program),
+            SAMPLE[start..end].trim_end().to_string() + "\n"
+        );
+    }
+
+    #[test]
+    fn test_parse_distribution() {
+        let cni = parse_cni(SAMPLE).unwrap();
+        assert_eq!(cni.schema_count(), 1);
+        let dist = &cni.distributions[0];
+        assert_eq!(dist.schema_num, 1);
+        assert_eq!(dist.code, "job1");
+        assert_eq!(dist.quantity, 2);
+        assert_eq!(dist.pieces.len(), 1);
+        assert_eq!(dist.pieces[0].quantity, 3);
+        assert_eq!(dist.pieces[0].customer, "ACME");
+    }
+
+    #[test]
+    fn test_parse_preview_entities() {
+        let cni = parse_cni(SAMPLE).unwrap();
+        let preview = cni.preview(1, false).unwrap();
+        assert_eq!(preview.entities.len(), 2);
+        assert_eq!(preview.entities[0].layer, "Tagli");
+        assert_eq!(preview.entities[0].color, 140);
+        assert_eq!(
+            preview.entities[1].geometry,
+            DxfGeometry::Text {
+                x: 12.0,
+                y: 34.0,
+                text: "ACME".to_string()
+            }
+        );
+        assert!(cni.preview(1, true).is_none());
+    }
+
+    #[test]
+    fn test_parse_invalid_number() {
+        let content = "[PARAMETRI01]\nN10 G71 LX=abc LY=1 LZ=1 P103=130\n%\n";
+        match parse_cni(content) {
+            Err(ConvertError::ParseError { line, message }) => {
+                assert_eq!(line, 2);
+                assert!(message.contains("abc"));
+            }
+            other => panic!("Expected ParseError, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,668 @@
+//! DXF template reader for shape contours.
+//!
+//! Customers send new shapes as DXF drawings. The reader takes the LINE, ARC,
+//! LWPOLYLINE (with bulges) and CIRCLE entities of one layer, chains them into
+//! closed contours and turns every contour into a [`Shape`] built from
+//! [`Cut::new_line`], [`Cut::new_arc_cw`] and [`Cut::new_arc_ccw`] segments.
+//!
+//! Endpoints closer than the gap tolerance are joined, contours are turned
+//! clockwise like the shapes of the optimizer, and arcs wider than 180° are
+//! split because a cut arc is always the minor arc between its endpoints.
+
+use std::f64::consts::{PI, TAU};
+use std::path::Path;
+
+use crate::config::EPS;
+use crate::error::{ConvertError, Result};
+use crate::model::{Cut, Shape};
+
+/// Default gap tolerance between chained endpoints, in drawing units.
+pub const DEFAULT_GAP_TOLERANCE: f64 = 0.01;
+
+/// Options for importing shapes from a DXF template.
+#[derive(Debug, Clone)]
+pub struct DxfImportOptions {
+    /// Layer holding the contours (compared case-insensitively, as in CAD).
+    pub layer: String,
+    /// Largest gap between two endpoints that still chains them.
+    pub tolerance: f64,
+    /// Factor from drawing units to layout units (e.g. 1/25.4 for mm to inches).
+    pub scale: f64,
+    /// Move every contour so that its bounding box starts at the origin.
+    pub move_to_origin: bool,
+}
+
+impl DxfImportOptions {
+    /// Options reading the given layer with the default tolerance.
+    pub fn new(layer: impl Into<String>) -> Self {
+        Self {
+            layer: layer.into(),
+            tolerance: DEFAULT_GAP_TOLERANCE,
+            scale: 1.0,
+            move_to_origin: true,
+        }
+    }
+}
+
+/// Import the shape contours of a DXF file.
+pub fn import_dxf_shapes_file(path: &Path, options: &DxfImportOptions) -> Result<Vec<Shape>> {
+    if !path.exists() {
+        return Err(ConvertError::FileNotFound {
+            path: path.to_path_buf(),
+        });
+    }
+
+    let content = std::fs::read_to_string(path)?;
+    if content.trim().is_empty() {
+        return Err(ConvertError::EmptyFile {
+            path: path.to_path_buf(),
+        });
+    }
+
+    import_dxf_shapes(&content, options)
+}
+
+/// Import the shape contours of DXF content.
+///
+/// Shapes are numbered from 1 in the order their first entity appears.
+pub fn import_dxf_shapes(content: &str, options: &DxfImportOptions) -> Result<Vec<Shape>> {
+    let pairs = entity_pairs(content);
+
+    let mut edges = Vec::new();
+    let mut start = 0;
+    while start < pairs.len() {
+        let end = pairs[start + 1..]
+            .iter()
+            .position(|(_, code, _)| *code == 0)
+            .map_or(pairs.len(), |p| start + 1 + p);
+        read_entity(&pairs[start..end], options, &mut edges)?;
+        start = end;
+    }
+
+    // Segments shorter than the tolerance are gaps to bridge, not geometry
+    edges.retain(|edge| edge.is_loop() || edge.chord() > options.tolerance);
+    if edges.is_empty() {
+        return Err(ConvertError::InvalidValue {
+            field: "layer".to_string(),
+            expected: "a layer with LINE, ARC, LWPOLYLINE or CIRCLE entities".to_string(),
+            value: options.layer.clone(),
+        });
+    }
+
+    let mut shapes = Vec::new();
+    for mut contour in chain_contours(edges, options.tolerance)? {
+        if signed_area(&contour) > 0.0 {
+            contour = reverse_contour(contour);
+        }
+
+        let mut shape = Shape::new(shapes.len() as i32 + 1);
+        shape.name = options.layer.clone();
+        shape.description = format!("DXF {}", options.layer);
+        for edge in &contour {
+            edge.push_cuts(&mut shape.cuts);
+        }
+
+        if options.move_to_origin {
+            let (min_x, min_y, _, _) = contour_bounds(&contour);
+            for cut in &mut shape.cuts {
+                cut.xi -= min_x;
+                cut.yi -= min_y;
+                cut.xf -= min_x;
+                cut.yf -= min_y;
+                cut.xc -= min_x;
+                cut.yc -= min_y;
+            }
+        }
+
+        shape.calculate_perimeter();
+        shape.is_open = !shape.is_closed();
+        shapes.push(shape);
+    }
+
+    Ok(shapes)
+}
+
+type Point = (f64, f64);
+
+/// Arc part of an edge: counter-clockwise when the sweep is positive.
+#[derive(Debug, Clone, Copy)]
+struct ArcGeometry {
+    center: Point,
+    radius: f64,
+    sweep: f64,
+}
+
+/// Line or arc between two points, tagged with the line of its entity.
+#[derive(Debug, Clone, Copy)]
+struct Edge {
+    start: Point,
+    end: Point,
+    arc: Option<ArcGeometry>,
+    line: usize,
+}
+
+impl Edge {
+    fn chord(&self) -> f64 {
+        distance(self.start, self.end)
+    }
+
+    /// Full circle, a contour on its own.
+    fn is_loop(&self) -> bool {
+        self.arc.is_some_and(|arc| arc.sweep.abs() >= TAU - EPS)
+    }
+
+    fn reversed(self) -> Self {
+        Self {
+            start: self.end,
+            end: self.start,
+            arc: self.arc.map(|arc| ArcGeometry {
+                sweep: -arc.sweep,
+                ..arc
+            }),
+            line: self.line,
+        }
+    }
+
+    /// Append the cuts of this edge, splitting arcs wider than 180°.
+    fn push_cuts(&self, cuts: &mut Vec<Cut>) {
+        let Some(arc) = self.arc else {
+            cuts.push(Cut::new_line(
+                self.start.0,
+                self.start.1,
+                self.end.0,
+                self.end.1,
+            ));
+            return;
+        };
+
+        let parts = (arc.sweep.abs() / PI - EPS).ceil().max(1.0) as usize;
+        let start_angle = (self.start.1 - arc.center.1).atan2(self.start.0 - arc.center.0);
+        let step = arc.sweep / parts as f64;
+        let mut from = self.start;
+        for part in 1..=parts {
+            let to = if part == parts {
+                self.end
+            } else {
+                let angle = start_angle + step * part as f64;
+                (
+                    arc.center.0 + arc.radius * angle.cos(),
+                    arc.center.1 + arc.radius * angle.sin(),
+                )
+            };
+            cuts.push(if arc.sweep > 0.0 {
+                Cut::new_arc_ccw(from.0, from.1, to.0, to.1, arc.radius)
+            } else {
+                Cut::new_arc_cw(from.0, from.1, to.0, to.1, arc.radius)
+            });
+            from = to;
+        }
+    }
+}
+
+/// Group code / value pairs of the ENTITIES section, with their line numbers.
+fn entity_pairs(content: &str) -> Vec<(usize, i32, &str)> {
+    let lines: Vec<&str> = content.lines().collect();
+    let mut pairs = Vec::new();
+    let mut in_entities = false;
+    let mut idx = 0;
+    while idx + 1 < lines.len() {
+        let Ok(code) = lines[idx].trim().parse::<i32>() else {
+            idx += 1;
+            continue;
+        };
+        let value = lines[idx + 1].trim();
+        let line_num = idx + 1;
+        idx += 2;
+
+        if code == 2 && value == "ENTITIES" {
+            in_entities = true;
+        } else if code == 0 && value == "ENDSEC" {
+            in_entities = false;
+        } else if in_entities {
+            pairs.push((line_num, code, value));
+        }
+    }
+    pairs
+}
+
+fn parse_number(line: usize, value: &str) -> Result<f64> {
+    value.parse().map_err(|_| ConvertError::InvalidNumber {
+        line,
+        value: value.to_string(),
+    })
+}
+
+/// Read one entity of the import layer into edges.
+fn read_entity(
+    pairs: &[(usize, i32, &str)],
+    options: &DxfImportOptions,
+    edges: &mut Vec<Edge>,
+) -> Result<()> {
+    let (line, _, kind) = pairs[0];
+    if !matches!(kind, "LINE" | "ARC" | "CIRCLE" | "LWPOLYLINE") {
+        return Ok(());
+    }
+    let on_layer = pairs
+        .iter()
+        .any(|&(_, code, value)| code == 8 && value.eq_ignore_ascii_case(&options.layer));
+    if !on_layer {
+        return Ok(());
+    }
+
+    let mut values = [0.0_f64; 60];
+    // Arcs, circles and polylines drawn with a flipped extrusion direction
+    // are mirrored in X; lines are already in world coordinates
+    let mut extrusion_z = 1.0;
+    let mut closed = false;
+    // LWPOLYLINE vertices as (x, y, bulge)
+    let mut vertices: Vec<(f64, f64, f64)> = Vec::new();
+
+    for &(line_num, code, value) in &pairs[1..] {
+        match code {
+            10 if kind == "LWPOLYLINE" => {
+                vertices.push((parse_number(line_num, value)?, 0.0, 0.0));
+            }
+            20 if kind == "LWPOLYLINE" => {
+                if let Some(vertex) = vertices.last_mut() {
+                    vertex.1 = parse_number(line_num, value)?;
+                }
+            }
+            42 => {
+                if let Some(vertex) = vertices.last_mut() {
+                    vertex.2 = parse_number(line_num, value)?;
+                }
+            }
+            70 => closed = value.parse::<i32>().unwrap_or(0) & 1 != 0,
+            230 => extrusion_z = parse_number(line_num, value)?,
+            10..=59 => values[code as usize] = parse_number(line_num, value)?,
+            _ => {}
+        }
+    }
+
+    let mirror = if extrusion_z < 0.0 { -1.0 } else { 1.0 };
+    let scale = options.scale;
+    let point = |x: f64, y: f64| (x * scale, y * scale);
+    let ocs_point = |x: f64, y: f64| (mirror * x * scale, y * scale);
+
+    match kind {
+        "LINE" => edges.push(Edge {
+            start: point(values[10], values[20]),
+            end: point(values[11], values[21]),
+            arc: None,
+            line,
+        }),
+        "CIRCLE" => {
+            let center = ocs_point(values[10], values[20]);
+            let radius = values[40] * scale;
+            let start = (center.0 + radius, center.1);
+            edges.push(Edge {
+                start,
+                end: start,
+                arc: Some(ArcGeometry {
+                    center,
+                    radius,
+                    sweep: TAU,
+                }),
+                line,
+            });
+        }
+        "ARC" => {
+            let center = ocs_point(values[10], values[20]);
+            let radius = values[40] * scale;
+            // Arcs run counter-clockwise from 50 to 51; mirroring flips both
+            let (start_deg, end_deg) = if mirror < 0.0 {
+                (180.0 - values[51], 180.0 - values[50])
+            } else {
+                (values[50], values[51])
+            };
+            let mut sweep = (end_deg - start_deg).to_radians().rem_euclid(TAU);
+            if sweep < EPS {
+                sweep = TAU;
+            }
+            let start_angle = start_deg.to_radians();
+            let end_angle = start_angle + sweep;
+            edges.push(Edge {
+                start: (
+                    center.0 + radius * start_angle.cos(),
+                    center.1 + radius * start_angle.sin(),
+                ),
+                end: (
+                    center.0 + radius * end_angle.cos(),
+                    center.1 + radius * end_angle.sin(),
+                ),
+                arc: Some(ArcGeometry {
+                    center,
+                    radius,
+                    sweep,
+                }),
+                line,
+            });
+        }
+        _ => {
+            let count = vertices.len();
+            let segments = if closed {
+                count
+            } else {
+                count.saturating_sub(1)
+            };
+            for idx in 0..segments {
+                let (x1, y1, bulge) = vertices[idx];
+                let (x2, y2, _) = vertices[(idx + 1) % count];
+                let start = ocs_point(x1, y1);
+                let end = ocs_point(x2, y2);
+                edges.push(Edge {
+                    start,
+                    end,
+                    arc: bulge_arc(start, end, mirror * bulge),
+                    line,
+                });
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Arc of a polyline segment from its bulge (tangent of a quarter of the sweep,
+/// positive counter-clockwise).
+fn bulge_arc(start: Point, end: Point, bulge: f64) -> Option<ArcGeometry> {
+    let chord = distance(start, end);
+    if bulge.abs() < EPS || chord < EPS {
+        return None;
+    }
+
+    let sweep = 4.0 * bulge.atan();
+    let radius = chord * (1.0 + bulge * bulge) / (4.0 * bulge.abs());
+    // Signed distance from the chord midpoint to the center, left of the chord
+    let offset = chord * (1.0 - bulge * bulge) / (4.0 * bulge);
+    let (ux, uy) = ((end.0 - start.0) / chord, (end.1 - start.1) / chord);
+    let center = (
+        (start.0 + end.0) / 2.0 - uy * offset,
+        (start.1 + end.1) / 2.0 + ux * offset,
+    );
+
+    Some(ArcGeometry {
+        center,
+        radius,
+        sweep,
+    })
+}
+
+/// Chain edges into closed contours, joining endpoints within the tolerance.
+fn chain_contours(mut pool: Vec<Edge>, tolerance: f64) -> Result<Vec<Vec<Edge>>> {
+    let mut contours = Vec::new();
+
+    while !pool.is_empty() {
+        let first = pool.remove(0);
+        let origin = first.start;
+        let mut contour = vec![first];
+
+        while distance(contour[contour.len() - 1].end, origin) > tolerance {
+            let last = contour[contour.len() - 1];
+            let next = pool
+                .iter()
+                .enumerate()
+                .filter(|(_, edge)| !edge.is_loop())
+                .flat_map(|(idx, edge)| {
+                    [
+                        (idx, false, distance(last.end, edge.start)),
+                        (idx, true, distance(last.end, edge.end)),
+                    ]
+                })
+                .filter(|&(_, _, gap)| gap <= tolerance)
+                .min_by(|a, b| a.2.total_cmp(&b.2));
+
+            let Some((idx, reverse, _)) = next else {
+                return Err(ConvertError::ParseError {
+                    line: last.line,
+                    message: format!(
+                        "open contour: no entity continues from ({:.4}, {:.4}) within {}",
+                        last.end.0, last.end.1, tolerance
+                    ),
+                });
+            };
+
+            let mut edge = pool.remove(idx);
+            if reverse {
+                edge = edge.reversed();
+            }
+            edge.start = last.end;
+            contour.push(edge);
+        }
+
+        // Close the remaining gap exactly on the start point
+        if let Some(last) = contour.last_mut() {
+            last.end = origin;
+        }
+        contours.push(contour);
+    }
+
+    Ok(contours)
+}
+
+/// Signed area of a contour, positive when it runs counter-clockwise.
+fn signed_area(contour: &[Edge]) -> f64 {
+    contour
+        .iter()
+        .map(|edge| {
+            let chord = (edge.start.0 * edge.end.1 - edge.end.0 * edge.start.1) / 2.0;
+            // Circular segment between the chord and the arc
+            let bulge = edge.arc.map_or(0.0, |arc| {
+                let sweep = arc.sweep.abs();
+                arc.sweep.signum() * arc.radius * arc.radius * (sweep - sweep.sin()) / 2.0
+            });
+            chord + bulge
+        })
+        .sum()
+}
+
+fn reverse_contour(contour: Vec<Edge>) -> Vec<Edge> {
+    contour.into_iter().rev().map(Edge::reversed).collect()
+}
+
+/// Exact bounding box of a contour, including arc extremes.
+fn contour_bounds(contour: &[Edge]) -> (f64, f64, f64, f64) {
+    let mut bounds = (f64::MAX, f64::MAX, f64::MIN, f64::MIN);
+    let mut include = |(x, y): Point| {
+        bounds.0 = bounds.0.min(x);
+        bounds.1 = bounds.1.min(y);
+        bounds.2 = bounds.2.max(x);
+        bounds.3 = bounds.3.max(y);
+    };
+
+    for edge in contour {
+        include(edge.start);
+        include(edge.end);
+        if let Some(arc) = edge.arc {
+            let start = (edge.start.1 - arc.center.1).atan2(edge.start.0 - arc.center.0);
+            for quadrant in 0..4 {
+                let angle = quadrant as f64 * PI / 2.0;
+                // Offset of the quadrant point along the sweep direction
+                let offset = if arc.sweep > 0.0 {
+                    (angle - start).rem_euclid(TAU)
+                } else {
+                    (start - angle).rem_euclid(TAU)
+                };
+                if offset <= arc.sweep.abs() {
+                    include((
+                        arc.center.0 + arc.radius * angle.cos(),
+                        arc.center.1 + arc.radius * angle.sin(),
+                    ));
+                }
+            }
+        }
+    }
+
+    bounds
+}
+
+fn distance(a: Point, b: Point) -> f64 {
+    (b.0 - a.0).hypot(b.1 - a.1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::CutType;
+
+    /// Wrap entity group codes into a minimal DXF file.
+    fn dxf(entities: &[&str]) -> String {
+        let mut content = String::from("  0\nSECTION\n  2\nENTITIES\n");
+        for entity in entities {
+            content.push_str(entity);
+        }
+        content.push_str("  0\nENDSEC\n  0\nEOF\n");
+        content
+    }
+
+    fn line(layer: &str, x1: f64, y1: f64, x2: f64, y2: f64) -> String {
+        format!("  0\nLINE\n  8\n{layer}\n 10\n{x1}\n 20\n{y1}\n 11\n{x2}\n 21\n{y2}\n")
+    }
+
+    fn assert_chained(shape: &Shape) {
+        for pair in shape.cuts.windows(2) {
+            assert!(distance((pair[0].xf, pair[0].yf), (pair[1].xi, pair[1].yi)) < EPS);
+        }
+        assert!(shape.is_closed());
+        assert!(!shape.is_open);
+    }
+
+    #[test]
+    fn test_lines_chain_into_clockwise_contour() {
+        // Counter-clockwise square drawn out of order, one line reversed, small gap
+        let content = dxf(&[
+            &line("CUT", 10.0, 0.0, 10.0, 10.0),
+            &line("CUT", 0.0, 0.0, 10.0, 0.0),
+            &line("CUT", 0.0, 10.0, 0.0, 0.005),
+            &line("CUT", 10.0, 10.0, 0.0, 10.0),
+            &line("OTHER", 50.0, 50.0, 60.0, 60.0),
+        ]);
+
+        let shapes = import_dxf_shapes(&content, &DxfImportOptions::new("cut")).unwrap();
+        assert_eq!(shapes.len(), 1);
+        let shape = &shapes[0];
+        assert_eq!(shape.id, 1);
+        assert_eq!(shape.cuts.len(), 4);
+        assert_chained(shape);
+        assert!(shape.cuts.iter().all(|c| c.cut_type == CutType::Line));
+        assert!(signed_area_of(shape) < 0.0);
+        assert!((shape.perimeter - 40.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_bulged_polyline_and_circle() {
+        // Slot: two straight sides and two semicircular ends, plus a hole
+        let polyline = "  0\nLWPOLYLINE\n  8\nCUT\n 90\n4\n 70\n1\n\
+                        10\n10\n 20\n0\n 10\n30\n 20\n0\n 42\n1\n\
+                        10\n30\n 20\n20\n 10\n10\n 20\n20\n 42\n1\n";
+        let circle = "  0\nCIRCLE\n  8\nCUT\n 10\n100\n 20\n100\n 40\n5\n";
+        let content = dxf(&[polyline, circle]);
+
+        let shapes = import_dxf_shapes(&content, &DxfImportOptions::new("CUT")).unwrap();
+        assert_eq!(shapes.len(), 2);
+
+        let slot = &shapes[0];
+        assert_chained(slot);
+        assert_eq!(slot.cuts.len(), 4);
+        assert_eq!(
+            slot.cuts
+                .iter()
+                .filter(|c| c.cut_type == CutType::ArcCW)
+                .count(),
+            2
+        );
+        let expected = 2.0 * 20.0 + 2.0 * PI * 10.0;
+        assert!((slot.perimeter - expected).abs() < 0.001);
+        assert!((slot.width() - 40.0).abs() < 0.001);
+        assert!((slot.height() - 20.0).abs() < 0.001);
+
+        let hole = &shapes[1];
+        assert_chained(hole);
+        assert_eq!(hole.cuts.len(), 2);
+        assert!(hole.cuts.iter().all(|c| c.cut_type == CutType::ArcCW));
+        assert!((hole.perimeter - 2.0 * PI * 5.0).abs() < 0.001);
+        // Moved to the origin: the circle center ends up at (r, r)
+        assert!((hole.cuts[0].xc - 5.0).abs() < 0.001);
+        assert!((hole.cuts[0].yc - 5.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_wide_arc_is_split() {
+        // 270° arc closed by two radii
+        let arc = "  0\nARC\n  8\nCUT\n 10\n0\n 20\n0\n 40\n10\n 50\n0\n 51\n270\n";
+        let content = dxf(&[
+            arc,
+            &line("CUT", 0.0, -10.0, 0.0, 0.0),
+            &line("CUT", 0.0, 0.0, 10.0, 0.0),
+        ]);
+
+        let options = DxfImportOptions {
+            move_to_origin: false,
+            ..DxfImportOptions::new("CUT")
+        };
+        let shapes = import_dxf_shapes(&content, &options).unwrap();
+        let shape = &shapes[0];
+        assert_chained(shape);
+        let arcs: Vec<&Cut> = shape.cuts.iter().filter(|c| c.is_arc()).collect();
+        assert_eq!(arcs.len(), 2);
+        for arc in arcs {
+            assert!(arc.xc.abs() < 0.001 && arc.yc.abs() < 0.001);
+            assert!((arc.arc_angle().abs() - 0.75 * PI).abs() < 0.001);
+        }
+    }
+
+    #[test]
+    fn test_flipped_extrusion_mirrors_arcs_only() {
+        // World arc around (20, 5) from 0° to 270°, stored in object
+        // coordinates with extrusion -1; the closing lines stay in world
+        // coordinates whatever their extrusion
+        let arc = "  0\nARC\n  8\nCUT\n 10\n-20\n 20\n5\n 40\n10\n 50\n270\n 51\n180\n\
+                   230\n-1\n";
+        let flipped =
+            |x1: f64, y1: f64, x2: f64, y2: f64| line("CUT", x1, y1, x2, y2) + "230\n-1\n";
+        let content = dxf(&[
+            arc,
+            &flipped(20.0, -5.0, 20.0, 5.0),
+            &flipped(20.0, 5.0, 30.0, 5.0),
+        ]);
+
+        let options = DxfImportOptions {
+            move_to_origin: false,
+            ..DxfImportOptions::new("CUT")
+        };
+        let shapes = import_dxf_shapes(&content, &options).unwrap();
+        let shape = &shapes[0];
+        assert_chained(shape);
+        for cut in shape.cuts.iter().filter(|c| c.is_arc()) {
+            assert!((cut.xc - 20.0).abs() < 0.001 && (cut.yc - 5.0).abs() < 0.001);
+        }
+        let swept: f64 = shape.cuts.iter().map(|c| c.arc_angle().abs()).sum();
+        assert!((swept - 1.5 * PI).abs() < 0.001);
+        assert!(shape.cuts.iter().all(|c| c.xi > 9.999 && c.xf > 9.999));
+    }
+
+    #[test]
+    fn test_open_contour_reports_line() {
+        let content = dxf(&[
+            &line("CUT", 0.0, 0.0, 10.0, 0.0),
+            &line("CUT", 10.0, 0.0, 10.0, 10.0),
+        ]);
+
+        let err = import_dxf_shapes(&content, &DxfImportOptions::new("CUT")).unwrap_err();
+        assert!(
+            matches!(err, ConvertError::ParseError { line: 17, .. }),
+            "{err}"
+        );
+
+        let err = import_dxf_shapes(&content, &DxfImportOptions::new("MISSING")).unwrap_err();
+        assert!(matches!(err, ConvertError::InvalidValue { .. }));
+    }
+
+    fn signed_area_of(shape: &Shape) -> f64 {
+        shape
+            .cuts
+            .iter()
+            .map(|c| (c.xi * c.yf - c.xf * c.yi) / 2.0)
+            .sum()
+    }
+}
@@ -1,7 +1,11 @@
 //! OTD file parser module.
 
+pub mod cni;
+mod dxf;
 mod otd;
 mod sections;
 
-pub use otd::{parse_otd_file, OtdParser};
+pub use cni::{parse_cni, parse_cni_file, CniFile};
+pub use dxf::{import_dxf_shapes, import_dxf_shapes_file, DxfImportOptions, DEFAULT_GAP_TOLERANCE};
+pub use otd::{decrypt_otx, encrypt_otx, parse_otd_file, read_otd_source, OtdParser};
 pub use sections::*;
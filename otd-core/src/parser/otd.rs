@@ -1,7 +1,7 @@
 //! Main OTD file parser.
 
 use crate::error::{ConvertError, Result};
-use crate::model::{Cut, Piece, Schema};
+use crate::model::{Cut, HierarchyEntry, Piece, Schema};
 use std::path::Path;
 
 use super::sections::*;
@@ -75,6 +75,15 @@ impl OtdParser {
             .collect()
     }
 
+    /// Line numbers, from 1, of the headers of all sections with a given name.
+    fn section_header_lines(&self, name: &str) -> Vec<usize> {
+        self.sections
+            .iter()
+            .filter(|(n, _, _)| n == name)
+            .map(|(_, start, _)| start + 1)
+            .collect()
+    }
+
     /// Count sections with a given name.
     fn count_sections(&self, name: &str) -> usize {
         self.sections.iter().filter(|(n, _, _)| n == name).count()
@@ -136,6 +145,7 @@ impl OtdParser {
             .unwrap_or_default();
 
         let pattern_sections = self.get_all_sections("Pattern");
+        let pattern_header_lines = self.section_header_lines("Pattern");
         let mut schemas = Vec::with_capacity(num_patterns);
 
         for (pattern_idx, pattern_lines) in pattern_sections.iter().enumerate() {
@@ -181,7 +191,25 @@ impl OtdParser {
 
             // Parse nested coordinates to get pieces and cuts
             let coord_entries = parse_pattern_coordinates(pattern_lines);
-            let (linear_cuts, pieces) = self.process_coordinates(&coord_entries, &schema);
+            let header_line = pattern_header_lines[pattern_idx];
+            schema.hierarchy = coord_entries
+                .iter()
+                .map(|entry| HierarchyEntry {
+                    var: entry.var,
+                    level: entry.level as usize,
+                    value: entry.value,
+                    line: header_line + entry.line,
+                })
+                .collect();
+
+            // Levels past the supported ones are reported by validation
+            let supported: Vec<CoordEntry> = coord_entries
+                .into_iter()
+                .zip(&schema.hierarchy)
+                .filter(|(_, entry)| entry.is_supported())
+                .map(|(coord, _)| coord)
+                .collect();
+            let (linear_cuts, pieces) = self.process_coordinates(&supported, &schema);
             schema.linear_cuts = linear_cuts;
             schema.pieces = pieces;
 
@@ -418,6 +446,13 @@ impl OtdParser {
 
 /// Parse an OTD file from a path.
 pub fn parse_otd_file(path: &Path) -> Result<Vec<Schema>> {
+    let content = read_otd_source(path)?;
+    let parser = OtdParser::new(content);
+    parser.parse()
+}
+
+/// Read the OTD text of a file, decrypting `.otx` files.
+pub fn read_otd_source(path: &Path) -> Result<String> {
     use std::fs;
 
     if !path.exists() {
@@ -445,27 +480,48 @@ pub fn parse_otd_file(path: &Path) -> Result<Vec<Schema>> {
             path: path.to_path_buf(),
         });
     }
-
-    let parser = OtdParser::new(content);
-    parser.parse()
+    Ok(content)
 }
 
-/// Decrypt an OTX file.
-fn decrypt_otx(encrypted: &[u8]) -> Result<String> {
-    use cipher::{BlockDecryptMut, KeyIvInit};
-    use md5::{Digest, Md5};
+/// Password and IV shared by OTX encryption and decryption.
+const OTX_PASSWORD: &[u8] = b"%x$Intermac^(zx";
+const OTX_IV: [u8; 8] = [68, 101, 67, 97, 114, 110, 101, 68]; // "DeCarneD"
 
-    // Password and IV for OTX decryption
-    let password = b"%x$Intermac^(zx";
-    let iv: [u8; 8] = [68, 101, 67, 97, 114, 110, 101, 68]; // "DeCarneD"
+/// RC2 key derived from the OTX password.
+fn otx_key() -> [u8; 16] {
+    use md5::{Digest, Md5};
 
     // Derive key using MD5 (simplified version of CryptDeriveKey)
     let mut hasher = Md5::new();
-    hasher.update(password);
+    hasher.update(OTX_PASSWORD);
     let hash = hasher.finalize();
 
     // RC2 with 128-bit effective key length uses 16 bytes
-    let key: [u8; 16] = hash.into();
+    hash.into()
+}
+
+/// Encrypt OTD text as an OTX file, as the optimizer writes them.
+pub fn encrypt_otx(content: &str) -> Vec<u8> {
+    use cipher::{BlockEncryptMut, KeyIvInit};
+
+    type Rc2CbcEnc = cbc::Encryptor<rc2::Rc2>;
+
+    // PKCS7 always adds between 1 and 8 bytes of padding
+    let len = content.len();
+    let mut buffer = content.as_bytes().to_vec();
+    buffer.resize(len + 8 - len % 8, 0);
+
+    // Key and IV sizes are fixed and the buffer has room for the padding
+    Rc2CbcEnc::new_from_slices(&otx_key(), &OTX_IV)
+        .expect("RC2 key and IV sizes")
+        .encrypt_padded_mut::<cipher::block_padding::Pkcs7>(&mut buffer, len)
+        .expect("room for the padding")
+        .to_vec()
+}
+
+/// Decrypt the content of an OTX file.
+pub fn decrypt_otx(encrypted: &[u8]) -> Result<String> {
+    use cipher::{BlockDecryptMut, KeyIvInit};
 
     // Create RC2-CBC decryptor
     type Rc2CbcDec = cbc::Decryptor<rc2::Rc2>;
@@ -479,10 +535,11 @@ fn decrypt_otx(encrypted: &[u8]) -> Result<String> {
         buffer.extend(std::iter::repeat_n(padding as u8, padding));
     }
 
-    let decryptor =
-        Rc2CbcDec::new_from_slices(&key, &iv).map_err(|e| ConvertError::DecryptionFailed {
+    let decryptor = Rc2CbcDec::new_from_slices(&otx_key(), &OTX_IV).map_err(|e| {
+        ConvertError::DecryptionFailed {
             message: format!("Failed to create decryptor: {}", e),
-        })?;
+        }
+    })?;
 
     let decrypted = decryptor
         .decrypt_padded_mut::<cipher::block_padding::Pkcs7>(&mut buffer)
@@ -494,3 +551,17 @@ fn decrypt_otx(encrypted: &[u8]) -> Result<String> {
         message: format!("Invalid UTF-8 in decrypted content: {}", e),
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_otx_round_trip() {
+        let content = "[Header]\nVersion=2.0\nUnit=mm\n";
+        let encrypted = encrypt_otx(content);
+        assert_eq!(encrypted.len() % 8, 0);
+        assert_ne!(&encrypted[..8], &content.as_bytes()[..8]);
+        assert_eq!(decrypt_otx(&encrypted).unwrap(), content);
+    }
+}
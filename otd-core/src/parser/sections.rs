@@ -1,7 +1,7 @@
 //! Section-specific parsers for OTD format.
 
 use crate::config::Unit;
-use crate::model::{Cut, CutType, Piece, PieceType, Shape};
+use crate::model::{Cut, CutType, Piece, PieceType, Shape, LEVEL_VARS};
 
 /// Parse a key=value pair from a line.
 pub fn parse_key_value(line: &str) -> Option<(&str, &str)> {
@@ -248,14 +248,18 @@ pub struct CoordEntry {
     pub rotation: Option<f64>,
     /// Tcut if present.
     pub tcut: Option<i32>,
+    /// Line within the given section lines, from 1.
+    pub line: usize,
 }
 
 /// Parse nested coordinate lines from Pattern section.
+///
+/// Single-letter variables beyond `E` get a level past the supported ones,
+/// so that validation can report them.
 pub fn parse_pattern_coordinates(lines: &[&str]) -> Vec<CoordEntry> {
-    let coord_vars = ['X', 'Y', 'Z', 'W', 'V', 'A', 'B', 'C', 'D', 'E'];
     let mut entries = Vec::new();
 
-    for line in lines {
+    for (line_idx, line) in lines.iter().enumerate() {
         let line = line.trim();
         if line.is_empty() || line.starts_with(';') {
             continue;
@@ -263,7 +267,7 @@ pub fn parse_pattern_coordinates(lines: &[&str]) -> Vec<CoordEntry> {
 
         // Check if this is a coordinate line
         let first_char = line.chars().next().unwrap_or(' ');
-        if !coord_vars.contains(&first_char) {
+        if !first_char.is_ascii_uppercase() {
             continue;
         }
 
@@ -279,7 +283,10 @@ pub fn parse_pattern_coordinates(lines: &[&str]) -> Vec<CoordEntry> {
         }
 
         let var = var_key.chars().next().unwrap();
-        let level = coord_vars.iter().position(|&c| c == var).unwrap_or(0) as i32;
+        let level = LEVEL_VARS
+            .iter()
+            .position(|&c| c == var)
+            .unwrap_or(LEVEL_VARS.len()) as i32;
 
         let value: f64 = match var_value.parse() {
             Ok(v) => v,
@@ -294,6 +301,7 @@ pub fn parse_pattern_coordinates(lines: &[&str]) -> Vec<CoordEntry> {
             info_id: None,
             rotation: None,
             tcut: None,
+            line: line_idx + 1,
         };
 
         // Parse additional fields
@@ -891,4 +899,15 @@ mod tests {
         assert_eq!(coords.len(), 1);
         assert_eq!(coords[0].rotation, Some(90.0));
     }
+
+    #[test]
+    fn test_parse_pattern_coordinates_lines_and_unknown_levels() {
+        let lines = vec!["Width=3210", "", "X=1000", "; comment", "  F=50"];
+        let coords = parse_pattern_coordinates(&lines);
+        assert_eq!(coords.len(), 2);
+        assert_eq!(coords[0].line, 3);
+        assert_eq!(coords[1].var, 'F');
+        assert_eq!(coords[1].level, 10);
+        assert_eq!(coords[1].line, 5);
+    }
 }
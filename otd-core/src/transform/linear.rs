@@ -1,6 +1,9 @@
 //! Linear cut processing transformations.
 
-use crate::config::{float_cmp, EPS};
+use crate::config::{
+    float_cmp, DIM_MIN_PEZZO_SOLO_INCISIONE, DIM_MIN_RESTO_SOLO_INCISIONE,
+    DIM_MIN_RESTO_SOLO_INC_HI_SPESS, EPS, SOGLIA_SPESSORE_ELEVATO,
+};
 use crate::model::{Cut, LineType, Schema};
 
 /// Merge overlapping linear cuts on the same line.
@@ -110,12 +113,42 @@ fn merge_overlapping_by_indices(cuts: &mut [Cut], indices: &[usize], is_vertical
                     cuts[idx_i].xf = new_end;
                     cuts[idx_i].xi = start1;
                 }
+                // Only scored if no merged part needs separating
+                cuts[idx_i].score_only &= cuts[idx_j].score_only;
                 cuts[idx_j].active = false;
             }
         }
     }
 }
 
+/// Mark linear cuts that are only scored, without separating.
+///
+/// A cut is only scored when the strip it cuts off (`quota`) is smaller than
+/// `DIM_MIN_PEZZO_SOLO_INCISIONE` or the rest it leaves is narrower than
+/// `DIM_MIN_RESTO_SOLO_INCISIONE` (`DIM_MIN_RESTO_SOLO_INC_HI_SPESS` from
+/// `SOGLIA_SPESSORE_ELEVATO` up). The limits are in mm, converted to the
+/// file's units. Trim cuts are always separated.
+pub fn mark_score_only_cuts(schema: &mut Schema) {
+    let factor = schema.unit.to_mm_factor();
+    let min_piece = DIM_MIN_PEZZO_SOLO_INCISIONE / factor;
+    let min_rest = if schema.thickness_mm() >= SOGLIA_SPESSORE_ELEVATO {
+        DIM_MIN_RESTO_SOLO_INC_HI_SPESS
+    } else {
+        DIM_MIN_RESTO_SOLO_INCISIONE
+    } / factor;
+
+    // Zero or negative values are unknown (no Rcut) or exact fits
+    let below = |value: f64, min: f64| value > EPS && value < min - EPS;
+    let trims: Vec<bool> = schema
+        .linear_cuts
+        .iter()
+        .map(|cut| schema.is_trim_cut(cut))
+        .collect();
+    for (cut, trim) in schema.linear_cuts.iter_mut().zip(trims) {
+        cut.score_only = !trim && (below(cut.quota, min_piece) || below(cut.rest, min_rest));
+    }
+}
+
 /// Remove cuts that are at the sheet edges.
 ///
 /// Edge filtering logic:
@@ -317,6 +350,9 @@ pub fn apply_linear_advance(schema: &mut Schema, advance: f64) {
 
 /// Process all linear cut transformations.
 pub fn process_linear_cuts(schema: &mut Schema) {
+    // Pre-optimized cuts are still checked for score-only cutting
+    mark_score_only_cuts(schema);
+
     if schema.linear_cuts_optimized {
         // Cuts were already optimized in the OTD file
         return;
@@ -339,3 +375,79 @@ pub fn process_linear_cuts(schema: &mut Schema) {
 
     schema.linear_cuts_optimized = true;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Unit;
+
+    fn cut(quota: f64, rest: f64) -> Cut {
+        let mut cut = Cut::new_line(0.0, 0.0, 0.0, 1000.0);
+        cut.quota = quota;
+        cut.rest = rest;
+        cut
+    }
+
+    fn score_only(schema: &Schema) -> Vec<bool> {
+        schema.linear_cuts.iter().map(|c| c.score_only).collect()
+    }
+
+    #[test]
+    fn test_mark_score_only_cuts() {
+        let mut schema = Schema::new();
+        schema.thickness = 4.0;
+        schema.linear_cuts = vec![
+            cut(500.0, 1000.0),
+            cut(80.0, 1000.0),
+            cut(500.0, 30.0),
+            cut(500.0, 60.0),
+            cut(500.0, 0.0),
+            cut(500.0, -1.0),
+        ];
+        mark_score_only_cuts(&mut schema);
+        assert_eq!(
+            score_only(&schema),
+            [false, true, true, false, false, false]
+        );
+
+        // Thick glass needs a wider rest to be separated
+        schema.thickness = 8.0;
+        mark_score_only_cuts(&mut schema);
+        assert_eq!(score_only(&schema), [false, true, true, true, false, false]);
+
+        // Limits follow the file unit: 3 in = 76.2 mm
+        schema.unit = Unit::Inches;
+        schema.thickness = 0.25;
+        schema.linear_cuts = vec![cut(20.0, 3.0), cut(3.0, 20.0), cut(20.0, 3.5)];
+        mark_score_only_cuts(&mut schema);
+        assert_eq!(score_only(&schema), [true, true, false]);
+
+        // Trim cuts are separated whatever the strip they cut off
+        let mut schema = Schema::new();
+        schema.thickness = 4.0;
+        schema.trim_left = 10.0;
+        schema.trim_bottom = 10.0;
+        let mut left = Cut::new_line(10.0, 0.0, 10.0, 1000.0);
+        left.quota = 10.0;
+        left.rest = 30.0;
+        let mut bottom = Cut::new_line(0.0, 10.0, 2000.0, 10.0);
+        bottom.quota = 10.0;
+        schema.linear_cuts = vec![left, bottom, cut(80.0, 1000.0)];
+        mark_score_only_cuts(&mut schema);
+        assert_eq!(score_only(&schema), [false, false, true]);
+    }
+
+    #[test]
+    fn test_merged_cut_is_only_scored_if_all_parts_are() {
+        let mut schema = Schema::new();
+        let mut lower = Cut::new_line(100.0, 0.0, 100.0, 500.0);
+        lower.score_only = true;
+        let upper = Cut::new_line(100.0, 500.0, 100.0, 1000.0);
+        schema.linear_cuts = vec![lower, upper];
+
+        merge_linear_cuts(&mut schema);
+        let merged: Vec<_> = schema.linear_cuts.iter().filter(|c| c.active).collect();
+        assert_eq!(merged.len(), 1);
+        assert!(!merged[0].score_only);
+    }
+}
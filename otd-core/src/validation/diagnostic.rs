@@ -0,0 +1,153 @@
+//! Structured validation findings.
+
+use crate::error::ErrorCode;
+use serde::Serialize;
+use std::fmt;
+
+/// How serious a finding is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// The layout can be converted but should be checked.
+    Warning,
+    /// The layout cannot be converted as is.
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// Layout element a finding refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase", tag = "kind")]
+pub enum Entity {
+    /// Piece by its position in `Schema::pieces`, from 1.
+    Piece { number: usize },
+    /// Shape by its `[Shape]` id.
+    Shape { id: i32 },
+    /// Cut by its position, from 1, in a shape or in the linear cuts.
+    Cut { shape: Option<i32>, number: usize },
+    /// Piece type by its `[Info]` id.
+    Info { id: i32 },
+}
+
+impl fmt::Display for Entity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Entity::Piece { number } => write!(f, "Piece {}", number),
+            Entity::Shape { id } => write!(f, "Shape {}", id),
+            Entity::Cut {
+                shape: Some(id),
+                number,
+            } => write!(f, "Shape {}, Cut {}", id, number),
+            Entity::Cut {
+                shape: None,
+                number,
+            } => write!(f, "Cut {}", number),
+            Entity::Info { id } => write!(f, "Info {}", id),
+        }
+    }
+}
+
+/// Where a finding is located.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase", tag = "kind")]
+pub enum Span {
+    /// Line of the source file, from 1.
+    Line { line: usize },
+    /// Sheet region in layout coordinates.
+    Region { x1: f64, y1: f64, x2: f64, y2: f64 },
+}
+
+/// A single validation finding.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Diagnostic {
+    /// Error code shared with [`ConvertError`](crate::error::ConvertError).
+    pub code: ErrorCode,
+    /// Error or warning.
+    pub severity: Severity,
+    /// Pattern number, from 1, for findings within a pattern.
+    pub schema: Option<usize>,
+    /// Layout element the finding refers to.
+    pub entity: Option<Entity>,
+    /// Description without location prefix.
+    pub message: String,
+    /// Source line or sheet region, when known.
+    pub span: Option<Span>,
+    /// Name of the rule that reported the finding.
+    pub rule: Option<String>,
+}
+
+impl Diagnostic {
+    /// Create an error finding.
+    pub fn error(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self::new(code, Severity::Error, message)
+    }
+
+    /// Create a warning finding.
+    pub fn warning(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self::new(code, Severity::Warning, message)
+    }
+
+    fn new(code: ErrorCode, severity: Severity, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            severity,
+            schema: None,
+            entity: None,
+            message: message.into(),
+            span: None,
+            rule: None,
+        }
+    }
+
+    /// Attach the pattern number.
+    pub fn in_schema(mut self, schema: usize) -> Self {
+        self.schema = Some(schema);
+        self
+    }
+
+    /// Attach the layout element.
+    pub fn on(mut self, entity: Entity) -> Self {
+        self.entity = Some(entity);
+        self
+    }
+
+    /// Attach the location.
+    pub fn at(mut self, span: Span) -> Self {
+        self.span = Some(span);
+        self
+    }
+
+    /// Message with the "Schema N, Piece M: " prefix used by the string lists.
+    ///
+    /// Findings on a source line get a "Line L" part.
+    pub fn located_message(&self) -> String {
+        let mut location = Vec::new();
+        if let Some(schema) = self.schema {
+            location.push(format!("Schema {}", schema));
+        }
+        if let Some(entity) = self.entity {
+            location.push(entity.to_string());
+        }
+        if let Some(Span::Line { line }) = self.span {
+            location.push(format!("Line {}", line));
+        }
+        if location.is_empty() {
+            return self.message.clone();
+        }
+        format!("{}: {}", location.join(", "), self.message)
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] {}", self.code, self.located_message())
+    }
+}
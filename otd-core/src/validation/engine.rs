@@ -0,0 +1,333 @@
+//! Rule engine and its configuration.
+
+use super::diagnostic::Severity;
+use super::rules::{builtin_rules, Level, Params, Rule};
+use super::validate::ValidationResult;
+use crate::error::{ConvertError, Result};
+use crate::model::Schema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Plant policy: level and parameters per rule name.
+///
+/// ```json
+/// { "rules": { "piece-overlap": { "level": "error" },
+///              "piece-bounds": { "params": { "tolerance": 0.5 } } } }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ValidationConfig {
+    /// Settings per rule; rules not listed keep their defaults.
+    pub rules: BTreeMap<String, RuleConfig>,
+}
+
+/// Settings of one rule.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RuleConfig {
+    /// Level overriding the rule default.
+    pub level: Option<Level>,
+    /// Parameter overrides.
+    pub params: BTreeMap<String, f64>,
+}
+
+impl ValidationConfig {
+    /// Load a configuration from JSON.
+    pub fn from_json(json: &str) -> Result<Self> {
+        serde_json::from_str(json).map_err(|e| ConvertError::InvalidValue {
+            field: "validation config".to_string(),
+            expected: "a JSON object of rule settings".to_string(),
+            value: e.to_string(),
+        })
+    }
+
+    /// Set the level of a rule.
+    pub fn with_level(mut self, rule: &str, level: Level) -> Self {
+        self.rules.entry(rule.to_string()).or_default().level = Some(level);
+        self
+    }
+
+    /// Set a parameter of a rule.
+    pub fn with_param(mut self, rule: &str, name: &str, value: f64) -> Self {
+        self.rules
+            .entry(rule.to_string())
+            .or_default()
+            .params
+            .insert(name.to_string(), value);
+        self
+    }
+}
+
+/// Runs a set of rules under a configuration.
+pub struct Validator {
+    rules: Vec<Box<dyn Rule>>,
+    config: ValidationConfig,
+}
+
+impl Default for Validator {
+    fn default() -> Self {
+        Self {
+            rules: builtin_rules(),
+            config: ValidationConfig::default(),
+        }
+    }
+}
+
+impl Validator {
+    /// Validator with the built-in rules at their default levels.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a custom rule, run after the built-in ones.
+    ///
+    /// Add custom rules before applying a configuration that refers to them.
+    pub fn with_rule(mut self, rule: impl Rule + 'static) -> Self {
+        self.rules.push(Box::new(rule));
+        self
+    }
+
+    /// Apply a configuration.
+    ///
+    /// Unknown rule or parameter names are rejected so that typos in a plant
+    /// policy do not silently disable a check.
+    pub fn with_config(mut self, config: ValidationConfig) -> Result<Self> {
+        for (name, settings) in &config.rules {
+            let rule = self.rule(name).ok_or_else(|| ConvertError::InvalidValue {
+                field: "validation rule".to_string(),
+                expected: format!("one of {}", self.rule_names().join(", ")),
+                value: name.clone(),
+            })?;
+            for param in settings.params.keys() {
+                if !rule.params().iter().any(|(p, _)| p == param) {
+                    let known: Vec<&str> = rule.params().iter().map(|(p, _)| *p).collect();
+                    return Err(ConvertError::InvalidValue {
+                        field: format!("{} parameter", name),
+                        expected: if known.is_empty() {
+                            "no parameters".to_string()
+                        } else {
+                            format!("one of {}", known.join(", "))
+                        },
+                        value: param.clone(),
+                    });
+                }
+            }
+        }
+        self.config = config;
+        Ok(self)
+    }
+
+    /// The rules in reporting order.
+    pub fn rules(&self) -> impl Iterator<Item = &dyn Rule> {
+        self.rules.iter().map(|rule| rule.as_ref())
+    }
+
+    fn rule(&self, name: &str) -> Option<&dyn Rule> {
+        self.rules().find(|rule| rule.name() == name)
+    }
+
+    fn rule_names(&self) -> Vec<&str> {
+        self.rules().map(|rule| rule.name()).collect()
+    }
+
+    /// Configured level of a rule.
+    pub fn level(&self, rule: &dyn Rule) -> Level {
+        self.config
+            .rules
+            .get(rule.name())
+            .and_then(|settings| settings.level)
+            .unwrap_or_else(|| rule.default_level())
+    }
+
+    /// Configured parameters of a rule.
+    pub fn params(&self, rule: &dyn Rule) -> Params {
+        let overrides = self
+            .config
+            .rules
+            .get(rule.name())
+            .map(|settings| settings.params.clone())
+            .unwrap_or_default();
+        Params::new(rule.params(), &overrides)
+    }
+
+    /// Enabled rules with their severity and parameters.
+    fn active(&self) -> impl Iterator<Item = (&dyn Rule, Severity, Params)> {
+        self.rules().filter_map(|rule| {
+            let severity = match self.level(rule) {
+                Level::Off => return None,
+                Level::Warn => Severity::Warning,
+                Level::Error => Severity::Error,
+            };
+            Some((rule, severity, self.params(rule)))
+        })
+    }
+
+    /// Validate all patterns of a file.
+    pub fn validate(&self, schemas: &[Schema]) -> Result<ValidationResult> {
+        if schemas.is_empty() {
+            return Err(ConvertError::NoPatternSection);
+        }
+
+        let mut result = ValidationResult::ok();
+        for (idx, schema) in schemas.iter().enumerate() {
+            result.merge(self.validate_schema(schema, idx + 1));
+        }
+        for (rule, severity, params) in self.active() {
+            for mut diagnostic in rule.check_job(schemas, &params) {
+                diagnostic.severity = severity;
+                diagnostic.rule = Some(rule.name().to_string());
+                result.push(diagnostic);
+            }
+        }
+        Ok(result)
+    }
+
+    /// Validate one pattern.
+    pub fn validate_schema(&self, schema: &Schema, schema_num: usize) -> ValidationResult {
+        let mut result = ValidationResult::ok();
+        for (rule, severity, params) in self.active() {
+            for mut diagnostic in rule.check_schema(schema, &params) {
+                diagnostic.severity = severity;
+                diagnostic.rule = Some(rule.name().to_string());
+                result.push(diagnostic.in_schema(schema_num));
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ErrorCode;
+    use crate::model::Piece;
+    use crate::validation::{Diagnostic, Entity};
+
+    fn overlapping_schema() -> Schema {
+        let mut schema = Schema {
+            width: 1000.0,
+            height: 500.0,
+            thickness: 4.0,
+            ..Default::default()
+        };
+        for x in [0.0, 99.0, 300.0] {
+            schema.pieces.push(Piece::new(x, 0.0, 100.0, 100.0));
+        }
+        schema
+    }
+
+    /// Plant rule: pieces must be at least `min` wide.
+    struct MinWidth;
+
+    impl Rule for MinWidth {
+        fn name(&self) -> &str {
+            "min-width"
+        }
+
+        fn description(&self) -> &str {
+            "Pieces are wide enough to handle"
+        }
+
+        fn params(&self) -> &[(&'static str, f64)] {
+            &[("min", 50.0)]
+        }
+
+        fn check_schema(&self, schema: &Schema, params: &Params) -> Vec<Diagnostic> {
+            schema
+                .pieces
+                .iter()
+                .enumerate()
+                .filter(|(_, piece)| piece.width < params.get("min"))
+                .map(|(idx, _)| {
+                    Diagnostic::warning(ErrorCode::OutOfBounds, "Too narrow")
+                        .on(Entity::Piece { number: idx + 1 })
+                })
+                .collect()
+        }
+    }
+
+    #[test]
+    fn test_default_rules_report_overlap_as_warning() {
+        let result = Validator::new().validate(&[overlapping_schema()]).unwrap();
+        assert!(result.passed);
+        let overlap = &result.diagnostics[0];
+        assert_eq!(overlap.rule.as_deref(), Some("piece-overlap"));
+        assert_eq!(overlap.severity, Severity::Warning);
+        assert_eq!(overlap.entity, Some(Entity::Piece { number: 1 }));
+        assert_eq!(
+            overlap.located_message(),
+            "Schema 1, Piece 1: Overlaps piece 2"
+        );
+    }
+
+    #[test]
+    fn test_config_levels_and_params() {
+        let config = ValidationConfig::from_json(
+            r#"{"rules": {"piece-overlap": {"level": "error"}, "thickness": {"level": "error"}}}"#,
+        )
+        .unwrap();
+        let validator = Validator::new().with_config(config).unwrap();
+        let result = validator.validate(&[overlapping_schema()]).unwrap();
+        assert!(!result.passed);
+        assert_eq!(result.errors, ["Schema 1, Piece 1: Overlaps piece 2"]);
+
+        let mut thin = overlapping_schema();
+        thin.pieces.truncate(1);
+        thin.thickness = 0.0;
+        assert!(!validator.validate(&[thin]).unwrap().passed);
+
+        // A wider tolerance accepts the 1 mm overlap
+        let config = ValidationConfig::default()
+            .with_level("piece-overlap", Level::Error)
+            .with_param("piece-overlap", "tolerance", 2.0);
+        let validator = Validator::new().with_config(config).unwrap();
+        assert!(validator.validate(&[overlapping_schema()]).unwrap().passed);
+
+        let config = ValidationConfig::default().with_level("piece-overlap", Level::Off);
+        let validator = Validator::new().with_config(config).unwrap();
+        let result = validator.validate(&[overlapping_schema()]).unwrap();
+        assert!(result
+            .diagnostics
+            .iter()
+            .all(|d| d.rule.as_deref() != Some("piece-overlap")));
+    }
+
+    #[test]
+    fn test_config_rejects_unknown_names() {
+        let config = ValidationConfig::default().with_level("piece-overlapp", Level::Error);
+        assert!(Validator::new().with_config(config).is_err());
+
+        let config = ValidationConfig::default().with_param("thickness", "min", 3.0);
+        assert!(Validator::new().with_config(config).is_err());
+
+        assert!(ValidationConfig::from_json(r#"{"rule": {}}"#).is_err());
+        assert!(
+            ValidationConfig::from_json(r#"{"rules": {"thickness": {"level": "fatal"}}}"#).is_err()
+        );
+    }
+
+    #[test]
+    fn test_custom_rule() {
+        // The rule must be added before a configuration can refer to it
+        let config = ValidationConfig::default().with_level("min-width", Level::Error);
+        assert!(Validator::new().with_config(config).is_err());
+
+        let config = ValidationConfig::default()
+            .with_level("min-width", Level::Error)
+            .with_param("min-width", "min", 150.0);
+        let validator = Validator::new()
+            .with_rule(MinWidth)
+            .with_config(config)
+            .unwrap();
+        assert_eq!(validator.rules().last().unwrap().name(), "min-width");
+
+        let result = validator.validate(&[overlapping_schema()]).unwrap();
+        let narrow: Vec<_> = result
+            .diagnostics
+            .iter()
+            .filter(|d| d.rule.as_deref() == Some("min-width"))
+            .collect();
+        assert_eq!(narrow.len(), 3);
+        assert!(narrow.iter().all(|d| d.severity == Severity::Error));
+    }
+}
@@ -0,0 +1,470 @@
+//! Exact geometry of shape contours.
+//!
+//! Lines and arcs are intersected analytically; only the inside test of a
+//! point flattens arcs, in 1° steps.
+
+use crate::analysis::Segment;
+use crate::model::{Piece, Shape};
+use std::f64::consts::{FRAC_PI_2, TAU};
+
+/// A point in layout coordinates.
+pub(crate) type Point = (f64, f64);
+
+/// Angle step when flattening arcs for the inside test.
+const FLATTEN_STEP: f64 = TAU / 360.0;
+
+/// Contour of a shape placed at the origin of its piece, in sheet coordinates.
+pub(crate) fn placed_contour(shape: &Shape, piece: &Piece) -> Vec<Segment> {
+    shape
+        .cuts
+        .iter()
+        .map(|cut| Segment::from(cut).translated(piece.x_origin, piece.y_origin))
+        .collect()
+}
+
+/// Contour of a shape in its own coordinates.
+pub(crate) fn contour(shape: &Shape) -> Vec<Segment> {
+    shape.cuts.iter().map(Segment::from).collect()
+}
+
+/// Endpoints of a segment and, for arcs, the points where it reaches its
+/// extremes along X or Y.
+pub(crate) fn extreme_points(segment: &Segment) -> Vec<Point> {
+    let mut points = vec![segment.start(), segment.end()];
+    if let Segment::Arc { center, .. } = segment {
+        let radius = segment.radius();
+        for quadrant in 0..4 {
+            let angle = quadrant as f64 * FRAC_PI_2;
+            let point = (
+                center.0 + radius * angle.cos(),
+                center.1 + radius * angle.sin(),
+            );
+            if on_sweep(segment, point, 0.0) {
+                points.push(point);
+            }
+        }
+    }
+    points
+}
+
+/// Bounding box `(x1, y1, x2, y2)` of a contour.
+pub(crate) fn extent(contour: &[Segment]) -> (f64, f64, f64, f64) {
+    contour.iter().flat_map(extreme_points).fold(
+        (f64::MAX, f64::MAX, f64::MIN, f64::MIN),
+        |(x1, y1, x2, y2), (x, y)| (x1.min(x), y1.min(y), x2.max(x), y2.max(y)),
+    )
+}
+
+/// Point halfway along a segment.
+pub(crate) fn midpoint(segment: &Segment) -> Point {
+    match segment {
+        Segment::Line { start, end } => ((start.0 + end.0) / 2.0, (start.1 + end.1) / 2.0),
+        Segment::Arc { .. } => point_at(segment, segment.sweep() / 2.0),
+    }
+}
+
+/// Point of an arc `angle` radians from its start, in its direction of travel.
+fn point_at(segment: &Segment, angle: f64) -> Point {
+    let Segment::Arc {
+        start,
+        center,
+        clockwise,
+        ..
+    } = segment
+    else {
+        return segment.start();
+    };
+    let a0 = (start.1 - center.1).atan2(start.0 - center.0);
+    let a = if *clockwise { a0 - angle } else { a0 + angle };
+    let radius = segment.radius();
+    (center.0 + radius * a.cos(), center.1 + radius * a.sin())
+}
+
+/// Whether a point on the circle of an arc lies within its sweep, allowing
+/// `tolerance` along the arc at either end. Always true for lines.
+fn on_sweep(segment: &Segment, point: Point, tolerance: f64) -> bool {
+    let Segment::Arc {
+        start,
+        center,
+        clockwise,
+        ..
+    } = segment
+    else {
+        return true;
+    };
+    let slack = tolerance / segment.radius().max(f64::EPSILON) + 1e-12;
+    let a0 = (start.1 - center.1).atan2(start.0 - center.0);
+    let a = (point.1 - center.1).atan2(point.0 - center.0);
+    let offset = if *clockwise { a0 - a } else { a - a0 }.rem_euclid(TAU);
+    offset <= segment.sweep() + slack || offset >= TAU - slack
+}
+
+/// Distance from a point to a segment.
+pub(crate) fn distance(segment: &Segment, point: Point) -> f64 {
+    match segment {
+        Segment::Line { start, end } => {
+            let d = (end.0 - start.0, end.1 - start.1);
+            let length2 = d.0 * d.0 + d.1 * d.1;
+            let t = if length2 > 0.0 {
+                (((point.0 - start.0) * d.0 + (point.1 - start.1) * d.1) / length2).clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
+            (point.0 - start.0 - t * d.0).hypot(point.1 - start.1 - t * d.1)
+        }
+        Segment::Arc {
+            start, end, center, ..
+        } => {
+            if on_sweep(segment, point, 0.0) {
+                ((point.0 - center.0).hypot(point.1 - center.1) - segment.radius()).abs()
+            } else {
+                (point.0 - start.0)
+                    .hypot(point.1 - start.1)
+                    .min((point.0 - end.0).hypot(point.1 - end.1))
+            }
+        }
+    }
+}
+
+/// Unit direction of travel of a segment at a point on it.
+fn direction_at(segment: &Segment, point: Point) -> Point {
+    let (dx, dy) = match segment {
+        Segment::Line { start, end } => (end.0 - start.0, end.1 - start.1),
+        Segment::Arc {
+            center, clockwise, ..
+        } => {
+            let (rx, ry) = (point.0 - center.0, point.1 - center.1);
+            if *clockwise {
+                (ry, -rx)
+            } else {
+                (-ry, rx)
+            }
+        }
+    };
+    let length = dx.hypot(dy).max(f64::EPSILON);
+    (dx / length, dy / length)
+}
+
+/// Angle in degrees between a segment and the next one at their joint:
+/// 180 when the contour runs straight on, 0 when it doubles back.
+pub(crate) fn corner_angle(a: &Segment, b: &Segment) -> f64 {
+    let incoming = direction_at(a, a.end());
+    let outgoing = direction_at(b, b.start());
+    let dot = -incoming.0 * outgoing.0 - incoming.1 * outgoing.1;
+    dot.clamp(-1.0, 1.0).acos().to_degrees()
+}
+
+/// Whether a closed contour runs clockwise, from its signed area with each
+/// arc approximated by its chord halves.
+pub(crate) fn is_clockwise(contour: &[Segment]) -> bool {
+    let vertices: Vec<Point> = contour
+        .iter()
+        .flat_map(|segment| match segment {
+            Segment::Line { start, .. } => vec![*start],
+            Segment::Arc { start, .. } => vec![*start, midpoint(segment)],
+        })
+        .collect();
+    let area: f64 = vertices
+        .iter()
+        .zip(vertices.iter().cycle().skip(1))
+        .map(|(a, b)| cross(*a, *b))
+        .sum();
+    area < 0.0
+}
+
+/// Add a point unless one within `tolerance` is already listed.
+fn push_unique(points: &mut Vec<Point>, point: Point, tolerance: f64) {
+    if !points
+        .iter()
+        .any(|p| (p.0 - point.0).hypot(p.1 - point.1) <= tolerance)
+    {
+        points.push(point);
+    }
+}
+
+/// Points shared by two segments. Where they run along each other, the ends
+/// of the shared part are returned.
+pub(crate) fn intersections(a: &Segment, b: &Segment, tolerance: f64) -> Vec<Point> {
+    match (a, b) {
+        (Segment::Line { start, end }, Segment::Line { start: s, end: e }) => {
+            line_line(*start, *end, *s, *e, tolerance)
+        }
+        (Segment::Line { start, end }, Segment::Arc { .. }) => line_arc(*start, *end, b, tolerance),
+        (Segment::Arc { .. }, Segment::Line { start, end }) => line_arc(*start, *end, a, tolerance),
+        (Segment::Arc { .. }, Segment::Arc { .. }) => arc_arc(a, b, tolerance),
+    }
+}
+
+fn cross(a: Point, b: Point) -> f64 {
+    a.0 * b.1 - a.1 * b.0
+}
+
+fn line_line(p0: Point, p1: Point, q0: Point, q1: Point, tolerance: f64) -> Vec<Point> {
+    let d = (p1.0 - p0.0, p1.1 - p0.1);
+    let e = (q1.0 - q0.0, q1.1 - q0.1);
+    let (len_d, len_e) = (d.0.hypot(d.1), e.0.hypot(e.1));
+    if len_d <= tolerance || len_e <= tolerance {
+        return Vec::new();
+    }
+    let w = (q0.0 - p0.0, q0.1 - p0.1);
+    let denom = cross(d, e);
+    let at = |t: f64| (p0.0 + t * d.0, p0.1 + t * d.1);
+
+    if denom.abs() <= 1e-9 * len_d * len_e {
+        // Parallel: only collinear lines share points
+        if cross(d, w).abs() / len_d > tolerance {
+            return Vec::new();
+        }
+        let project = |q: Point| ((q.0 - p0.0) * d.0 + (q.1 - p0.1) * d.1) / (len_d * len_d);
+        let (t0, t1) = (project(q0), project(q1));
+        let lo = t0.min(t1).max(0.0);
+        let hi = t0.max(t1).min(1.0);
+        if lo > hi + tolerance / len_d {
+            return Vec::new();
+        }
+        let mut points = vec![at(lo.min(hi))];
+        push_unique(&mut points, at(hi.max(lo)), tolerance);
+        return points;
+    }
+
+    let t = cross(w, e) / denom;
+    let u = cross(w, d) / denom;
+    let (slack_t, slack_u) = (tolerance / len_d, tolerance / len_e);
+    if (-slack_t..=1.0 + slack_t).contains(&t) && (-slack_u..=1.0 + slack_u).contains(&u) {
+        vec![at(t.clamp(0.0, 1.0))]
+    } else {
+        Vec::new()
+    }
+}
+
+fn line_arc(p0: Point, p1: Point, arc: &Segment, tolerance: f64) -> Vec<Point> {
+    let Segment::Arc { center, .. } = arc else {
+        return Vec::new();
+    };
+    let radius = arc.radius();
+    let d = (p1.0 - p0.0, p1.1 - p0.1);
+    let len = d.0.hypot(d.1);
+    if len <= tolerance {
+        return Vec::new();
+    }
+
+    // Foot of the perpendicular from the centre, as a parameter along the line
+    let f = (p0.0 - center.0, p0.1 - center.1);
+    let t_foot = -(f.0 * d.0 + f.1 * d.1) / (len * len);
+    let foot = (p0.0 + t_foot * d.0, p0.1 + t_foot * d.1);
+    let dist = (foot.0 - center.0).hypot(foot.1 - center.1);
+    if dist > radius + tolerance {
+        return Vec::new();
+    }
+    let half = (radius * radius - dist * dist).max(0.0).sqrt() / len;
+
+    let slack = tolerance / len;
+    let mut points = Vec::new();
+    for t in [t_foot - half, t_foot + half] {
+        if (-slack..=1.0 + slack).contains(&t) {
+            let t = t.clamp(0.0, 1.0);
+            let point = (p0.0 + t * d.0, p0.1 + t * d.1);
+            if on_sweep(arc, point, tolerance) {
+                push_unique(&mut points, point, tolerance);
+            }
+        }
+    }
+    points
+}
+
+fn arc_arc(a: &Segment, b: &Segment, tolerance: f64) -> Vec<Point> {
+    let (Segment::Arc { center: c1, .. }, Segment::Arc { center: c2, .. }) = (a, b) else {
+        return Vec::new();
+    };
+    let (r1, r2) = (a.radius(), b.radius());
+    let dist = (c2.0 - c1.0).hypot(c2.1 - c1.1);
+    let mut points = Vec::new();
+
+    if dist <= tolerance {
+        // Concentric: arcs on the same circle share the ends of their overlap
+        if (r1 - r2).abs() <= tolerance {
+            for (arc, other) in [(a, b), (b, a)] {
+                for point in [arc.start(), arc.end()] {
+                    if on_sweep(other, point, tolerance) {
+                        push_unique(&mut points, point, tolerance);
+                    }
+                }
+            }
+        }
+        return points;
+    }
+    if dist > r1 + r2 + tolerance || dist < (r1 - r2).abs() - tolerance {
+        return points;
+    }
+
+    let along = (r1 * r1 - r2 * r2 + dist * dist) / (2.0 * dist);
+    let height = (r1 * r1 - along * along).max(0.0).sqrt();
+    let unit = ((c2.0 - c1.0) / dist, (c2.1 - c1.1) / dist);
+    let mid = (c1.0 + along * unit.0, c1.1 + along * unit.1);
+    for sign in [-1.0, 1.0] {
+        let point = (
+            mid.0 - sign * height * unit.1,
+            mid.1 + sign * height * unit.0,
+        );
+        if on_sweep(a, point, tolerance) && on_sweep(b, point, tolerance) {
+            push_unique(&mut points, point, tolerance);
+        }
+    }
+    points
+}
+
+/// Point where two segments cross each other transversally, away from their ends.
+pub(crate) fn crossing(a: &Segment, b: &Segment, tolerance: f64) -> Option<Point> {
+    let near_end = |segment: &Segment, point: Point| {
+        [segment.start(), segment.end()]
+            .iter()
+            .any(|p| (p.0 - point.0).hypot(p.1 - point.1) <= tolerance)
+    };
+    intersections(a, b, tolerance).into_iter().find(|&point| {
+        !near_end(a, point)
+            && !near_end(b, point)
+            && cross(direction_at(a, point), direction_at(b, point)).abs() > 1e-6
+    })
+}
+
+/// Whether a point lies inside a closed contour, more than `tolerance` away
+/// from its boundary.
+pub(crate) fn strictly_inside(contour: &[Segment], point: Point, tolerance: f64) -> bool {
+    if contour.iter().any(|s| distance(s, point) <= tolerance) {
+        return false;
+    }
+
+    // Winding number over the flattened contour
+    let mut polyline = Vec::new();
+    for segment in contour {
+        polyline.push(segment.start());
+        if let Segment::Arc { .. } = segment {
+            let steps = (segment.sweep() / FLATTEN_STEP).ceil() as usize;
+            for step in 1..steps {
+                polyline.push(point_at(
+                    segment,
+                    segment.sweep() * step as f64 / steps as f64,
+                ));
+            }
+        }
+    }
+    let mut winding = 0;
+    for (i, &a) in polyline.iter().enumerate() {
+        let b = polyline[(i + 1) % polyline.len()];
+        let side = cross((b.0 - a.0, b.1 - a.1), (point.0 - a.0, point.1 - a.1));
+        if a.1 <= point.1 && b.1 > point.1 && side > 0.0 {
+            winding += 1;
+        } else if a.1 > point.1 && b.1 <= point.1 && side < 0.0 {
+            winding -= 1;
+        }
+    }
+    winding != 0
+}
+
+/// Value rounded to 0.0001 for messages.
+pub(crate) fn round_value(value: f64) -> f64 {
+    let value = (value * 10_000.0).round() / 10_000.0;
+    // Avoid printing -0
+    if value == 0.0 {
+        0.0
+    } else {
+        value
+    }
+}
+
+/// Coordinates rounded to 0.0001 for messages.
+pub(crate) fn format_point(point: Point) -> String {
+    format!("({}, {})", round_value(point.0), round_value(point.1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(start: Point, end: Point) -> Segment {
+        Segment::Line { start, end }
+    }
+
+    fn arc(start: Point, end: Point, center: Point, clockwise: bool) -> Segment {
+        Segment::Arc {
+            start,
+            end,
+            center,
+            clockwise,
+        }
+    }
+
+    #[test]
+    fn test_intersections() {
+        let tol = 1e-6;
+        let x = line((0.0, 0.0), (10.0, 10.0));
+        let y = line((0.0, 10.0), (10.0, 0.0));
+        assert_eq!(intersections(&x, &y, tol), [(5.0, 5.0)]);
+        assert_eq!(crossing(&x, &y, tol), Some((5.0, 5.0)));
+
+        // Collinear overlap gives the ends of the shared part
+        let a = line((0.0, 0.0), (10.0, 0.0));
+        let b = line((4.0, 0.0), (20.0, 0.0));
+        assert_eq!(intersections(&a, &b, tol), [(4.0, 0.0), (10.0, 0.0)]);
+        assert_eq!(crossing(&a, &b, tol), None);
+
+        // Upper half circle, clockwise from (-5, 0) to (5, 0)
+        let half = arc((-5.0, 0.0), (5.0, 0.0), (0.0, 0.0), true);
+        let vertical = line((3.0, -10.0), (3.0, 10.0));
+        let points = intersections(&half, &vertical, tol);
+        assert_eq!(points.len(), 1);
+        assert!((points[0].0 - 3.0).abs() < 1e-9 && (points[0].1 - 4.0).abs() < 1e-9);
+
+        // Tangent line touches without crossing
+        let tangent = line((-10.0, 5.0), (10.0, 5.0));
+        assert_eq!(intersections(&half, &tangent, tol).len(), 1);
+        assert_eq!(crossing(&half, &tangent, tol), None);
+
+        // Circles meet at (0, -5) and (0, 5); only the latter is on both arcs
+        let r = 50f64.sqrt();
+        let other = arc((5.0, -r), (5.0, r), (5.0, 0.0), true);
+        let points = intersections(&half, &other, tol);
+        assert_eq!(points.len(), 1);
+        assert!(points[0].0.abs() < 1e-9 && (points[0].1 - 5.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_extent_and_inside() {
+        // Circle of radius 5 around (10, 10) as two clockwise halves
+        let circle = [
+            arc((5.0, 10.0), (15.0, 10.0), (10.0, 10.0), true),
+            arc((15.0, 10.0), (5.0, 10.0), (10.0, 10.0), true),
+        ];
+        assert_eq!(extent(&circle), (5.0, 5.0, 15.0, 15.0));
+        assert!((midpoint(&circle[0]).1 - 15.0).abs() < 1e-9);
+
+        assert!(strictly_inside(&circle, (10.0, 10.0), 1e-6));
+        assert!(strictly_inside(&circle, (14.99, 10.0), 1e-6));
+        assert!(!strictly_inside(&circle, (15.0, 10.0), 1e-6));
+        assert!(!strictly_inside(&circle, (14.0, 14.0), 1e-6));
+        assert_eq!(format_point((1.000_04, -0.000_01)), "(1, 0)");
+    }
+
+    #[test]
+    fn test_corners_and_orientation() {
+        let up = line((0.0, 0.0), (0.0, 10.0));
+        let right = line((0.0, 10.0), (10.0, 10.0));
+        let back = line((0.0, 10.0), (1.0, 0.0));
+        assert!((corner_angle(&up, &right) - 90.0).abs() < 1e-9);
+        assert!(corner_angle(&up, &back) < 6.0);
+
+        // Tangent arc continues straight on
+        let bend = arc((0.0, 10.0), (5.0, 15.0), (5.0, 10.0), true);
+        assert!((corner_angle(&up, &bend) - 180.0).abs() < 1e-9);
+
+        let square = [
+            up.clone(),
+            right.clone(),
+            line((10.0, 10.0), (10.0, 0.0)),
+            line((10.0, 0.0), (0.0, 0.0)),
+        ];
+        assert!(is_clockwise(&square));
+        let reversed: Vec<_> = square.iter().rev().map(Segment::reversed).collect();
+        assert!(!is_clockwise(&reversed));
+    }
+}
@@ -0,0 +1,256 @@
+//! Consistency of the nested X/Y/Z... hierarchy with the sheet.
+//!
+//! The parser trusts the strip dimensions; a level that does not fit its
+//! parent produces pieces off the sheet. These checks point at the
+//! coordinate line instead.
+
+use super::diagnostic::{Diagnostic, Span};
+use super::geometry::round_value;
+use super::rules::{finding, Params};
+use crate::error::ErrorCode;
+use crate::model::{Schema, LEVEL_VARS};
+
+/// Finding located on a line of the source file.
+fn line_finding(code: ErrorCode, message: impl Into<String>, line: usize) -> Diagnostic {
+    finding(code, message).at(Span::Line { line })
+}
+
+pub(super) fn check_hierarchy(schema: &Schema, params: &Params) -> Vec<Diagnostic> {
+    let tolerance = params.get("tolerance");
+    let entries = &schema.hierarchy;
+    let mut findings = Vec::new();
+
+    // path[l]: entry at level l enclosing the current one
+    // sums[l]: running sum of the strips at level l within path[l - 1]
+    let mut path: Vec<usize> = Vec::new();
+    let mut sums: Vec<f64> = Vec::new();
+
+    for (idx, entry) in entries.iter().enumerate() {
+        let level = entry.level;
+        if !entry.is_supported() {
+            findings.push(line_finding(
+                ErrorCode::InvalidHierarchy,
+                format!(
+                    "{}= is nested deeper than the {} supported levels (X to E)",
+                    entry.var,
+                    LEVEL_VARS.len()
+                ),
+                entry.line,
+            ));
+            continue;
+        }
+        if level > path.len() {
+            let expected = LEVEL_VARS[path.len()];
+            findings.push(line_finding(
+                ErrorCode::InvalidHierarchy,
+                format!("{}= skips a level, expected {}=", entry.var, expected),
+                entry.line,
+            ));
+            continue;
+        }
+
+        path.truncate(level);
+        sums.truncate(level + 1);
+        if sums.len() == level {
+            sums.push(0.0);
+        }
+        let before = sums[level];
+        sums[level] += entry.value;
+
+        // Even levels split the width, odd levels the height; deeper levels
+        // split the strip two levels up
+        let (available, context) = match level {
+            0 => (
+                schema.usable_width(),
+                format!("the usable width of {}", round_value(schema.usable_width())),
+            ),
+            1 => (
+                schema.usable_height(),
+                format!(
+                    "the usable height of {}",
+                    round_value(schema.usable_height())
+                ),
+            ),
+            _ => {
+                let parent = &entries[path[level - 2]];
+                (
+                    parent.value,
+                    format!(
+                        "the {}={} strip at line {}",
+                        parent.var,
+                        round_value(parent.value),
+                        parent.line
+                    ),
+                )
+            }
+        };
+        if before <= available + tolerance && sums[level] > available + tolerance {
+            findings.push(line_finding(
+                ErrorCode::OutOfBounds,
+                format!(
+                    "{} strips sum to {}, beyond {}",
+                    entry.var,
+                    round_value(sums[level]),
+                    context
+                ),
+                entry.line,
+            ));
+        }
+
+        path.push(idx);
+    }
+    findings
+}
+
+pub(super) fn check_trims(schema: &Schema, _: &Params) -> Vec<Diagnostic> {
+    let mut findings = Vec::new();
+    for (name, trim, size) in [
+        ("TrimLeft", schema.trim_left, schema.width),
+        ("TrimBottom", schema.trim_bottom, schema.height),
+    ] {
+        if trim < 0.0 {
+            findings.push(finding(
+                ErrorCode::InvalidDimension,
+                format!("{} {} is negative", name, round_value(trim)),
+            ));
+        } else if size > 0.0 && trim >= size {
+            findings.push(finding(
+                ErrorCode::OutOfBounds,
+                format!(
+                    "{} {} leaves nothing of the {} sheet",
+                    name,
+                    round_value(trim),
+                    round_value(size)
+                ),
+            ));
+        }
+    }
+    findings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::EPS;
+    use crate::model::HierarchyEntry;
+    use std::collections::BTreeMap;
+
+    /// Sheet with 10 mm trims and the given `(var, value)` lines from line 20.
+    fn schema(lines: &[(char, f64)]) -> Schema {
+        let mut schema = Schema {
+            width: 3210.0,
+            height: 2250.0,
+            trim_left: 10.0,
+            trim_bottom: 10.0,
+            ..Default::default()
+        };
+        schema.hierarchy = lines
+            .iter()
+            .enumerate()
+            .map(|(idx, &(var, value))| HierarchyEntry {
+                var,
+                level: LEVEL_VARS
+                    .iter()
+                    .position(|&c| c == var)
+                    .unwrap_or(LEVEL_VARS.len()),
+                value,
+                line: 20 + idx,
+            })
+            .collect();
+        schema
+    }
+
+    fn params() -> Params {
+        Params::new(&[("tolerance", EPS)], &BTreeMap::new())
+    }
+
+    fn lines(findings: &[Diagnostic]) -> Vec<usize> {
+        findings
+            .iter()
+            .map(|d| match d.span {
+                Some(Span::Line { line }) => line,
+                _ => 0,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_consistent_hierarchy() {
+        let schema = schema(&[
+            ('X', 1200.0),
+            ('Y', 800.0),
+            ('Y', 800.0),
+            ('X', 2000.0),
+            ('Y', 1000.0),
+            ('Z', 600.0),
+            ('Z', 1400.0),
+            ('W', 1000.0),
+        ]);
+        assert!(check_hierarchy(&schema, &params()).is_empty());
+        assert!(check_trims(&schema, &params()).is_empty());
+    }
+
+    #[test]
+    fn test_levels_exceeding_their_parent() {
+        let schema = schema(&[
+            ('X', 1200.0),
+            ('Y', 1500.0),
+            ('Y', 800.0),
+            ('Z', 700.0),
+            ('Z', 600.0),
+            ('X', 2100.0),
+        ]);
+        let findings = check_hierarchy(&schema, &params());
+        assert_eq!(lines(&findings), [22, 24, 25]);
+        assert!(findings.iter().all(|d| d.code == ErrorCode::OutOfBounds));
+        assert_eq!(
+            findings[0].message,
+            "Y strips sum to 2300, beyond the usable height of 2240"
+        );
+        assert_eq!(
+            findings[1].message,
+            "Z strips sum to 1300, beyond the X=1200 strip at line 20"
+        );
+        assert_eq!(
+            findings[2].message,
+            "X strips sum to 3300, beyond the usable width of 3200"
+        );
+    }
+
+    #[test]
+    fn test_depth_and_skipped_levels() {
+        let mut deep = vec![('X', 1000.0)];
+        deep.extend(LEVEL_VARS[1..].iter().map(|&var| (var, 100.0)));
+        deep.push(('F', 50.0));
+        deep.push(('X', 500.0));
+        deep.push(('Z', 100.0));
+        let schema = schema(&deep);
+
+        let findings = check_hierarchy(&schema, &params());
+        assert_eq!(lines(&findings), [30, 32]);
+        assert_eq!(
+            findings[0].message,
+            "F= is nested deeper than the 10 supported levels (X to E)"
+        );
+        assert_eq!(findings[1].message, "Z= skips a level, expected Y=");
+        assert!(findings
+            .iter()
+            .all(|d| d.code == ErrorCode::InvalidHierarchy));
+    }
+
+    #[test]
+    fn test_trims() {
+        let mut schema = schema(&[]);
+        schema.trim_left = -1.0;
+        schema.trim_bottom = 2250.0;
+        let findings = check_trims(&schema, &params());
+        assert_eq!(findings.len(), 2);
+        assert_eq!(findings[0].message, "TrimLeft -1 is negative");
+        assert_eq!(findings[0].code, ErrorCode::InvalidDimension);
+        assert_eq!(
+            findings[1].message,
+            "TrimBottom 2250 leaves nothing of the 2250 sheet"
+        );
+        assert_eq!(findings[1].code, ErrorCode::OutOfBounds);
+    }
+}
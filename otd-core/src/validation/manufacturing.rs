@@ -0,0 +1,406 @@
+//! Manufacturability checks.
+//!
+//! These findings do not stop a conversion: the layout can be cut, but
+//! pieces may break badly, chip or be hard to break out. Limits are given
+//! in millimetres and converted to the pattern unit.
+
+use super::diagnostic::{Diagnostic, Entity, Span};
+use super::geometry::{
+    contour, corner_angle, extreme_points, format_point, is_clockwise, placed_contour, round_value,
+};
+use super::rules::{finding, piece_finding, shaped_pieces, Params};
+use crate::analysis::Segment;
+use crate::config::{
+    DIM_MIN_PEZZO_SOLO_INCISIONE, DIM_MIN_RESTO_SOLO_INCISIONE, DIM_MIN_RESTO_SOLO_INC_HI_SPESS,
+    D_MIN_BORDO, EPS, EPS_COARSE, SOGLIA_SPESSORE_ELEVATO,
+};
+use crate::error::ErrorCode;
+use crate::model::Schema;
+
+/// Smallest piece side that can be broken out after scoring.
+pub(super) const PIECE_SIZE: &[(&str, f64)] = &[("min", DIM_MIN_PEZZO_SOLO_INCISIONE)];
+
+/// Narrowest rest strip that can be broken off, for normal and thick glass.
+pub(super) const STRIP_WIDTH: &[(&str, f64)] = &[
+    ("min", DIM_MIN_RESTO_SOLO_INCISIONE),
+    ("min-thick", DIM_MIN_RESTO_SOLO_INC_HI_SPESS),
+    ("thick-glass", SOGLIA_SPESSORE_ELEVATO),
+];
+
+/// Tightest inner radius: `min`, or `per-thickness` times the thickness
+/// when larger.
+pub(super) const INNER_RADIUS: &[(&str, f64)] = &[("min", 10.0), ("per-thickness", 3.0)];
+
+/// Smallest distance between a shape and the sheet edge.
+pub(super) const EDGE_DISTANCE: &[(&str, f64)] = &[("min", D_MIN_BORDO)];
+
+/// A length in millimetres, in the pattern unit.
+fn in_unit(schema: &Schema, mm: f64) -> f64 {
+    mm / schema.unit.to_mm_factor()
+}
+
+/// Segments of the closed shapes with their shape id.
+fn closed_contours(schema: &Schema) -> impl Iterator<Item = (i32, Vec<Segment>)> + '_ {
+    schema
+        .shapes
+        .iter()
+        .filter(|shape| !shape.is_open && shape.is_closed() && !shape.cuts.is_empty())
+        .map(|shape| (shape.id, contour(shape)))
+}
+
+pub(super) fn check_piece_size(schema: &Schema, params: &Params) -> Vec<Diagnostic> {
+    let min = in_unit(schema, params.get("min"));
+    schema
+        .pieces
+        .iter()
+        .enumerate()
+        .filter(|(_, piece)| !schema.is_waste_piece(piece))
+        .filter(|(_, piece)| piece.width.min(piece.height) < min - EPS)
+        .map(|(idx, piece)| {
+            piece_finding(
+                ErrorCode::Manufacturability,
+                format!(
+                    "{} x {} is below the minimum of {} for breaking out",
+                    round_value(piece.width),
+                    round_value(piece.height),
+                    round_value(min)
+                ),
+                idx,
+                piece,
+            )
+        })
+        .collect()
+}
+
+pub(super) fn check_strip_width(schema: &Schema, params: &Params) -> Vec<Diagnostic> {
+    let thickness = schema.thickness_mm();
+    let min = if thickness >= params.get("thick-glass") {
+        params.get("min-thick")
+    } else {
+        params.get("min")
+    };
+    let min = in_unit(schema, min);
+    schema
+        .linear_cuts
+        .iter()
+        .enumerate()
+        .filter(|(_, cut)| cut.active && cut.rest > EPS && cut.rest < min - EPS)
+        .map(|(idx, cut)| {
+            finding(
+                ErrorCode::Manufacturability,
+                format!(
+                    "Leaves a strip of {}, below the minimum of {} for {} mm glass",
+                    round_value(cut.rest),
+                    round_value(min),
+                    round_value(thickness)
+                ),
+            )
+            .on(Entity::Cut {
+                shape: None,
+                number: idx + 1,
+            })
+        })
+        .collect()
+}
+
+pub(super) fn check_inner_radius(schema: &Schema, params: &Params) -> Vec<Diagnostic> {
+    let thickness = schema.thickness_mm();
+    let min = in_unit(
+        schema,
+        params
+            .get("min")
+            .max(params.get("per-thickness") * thickness),
+    );
+    let mut findings = Vec::new();
+    for (shape_id, segments) in closed_contours(schema) {
+        // Arcs turning against the contour are concave
+        let clockwise = is_clockwise(&segments);
+        for (idx, segment) in segments.iter().enumerate() {
+            if let Segment::Arc {
+                clockwise: arc_clockwise,
+                ..
+            } = segment
+            {
+                let radius = segment.radius();
+                if *arc_clockwise != clockwise && radius < min - EPS {
+                    findings.push(
+                        finding(
+                            ErrorCode::Manufacturability,
+                            format!(
+                                "Inner radius {} is below the minimum of {} for {} mm glass",
+                                round_value(radius),
+                                round_value(min),
+                                round_value(thickness)
+                            ),
+                        )
+                        .on(Entity::Cut {
+                            shape: Some(shape_id),
+                            number: idx + 1,
+                        }),
+                    );
+                }
+            }
+        }
+    }
+    findings
+}
+
+pub(super) fn check_min_angle(schema: &Schema, _: &Params) -> Vec<Diagnostic> {
+    let min = if schema.glass_coated {
+        schema.coating_min_angle
+    } else {
+        schema.min_angle
+    };
+    let mut findings = Vec::new();
+    for shape in &schema.shapes {
+        let segments = contour(shape);
+        let closed = !shape.is_open && shape.is_closed();
+        let count = segments.len();
+        let joints = if closed {
+            count
+        } else {
+            count.saturating_sub(1)
+        };
+        for idx in 0..joints {
+            let next = (idx + 1) % count;
+            let (a, b) = (&segments[idx], &segments[next]);
+            let gap = (a.end().0 - b.start().0).hypot(a.end().1 - b.start().1);
+            if gap > EPS_COARSE {
+                continue;
+            }
+            let angle = corner_angle(a, b);
+            if angle < min - EPS {
+                findings.push(
+                    finding(
+                        ErrorCode::Manufacturability,
+                        format!(
+                            "Corner of {}° with cut {} is below the minimum angle of {}°",
+                            (angle * 100.0).round() / 100.0,
+                            next + 1,
+                            min
+                        ),
+                    )
+                    .on(Entity::Cut {
+                        shape: Some(shape.id),
+                        number: idx + 1,
+                    }),
+                );
+            }
+        }
+    }
+    findings
+}
+
+pub(super) fn check_edge_distance(schema: &Schema, params: &Params) -> Vec<Diagnostic> {
+    let min = in_unit(schema, params.get("min"));
+    let mut findings = Vec::new();
+    for (idx, piece, shape) in shaped_pieces(schema) {
+        // Shapes leaving the sheet are reported by shape-containment
+        let closest = placed_contour(shape, piece)
+            .iter()
+            .flat_map(extreme_points)
+            .map(|p| {
+                let distance =
+                    p.0.min(p.1)
+                        .min(schema.width - p.0)
+                        .min(schema.height - p.1);
+                (p, distance)
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1));
+        if let Some((point, distance)) = closest {
+            if distance >= -EPS_COARSE && distance < min - EPS {
+                findings.push(
+                    finding(
+                        ErrorCode::Manufacturability,
+                        format!(
+                            "Shape {} is {} from the sheet edge at {}, below the minimum of {}",
+                            shape.id,
+                            round_value(distance.max(0.0)),
+                            format_point(point),
+                            round_value(min)
+                        ),
+                    )
+                    .on(Entity::Piece { number: idx + 1 })
+                    .at(Span::Region {
+                        x1: point.0,
+                        y1: point.1,
+                        x2: point.0,
+                        y2: point.1,
+                    }),
+                );
+            }
+        }
+    }
+    findings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Unit;
+    use crate::model::{Cut, Piece, Shape};
+    use std::collections::BTreeMap;
+
+    fn params(defaults: &[(&str, f64)]) -> Params {
+        Params::new(defaults, &BTreeMap::new())
+    }
+
+    fn polygon(id: i32, points: &[(f64, f64)]) -> Shape {
+        let mut shape = Shape::new(id);
+        for (i, &(x, y)) in points.iter().enumerate() {
+            let (xf, yf) = points[(i + 1) % points.len()];
+            shape.cuts.push(Cut::new_line(x, y, xf, yf));
+        }
+        shape
+    }
+
+    fn schema(thickness: f64) -> Schema {
+        Schema {
+            width: 1000.0,
+            height: 500.0,
+            thickness,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_piece_size_and_strip_width() {
+        let mut schema = schema(4.0);
+        schema.pieces.push(Piece::new(0.0, 0.0, 80.0, 300.0));
+        schema.pieces.push(Piece::new(80.0, 0.0, 300.0, 300.0));
+        let findings = check_piece_size(&schema, &params(PIECE_SIZE));
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].entity, Some(Entity::Piece { number: 1 }));
+        assert_eq!(
+            findings[0].message,
+            "80 x 300 is below the minimum of 100 for breaking out"
+        );
+
+        for rest in [0.0, 30.0, 60.0, 900.0] {
+            let mut cut = Cut::new_line(0.0, 0.0, 0.0, 500.0);
+            cut.active = true;
+            cut.rest = rest;
+            schema.linear_cuts.push(cut);
+        }
+        let findings = check_strip_width(&schema, &params(STRIP_WIDTH));
+        assert_eq!(findings.len(), 1);
+        assert_eq!(
+            findings[0].entity,
+            Some(Entity::Cut {
+                shape: None,
+                number: 2
+            })
+        );
+
+        // Thick glass needs wider strips
+        schema.thickness = 8.0;
+        let findings = check_strip_width(&schema, &params(STRIP_WIDTH));
+        assert_eq!(findings.len(), 2);
+        assert_eq!(
+            findings[1].message,
+            "Leaves a strip of 60, below the minimum of 80 for 8 mm glass"
+        );
+
+        // Limits are converted to the pattern unit
+        schema.unit = Unit::Inches;
+        schema.thickness = 0.25;
+        schema.linear_cuts.iter_mut().for_each(|c| c.rest /= 25.4);
+        assert_eq!(check_strip_width(&schema, &params(STRIP_WIDTH)).len(), 2);
+    }
+
+    #[test]
+    fn test_inner_radius() {
+        // Clockwise 100 x 100 square with a rounded notch in its top edge
+        // and a rounded outer corner
+        let mut shape = Shape::new(1);
+        shape.cuts.push(Cut::new_line(0.0, 0.0, 0.0, 100.0));
+        shape.cuts.push(Cut::new_line(0.0, 100.0, 40.0, 100.0));
+        shape
+            .cuts
+            .push(Cut::new_arc_ccw(40.0, 100.0, 50.0, 100.0, 5.0));
+        shape.cuts.push(Cut::new_line(50.0, 100.0, 90.0, 100.0));
+        shape
+            .cuts
+            .push(Cut::new_arc_cw(90.0, 100.0, 100.0, 90.0, 10.0));
+        shape.cuts.push(Cut::new_line(100.0, 90.0, 100.0, 0.0));
+        shape.cuts.push(Cut::new_line(100.0, 0.0, 0.0, 0.0));
+        let mut schema = schema(4.0);
+        schema.shapes.push(shape);
+
+        let findings = check_inner_radius(&schema, &params(INNER_RADIUS));
+        assert_eq!(findings.len(), 1);
+        assert_eq!(
+            findings[0].entity,
+            Some(Entity::Cut {
+                shape: Some(1),
+                number: 3
+            })
+        );
+        assert_eq!(
+            findings[0].message,
+            "Inner radius 5 is below the minimum of 12 for 4 mm glass"
+        );
+
+        let relaxed = Params::new(
+            INNER_RADIUS,
+            &BTreeMap::from([("per-thickness".to_string(), 1.0), ("min".to_string(), 5.0)]),
+        );
+        assert!(check_inner_radius(&schema, &relaxed).is_empty());
+    }
+
+    #[test]
+    fn test_min_angle() {
+        let mut schema = schema(4.0);
+        schema.min_angle = 5.0;
+        schema
+            .shapes
+            .push(polygon(1, &[(0.0, 0.0), (0.0, 100.0), (3.0, 0.0)]));
+        schema.shapes.push(polygon(
+            2,
+            &[(0.0, 0.0), (0.0, 100.0), (100.0, 100.0), (100.0, 0.0)],
+        ));
+
+        let findings = check_min_angle(&schema, &params(&[]));
+        assert_eq!(findings.len(), 1);
+        assert_eq!(
+            findings[0].entity,
+            Some(Entity::Cut {
+                shape: Some(1),
+                number: 1
+            })
+        );
+        assert_eq!(
+            findings[0].message,
+            "Corner of 1.72° with cut 2 is below the minimum angle of 5°"
+        );
+
+        // Coated glass uses its own limit
+        schema.glass_coated = true;
+        schema.coating_min_angle = 1.0;
+        assert!(check_min_angle(&schema, &params(&[])).is_empty());
+    }
+
+    #[test]
+    fn test_edge_distance() {
+        let mut schema = schema(4.0);
+        schema.shapes.push(polygon(
+            1,
+            &[(0.0, 0.0), (0.0, 100.0), (100.0, 100.0), (100.0, 0.0)],
+        ));
+        for (x, y) in [(1.0, 10.0), (10.0, 10.0), (899.5, 200.0)] {
+            let mut piece = Piece::new(x, y, 100.0, 100.0);
+            piece.shape_id = Some(1);
+            schema.pieces.push(piece);
+        }
+        schema.resolve_piece_references();
+
+        let findings = check_edge_distance(&schema, &params(EDGE_DISTANCE));
+        assert_eq!(findings.len(), 2);
+        assert_eq!(
+            findings[0].message,
+            "Shape 1 is 1 from the sheet edge at (1, 10), below the minimum of 2"
+        );
+        assert_eq!(findings[1].entity, Some(Entity::Piece { number: 3 }));
+        assert!(findings[1].message.contains("is 0.5 from"));
+    }
+}
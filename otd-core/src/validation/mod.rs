@@ -1,5 +1,14 @@
 //! Validation module for OTD conversion.
 
+mod diagnostic;
+mod engine;
+mod geometry;
+mod hierarchy;
+mod manufacturing;
+mod rules;
 mod validate;
 
+pub use diagnostic::{Diagnostic, Entity, Severity, Span};
+pub use engine::{RuleConfig, ValidationConfig, Validator};
+pub use rules::{builtin_rules, Level, Params, Rule};
 pub use validate::*;
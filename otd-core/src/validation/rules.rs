@@ -0,0 +1,885 @@
+//! Validation rules.
+//!
+//! Each check is a named [`Rule`] whose level (off, warn, error) and
+//! parameters come from the [`ValidationConfig`](super::ValidationConfig).
+//! Rules report findings as [`Diagnostic`]s; the engine sets their severity
+//! from the configured level and their schema number.
+
+use super::diagnostic::{Diagnostic, Entity, Span};
+use super::geometry::{
+    contour, crossing, extent, extreme_points, format_point, intersections, midpoint,
+    placed_contour, round_value, strictly_inside, Point,
+};
+use super::hierarchy::{check_hierarchy, check_trims};
+use super::manufacturing::{
+    check_edge_distance, check_inner_radius, check_min_angle, check_piece_size, check_strip_width,
+    EDGE_DISTANCE, INNER_RADIUS, PIECE_SIZE, STRIP_WIDTH,
+};
+use super::validate::{piece_overlaps, validate_has_cuts, validate_sheet_formats};
+use crate::analysis::Segment;
+use crate::config::{EPS, EPS_COARSE};
+use crate::error::ErrorCode;
+use crate::model::{Piece, Schema, Shape};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Configured level of a rule.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    /// The rule does not run.
+    Off,
+    /// Findings are warnings.
+    Warn,
+    /// Findings fail validation.
+    Error,
+}
+
+/// Resolved parameters of a rule: its defaults overridden by the configuration.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Params(BTreeMap<String, f64>);
+
+impl Params {
+    /// Build the parameters from defaults and overrides.
+    pub fn new(defaults: &[(&str, f64)], overrides: &BTreeMap<String, f64>) -> Self {
+        let mut values: BTreeMap<String, f64> = defaults
+            .iter()
+            .map(|(name, value)| (name.to_string(), *value))
+            .collect();
+        values.extend(overrides.iter().map(|(k, v)| (k.clone(), *v)));
+        Self(values)
+    }
+
+    /// Value of a parameter, 0 when the rule does not declare it.
+    pub fn get(&self, name: &str) -> f64 {
+        self.0.get(name).copied().unwrap_or_default()
+    }
+}
+
+/// A named validation check.
+///
+/// Custom rules implement this trait and are added with
+/// [`Validator::with_rule`](super::Validator::with_rule).
+pub trait Rule: Send + Sync {
+    /// Name used in the configuration, e.g. `piece-overlap`.
+    fn name(&self) -> &str;
+
+    /// One-line description of what the rule checks.
+    fn description(&self) -> &str;
+
+    /// Level when the configuration does not set one.
+    fn default_level(&self) -> Level {
+        Level::Warn
+    }
+
+    /// Parameter names with their default values.
+    fn params(&self) -> &[(&'static str, f64)] {
+        &[]
+    }
+
+    /// Check one pattern.
+    fn check_schema(&self, _schema: &Schema, _params: &Params) -> Vec<Diagnostic> {
+        Vec::new()
+    }
+
+    /// Check all patterns of a file together.
+    fn check_job(&self, _schemas: &[Schema], _params: &Params) -> Vec<Diagnostic> {
+        Vec::new()
+    }
+}
+
+type SchemaCheck = fn(&Schema, &Params) -> Vec<Diagnostic>;
+type JobCheck = fn(&[Schema], &Params) -> Vec<Diagnostic>;
+
+/// A rule shipped with the library.
+struct BuiltinRule {
+    name: &'static str,
+    description: &'static str,
+    level: Level,
+    params: &'static [(&'static str, f64)],
+    schema: Option<SchemaCheck>,
+    job: Option<JobCheck>,
+}
+
+impl Rule for BuiltinRule {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn description(&self) -> &str {
+        self.description
+    }
+
+    fn default_level(&self) -> Level {
+        self.level
+    }
+
+    fn params(&self) -> &[(&'static str, f64)] {
+        self.params
+    }
+
+    fn check_schema(&self, schema: &Schema, params: &Params) -> Vec<Diagnostic> {
+        self.schema.map_or_else(Vec::new, |f| f(schema, params))
+    }
+
+    fn check_job(&self, schemas: &[Schema], params: &Params) -> Vec<Diagnostic> {
+        self.job.map_or_else(Vec::new, |f| f(schemas, params))
+    }
+}
+
+const TOLERANCE: &[(&str, f64)] = &[("tolerance", EPS)];
+
+/// Contour coordinates are written with 3 decimals.
+const CONTOUR_TOLERANCE: &[(&str, f64)] = &[("tolerance", EPS_COARSE)];
+
+/// Rules run by default, in reporting order.
+pub fn builtin_rules() -> Vec<Box<dyn Rule>> {
+    let rule = |name, description, level, params, schema: SchemaCheck| -> Box<dyn Rule> {
+        Box::new(BuiltinRule {
+            name,
+            description,
+            level,
+            params,
+            schema: Some(schema),
+            job: None,
+        })
+    };
+
+    vec![
+        rule(
+            "sheet-dimensions",
+            "Sheet width and height are positive",
+            Level::Error,
+            &[],
+            check_sheet_dimensions,
+        ),
+        rule(
+            "trims",
+            "TrimLeft and TrimBottom lie within the sheet",
+            Level::Error,
+            &[],
+            check_trims,
+        ),
+        rule(
+            "hierarchy",
+            "Nested strips fit their parent and stay within ten levels",
+            Level::Error,
+            TOLERANCE,
+            check_hierarchy,
+        ),
+        rule(
+            "thickness",
+            "Sheet thickness is set, so a tool can be selected",
+            Level::Warn,
+            &[],
+            check_thickness,
+        ),
+        rule(
+            "piece-dimensions",
+            "Piece width and height are positive",
+            Level::Error,
+            &[],
+            check_piece_dimensions,
+        ),
+        rule(
+            "piece-bounds",
+            "Pieces lie within the sheet",
+            Level::Warn,
+            TOLERANCE,
+            check_piece_bounds,
+        ),
+        rule(
+            "shape-reference",
+            "Piece Shape references exist",
+            Level::Error,
+            &[],
+            check_shape_references,
+        ),
+        rule(
+            "info-reference",
+            "Piece Info references exist",
+            Level::Error,
+            &[],
+            check_info_references,
+        ),
+        rule(
+            "piece-overlap",
+            "Pieces do not overlap each other",
+            Level::Warn,
+            TOLERANCE,
+            check_piece_overlap,
+        ),
+        rule(
+            "empty-shape",
+            "Shapes have cuts",
+            Level::Warn,
+            &[],
+            check_empty_shapes,
+        ),
+        rule(
+            "shape-closed",
+            "Closed shapes end where they start",
+            Level::Warn,
+            &[],
+            check_shapes_closed,
+        ),
+        rule(
+            "arc-radius",
+            "Arc radii reach both endpoints",
+            Level::Error,
+            TOLERANCE,
+            check_arc_radii,
+        ),
+        rule(
+            "shape-containment",
+            "Shape contours lie within their piece and the sheet",
+            Level::Error,
+            CONTOUR_TOLERANCE,
+            check_shape_containment,
+        ),
+        rule(
+            "self-intersection",
+            "Shape contours do not cross or touch themselves",
+            Level::Error,
+            CONTOUR_TOLERANCE,
+            check_self_intersection,
+        ),
+        rule(
+            "shape-overlap",
+            "Shapes on different pieces do not overlap",
+            Level::Error,
+            CONTOUR_TOLERANCE,
+            check_shape_overlap,
+        ),
+        rule(
+            "shape-size",
+            "Each shape is used on pieces of one size",
+            Level::Error,
+            &[],
+            check_shape_sizes,
+        ),
+        rule(
+            "has-cuts",
+            "The pattern has active cuts or shapes",
+            Level::Warn,
+            &[],
+            check_has_cuts,
+        ),
+        rule(
+            "piece-size",
+            "Pieces are large enough to break out after scoring",
+            Level::Warn,
+            PIECE_SIZE,
+            check_piece_size,
+        ),
+        rule(
+            "strip-width",
+            "Rest strips are wide enough to break off for the thickness",
+            Level::Warn,
+            STRIP_WIDTH,
+            check_strip_width,
+        ),
+        rule(
+            "inner-radius",
+            "Concave arcs are not tighter than the thickness allows",
+            Level::Warn,
+            INNER_RADIUS,
+            check_inner_radius,
+        ),
+        rule(
+            "min-angle",
+            "Corners between shape cuts are not sharper than MinAngle",
+            Level::Warn,
+            &[],
+            check_min_angle,
+        ),
+        rule(
+            "edge-distance",
+            "Shapes keep clear of the sheet edge",
+            Level::Warn,
+            EDGE_DISTANCE,
+            check_edge_distance,
+        ),
+        Box::new(BuiltinRule {
+            name: "sheet-format",
+            description: "All patterns share one sheet format",
+            level: Level::Warn,
+            params: &[],
+            schema: None,
+            job: Some(check_sheet_formats),
+        }),
+    ]
+}
+
+/// Finding of a rule; the engine sets its severity.
+pub(super) fn finding(code: ErrorCode, message: impl Into<String>) -> Diagnostic {
+    Diagnostic::warning(code, message)
+}
+
+/// Sheet region covered by a piece.
+fn piece_span(piece: &Piece) -> Span {
+    Span::Region {
+        x1: piece.x_origin,
+        y1: piece.y_origin,
+        x2: piece.x_max(),
+        y2: piece.y_max(),
+    }
+}
+
+/// Finding located on a piece.
+pub(super) fn piece_finding(
+    code: ErrorCode,
+    message: impl Into<String>,
+    idx: usize,
+    piece: &Piece,
+) -> Diagnostic {
+    finding(code, message)
+        .on(Entity::Piece { number: idx + 1 })
+        .at(piece_span(piece))
+}
+
+fn check_sheet_dimensions(schema: &Schema, _: &Params) -> Vec<Diagnostic> {
+    if schema.width <= 0.0 || schema.height <= 0.0 {
+        vec![finding(
+            ErrorCode::InvalidDimension,
+            format!(
+                "Invalid sheet dimensions ({}x{})",
+                schema.width, schema.height
+            ),
+        )]
+    } else {
+        Vec::new()
+    }
+}
+
+fn check_thickness(schema: &Schema, _: &Params) -> Vec<Diagnostic> {
+    if schema.thickness <= 0.0 {
+        vec![finding(
+            ErrorCode::ToolNotFound,
+            "Missing or zero thickness",
+        )]
+    } else {
+        Vec::new()
+    }
+}
+
+fn check_piece_dimensions(schema: &Schema, _: &Params) -> Vec<Diagnostic> {
+    schema
+        .pieces
+        .iter()
+        .enumerate()
+        .filter(|(_, piece)| piece.width <= 0.0 || piece.height <= 0.0)
+        .map(|(idx, piece)| {
+            piece_finding(
+                ErrorCode::InvalidDimension,
+                format!("Invalid dimensions ({}x{})", piece.width, piece.height),
+                idx,
+                piece,
+            )
+        })
+        .collect()
+}
+
+fn check_piece_bounds(schema: &Schema, params: &Params) -> Vec<Diagnostic> {
+    let tolerance = params.get("tolerance");
+    schema
+        .pieces
+        .iter()
+        .enumerate()
+        .filter(|(_, piece)| {
+            piece.x_origin < -tolerance
+                || piece.y_origin < -tolerance
+                || piece.x_max() > schema.width + tolerance
+                || piece.y_max() > schema.height + tolerance
+        })
+        .map(|(idx, piece)| {
+            piece_finding(
+                ErrorCode::OutOfBounds,
+                "Extends beyond sheet bounds",
+                idx,
+                piece,
+            )
+        })
+        .collect()
+}
+
+fn check_shape_references(schema: &Schema, _: &Params) -> Vec<Diagnostic> {
+    let mut findings = Vec::new();
+    for (idx, piece) in schema.pieces.iter().enumerate() {
+        if let Some(shape_id) = piece.shape_id {
+            if schema.find_shape(shape_id).is_none() {
+                findings.push(piece_finding(
+                    ErrorCode::UnresolvedReference,
+                    format!("Shape {} not found", shape_id),
+                    idx,
+                    piece,
+                ));
+            }
+        }
+    }
+    findings
+}
+
+fn check_info_references(schema: &Schema, _: &Params) -> Vec<Diagnostic> {
+    let mut findings = Vec::new();
+    for (idx, piece) in schema.pieces.iter().enumerate() {
+        if let Some(info_id) = piece.info_id {
+            if schema.find_piece_type(info_id).is_none() {
+                findings.push(piece_finding(
+                    ErrorCode::UnresolvedReference,
+                    format!("Info {} not found", info_id),
+                    idx,
+                    piece,
+                ));
+            }
+        }
+    }
+    findings
+}
+
+fn check_piece_overlap(schema: &Schema, params: &Params) -> Vec<Diagnostic> {
+    piece_overlaps(schema, params.get("tolerance"))
+        .into_iter()
+        .map(|(i, j)| {
+            let (a, b) = (&schema.pieces[i], &schema.pieces[j]);
+            finding(ErrorCode::OutOfBounds, format!("Overlaps piece {}", j + 1))
+                .on(Entity::Piece { number: i + 1 })
+                .at(Span::Region {
+                    x1: a.x_origin.max(b.x_origin),
+                    y1: a.y_origin.max(b.y_origin),
+                    x2: a.x_max().min(b.x_max()),
+                    y2: a.y_max().min(b.y_max()),
+                })
+        })
+        .collect()
+}
+
+fn check_empty_shapes(schema: &Schema, _: &Params) -> Vec<Diagnostic> {
+    schema
+        .shapes
+        .iter()
+        .filter(|shape| shape.cuts.is_empty())
+        .map(|shape| {
+            finding(ErrorCode::NoCutsFound, "No cuts defined").on(Entity::Shape { id: shape.id })
+        })
+        .collect()
+}
+
+fn check_shapes_closed(schema: &Schema, _: &Params) -> Vec<Diagnostic> {
+    schema
+        .shapes
+        .iter()
+        .filter(|shape| !shape.is_open && !shape.is_closed())
+        .map(|shape| {
+            finding(ErrorCode::OpenContour, "Shape is not closed")
+                .on(Entity::Shape { id: shape.id })
+        })
+        .collect()
+}
+
+fn check_arc_radii(schema: &Schema, params: &Params) -> Vec<Diagnostic> {
+    let tolerance = params.get("tolerance");
+    let mut findings = Vec::new();
+    for shape in &schema.shapes {
+        for (cut_idx, cut) in shape.cuts.iter().enumerate() {
+            if cut.is_arc() {
+                let chord_len = ((cut.xf - cut.xi).powi(2) + (cut.yf - cut.yi).powi(2)).sqrt();
+                if cut.radius < chord_len / 2.0 - tolerance {
+                    findings.push(
+                        finding(
+                            ErrorCode::InvalidArc,
+                            format!(
+                                "Arc radius {} is too small for chord length {}",
+                                cut.radius, chord_len
+                            ),
+                        )
+                        .on(Entity::Cut {
+                            shape: Some(shape.id),
+                            number: cut_idx + 1,
+                        }),
+                    );
+                }
+            }
+        }
+    }
+    findings
+}
+
+/// Pieces with their shape, in layout order.
+pub(super) fn shaped_pieces(schema: &Schema) -> impl Iterator<Item = (usize, &Piece, &Shape)> {
+    schema.pieces.iter().enumerate().filter_map(|(idx, piece)| {
+        let shape = schema.shapes.get(piece.shape_index?)?;
+        (!shape.cuts.is_empty()).then_some((idx, piece, shape))
+    })
+}
+
+/// Point of a contour farthest outside a rectangle, with its distance.
+fn farthest_outside(
+    contour: &[Segment],
+    (x1, y1, x2, y2): (f64, f64, f64, f64),
+) -> Option<(usize, Point, f64)> {
+    contour
+        .iter()
+        .enumerate()
+        .flat_map(|(idx, segment)| extreme_points(segment).into_iter().map(move |p| (idx, p)))
+        .map(|(idx, p)| {
+            let excess = (x1 - p.0).max(p.0 - x2).max(y1 - p.1).max(p.1 - y2);
+            (idx, p, excess)
+        })
+        .max_by(|a, b| a.2.total_cmp(&b.2))
+}
+
+fn check_shape_containment(schema: &Schema, params: &Params) -> Vec<Diagnostic> {
+    let tolerance = params.get("tolerance");
+    let sheet = (0.0, 0.0, schema.width, schema.height);
+    let mut findings = Vec::new();
+    for (idx, piece, shape) in shaped_pieces(schema) {
+        let contour = placed_contour(shape, piece);
+        let bounds = (piece.x_origin, piece.y_origin, piece.x_max(), piece.y_max());
+        let outside = [("sheet", sheet), ("piece", bounds)]
+            .into_iter()
+            .find_map(|(name, rect)| {
+                farthest_outside(&contour, rect)
+                    .filter(|(_, _, excess)| *excess > tolerance)
+                    .map(|found| (name, found))
+            });
+        if let Some((name, (cut_idx, point, excess))) = outside {
+            findings.push(
+                finding(
+                    ErrorCode::OutOfBounds,
+                    format!(
+                        "Shape {}, cut {} leaves the {} at {} by {}",
+                        shape.id,
+                        cut_idx + 1,
+                        name,
+                        format_point(point),
+                        round_value(excess)
+                    ),
+                )
+                .on(Entity::Piece { number: idx + 1 })
+                .at(Span::Region {
+                    x1: point.0,
+                    y1: point.1,
+                    x2: point.0,
+                    y2: point.1,
+                }),
+            );
+        }
+    }
+    findings
+}
+
+fn check_self_intersection(schema: &Schema, params: &Params) -> Vec<Diagnostic> {
+    let tolerance = params.get("tolerance");
+    let mut findings = Vec::new();
+    for shape in &schema.shapes {
+        let segments = contour(shape);
+        let count = segments.len();
+        let closed = !shape.is_open && shape.is_closed();
+        'pairs: for i in 0..count {
+            for j in i + 1..count {
+                // Joints shared with the neighbouring cut are not crossings
+                let mut joints = Vec::new();
+                if j == i + 1 {
+                    joints.push(segments[i].end());
+                }
+                if closed && i == 0 && j == count - 1 {
+                    joints.push(segments[i].start());
+                }
+                let point = intersections(&segments[i], &segments[j], tolerance)
+                    .into_iter()
+                    .find(|p| {
+                        !joints
+                            .iter()
+                            .any(|q| (p.0 - q.0).hypot(p.1 - q.1) <= tolerance)
+                    });
+                if let Some(point) = point {
+                    findings.push(
+                        finding(
+                            ErrorCode::SelfIntersection,
+                            format!("Crosses cut {} at {}", j + 1, format_point(point)),
+                        )
+                        .on(Entity::Cut {
+                            shape: Some(shape.id),
+                            number: i + 1,
+                        }),
+                    );
+                    // One finding per shape
+                    break 'pairs;
+                }
+            }
+        }
+    }
+    findings
+}
+
+fn check_shape_overlap(schema: &Schema, params: &Params) -> Vec<Diagnostic> {
+    let tolerance = params.get("tolerance");
+    let placed: Vec<_> = shaped_pieces(schema)
+        .map(|(idx, piece, shape)| {
+            let contour = placed_contour(shape, piece);
+            let closed = !shape.is_open && shape.is_closed();
+            (idx, extent(&contour), contour, closed)
+        })
+        .collect();
+
+    let mut findings = Vec::new();
+    for (a_idx, (piece_a, box_a, contour_a, closed_a)) in placed.iter().enumerate() {
+        for (piece_b, box_b, contour_b, closed_b) in &placed[a_idx + 1..] {
+            let apart = box_a.2 < box_b.0 - tolerance
+                || box_b.2 < box_a.0 - tolerance
+                || box_a.3 < box_b.1 - tolerance
+                || box_b.3 < box_a.1 - tolerance;
+            if apart {
+                continue;
+            }
+
+            // Contours crossing each other, or one inside the other
+            let point = contour_a
+                .iter()
+                .flat_map(|a| contour_b.iter().map(move |b| (a, b)))
+                .find_map(|(a, b)| crossing(a, b, tolerance))
+                .or_else(|| {
+                    let inside = |contour: &[Segment], closed: bool, other: &[Segment]| {
+                        if !closed {
+                            return None;
+                        }
+                        other
+                            .iter()
+                            .map(midpoint)
+                            .find(|&p| strictly_inside(contour, p, tolerance))
+                    };
+                    inside(contour_b, *closed_b, contour_a)
+                        .or_else(|| inside(contour_a, *closed_a, contour_b))
+                });
+
+            if let Some(point) = point {
+                findings.push(
+                    finding(
+                        ErrorCode::OutOfBounds,
+                        format!(
+                            "Shape overlaps the shape of piece {} at {}",
+                            piece_b + 1,
+                            format_point(point)
+                        ),
+                    )
+                    .on(Entity::Piece {
+                        number: piece_a + 1,
+                    })
+                    .at(Span::Region {
+                        x1: point.0,
+                        y1: point.1,
+                        x2: point.0,
+                        y2: point.1,
+                    }),
+                );
+            }
+        }
+    }
+    findings
+}
+
+fn check_shape_sizes(schema: &Schema, _: &Params) -> Vec<Diagnostic> {
+    match crate::transform::check_shape_piece_sizes(schema) {
+        Ok(()) => Vec::new(),
+        Err(mismatched) => mismatched
+            .into_iter()
+            .map(|shape_id| {
+                finding(
+                    ErrorCode::ShapeSizeMismatch,
+                    "Used on pieces of different sizes",
+                )
+                .on(Entity::Shape { id: shape_id })
+            })
+            .collect(),
+    }
+}
+
+fn check_has_cuts(schema: &Schema, _: &Params) -> Vec<Diagnostic> {
+    if validate_has_cuts(schema) {
+        Vec::new()
+    } else if schema.linear_cuts.is_empty() && schema.shapes.is_empty() {
+        vec![finding(ErrorCode::NoCutsFound, "No cuts or shapes defined")]
+    } else {
+        vec![finding(ErrorCode::NoCutsFound, "No active cuts or shapes")]
+    }
+}
+
+fn check_sheet_formats(schemas: &[Schema], _: &Params) -> Vec<Diagnostic> {
+    validate_sheet_formats(schemas).diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{Cut, HierarchyEntry};
+
+    fn polygon(id: i32, points: &[(f64, f64)]) -> Shape {
+        let mut shape = Shape::new(id);
+        for (i, &(x, y)) in points.iter().enumerate() {
+            let (xf, yf) = points[(i + 1) % points.len()];
+            shape.cuts.push(Cut::new_line(x, y, xf, yf));
+        }
+        shape
+    }
+
+    fn rectangle(id: i32, width: f64, height: f64) -> Shape {
+        polygon(
+            id,
+            &[(0.0, 0.0), (0.0, height), (width, height), (width, 0.0)],
+        )
+    }
+
+    fn schema_with(shapes: Vec<Shape>, pieces: &[(f64, f64, i32)]) -> Schema {
+        let mut schema = Schema {
+            width: 1000.0,
+            height: 500.0,
+            shapes,
+            ..Default::default()
+        };
+        for &(x, y, shape_id) in pieces {
+            let mut piece = Piece::new(x, y, 100.0, 100.0);
+            piece.shape_id = Some(shape_id);
+            schema.pieces.push(piece);
+        }
+        schema.resolve_piece_references();
+        schema
+    }
+
+    fn params() -> Params {
+        Params::new(CONTOUR_TOLERANCE, &BTreeMap::new())
+    }
+
+    #[test]
+    fn test_shape_containment() {
+        // Half disc touching the top of its 100 x 50 piece
+        let mut arch = Shape::new(1);
+        arch.cuts.push(Cut::new_arc_cw(0.0, 0.0, 100.0, 0.0, 50.0));
+        arch.cuts.push(Cut::new_line(100.0, 0.0, 0.0, 0.0));
+        let mut schema = schema_with(vec![arch, rectangle(2, 120.0, 100.0)], &[(10.0, 10.0, 1)]);
+        schema.pieces[0].height = 50.0;
+        assert!(check_shape_containment(&schema, &params()).is_empty());
+
+        // Taller piece, arch shifted up beyond it
+        schema.shapes[0].cuts.iter_mut().for_each(|c| {
+            c.yi += 2.0;
+            c.yf += 2.0;
+            c.yc += 2.0;
+        });
+        let findings = check_shape_containment(&schema, &params());
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].code, ErrorCode::OutOfBounds);
+        assert_eq!(
+            findings[0].message,
+            "Shape 1, cut 1 leaves the piece at (60, 62) by 2"
+        );
+
+        // 120 wide rectangle on a 100 wide piece, then at the sheet edge
+        let mut schema = schema_with(vec![rectangle(2, 120.0, 100.0)], &[(10.0, 10.0, 2)]);
+        let findings = check_shape_containment(&schema, &params());
+        assert!(findings[0].message.contains("leaves the piece at (130,"));
+        assert_eq!(findings[0].entity, Some(Entity::Piece { number: 1 }));
+        schema.pieces[0].x_origin = 900.0;
+        let findings = check_shape_containment(&schema, &params());
+        assert!(findings[0].message.contains("leaves the sheet at (1020,"));
+        assert!(findings[0].message.ends_with("by 20"));
+    }
+
+    #[test]
+    fn test_rule_codes() {
+        // Zero-sized sheet and piece, negative trim, missing Shape and Info,
+        // open contour and a Z strip right under an X strip
+        let mut schema = schema_with(vec![rectangle(1, 100.0, 100.0)], &[(10.0, 10.0, 2)]);
+        schema.height = 0.0;
+        schema.trim_left = -1.0;
+        schema.pieces[0].width = 0.0;
+        schema.pieces[0].info_id = Some(9);
+        schema.shapes[0].cuts.pop();
+        schema.hierarchy = vec![
+            HierarchyEntry {
+                var: 'X',
+                level: 0,
+                value: 500.0,
+                line: 20,
+            },
+            HierarchyEntry {
+                var: 'Z',
+                level: 2,
+                value: 100.0,
+                line: 21,
+            },
+        ];
+
+        let rules = builtin_rules();
+        for (name, code) in [
+            ("sheet-dimensions", ErrorCode::InvalidDimension),
+            ("trims", ErrorCode::InvalidDimension),
+            ("hierarchy", ErrorCode::InvalidHierarchy),
+            ("piece-dimensions", ErrorCode::InvalidDimension),
+            ("shape-reference", ErrorCode::UnresolvedReference),
+            ("info-reference", ErrorCode::UnresolvedReference),
+            ("shape-closed", ErrorCode::OpenContour),
+        ] {
+            let rule = rules.iter().find(|r| r.name() == name).unwrap();
+            let params = Params::new(rule.params(), &BTreeMap::new());
+            let findings = rule.check_schema(&schema, &params);
+            assert!(!findings.is_empty(), "{}", name);
+            assert!(findings.iter().all(|d| d.code == code), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_self_intersection() {
+        let square = rectangle(1, 100.0, 100.0);
+        let bow_tie = polygon(2, &[(0.0, 0.0), (0.0, 100.0), (100.0, 0.0), (100.0, 100.0)]);
+        let schema = schema_with(vec![square, bow_tie], &[]);
+
+        let findings = check_self_intersection(&schema, &params());
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].code, ErrorCode::SelfIntersection);
+        assert_eq!(
+            findings[0].entity,
+            Some(Entity::Cut {
+                shape: Some(2),
+                number: 2
+            })
+        );
+        assert_eq!(findings[0].message, "Crosses cut 4 at (50, 50)");
+    }
+
+    #[test]
+    fn test_shape_overlap() {
+        // Neighbouring shapes sharing the piece edge do not overlap
+        let schema = schema_with(
+            vec![rectangle(1, 100.0, 100.0)],
+            &[(0.0, 0.0, 1), (100.0, 0.0, 1)],
+        );
+        assert!(check_shape_overlap(&schema, &params()).is_empty());
+
+        // A shape wider than its piece runs into the neighbour
+        let schema = schema_with(
+            vec![rectangle(1, 100.0, 100.0), rectangle(2, 120.0, 100.0)],
+            &[(0.0, 0.0, 2), (100.0, 0.0, 1), (300.0, 0.0, 2)],
+        );
+        let findings = check_shape_overlap(&schema, &params());
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].entity, Some(Entity::Piece { number: 1 }));
+        assert_eq!(
+            findings[0].message,
+            "Shape overlaps the shape of piece 2 at (120, 50)"
+        );
+
+        // Crossing contours report the crossing point
+        let diamond = polygon(
+            3,
+            &[(50.0, -10.0), (-10.0, 50.0), (50.0, 110.0), (110.0, 50.0)],
+        );
+        let schema = schema_with(
+            vec![rectangle(1, 100.0, 100.0), diamond],
+            &[(0.0, 0.0, 1), (60.0, 0.0, 3)],
+        );
+        let findings = check_shape_overlap(&schema, &params());
+        assert_eq!(findings.len(), 1);
+        assert!(findings[0].message.contains(" at ("));
+    }
+}
@@ -1,16 +1,26 @@
 //! Validation logic for OTD to CNI conversion.
 
-use crate::error::{ConvertError, Result};
+use super::diagnostic::{Diagnostic, Severity};
+use super::engine::Validator;
+use crate::error::{ConvertError, ErrorCode, Result};
 use crate::model::Schema;
+use serde::Serialize;
 
 /// Validation result with warnings.
-#[derive(Debug, Default)]
+///
+/// `diagnostics` holds the structured findings; `warnings` and `errors` keep
+/// the same findings as "Schema N, Piece M: ..." strings for existing callers.
+#[derive(Debug, Default, Serialize)]
 pub struct ValidationResult {
     /// Whether validation passed.
     pub passed: bool,
+    /// Structured findings in the order they were found.
+    pub diagnostics: Vec<Diagnostic>,
     /// Warning messages.
+    #[serde(skip)]
     pub warnings: Vec<String>,
     /// Error messages.
+    #[serde(skip)]
     pub errors: Vec<String>,
 }
 
@@ -25,168 +35,98 @@ impl ValidationResult {
 
     /// Create a failing result with an error.
     pub fn error(message: impl Into<String>) -> Self {
-        Self {
-            passed: false,
-            errors: vec![message.into()],
-            ..Default::default()
+        let mut result = Self::ok();
+        result.add_error(message);
+        result
+    }
+
+    /// Add a finding.
+    pub fn push(&mut self, diagnostic: Diagnostic) {
+        match diagnostic.severity {
+            Severity::Warning => self.warnings.push(diagnostic.located_message()),
+            Severity::Error => {
+                self.errors.push(diagnostic.located_message());
+                self.passed = false;
+            }
         }
+        self.diagnostics.push(diagnostic);
     }
 
-    /// Add a warning.
+    /// Add a warning without code or location.
     pub fn add_warning(&mut self, message: impl Into<String>) {
-        self.warnings.push(message.into());
+        self.push(Diagnostic::warning(ErrorCode::ParseError, message));
     }
 
-    /// Add an error.
+    /// Add an error without code or location.
     pub fn add_error(&mut self, message: impl Into<String>) {
-        self.errors.push(message.into());
-        self.passed = false;
+        self.push(Diagnostic::error(ErrorCode::ParseError, message));
     }
 
     /// Merge another result into this one.
     pub fn merge(&mut self, other: ValidationResult) {
+        self.diagnostics.extend(other.diagnostics);
         self.warnings.extend(other.warnings);
         self.errors.extend(other.errors);
         if !other.passed {
             self.passed = false;
         }
     }
-}
 
-/// Validate all schemas.
-pub fn validate_schemas(schemas: &[Schema]) -> Result<ValidationResult> {
-    let mut result = ValidationResult::ok();
-
-    if schemas.is_empty() {
-        return Err(ConvertError::NoPatternSection);
+    /// Findings of one severity.
+    pub fn with_severity(&self, severity: Severity) -> impl Iterator<Item = &Diagnostic> {
+        self.diagnostics
+            .iter()
+            .filter(move |d| d.severity == severity)
     }
 
-    for (idx, schema) in schemas.iter().enumerate() {
-        let schema_result = validate_schema(schema, idx + 1);
-        result.merge(schema_result);
+    /// Serialize the findings as pretty-printed JSON.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_default()
     }
+}
 
-    Ok(result)
+/// Validate all schemas with the built-in rules at their default levels.
+pub fn validate_schemas(schemas: &[Schema]) -> Result<ValidationResult> {
+    Validator::new().validate(schemas)
 }
 
-/// Validate a single schema.
-pub fn validate_schema(schema: &Schema, schema_num: usize) -> ValidationResult {
+/// Check that all schemas share one sheet format.
+///
+/// A CNI program has a single `[PARAMETRI01]` header, so mixed sheet sizes or
+/// thicknesses are split into one program per sheet format.
+pub fn validate_sheet_formats(schemas: &[Schema]) -> ValidationResult {
     let mut result = ValidationResult::ok();
 
-    // Check sheet dimensions
-    if schema.width <= 0.0 || schema.height <= 0.0 {
-        result.add_error(format!(
-            "Schema {}: Invalid sheet dimensions ({}x{})",
-            schema_num, schema.width, schema.height
-        ));
-    }
-
-    // Check thickness
-    if schema.thickness <= 0.0 {
-        result.add_warning(format!("Schema {}: Missing or zero thickness", schema_num));
-    }
-
-    // Validate pieces
-    for (piece_idx, piece) in schema.pieces.iter().enumerate() {
-        // Check piece dimensions
-        if piece.width <= 0.0 || piece.height <= 0.0 {
-            result.add_error(format!(
-                "Schema {}, Piece {}: Invalid dimensions ({}x{})",
-                schema_num,
-                piece_idx + 1,
-                piece.width,
-                piece.height
-            ));
-        }
-
-        // Check piece is within sheet bounds
-        if piece.x_origin < 0.0
-            || piece.y_origin < 0.0
-            || piece.x_max() > schema.width + crate::config::EPS
-            || piece.y_max() > schema.height + crate::config::EPS
-        {
-            result.add_warning(format!(
-                "Schema {}, Piece {}: Extends beyond sheet bounds",
-                schema_num,
-                piece_idx + 1
-            ));
-        }
-
-        // Check shape reference
-        if let Some(shape_id) = piece.shape_id {
-            if schema.find_shape(shape_id).is_none() {
-                result.add_error(format!(
-                    "Schema {}, Piece {}: Shape {} not found",
-                    schema_num,
-                    piece_idx + 1,
-                    shape_id
-                ));
-            }
-        }
-
-        // Check info reference
-        if let Some(info_id) = piece.info_id {
-            if schema.find_piece_type(info_id).is_none() {
-                result.add_error(format!(
-                    "Schema {}, Piece {}: Info {} not found",
-                    schema_num,
-                    piece_idx + 1,
-                    info_id
-                ));
-            }
+    let mut formats: Vec<&Schema> = Vec::new();
+    for schema in schemas {
+        if !formats.iter().any(|f| f.same_sheet(schema)) {
+            formats.push(schema);
         }
     }
 
-    // Validate shapes
-    for shape in &schema.shapes {
-        // Check for empty shapes
-        if shape.cuts.is_empty() {
-            result.add_warning(format!(
-                "Schema {}, Shape {}: No cuts defined",
-                schema_num, shape.id
-            ));
-        }
-
-        // Check shape closure
-        if !shape.is_open && !shape.is_closed() {
-            result.add_warning(format!(
-                "Schema {}, Shape {}: Shape is not closed",
-                schema_num, shape.id
-            ));
-        }
-
-        // Validate arc radii
-        for (cut_idx, cut) in shape.cuts.iter().enumerate() {
-            if cut.is_arc() {
-                let chord_len = ((cut.xf - cut.xi).powi(2) + (cut.yf - cut.yi).powi(2)).sqrt();
-                if cut.radius < chord_len / 2.0 - crate::config::EPS {
-                    result.add_error(format!(
-                        "Schema {}, Shape {}, Cut {}: Arc radius {} is too small for chord length {}",
-                        schema_num, shape.id, cut_idx + 1, cut.radius, chord_len
-                    ));
-                }
-            }
-        }
-    }
-
-    // Check for shape/piece size mismatch
-    if let Err(mismatched) = crate::transform::check_shape_piece_sizes(schema) {
-        for shape_id in mismatched {
-            result.add_error(format!(
-                "Schema {}: Shape {} is used on pieces of different sizes",
-                schema_num, shape_id
-            ));
-        }
-    }
-
-    // Check for cuts
-    if schema.linear_cuts.is_empty() && schema.shapes.is_empty() {
-        result.add_warning(format!("Schema {}: No cuts or shapes defined", schema_num));
+    if formats.len() > 1 {
+        let list: Vec<String> = formats
+            .iter()
+            .map(|s| format!("{}x{}x{}", s.width, s.height, s.thickness))
+            .collect();
+        result.push(Diagnostic::warning(
+            ErrorCode::SheetFormatMismatch,
+            format!(
+                "Patterns use {} different sheet formats ({}); output is split into one CNI program per sheet format",
+                formats.len(),
+                list.join(", ")
+            ),
+        ));
     }
 
     result
 }
 
+/// Validate a single schema with the built-in rules at their default levels.
+pub fn validate_schema(schema: &Schema, schema_num: usize) -> ValidationResult {
+    Validator::new().validate_schema(schema, schema_num)
+}
+
 /// Validate that there are active linear cuts.
 pub fn validate_has_cuts(schema: &Schema) -> bool {
     schema.linear_cuts.iter().any(|c| c.active) || schema.shapes.iter().any(|s| !s.cuts.is_empty())
@@ -194,6 +134,11 @@ pub fn validate_has_cuts(schema: &Schema) -> bool {
 
 /// Validate piece layout (no overlaps).
 pub fn validate_piece_layout(schema: &Schema) -> Vec<(usize, usize)> {
+    piece_overlaps(schema, 0.0)
+}
+
+/// Pairs of pieces overlapping by more than `tolerance` in both directions.
+pub fn piece_overlaps(schema: &Schema, tolerance: f64) -> Vec<(usize, usize)> {
     let mut overlaps = Vec::new();
 
     for i in 0..schema.pieces.len() {
@@ -202,8 +147,10 @@ pub fn validate_piece_layout(schema: &Schema) -> Vec<(usize, usize)> {
             let b = &schema.pieces[j];
 
             // Check for overlap
-            let x_overlap = a.x_origin < b.x_max() && b.x_origin < a.x_max();
-            let y_overlap = a.y_origin < b.y_max() && b.y_origin < a.y_max();
+            let x_overlap =
+                a.x_origin + tolerance < b.x_max() && b.x_origin + tolerance < a.x_max();
+            let y_overlap =
+                a.y_origin + tolerance < b.y_max() && b.y_origin + tolerance < a.y_max();
 
             if x_overlap && y_overlap {
                 overlaps.push((i, j));
@@ -233,6 +180,7 @@ pub fn quick_validate(schemas: &[Schema]) -> Result<()> {
 mod tests {
     use super::*;
     use crate::model::{Cut, CutType, Piece, Shape};
+    use crate::validation::Entity;
 
     fn create_basic_schema() -> Schema {
         Schema {
@@ -292,6 +240,58 @@ mod tests {
         assert_eq!(result1.errors.len(), 1);
     }
 
+    #[test]
+    fn test_validation_result_diagnostics_in_sync() {
+        let mut result = ValidationResult::ok();
+        result.push(
+            Diagnostic::warning(ErrorCode::OutOfBounds, "Extends beyond sheet bounds")
+                .in_schema(2)
+                .on(Entity::Piece { number: 3 }),
+        );
+        result.add_error("Something went wrong");
+
+        assert!(!result.passed);
+        assert_eq!(result.diagnostics.len(), 2);
+        assert_eq!(
+            result.warnings,
+            ["Schema 2, Piece 3: Extends beyond sheet bounds"]
+        );
+        assert_eq!(result.errors, ["Something went wrong"]);
+        assert_eq!(
+            result.diagnostics[0].to_string(),
+            "[E101] Schema 2, Piece 3: Extends beyond sheet bounds"
+        );
+        assert_eq!(result.with_severity(Severity::Error).count(), 1);
+    }
+
+    #[test]
+    fn test_validation_result_to_json() {
+        let mut schema = create_basic_schema();
+        schema.pieces.push(Piece {
+            x_origin: 900.0,
+            width: 200.0,
+            height: 100.0,
+            ..Default::default()
+        });
+        let result = validate_schema(&schema, 1);
+        let json: serde_json::Value = serde_json::from_str(&result.to_json()).unwrap();
+
+        assert_eq!(json["passed"], true);
+        assert!(json.get("warnings").is_none());
+        let bounds = json["diagnostics"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|d| d["code"] == "E101")
+            .expect("Missing E101 diagnostic");
+        assert_eq!(bounds["severity"], "warning");
+        assert_eq!(bounds["schema"], 1);
+        assert_eq!(bounds["entity"]["kind"], "piece");
+        assert_eq!(bounds["entity"]["number"], 1);
+        assert_eq!(bounds["span"]["kind"], "region");
+        assert_eq!(bounds["span"]["x2"], 1100.0);
+    }
+
     // ==================== validate_schemas tests ====================
 
     #[test]
@@ -312,6 +312,29 @@ mod tests {
         assert!(result.passed);
     }
 
+    #[test]
+    fn test_validate_schemas_mixed_sheets_warning() {
+        let mut thick = create_basic_schema();
+        thick.thickness = 8.0;
+        let result =
+            validate_schemas(&[create_basic_schema(), thick, create_basic_schema()]).unwrap();
+        assert!(result.passed); // Mixed sheets are split, not rejected
+        let sheet_warnings: Vec<_> = result
+            .warnings
+            .iter()
+            .filter(|w| w.contains("sheet formats"))
+            .collect();
+        assert_eq!(sheet_warnings.len(), 1);
+        assert!(sheet_warnings[0].contains("2 different sheet formats"));
+    }
+
+    #[test]
+    fn test_validate_sheet_formats_same_sheet() {
+        let result = validate_sheet_formats(&[create_basic_schema(), create_basic_schema()]);
+        assert!(result.passed);
+        assert!(result.warnings.is_empty());
+    }
+
     // ==================== validate_schema tests ====================
 
     #[test]
@@ -463,6 +486,63 @@ mod tests {
             .any(|w| w.contains("No cuts or shapes")));
     }
 
+    #[test]
+    fn test_validate_schema_diagnostic_codes() {
+        let mut schema = create_basic_schema();
+        let mut shape = Shape::new(1);
+        shape.cuts.push(Cut {
+            cut_type: CutType::ArcCW,
+            xf: 100.0,
+            radius: 10.0,
+            ..Default::default()
+        });
+        schema.shapes.push(shape);
+        schema.shapes.push(Shape::new(2));
+        for (x, width) in [(0.0, 100.0), (100.0, 200.0)] {
+            schema.pieces.push(Piece {
+                x_origin: x,
+                width,
+                height: 100.0,
+                shape_id: Some(1),
+                ..Default::default()
+            });
+        }
+        schema.thickness = 0.0;
+        schema.resolve_piece_references();
+
+        let result = validate_schema(&schema, 1);
+        let find = |code: ErrorCode| {
+            result
+                .diagnostics
+                .iter()
+                .find(|d| d.code == code)
+                .unwrap_or_else(|| panic!("Missing {}", code))
+        };
+
+        let arc = find(ErrorCode::InvalidArc);
+        assert_eq!(arc.severity, Severity::Error);
+        assert_eq!(
+            arc.entity,
+            Some(Entity::Cut {
+                shape: Some(1),
+                number: 1
+            })
+        );
+        assert_eq!(
+            find(ErrorCode::OpenContour).entity,
+            Some(Entity::Shape { id: 1 })
+        );
+        assert_eq!(
+            find(ErrorCode::ShapeSizeMismatch).entity,
+            Some(Entity::Shape { id: 1 })
+        );
+        assert_eq!(
+            find(ErrorCode::NoCutsFound).entity,
+            Some(Entity::Shape { id: 2 })
+        );
+        assert_eq!(find(ErrorCode::ToolNotFound).severity, Severity::Warning);
+    }
+
     // ==================== validate_has_cuts tests ====================
 
     #[test]
@@ -9,7 +9,22 @@
 //!
 //! The tests verify that the generated output will produce correct machine behavior.
 
-use otd_core::{convert_otd_to_cni, parse_otd_file, validate_schemas};
+use otd_core::analysis::{
+    check_toolpath, diff_cni, simulate, verify_program, ChangeKind, DiffOptions,
+};
+use otd_core::error::ErrorCode;
+use otd_core::export::Paper;
+use otd_core::generator::{build_contour_program, generate_with_post, CniEmitter, Instruction};
+use otd_core::parser::{import_dxf_shapes_file, parse_cni, parse_cni_file, DxfImportOptions};
+use otd_core::validation::Span;
+use otd_core::{
+    convert_otd_to_cni, convert_otd_to_cni_programs, convert_otd_to_cni_with_config,
+    convert_otd_to_split_cni, encrypt_otx, estimate_schemas, export_dxf, export_pdf, export_svg,
+    generate_cni, labels_pdf, labels_zpl, layout_stats, load_schemas, parse_otd_file, piece_labels,
+    read_otd_source, transform, validate_schemas, CuttingOrder, IsoPost, LabelTemplate,
+    MachineConfig, MachineKinematics, PdfOptions, Severity, SplitManifest, SplitMode, SvgOptions,
+    ValidationConfig, Validator,
+};
 use std::collections::HashMap;
 use std::path::Path;
 
@@ -788,6 +803,972 @@ fn test_dxf_arc_handling() {
     );
 }
 
+// ==================== Program IR Tests ====================
+
+/// Load a fixture and run the transforms, as the conversion pipeline does.
+fn load_transformed(name: &str) -> Vec<otd_core::Schema> {
+    let mut schemas = parse_otd_file(&Path::new(FIXTURE_DIR).join(name)).unwrap();
+    for schema in &mut schemas {
+        transform::process_linear_cuts(schema);
+        transform::process_shapes(schema);
+    }
+    schemas
+}
+
+/// Test: Every jump and subroutine call targets a label of the program
+#[test]
+fn test_program_jump_targets_exist() {
+    let schemas = load_transformed("with_shapes.otd");
+    let program = build_contour_program(&schemas, &MachineConfig::new(130));
+
+    let labels: Vec<&str> = program.blocks().iter().filter_map(|b| b.label).collect();
+    for instruction in &program.instructions {
+        let target = match instruction {
+            Instruction::Jump { label, .. } if !label.starts_with('(') => label,
+            Instruction::CallLabel(label) => label,
+            _ => continue,
+        };
+        assert!(
+            labels.contains(&target.as_str()),
+            "Missing label {}",
+            target
+        );
+    }
+}
+
+/// Test: Every tool-down is followed by a tool-up before the program ends
+#[test]
+fn test_program_tool_balance() {
+    let schemas = load_transformed("with_shapes.otd");
+    let program = build_contour_program(&schemas, &MachineConfig::new(130));
+
+    let mut down = false;
+    for name in program.macro_calls() {
+        match name {
+            "PT_GIU" => {
+                assert!(!down, "Tool lowered twice");
+                down = true;
+            }
+            "PT_SU" => down = false,
+            _ => {}
+        }
+    }
+    assert!(!down, "Tool left down at program end");
+}
+
+/// Test: The emitted program is the [CONTORNATURA01] section of the CNI file
+#[test]
+fn test_program_emits_contour_section() {
+    let schemas = load_transformed("simple_linear.otd");
+    let config = MachineConfig::new(130);
+
+    let program = build_contour_program(&schemas, &config);
+    let cni = generate_cni(&schemas, "simple_linear.otd", &config).unwrap();
+
+    let emitted = CniEmitter.emit(&program);
+    assert!(cni.contains(&format!("[CONTORNATURA01]\n{}", emitted)));
+}
+
+/// Test: The ISO post-processor renders the same program without Intermac syntax
+#[test]
+fn test_iso_post_conversion() {
+    let schemas = load_transformed("with_shapes.otd");
+    let config = MachineConfig::new(130);
+
+    let iso = generate_with_post(&schemas, "with_shapes.otd", &config, &IsoPost::new()).unwrap();
+    let cni = generate_cni(&schemas, "with_shapes.otd", &config).unwrap();
+
+    assert!(iso.starts_with("%\n"));
+    assert!(iso.contains("G90 G17 G20"), "Inch file must select G20");
+    assert!(iso.trim_end().ends_with("M30\n%"));
+    for intermac in ["L=", "JM", "P539=", "[CONTORNATURA01]"] {
+        assert!(
+            !iso.contains(intermac),
+            "Unexpected {} in ISO output",
+            intermac
+        );
+    }
+
+    // Every scored line in the CNI is a feed move in the ISO program
+    let cni_cuts = cni.matches(" G01 X=").count();
+    let iso_cuts = iso.lines().filter(|l| l.starts_with("G1 ")).count();
+    assert!(iso_cuts >= cni_cuts, "{} < {}", iso_cuts, cni_cuts);
+    assert!(iso
+        .lines()
+        .any(|l| l.starts_with("G2 ") || l.starts_with("G3 ")));
+
+    // Wheel is lowered and lifted in pairs
+    let down = iso.lines().filter(|l| *l == "M3").count();
+    let up = iso.lines().filter(|l| *l == "M5").count();
+    assert!(down > 0 && up >= down);
+}
+
+// ==================== CNI Reader Tests ====================
+
+/// Text of the [CONTORNATURA01] section, without the trailing blank line
+fn contour_section(content: &str) -> String {
+    let start = content.find("[CONTORNATURA01]\n").unwrap() + "[CONTORNATURA01]\n".len();
+    let end = start + content[start..].find("\n[").unwrap();
+    content[start..end].trim_end().to_string() + "\n"
+}
+
+/// Test: Reference CNI files parse into a program that re-emits byte for byte
+#[test]
+fn test_cni_reader_round_trip_fixtures() {
+    let mut paths = vec![Path::new("tests/fixtures/cod1.cni").to_path_buf()];
+    for name in [
+        "simple_linear.cni",
+        "multi_piece.cni",
+        "with_shapes.cni",
+        "complex_shapes.cni",
+        "empty_shapes.cni",
+        "large_layout.cni",
+    ] {
+        paths.push(Path::new(FIXTURE_DIR).join(name));
+    }
+
+    for path in paths {
+        let content = std::fs::read_to_string(&path)
+            .unwrap()
+            .replace("\r\n", "\n");
+        let cni = parse_cni_file(&path).unwrap();
+
+        assert_eq!(
+            CniEmitter.emit(&cni.program),
+            contour_section(&content),
+            "Round trip failed for {}",
+            path.display()
+        );
+        assert!(
+            !cni.program
+                .instructions
+                .iter()
+                .any(|i| matches!(i, Instruction::Line(_))),
+            "Unrecognized blocks in {}",
+            path.display()
+        );
+        assert_eq!(cni.previews.len(), cni.schema_count() * 2);
+    }
+}
+
+/// Test: A generated CNI file reads back to the schemas it was built from
+#[test]
+fn test_cni_reader_matches_schemas() {
+    let schemas = load_transformed("with_shapes.otd");
+    let config = MachineConfig::new(130);
+    let generated = generate_cni(&schemas, "with_shapes.otd", &config).unwrap();
+
+    let cni = parse_cni(&generated).unwrap();
+    assert_eq!(cni.comment.project.as_deref(), Some("with_shapes.otd"));
+    assert_eq!(cni.comment.materials, vec![schemas[0].glass_id.clone()]);
+
+    let params = cni.parameters.as_ref().unwrap();
+    assert_eq!(params.machine_number, 130);
+    assert!((params.width - schemas[0].width).abs() < 0.001);
+    assert!((params.height - schemas[0].height).abs() < 0.001);
+    assert!(cni.tools.contains(&config.linear_tool));
+    assert!(cni.tools.contains(&config.shaped_tool));
+
+    assert_eq!(cni.schema_count(), schemas.len());
+    let dist = &cni.distributions[0];
+    assert_eq!(dist.code, "with_shapes1");
+    let total_pieces: u32 = dist.pieces.iter().map(|p| p.quantity).sum();
+    assert!(total_pieces > 0);
+
+    let preview = cni.preview(1, false).unwrap();
+    assert!(preview.entities.iter().any(|e| e.layer == "TagliSag"));
+    assert_eq!(CniEmitter.emit(&cni.program), contour_section(&generated));
+}
+
+// ==================== CNI Diff Tests ====================
+
+/// Test: Generated programs score the same cuts as the legacy converter
+#[test]
+fn test_cni_diff_matches_legacy_output() {
+    for name in [
+        "with_shapes",
+        "complex_shapes",
+        "empty_shapes",
+        "large_layout",
+    ] {
+        let otd = Path::new(FIXTURE_DIR).join(format!("{}.otd", name));
+        let legacy = parse_cni_file(&Path::new(FIXTURE_DIR).join(format!("{}.cni", name))).unwrap();
+        let generated = parse_cni(&convert_otd_to_cni(&otd, 130).unwrap()).unwrap();
+
+        let diff = diff_cni(&legacy, &generated, &DiffOptions::default()).unwrap();
+        assert!(diff.is_identical(), "{}:\n{}", name, diff.report());
+    }
+}
+
+/// Test: Tool differences are reported without geometry differences
+#[test]
+fn test_cni_diff_reports_tool_changes() {
+    let otd = Path::new(FIXTURE_DIR).join("simple_linear.otd");
+    let legacy = parse_cni_file(&Path::new(FIXTURE_DIR).join("simple_linear.cni")).unwrap();
+    let generated = parse_cni(&convert_otd_to_cni(&otd, 130).unwrap()).unwrap();
+
+    let diff = diff_cni(&legacy, &generated, &DiffOptions::default()).unwrap();
+    assert_eq!(diff.header.len(), 1);
+    assert_eq!(diff.header[0].field, "tools");
+    for change in &diff.patterns[0].changes {
+        assert!(
+            matches!(change.kind, ChangeKind::ToolChanged { to_tool: 3, .. }),
+            "{:?}",
+            change
+        );
+    }
+}
+
+/// Test: A shifted shape origin is reported as a moved shape cut
+#[test]
+fn test_cni_diff_reports_moved_shape() {
+    let content = std::fs::read_to_string(Path::new(FIXTURE_DIR).join("with_shapes.cni"))
+        .unwrap()
+        .replace("\r\n", "\n");
+    let original = parse_cni(&content).unwrap();
+    let shifted = parse_cni(&content.replacen("XO=14.59375\n", "XO=14.6\n", 1)).unwrap();
+
+    let diff = diff_cni(&original, &shifted, &DiffOptions::default()).unwrap();
+    assert_eq!(diff.change_count(), 1, "{}", diff.report());
+    let change = &diff.patterns[0].changes[0];
+    assert_eq!(change.shape_macro.as_deref(), Some("1010001003"));
+    match change.kind {
+        ChangeKind::Moved { dx, dy } => {
+            assert!((dx - 0.00625).abs() < 1e-9);
+            assert!(dy.abs() < 1e-9);
+        }
+        ref other => panic!("Expected moved cut, got {:?}", other),
+    }
+}
+
+// ==================== Toolpath Simulation Tests ====================
+
+/// Test: Legacy CNI programs score exactly the cuts and shapes of the layout
+#[test]
+fn test_toolpath_of_legacy_cni_matches_layout() {
+    for name in [
+        "simple_linear",
+        "with_shapes",
+        "complex_shapes",
+        "empty_shapes",
+        "large_layout",
+    ] {
+        let schemas = load_transformed(&format!("{}.otd", name));
+        let legacy = parse_cni_file(&Path::new(FIXTURE_DIR).join(format!("{}.cni", name))).unwrap();
+
+        let toolpath = simulate(&legacy.program).unwrap();
+        assert_eq!(toolpath.patterns.len(), schemas.len());
+
+        let divergences = check_toolpath(&schemas, &toolpath, 0.001);
+        assert!(divergences.is_empty(), "{}: {}", name, divergences[0]);
+    }
+}
+
+/// Test: Conversion fails when the scored path leaves the layout
+#[test]
+fn test_toolpath_divergence_fails_conversion() {
+    let schemas = load_transformed("with_shapes.otd");
+    let config = MachineConfig::new(130);
+    let mut program = build_contour_program(&schemas, &config);
+
+    // Drop the first shape placement
+    let call = program
+        .instructions
+        .iter()
+        .position(|i| matches!(i, Instruction::CallLabel(_)))
+        .unwrap();
+    program.instructions.remove(call);
+
+    let err = verify_program(&schemas, &program).unwrap_err();
+    assert_eq!(err.code(), ErrorCode::ToolpathMismatch);
+    assert!(err.to_string().contains("shape"), "{}", err);
+}
+
+// ==================== Cycle Time Tests ====================
+
+/// Test: Every pattern gets a cycle time in [COMMENTO], numbered like the source file
+#[test]
+fn test_cycle_time_reported_per_pattern() {
+    let otd_path = Path::new(FIXTURE_DIR).join("mixed_sheets.otd");
+    let programs =
+        convert_otd_to_split_cni(&otd_path, &MachineConfig::new(130), SplitMode::PerSheet).unwrap();
+
+    for program in &programs {
+        let numbers: Vec<usize> = program
+            .cycle_time
+            .patterns
+            .iter()
+            .map(|p| p.number)
+            .collect();
+        assert_eq!(numbers, program.patterns);
+
+        let cni = CniStructure::parse(&program.content);
+        let comment = cni.get_section("COMMENTO").unwrap();
+        for pattern in &program.cycle_time.patterns {
+            assert!(pattern.per_sheet() > 0.0);
+            let line = format!("; Cycle time {}: {}", pattern.number, pattern.summary());
+            assert!(comment.contains(&line), "missing '{}'", line);
+        }
+    }
+}
+
+/// Test: Slower feed rates give longer cycle times
+#[test]
+fn test_cycle_time_follows_machine_profile() {
+    let schemas = load_transformed("complex_shapes.otd");
+    let fast = MachineConfig::new(130);
+    let profile = MachineKinematics::from_json(r#"{"shape_feed": 10000}"#).unwrap();
+    assert_eq!(profile.linear_feed, fast.kinematics.linear_feed);
+    let slow = MachineConfig::new(130).with_kinematics(profile);
+
+    let fast = estimate_schemas(&schemas, &fast).unwrap();
+    let slow = estimate_schemas(&schemas, &slow).unwrap();
+    for (fast, slow) in fast.patterns.iter().zip(&slow.patterns) {
+        assert!(slow.cutting > fast.cutting, "pattern {}", fast.number);
+        assert_eq!(slow.rapid, fast.rapid);
+        assert_eq!(slow.cut_length, fast.cut_length);
+    }
+
+    assert!(MachineKinematics::from_json(r#"{"linar_feed": 1}"#).is_err());
+}
+
+// ==================== DXF Export Tests ====================
+
+/// Test: Standalone DXF has one block per shape and one insert per shaped piece
+#[test]
+fn test_export_dxf_fixture() {
+    let schemas = load_schemas(&Path::new(FIXTURE_DIR).join("complex_shapes.otd")).unwrap();
+
+    for schema in &schemas {
+        let dxf = export_dxf(schema);
+        let lines: Vec<&str> = dxf.lines().map(str::trim).collect();
+        let groups: Vec<(&str, &str)> = lines.chunks(2).map(|g| (g[0], g[1])).collect();
+        let count = |group: (&str, &str)| groups.iter().filter(|g| **g == group).count();
+
+        assert_eq!(count(("0", "BLOCK")), schema.shapes.len());
+        assert_eq!(
+            count(("0", "INSERT")),
+            schema
+                .pieces
+                .iter()
+                .filter(|p| p.shape_index.is_some())
+                .count()
+        );
+        let linear = groups
+            .windows(2)
+            .filter(|w| w[0] == ("0", "LINE") && w[1] == ("8", "LINEAR"))
+            .count();
+        assert_eq!(
+            linear,
+            schema.linear_cuts.iter().filter(|c| c.active).count()
+        );
+        assert_eq!(groups.last(), Some(&("0", "EOF")));
+    }
+}
+
+// ==================== DXF Import Tests ====================
+
+/// Test: Customer template contours become closed clockwise shapes
+#[test]
+fn test_import_dxf_template() {
+    let path = Path::new(FIXTURE_DIR).join("customer_template.dxf");
+    let shapes = import_dxf_shapes_file(&path, &DxfImportOptions::new("SHAPE")).unwrap();
+    assert_eq!(shapes.len(), 2, "DIM layer must be ignored");
+
+    // Arched window: 600 x 800 with a 300 radius top, gap at a corner closed
+    let arch = &shapes[0];
+    assert!(arch.is_closed());
+    assert_eq!(arch.cuts.len(), 4);
+    assert!((arch.width() - 600.0).abs() < 0.01);
+    assert!((arch.height() - 1100.0).abs() < 0.01);
+    let expected = 600.0 + 2.0 * 800.0 + std::f64::consts::PI * 300.0;
+    assert!((arch.perimeter - expected).abs() < 0.01);
+
+    // Rounded rectangle from a bulged polyline
+    let rounded = &shapes[1];
+    assert!(rounded.is_closed());
+    assert_eq!(rounded.cuts.iter().filter(|c| c.is_arc()).count(), 4);
+    assert!((rounded.width() - 350.0).abs() < 0.01);
+    assert!((rounded.height() - 300.0).abs() < 0.01);
+
+    for shape in &shapes {
+        let (min_x, min_y, _, _) = shape.bounding_box();
+        assert!(min_x.abs() < 0.01 && min_y.abs() < 0.01);
+        // Clockwise like the shapes of the optimizer
+        let area: f64 = shape.cuts.iter().map(|c| c.xi * c.yf - c.xf * c.yi).sum();
+        assert!(area < 0.0);
+        assert!(shape
+            .cuts
+            .iter()
+            .all(|c| c.cut_type != otd_core::CutType::ArcCCW));
+    }
+}
+
+// ==================== SVG Export Tests ====================
+
+/// Test: SVG preview has one contour per shaped piece and balanced layer groups
+#[test]
+fn test_export_svg_fixture() {
+    let schemas = load_schemas(&Path::new(FIXTURE_DIR).join("complex_shapes.otd")).unwrap();
+
+    for schema in &schemas {
+        let svg = export_svg(schema, &SvgOptions::default());
+        assert_eq!(svg.matches("<g ").count(), svg.matches("</g>").count());
+
+        let shapes = svg
+            .split(r#"<g id="shapes""#)
+            .nth(1)
+            .and_then(|rest| rest.split("</g>").next())
+            .expect("Missing shapes layer");
+        assert_eq!(
+            shapes.matches("<path ").count(),
+            schema
+                .pieces
+                .iter()
+                .filter(|p| p.shape_index.is_some())
+                .count()
+        );
+        // Shape arcs are drawn as true arcs, not polylines
+        assert!(shapes.contains(" A"));
+        assert!(svg.contains("<text "));
+    }
+}
+
+// ==================== PDF Cut List Tests ====================
+
+/// Test: Cut list has a cover and a page per pattern listing every order
+#[test]
+fn test_export_pdf_fixture() {
+    let schemas = load_schemas(&Path::new(FIXTURE_DIR).join("complex_shapes.otd")).unwrap();
+    let pdf = String::from_utf8(export_pdf(&schemas, &PdfOptions::default())).unwrap();
+
+    assert!(pdf.starts_with("%PDF-"));
+    assert!(pdf.trim_end().ends_with("%%EOF"));
+    assert!(pdf.matches("/Type /Page ").count() > schemas.len());
+    for number in 1..=schemas.len() {
+        assert!(pdf.contains(&format!("(Pattern {}) Tj", number)));
+    }
+    for piece_type in schemas.iter().flat_map(|s| &s.piece_types) {
+        if !piece_type.waste && !piece_type.order_no.is_empty() {
+            assert!(pdf.contains(&format!("({}) Tj", piece_type.order_no)));
+        }
+    }
+}
+
+// ==================== Piece Label Tests ====================
+
+/// Test: One label per physical piece, printed as ZPL and as a PDF sheet
+#[test]
+fn test_piece_labels_fixture() {
+    let schemas = load_schemas(&Path::new(FIXTURE_DIR).join("complex_shapes.otd")).unwrap();
+    let labels = piece_labels(&schemas);
+
+    let expected: usize = schemas
+        .iter()
+        .map(|s| {
+            let pieces = s.pieces.iter().filter(|p| !s.is_waste_piece(p)).count();
+            pieces * s.quantity.max(1) as usize
+        })
+        .sum();
+    assert!(expected > 0);
+    assert_eq!(labels.len(), expected);
+
+    let template = LabelTemplate::default();
+    let zpl = labels_zpl(&labels, &template);
+    assert_eq!(zpl.matches("^XA").count(), expected);
+    for label in &labels {
+        let data = format!("^FD{}-{}^FS", label.order_no, label.position_no);
+        assert!(zpl.contains(&data), "missing barcode {}", data);
+    }
+
+    let pdf = String::from_utf8(labels_pdf(&labels, &template, Paper::A4).unwrap()).unwrap();
+    assert!(pdf.starts_with("%PDF-"));
+    // 10 labels of 100 x 50 mm per A4 page
+    assert_eq!(pdf.matches("/Type /Page ").count(), expected.div_ceil(10));
+}
+
+// ==================== Diagnostics Tests ====================
+
+/// Test: Validation findings carry codes and serialize to JSON
+#[test]
+fn test_validation_diagnostics_json() {
+    let schemas = load_schemas(&Path::new(FIXTURE_DIR).join("mixed_sheets.otd")).unwrap();
+    let validation = validate_schemas(&schemas).unwrap();
+
+    assert_eq!(
+        validation.diagnostics.len(),
+        validation.warnings.len() + validation.errors.len()
+    );
+    let sheets = validation
+        .diagnostics
+        .iter()
+        .find(|d| d.code == ErrorCode::SheetFormatMismatch)
+        .expect("Missing sheet format diagnostic");
+    assert_eq!(sheets.severity, Severity::Warning);
+    assert_eq!(sheets.schema, None);
+
+    let json: serde_json::Value = serde_json::from_str(&validation.to_json()).unwrap();
+    assert!(json["diagnostics"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|d| d["code"] == "E203" && d["severity"] == "warning"));
+}
+
+/// Test: A plant policy turns the nested-piece overlap into an error
+#[test]
+fn test_validation_rules_config() {
+    let schemas = load_schemas(&Path::new(FIXTURE_DIR).join("laminated_linear.otd")).unwrap();
+    assert!(validate_schemas(&schemas).unwrap().passed);
+
+    let config =
+        ValidationConfig::from_json(r#"{"rules": {"piece-overlap": {"level": "error"}}}"#).unwrap();
+    let validation = Validator::new()
+        .with_config(config)
+        .unwrap()
+        .validate(&schemas)
+        .unwrap();
+    assert!(!validation.passed);
+    let overlap = validation.with_severity(Severity::Error).next().unwrap();
+    assert_eq!(overlap.rule.as_deref(), Some("piece-overlap"));
+    assert_eq!(overlap.code, ErrorCode::OutOfBounds);
+}
+
+/// Test: Placed shapes of the fixtures stay inside their pieces; a shifted cut is reported
+#[test]
+fn test_validation_shape_geometry() {
+    let mut schemas = load_schemas(&Path::new(FIXTURE_DIR).join("with_shapes.otd")).unwrap();
+    let geometric = ["shape-containment", "self-intersection", "shape-overlap"];
+    let validation = validate_schemas(&schemas).unwrap();
+    assert!(validation
+        .diagnostics
+        .iter()
+        .all(|d| !geometric.contains(&d.rule.as_deref().unwrap_or_default())));
+
+    let schema = &mut schemas[0];
+    let piece = schema
+        .pieces
+        .iter()
+        .position(|p| p.shape_id.is_some())
+        .unwrap();
+    let shape_id = schema.pieces[piece].shape_id.unwrap();
+    let width = schema.pieces[piece].width;
+    let shape = schema.shapes.iter_mut().find(|s| s.id == shape_id).unwrap();
+    for cut in &mut shape.cuts {
+        cut.xi += width;
+        cut.xf += width;
+        cut.xc += width;
+    }
+
+    let validation = validate_schemas(&schemas).unwrap();
+    assert!(!validation.passed);
+    let outside = validation
+        .diagnostics
+        .iter()
+        .find(|d| d.rule.as_deref() == Some("shape-containment"))
+        .unwrap();
+    assert_eq!(outside.code, ErrorCode::OutOfBounds);
+    assert!(outside.message.contains(" at ("));
+}
+
+/// Test: Narrow rest strips are manufacturability warnings that a policy can turn off
+#[test]
+fn test_validation_manufacturability() {
+    let schemas = load_schemas(&Path::new(FIXTURE_DIR).join("complex_shapes.otd")).unwrap();
+    let validation = validate_schemas(&schemas).unwrap();
+    assert!(validation.passed);
+    let strips: Vec<_> = validation
+        .diagnostics
+        .iter()
+        .filter(|d| d.code == ErrorCode::Manufacturability)
+        .collect();
+    assert!(!strips.is_empty());
+    assert!(strips
+        .iter()
+        .all(|d| d.rule.as_deref() == Some("strip-width") && d.severity == Severity::Warning));
+
+    let config =
+        ValidationConfig::from_json(r#"{"rules": {"strip-width": {"level": "off"}}}"#).unwrap();
+    let validation = Validator::new()
+        .with_config(config)
+        .unwrap()
+        .validate(&schemas)
+        .unwrap();
+    assert!(validation
+        .diagnostics
+        .iter()
+        .all(|d| d.code != ErrorCode::Manufacturability));
+}
+
+/// Test: Hierarchy levels exceeding their parent are reported at their coordinate line
+#[test]
+fn test_validation_hierarchy() {
+    let schemas = load_schemas(&Path::new(FIXTURE_DIR).join("hierarchy_overflow.otd")).unwrap();
+    let validation = validate_schemas(&schemas).unwrap();
+    assert!(!validation.passed);
+    let overflows: Vec<_> = validation
+        .diagnostics
+        .iter()
+        .filter(|d| d.rule.as_deref() == Some("hierarchy"))
+        .collect();
+    let lines: Vec<_> = overflows
+        .iter()
+        .map(|d| match d.span {
+            Some(Span::Line { line }) => line,
+            _ => 0,
+        })
+        .collect();
+    assert_eq!(lines, [26, 27, 28]);
+    assert!(overflows
+        .iter()
+        .all(|d| d.code == ErrorCode::OutOfBounds && d.severity == Severity::Error));
+    assert_eq!(
+        overflows[0].located_message(),
+        "Schema 1, Line 26: Z strips sum to 1300, beyond the X=1200 strip at line 23"
+    );
+
+    // The fixtures it was derived from are consistent
+    for name in [
+        "laminated_linear.otd",
+        "complex_shapes.otd",
+        "large_layout.otd",
+    ] {
+        let schemas = load_schemas(&Path::new(FIXTURE_DIR).join(name)).unwrap();
+        let validation = validate_schemas(&schemas).unwrap();
+        assert!(
+            validation
+                .diagnostics
+                .iter()
+                .all(|d| !matches!(d.rule.as_deref(), Some("hierarchy" | "trims"))),
+            "{}: {:?}",
+            name,
+            validation.diagnostics
+        );
+    }
+}
+
+// ==================== Score-Only Tests ====================
+
+/// Test: Cuts leaving narrow rests are only scored, with the incision tool when one is set
+#[test]
+fn test_score_only_incision_pass() {
+    let otd_path = Path::new(FIXTURE_DIR).join("complex_shapes.otd");
+    let mut schemas = load_schemas(&otd_path).unwrap();
+    let score_only = schemas[0]
+        .linear_cuts
+        .iter()
+        .filter(|c| c.active && c.score_only)
+        .count();
+    assert!(
+        score_only > 0,
+        "Cuts leaving rests under 50 mm are only scored"
+    );
+    assert!(schemas[0]
+        .linear_cuts
+        .iter()
+        .filter(|c| c.score_only)
+        .all(|c| c.rest < 50.0 / 25.4 || c.quota < 100.0 / 25.4));
+
+    // Without an incision tool the linear pass is unchanged
+    let generated = generate_cni(&schemas, "complex_shapes.otd", &MachineConfig::new(130)).unwrap();
+    assert!(!generated.contains(":030001"));
+
+    for schema in &mut schemas {
+        schema.incision_tool = 2;
+    }
+    let generated = generate_cni(&schemas, "complex_shapes.otd", &MachineConfig::new(130)).unwrap();
+    let cni = CniStructure::parse(&generated);
+    let tools = cni.get_section("UTENSILI").expect("Missing UTENSILI");
+    assert!(tools.iter().any(|t| t.trim() == "0002"));
+
+    let contour = cni
+        .get_section("CONTORNATURA")
+        .expect("Missing CONTORNATURA")
+        .join("\n");
+    assert!(contour.contains("JM((P260=2)~(P007=0002)):030001"));
+    let linear = contour.find("\n:010001").unwrap();
+    let incision = contour.find("\n:030001").unwrap();
+    let shaped = contour.find("\n:020001").unwrap();
+    assert!(linear < incision && incision < shaped);
+
+    // Every score-only cut of pattern 1 is in the incision pass
+    let pass = &contour[incision..shaped];
+    assert!(pass.contains("P007=0002"));
+    assert_eq!(pass.matches("L=PT_GIU").count(), score_only);
+}
+
+// ==================== OTX and Stats Tests ====================
+
+/// Test: An encrypted fixture parses like the plain one
+#[test]
+fn test_otx_round_trip_fixture() {
+    let otd_path = Path::new(FIXTURE_DIR).join("with_shapes.otd");
+    let content = read_otd_source(&otd_path).unwrap();
+    let otx_path = std::env::temp_dir().join(format!("otd_round_trip_{}.otx", std::process::id()));
+    std::fs::write(&otx_path, encrypt_otx(&content)).unwrap();
+
+    let plain = parse_otd_file(&otd_path).unwrap();
+    let encrypted = parse_otd_file(&otx_path);
+    std::fs::remove_file(&otx_path).unwrap();
+    let encrypted = encrypted.unwrap();
+    assert_eq!(
+        serde_json::to_string(&plain).unwrap(),
+        serde_json::to_string(&encrypted).unwrap()
+    );
+}
+
+/// Test: Layout figures agree with the schemas and the cycle time estimate
+#[test]
+fn test_layout_stats_fixture() {
+    let schemas = load_transformed("complex_shapes.otd");
+    let stats = layout_stats(&schemas);
+    assert_eq!(stats.patterns.len(), schemas.len());
+    for (pattern, schema) in stats.patterns.iter().zip(&schemas) {
+        assert!((pattern.yield_ratio() - schema.yield_ratio()).abs() < 1e-9);
+        assert_eq!(pattern.shaped_pieces, pattern.pieces);
+    }
+
+    // The simulated program scores what the layout says, in millimetres
+    let cycle_time = estimate_schemas(&schemas, &MachineConfig::new(130)).unwrap();
+    let mm = schemas[0].unit.to_mm_factor();
+    for pattern in &stats.patterns {
+        let scored = cycle_time.pattern(pattern.number).unwrap().cut_length;
+        assert!(
+            (scored - pattern.cut_length() * mm).abs() < 1.0,
+            "pattern {}: {} scored, {} in the layout",
+            pattern.number,
+            scored,
+            pattern.cut_length() * mm
+        );
+    }
+}
+
+/// Scored moves (`G00` start to `G01` end) of the pass under `label`.
+fn pass_moves(contour: &str, label: &str) -> Vec<(String, String)> {
+    let start = contour.find(&format!("\n:{}", label)).unwrap();
+    let end = contour[start..].find("JM(P260=2)").unwrap() + start;
+    let position = |line: &str| {
+        let coords: Vec<&str> = line
+            .split_whitespace()
+            .filter(|w| w.starts_with("X=") || w.starts_with("Y="))
+            .collect();
+        coords.join(" ")
+    };
+    let mut moves = Vec::new();
+    let mut from = String::new();
+    for line in contour[start..end].lines() {
+        if line.contains(" G00 ") {
+            from = position(line);
+        } else if line.contains(" G01 ") {
+            moves.push((from.clone(), position(line)));
+        }
+    }
+    moves
+}
+
+/// Test: Optimizer data with ToolCode2 puts exactly the narrow strips and rests in the incision pass
+#[test]
+fn test_score_only_fixture() {
+    let otd_path = Path::new(FIXTURE_DIR).join("score_only.otd");
+    let schemas = load_schemas(&otd_path).unwrap();
+    let schema = &schemas[0];
+    assert_eq!(schema.incision_tool, 2);
+
+    // Trims cut off a 10 mm strip but are always separated
+    let trims: Vec<_> = schema
+        .linear_cuts
+        .iter()
+        .filter(|c| c.active && schema.is_trim_cut(c))
+        .collect();
+    assert_eq!(trims.len(), 2);
+    assert!(trims.iter().all(|c| !c.score_only));
+
+    let generated = generate_cni(&schemas, "score_only.otd", &MachineConfig::new(130)).unwrap();
+    let cni = CniStructure::parse(&generated);
+    let contour = cni
+        .get_section("CONTORNATURA")
+        .expect("Missing CONTORNATURA")
+        .join("\n");
+
+    // 200 mm strip leaving 40 mm, 80 mm strip, 1580 mm strip leaving 40 mm
+    let moves = |pairs: &[(&str, &str)]| -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(a, b)| (a.to_string(), b.to_string()))
+            .collect()
+    };
+    assert_eq!(
+        pass_moves(&contour, "030001"),
+        moves(&[
+            ("X=11 Y=2210", "X=1509 Y=2210"),
+            ("X=1590 Y=11", "X=1590 Y=2249"),
+            ("X=3170 Y=2249", "X=3170 Y=11"),
+        ])
+    );
+    let linear = pass_moves(&contour, "010001");
+    assert_eq!(linear.len(), 6);
+    assert!(linear.contains(&("X=10 Y=1".to_string(), "X=10 Y=2249".to_string())));
+    assert!(linear.contains(&("X=3209 Y=10".to_string(), "X=1 Y=10".to_string())));
+}
+
+// ==================== Cutting Order Tests ====================
+
+/// Position of the linear (01xxxx) and shaped (02xxxx) labels for schema 1
+fn pass_label_positions(generated: &str) -> (usize, usize) {
+    let linear = generated.find("\n:010001").expect("Missing linear label");
+    let shaped = generated.find("\n:020001").expect("Missing shaped label");
+    (linear, shaped)
+}
+
+/// Test: Default cutting order emits linear cuts before shapes
+#[test]
+fn test_cutting_order_linear_first() {
+    let otd_path = Path::new(FIXTURE_DIR).join("with_shapes.otd");
+    let generated = convert_to_cni(&otd_path, 130);
+
+    let (linear, shaped) = pass_label_positions(&generated);
+    assert!(linear < shaped, "Linear section should come first");
+}
+
+/// Test: CuttingOrder=1 in the OTD emits shapes before linear cuts
+#[test]
+fn test_cutting_order_from_schema() {
+    let otd_path = Path::new(FIXTURE_DIR).join("with_shapes.otd");
+    let mut schemas = parse_otd_file(&otd_path).expect("Failed to parse OTD file");
+    for schema in &mut schemas {
+        schema.cutting_order = 1;
+        transform::process_linear_cuts(schema);
+        transform::process_shapes(schema);
+    }
+
+    let generated = generate_cni(&schemas, "with_shapes.otd", &MachineConfig::new(130))
+        .expect("Failed to generate CNI");
+
+    let (linear, shaped) = pass_label_positions(&generated);
+    assert!(shaped < linear, "Shaped section should come first");
+
+    // Tool jumps follow the same order
+    let linear_jump = generated.find("(P007=0003)):010001").unwrap();
+    let shaped_jump = generated.find("(P007=0031)):020001").unwrap();
+    assert!(shaped_jump < linear_jump);
+}
+
+/// Test: MachineConfig override wins over the OTD value
+#[test]
+fn test_cutting_order_config_override() {
+    let otd_path = Path::new(FIXTURE_DIR).join("with_shapes.otd");
+    let config = MachineConfig::new(130).with_cutting_order(CuttingOrder::ShapesFirst);
+    let generated =
+        convert_otd_to_cni_with_config(&otd_path, &config).expect("Failed to convert OTD");
+
+    let (linear, shaped) = pass_label_positions(&generated);
+    assert!(shaped < linear, "Override should put shapes first");
+
+    // Same program content, only reordered
+    let default = convert_to_cni(&otd_path, 130);
+    assert_eq!(generated.lines().count(), default.lines().count());
+}
+
+// ==================== Mixed Sheet Tests ====================
+
+/// Test: Patterns on different sheets are split into one program per sheet
+#[test]
+fn test_mixed_sheets_split_per_sheet() {
+    let otd_path = Path::new(FIXTURE_DIR).join("mixed_sheets.otd");
+
+    let schemas = parse_and_validate(&otd_path);
+    assert_eq!(schemas.len(), 3, "Expected 3 schemas/patterns");
+
+    let validation = validate_schemas(&schemas).unwrap();
+    assert!(validation
+        .warnings
+        .iter()
+        .any(|w| w.contains("2 different sheet formats")));
+
+    let programs = convert_otd_to_cni_programs(&otd_path, &MachineConfig::new(130)).unwrap();
+    assert_eq!(programs.len(), 2, "Expected one program per sheet format");
+
+    // First program: both 4mm patterns, header from the 4mm sheet
+    let first = CniStructure::parse(&programs[0]);
+    let params = first.get_section("PARAMETRI").unwrap().join(" ");
+    assert!(params.contains("LX=3210 LY=2250 LZ=4"), "{}", params);
+    assert!(programs[0].contains("; Material : F4"));
+    assert!(!programs[0].contains("; Material : F8"));
+    assert!(validate_dxf_sections(&first, 2).is_ok());
+
+    // Second program: the 8mm pattern only
+    let second = CniStructure::parse(&programs[1]);
+    let params = second.get_section("PARAMETRI").unwrap().join(" ");
+    assert!(params.contains("LX=2550 LY=1605 LZ=8"), "{}", params);
+    assert!(programs[1].contains("; Material : F8"));
+    assert!(validate_dxf_sections(&second, 1).is_ok());
+}
+
+/// Test: Single-sheet files still produce exactly one program
+#[test]
+fn test_single_sheet_single_program() {
+    let otd_path = Path::new(FIXTURE_DIR).join("simple_linear.otd");
+    let config = MachineConfig::new(130);
+
+    let programs = convert_otd_to_cni_programs(&otd_path, &config).unwrap();
+    assert_eq!(programs.len(), 1);
+    assert_eq!(
+        programs[0],
+        convert_otd_to_cni_with_config(&otd_path, &config).unwrap()
+    );
+}
+
+/// Test: Per-pattern split keeps source pattern numbers in names, codes and manifest
+#[test]
+fn test_split_per_pattern() {
+    let otd_path = Path::new(FIXTURE_DIR).join("mixed_sheets.otd");
+    let config = MachineConfig::new(130);
+
+    let programs = convert_otd_to_split_cni(&otd_path, &config, SplitMode::PerPattern).unwrap();
+    assert_eq!(programs.len(), 3);
+
+    for (idx, program) in programs.iter().enumerate() {
+        assert_eq!(
+            program.file_name,
+            format!("mixed_sheets_{:02}.cni", idx + 1)
+        );
+        assert_eq!(program.patterns, vec![idx + 1]);
+
+        let cni = CniStructure::parse(&program.content);
+        assert!(validate_dxf_sections(&cni, 1).is_ok());
+        assert!(validate_ldist_content(&cni).is_ok());
+        assert!(program
+            .content
+            .contains(&format!(";Cod=mixed_sheets{}\n", idx + 1)));
+    }
+
+    let manifest = SplitManifest::new("mixed_sheets.otd", SplitMode::PerPattern, &programs);
+    let materials: Vec<&str> = manifest
+        .programs
+        .iter()
+        .map(|p| p.material.as_str())
+        .collect();
+    assert_eq!(materials, vec!["F4", "F8", "F4"]);
+}
+
+/// Test: Per-material split groups non-adjacent patterns of the same glass
+#[test]
+fn test_split_per_material() {
+    let otd_path = Path::new(FIXTURE_DIR).join("mixed_sheets.otd");
+    let config = MachineConfig::new(130);
+
+    let programs = convert_otd_to_split_cni(&otd_path, &config, SplitMode::PerMaterial).unwrap();
+    assert_eq!(programs.len(), 2);
+    assert_eq!(programs[0].patterns, vec![1, 3]);
+    assert_eq!(programs[1].patterns, vec![2]);
+
+    // Pattern 3 is the second schema of the first program but keeps its code
+    assert!(programs[0]
+        .content
+        .contains("[*LDIST0002_01]\n;Cod=mixed_sheets3\n"));
+    assert!(programs[1].content.contains(";Cod=mixed_sheets2\n"));
+}
+
 // ==================== Edge Case Tests ====================
 
 /// Test: Verify parsing handles all fixtures without panic
@@ -800,6 +1781,8 @@ fn test_all_fixtures_parse() {
         "empty_shapes.otd",
         "complex_shapes.otd",
         "large_layout.otd",
+        "laminated_linear.otd",
+        "mixed_sheets.otd",
     ];
 
     for fixture in &fixtures {
@@ -827,6 +1810,8 @@ fn test_all_fixtures_convert() {
         "empty_shapes.otd",
         "complex_shapes.otd",
         "large_layout.otd",
+        "laminated_linear.otd",
+        "mixed_sheets.otd",
     ];
 
     for fixture in &fixtures {
@@ -3,7 +3,9 @@
 use std::path::PathBuf;
 
 use egui::{CentralPanel, Context, Key, Modifiers, SidePanel, TopBottomPanel, Vec2};
-use otd_core::{parse_otd_file, Schema};
+use otd_core::{
+    estimate_schemas, format_duration, parse_otd_file, transform, CycleTime, MachineConfig, Schema,
+};
 
 use crate::canvas;
 use crate::layers::LayerVisibility;
@@ -28,6 +30,8 @@ pub struct ViewerApp {
     show_inspector: bool,
     /// Status message
     status_message: String,
+    /// Estimated cycle time of each pattern
+    cycle_time: Option<CycleTime>,
 
     /// Error message to display
     error_message: Option<String>,
@@ -65,6 +69,7 @@ impl ViewerApp {
             layers: LayerVisibility::default(),
             show_inspector: true,
             status_message: "No file loaded. Use File > Open or Ctrl+O".to_string(),
+            cycle_time: None,
             error_message: None,
             fit_pending: false,
             mouse_sheet_pos: None,
@@ -90,6 +95,7 @@ impl ViewerApp {
                 let num_schemas = schemas.len();
                 let total_pieces: usize = schemas.iter().map(|s| s.pieces.len()).sum();
 
+                self.cycle_time = estimate_cycle_time(&schemas);
                 self.schemas = schemas;
                 self.current_schema = 0;
                 self.file_path = Some(path.clone());
@@ -537,6 +543,24 @@ impl ViewerApp {
                 ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
                     ui.label(self.transform.zoom_percent());
 
+                    // Estimated cycle time of the displayed pattern
+                    let pattern_time = self
+                        .cycle_time
+                        .as_ref()
+                        .and_then(|c| c.pattern(self.current_schema + 1));
+                    if let Some(time) = pattern_time {
+                        ui.separator();
+                        let breakdown = format!(
+                            "Cutting {} | Rapid {} | Tool {} | Rotation {}",
+                            format_duration(time.cutting),
+                            format_duration(time.rapid),
+                            format_duration(time.tool),
+                            format_duration(time.rotation)
+                        );
+                        ui.label(format!("Cycle time: {}", time.summary()))
+                            .on_hover_text(breakdown);
+                    }
+
                     // Show mouse coordinates if we have a schema
                     if self.current_schema().is_some() {
                         if let Some(pos) = &self.mouse_sheet_pos {
@@ -971,3 +995,25 @@ impl eframe::App for ViewerApp {
         }
     }
 }
+
+/// Estimate the cycle time of the program the converter would generate for
+/// the file, on the machine named in the OTD file.
+fn estimate_cycle_time(schemas: &[Schema]) -> Option<CycleTime> {
+    let mut schemas = schemas.to_vec();
+    for schema in &mut schemas {
+        transform::process_linear_cuts(schema);
+        transform::process_shapes(schema);
+    }
+
+    let config = match schemas.first().map(|s| s.machine_number) {
+        Some(machine) if machine >= 100 => MachineConfig::new(machine),
+        _ => MachineConfig::default(),
+    };
+    match estimate_schemas(&schemas, &config) {
+        Ok(cycle_time) => Some(cycle_time),
+        Err(e) => {
+            tracing::warn!("Cycle time estimation failed: {}", e);
+            None
+        }
+    }
+}
@@ -165,80 +165,22 @@ fn render_trim_zone(
 }
 
 /// Render waste regions (areas not covered by pieces).
-/// This uses a simple approach: draw the whole sheet as waste, then "punch out" the pieces.
-/// We approximate this by drawing waste-colored rectangles in gaps between pieces.
 fn render_waste_regions(
     painter: &Painter,
     schema: &Schema,
     transform: &ViewTransform,
     canvas_rect: Rect,
 ) {
-    // Skip if there are no pieces (entire sheet would be waste)
-    if schema.pieces.is_empty() {
-        return;
-    }
-
-    // Create a simple grid-based approach to find waste cells
-    // We'll use the linear cuts to define the grid cells, then check which cells have no pieces
-
-    // Collect all unique X and Y coordinates from linear cuts and sheet edges
-    let mut x_coords: Vec<f64> = vec![schema.trim_left, schema.width];
-    let mut y_coords: Vec<f64> = vec![schema.trim_bottom, schema.height];
+    for (x1, y1, x2, y2) in schema.waste_regions() {
+        let min = transform.sheet_to_screen(Pos2::new(x1 as f32, y1 as f32), canvas_rect);
+        let max = transform.sheet_to_screen(Pos2::new(x2 as f32, y2 as f32), canvas_rect);
+        let rect = Rect::from_two_pos(min, max);
 
-    for cut in &schema.linear_cuts {
-        if !cut.active {
-            continue;
-        }
-        // Vertical cut
-        if (cut.xi - cut.xf).abs() < 0.001 {
-            x_coords.push(cut.xi);
-        }
-        // Horizontal cut
-        if (cut.yi - cut.yf).abs() < 0.001 {
-            y_coords.push(cut.yi);
-        }
-    }
+        // Fill with waste color
+        painter.rect_filled(rect, 0.0, theme::WASTE_FILL);
 
-    // Sort and deduplicate
-    x_coords.sort_by(|a, b| a.partial_cmp(b).unwrap());
-    y_coords.sort_by(|a, b| a.partial_cmp(b).unwrap());
-    x_coords.dedup_by(|a, b| (*a - *b).abs() < 0.01);
-    y_coords.dedup_by(|a, b| (*a - *b).abs() < 0.01);
-
-    // For each grid cell, check if any piece covers it
-    for x_idx in 0..x_coords.len().saturating_sub(1) {
-        for y_idx in 0..y_coords.len().saturating_sub(1) {
-            let cell_x1 = x_coords[x_idx];
-            let cell_y1 = y_coords[y_idx];
-            let cell_x2 = x_coords[x_idx + 1];
-            let cell_y2 = y_coords[y_idx + 1];
-
-            let cell_center_x = (cell_x1 + cell_x2) / 2.0;
-            let cell_center_y = (cell_y1 + cell_y2) / 2.0;
-
-            // Check if any piece contains this cell's center
-            let is_covered = schema.pieces.iter().any(|piece| {
-                cell_center_x >= piece.x_origin
-                    && cell_center_x <= piece.x_origin + piece.width
-                    && cell_center_y >= piece.y_origin
-                    && cell_center_y <= piece.y_origin + piece.height
-            });
-
-            if !is_covered {
-                // This cell is waste - draw it
-                let min = transform
-                    .sheet_to_screen(Pos2::new(cell_x1 as f32, cell_y1 as f32), canvas_rect);
-                let max = transform
-                    .sheet_to_screen(Pos2::new(cell_x2 as f32, cell_y2 as f32), canvas_rect);
-                let rect = Rect::from_two_pos(min, max);
-
-                // Fill with waste color
-                painter.rect_filled(rect, 0.0, theme::WASTE_FILL);
-
-                // Draw diagonal hatch lines for visual distinction
-                draw_hatch_pattern(painter, rect, theme::WASTE_HATCH);
-            }
-        }
+        // Draw diagonal hatch lines for visual distinction
+        draw_hatch_pattern(painter, rect, theme::WASTE_HATCH);
     }
 }
 